@@ -2,9 +2,13 @@ use serde_json::Value;
 use std::time::Duration;
 use std::{collections::HashMap, sync::Arc};
 use tokio::time::sleep;
+use tracing::warn;
 
 use crate::{
-    account::trade::{OrderSide, Position},
+    account::{
+        portfolio::{Pnl, Portfolio},
+        trade::{Fill, OrderOptions, OrderSide, OrderType, Position, PositionTracker},
+    },
     exchange::api::ExchangeApi,
     market::{market::Market, types::ArcMutex},
 };
@@ -12,6 +16,8 @@ use crate::{
 pub struct Account {
     market: ArcMutex<Market>,
     positions: ArcMutex<HashMap<String, Position>>,
+    position_tracker: ArcMutex<PositionTracker>,
+    portfolio: ArcMutex<Portfolio>,
     exchange_api: Arc<Box<dyn ExchangeApi>>,
 }
 
@@ -20,6 +26,8 @@ impl Account {
         let _self = Self {
             market,
             positions: ArcMutex::new(HashMap::new()),
+            position_tracker: ArcMutex::new(PositionTracker::new()),
+            portfolio: ArcMutex::new(Portfolio::new()),
             exchange_api,
         };
 
@@ -48,7 +56,7 @@ impl Account {
 
         // only open position if market has last price for symbol
         if let Some(last_price) = last_price {
-            let new_position =
+            let mut new_position =
                 Position::new(&symbol, last_price, order_side, stop_loss, margin, leverage);
 
             let pos_clone = new_position.clone();
@@ -57,9 +65,44 @@ impl Account {
             // if successful position open spawn thread to update last price
             if let Ok(res) = self
                 .exchange_api
-                .open_position(&pos_clone.symbol, pos_clone.order_side, pos_clone.quantity)
+                .open_position(
+                    &pos_clone.symbol,
+                    pos_clone.order_side,
+                    pos_clone.quantity,
+                    OrderOptions::default(),
+                )
                 .await
             {
+                // record fill to track execution price and PnL, order may not be
+                // filled by time of response in which case last price is used.
+                // fill time follows exchange, eg. replayed kline time
+                let fill_time = self.exchange_api.clock().now();
+                let fill = Fill::from_order_response(
+                    &pos_clone.symbol,
+                    new_position.order_side.clone(),
+                    &res,
+                    fill_time,
+                )
+                .unwrap_or_else(|| {
+                    warn!(
+                        "Order response has no fill price for {}, using last price",
+                        pos_clone.symbol
+                    );
+                    Fill::new_at(
+                        &pos_clone.symbol,
+                        new_position.order_side.clone(),
+                        last_price,
+                        pos_clone.quantity,
+                        fill_time,
+                    )
+                });
+                self.portfolio.lock().await.record_fill(&fill);
+
+                // position entered at executed price rather than last price
+                new_position.entry_price = fill.price;
+                new_position.quantity = fill.quantity;
+                self.position_tracker.lock().await.record_fill(fill);
+
                 let position_id = "order_id";
                 // insert new position into account positions
                 positions
@@ -99,15 +142,85 @@ impl Account {
         method_res
     }
 
-    pub fn close_position(&mut self, _position_id: u64) {}
+    /// Close position opened by order with position ID with market order
+    /// for quantity of position, realized PnL is recorded in portfolio
+    /// at executed price of closing order
+    pub async fn close_position(&mut self, position_id: &str) -> Option<Value> {
+        let position = self.positions.lock().await.get(position_id).cloned()?;
+        let close_side = position.order_side.opposite();
 
-    pub async fn positions(&self) -> Vec<Position> {
-        self.positions
+        // other positions of symbol are left open
+        let res = match self
+            .exchange_api
+            .open_order(
+                &position.symbol,
+                close_side.clone(),
+                OrderType::Market,
+                position.quantity,
+                None,
+                OrderOptions {
+                    reduce_only: true,
+                    ..Default::default()
+                },
+            )
+            .await
+        {
+            Ok(res) => res,
+            Err(e) => {
+                warn!(
+                    "Unable to close {} position {position_id}: {e}",
+                    position.symbol
+                );
+                return None;
+            }
+        };
+
+        let fill_time = self.exchange_api.clock().now();
+        let fill = Fill::from_order_response(&position.symbol, close_side.clone(), &res, fill_time)
+            .unwrap_or_else(|| {
+                warn!(
+                    "Order response has no fill price for {}, using last price",
+                    position.symbol
+                );
+                Fill::new_at(
+                    &position.symbol,
+                    close_side,
+                    position.last_price,
+                    position.quantity,
+                    fill_time,
+                )
+            });
+
+        self.portfolio
             .lock()
             .await
-            .iter()
-            .map(|(_id, pos)| pos.clone())
-            .collect()
+            .close_position(&fill.symbol, fill.price, fill.quantity);
+        self.position_tracker.lock().await.record_fill(fill);
+
+        self.positions.lock().await.remove(position_id);
+
+        Some(res)
+    }
+
+    pub async fn positions(&self) -> Vec<Position> {
+        self.positions.lock().await.values().cloned().collect()
+    }
+
+    /// Realized and unrealized PnL of symbol at last price from market
+    pub async fn pnl(&self, symbol: &str) -> Option<Pnl> {
+        let last_price = self.market.lock().await.last_price(symbol).await?;
+
+        Some(self.portfolio.lock().await.pnl(symbol, last_price))
+    }
+
+    /// Average entry price and realized PnL of each symbol
+    pub fn portfolio(&self) -> ArcMutex<Portfolio> {
+        self.portfolio.clone()
+    }
+
+    /// Fills of executed orders, eg. to find VWAP of recent fills
+    pub fn position_tracker(&self) -> ArcMutex<PositionTracker> {
+        self.position_tracker.clone()
     }
 
     pub async fn init(&self) {
@@ -127,7 +240,7 @@ impl Account {
                     if let Some(stop_loss) = position.stop_loss {
                         if last_price < stop_loss {
                             // TODO: close position if stop loss hit
-                            println!("Stop loss hit")
+                            warn!(symbol = %position.symbol, "Stop loss hit")
                         }
                     }
 
@@ -141,3 +254,231 @@ impl Account {
         });
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    use super::*;
+    use crate::{
+        exchange::{bingx::BingXApi, mock::MockExchange, types::ExchangeError, types::StreamType},
+        market::{market::MarketConfig, messages::MarketMessage, ticker::Ticker},
+        storage::manager::StorageManager,
+        utils::{
+            channel::{build_arc_channel, DEFAULT_CHANNEL_CAPACITY},
+            clock::MockClock,
+        },
+    };
+
+    #[tokio::test]
+    async fn unknown_symbol_is_rejected_without_order_request() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/openApi/swap/v2/quote/contracts"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "code": 0,
+                "data": [
+                    {"symbol": "BTC-USDT", "status": 1},
+                    {"symbol": "ETH-USDT", "status": 0}
+                ]
+            })))
+            .mount(&server)
+            .await;
+
+        let dir = tempfile::tempdir().unwrap();
+        let (market_sender, market_receiver) = build_arc_channel(DEFAULT_CHANNEL_CAPACITY);
+        let exchange_api: Arc<Box<dyn ExchangeApi>> = Arc::new(Box::new(
+            BingXApi::builder("api_key", "secret_key", market_sender)
+                .hosts(&server.uri(), "ws://localhost")
+                .build()
+                .unwrap(),
+        ));
+        let config = MarketConfig {
+            streams: vec![("BTC-USDT".to_string(), StreamType::Ticker, None)],
+            backup_interval: None,
+            ..MarketConfig::default()
+        };
+        let market = Market::new(
+            market_receiver,
+            exchange_api.clone(),
+            StorageManager::new(dir.path().to_path_buf()),
+            config,
+        )
+        .await;
+        let market = ArcMutex::new(market);
+        let mut account = Account::new(market.clone(), exchange_api).await;
+
+        // symbol typo and contract which is not trading
+        for symbol in ["BTCUSDT", "ETH-USDT"] {
+            assert!(account
+                .open_position(symbol, 100.0, 1, OrderSide::Buy, None)
+                .await
+                .is_none());
+            assert!(matches!(
+                market
+                    .lock()
+                    .await
+                    .open_stream(StreamType::Ticker, symbol, None)
+                    .await,
+                Err(ExchangeError::UnknownSymbol(unknown)) if unknown == symbol
+            ));
+        }
+        market.lock().await.shutdown().await;
+
+        let requests = server.received_requests().await.unwrap();
+        assert!(!requests
+            .iter()
+            .any(|request| request.url.path() == "/openApi/swap/v2/trade/order"));
+        // symbols are cached after first lookup
+        assert_eq!(
+            requests
+                .iter()
+                .filter(|request| request.url.path() == "/openApi/swap/v2/quote/contracts")
+                .count(),
+            1
+        );
+    }
+
+    #[tokio::test]
+    async fn close_position_sends_reduce_only_order_for_position_quantity() {
+        const FILL_TS: u64 = 1_700_000_000_000;
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/openApi/swap/v2/quote/contracts"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "code": 0,
+                "data": [{"symbol": "BTC-USDT", "status": 1, "quantityPrecision": 4, "pricePrecision": 1}]
+            })))
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/openApi/swap/v2/trade/order"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "code": 0,
+                "data": {"order": {
+                    "orderId": 1,
+                    "symbol": "BTC-USDT",
+                    "avgPrice": "110.0",
+                    "executedQty": "0.5000",
+                    "status": "FILLED"
+                }}
+            })))
+            .mount(&server)
+            .await;
+
+        let dir = tempfile::tempdir().unwrap();
+        let (market_sender, market_receiver) = build_arc_channel(DEFAULT_CHANNEL_CAPACITY);
+        let exchange_api: Arc<Box<dyn ExchangeApi>> = Arc::new(Box::new(
+            BingXApi::builder("api_key", "secret_key", market_sender)
+                .hosts(&server.uri(), "ws://localhost")
+                .clock(Arc::new(MockClock::new(FILL_TS)))
+                .build()
+                .unwrap(),
+        ));
+        let config = MarketConfig {
+            backup_interval: None,
+            ..MarketConfig::default()
+        };
+        let market = ArcMutex::new(
+            Market::new(
+                market_receiver,
+                exchange_api.clone(),
+                StorageManager::new(dir.path().to_path_buf()),
+                config,
+            )
+            .await,
+        );
+        let mut account = Account::new(market.clone(), exchange_api).await;
+
+        // long position of 0.5 BTC-USDT at 100
+        let position = Position::new("BTC-USDT", 100.0, OrderSide::Buy, None, 50.0, 1);
+        let quantity = position.quantity;
+        account
+            .positions
+            .lock()
+            .await
+            .insert("position".to_string(), position);
+
+        assert!(account.close_position("position").await.is_some());
+        market.lock().await.shutdown().await;
+
+        let requests = server.received_requests().await.unwrap();
+        let order = requests
+            .iter()
+            .find(|request| request.url.path() == "/openApi/swap/v2/trade/order")
+            .unwrap();
+        let body = String::from_utf8(order.body.clone()).unwrap();
+        assert!(body.contains("side=SELL"));
+        assert!(body.contains("type=MARKET"));
+        assert!(body.contains(&format!("quantity={quantity:.4}")));
+        assert!(body.contains("reduceOnly=true"));
+        assert!(account.positions().await.is_empty());
+
+        // fill is recorded at exchange time of close
+        let position_tracker = account.position_tracker();
+        let position_tracker = position_tracker.lock().await;
+        assert_eq!(
+            position_tracker.recent_fill_vwap("BTC-USDT", FILL_TS),
+            Some(110.0)
+        );
+        assert_eq!(
+            position_tracker.recent_fill_vwap("BTC-USDT", FILL_TS + 1),
+            None
+        );
+    }
+
+    #[tokio::test]
+    async fn pnl_is_valued_at_last_price_of_market() {
+        let dir = tempfile::tempdir().unwrap();
+        let (market_sender, market_receiver) = build_arc_channel(DEFAULT_CHANNEL_CAPACITY);
+        let exchange_api: Arc<Box<dyn ExchangeApi>> = Arc::new(Box::new(MockExchange::new()));
+        let config = MarketConfig {
+            backup_interval: None,
+            ..MarketConfig::default()
+        };
+        let market = ArcMutex::new(
+            Market::new(
+                market_receiver,
+                exchange_api.clone(),
+                StorageManager::new(dir.path().to_path_buf()),
+                config,
+            )
+            .await,
+        );
+        let account = Account::new(market.clone(), exchange_api).await;
+
+        // no last price to value position at
+        assert!(account.pnl("ETH-USDT").await.is_none());
+
+        account.portfolio.lock().await.record_fill(&Fill::new(
+            "ETH-USDT",
+            OrderSide::Buy,
+            2_000.0,
+            2.0,
+        ));
+        market_sender
+            .send(MarketMessage::UpdateTicker(Ticker {
+                symbol: "ETH-USDT".to_string(),
+                last_price: 2_100.0,
+                ..Default::default()
+            }))
+            .unwrap();
+
+        let pnl = tokio::time::timeout(Duration::from_secs(5), async {
+            loop {
+                if let Some(pnl) = account.pnl("ETH-USDT").await {
+                    return pnl;
+                }
+                sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .unwrap();
+        market.lock().await.shutdown().await;
+
+        assert_eq!(pnl.realized, 0.0);
+        assert_eq!(pnl.unrealized, 200.0);
+    }
+}
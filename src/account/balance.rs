@@ -0,0 +1,135 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::exchange::types::ApiResult;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Balance {
+    pub asset: String,
+    pub free: f64,
+    pub locked: f64,
+}
+
+impl Balance {
+    pub fn new(asset: &str, free: f64, locked: f64) -> Self {
+        Self {
+            asset: asset.to_string(),
+            free,
+            locked,
+        }
+    }
+
+    /// Parse balance of asset from account response, balances list
+    /// is found at top level or nested in 'data' key.
+    /// Zeroed balance is returned if asset is not held in account
+    pub fn from_account_lookup(account: &Value, asset: &str) -> ApiResult<Self> {
+        let balances = account
+            .get("balances")
+            .or_else(|| account.get("data").and_then(|data| data.get("balances")))
+            .and_then(|balances| balances.as_array())
+            .ok_or_else(|| "Missing 'balances' list from account response".to_string())?;
+
+        let balance = balances
+            .iter()
+            .find(|balance| balance.get("asset").and_then(|a| a.as_str()) == Some(asset));
+
+        let balance = match balance {
+            Some(balance) => balance,
+            None => return Ok(Self::new(asset, 0.0, 0.0)),
+        };
+
+        let free = parse_amount(balance, "free")?;
+        let locked = parse_amount(balance, "locked")?;
+
+        Ok(Self::new(asset, free, locked))
+    }
+
+    /// Parse balance from BingX swap balance response, single balance
+    /// is nested in 'data.balance' key, available margin is free and
+    /// used plus frozen margin is locked.
+    /// Zeroed balance is returned if account is margined in other asset
+    pub fn from_bingx_lookup(account: &Value, asset: &str) -> ApiResult<Self> {
+        let balance = account
+            .get("data")
+            .and_then(|data| data.get("balance"))
+            .ok_or_else(|| "Missing 'data.balance' key from balance response".to_string())?;
+
+        if balance.get("asset").and_then(|a| a.as_str()) != Some(asset) {
+            return Ok(Self::new(asset, 0.0, 0.0));
+        }
+
+        let free = parse_amount(balance, "availableMargin")?;
+        let locked = parse_amount(balance, "usedMargin")? + parse_amount(balance, "freezedMargin")?;
+
+        Ok(Self::new(asset, free, locked))
+    }
+}
+
+// amounts are returned as strings by exchange, eg. "0.00100000"
+fn parse_amount(balance: &Value, key: &str) -> ApiResult<f64> {
+    let amount = balance
+        .get(key)
+        .and_then(|amount| amount.as_str())
+        .ok_or_else(|| format!("Missing '{key}' key from balance"))?
+        .parse::<f64>()?;
+
+    Ok(amount)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn account_balances_are_parsed_or_zeroed() {
+        let account = json!({
+            "data": {
+                "balances": [
+                    {"asset": "BTC", "free": "0.00100000", "locked": "0.00050000"},
+                    {"asset": "USDT", "free": "1250.75", "locked": "100.25"}
+                ]
+            }
+        });
+
+        let balance = Balance::from_account_lookup(&account, "USDT").unwrap();
+        assert_eq!(balance.asset, "USDT");
+        assert_eq!(balance.free, 1250.75);
+        assert_eq!(balance.locked, 100.25);
+
+        let balance = Balance::from_account_lookup(&account, "BTC").unwrap();
+        assert_eq!(balance.free, 0.001);
+        assert_eq!(balance.locked, 0.0005);
+
+        // asset not held in account
+        let balance = Balance::from_account_lookup(&account, "ETH").unwrap();
+        assert_eq!(balance.free, 0.0);
+        assert_eq!(balance.locked, 0.0);
+    }
+
+    #[test]
+    fn bingx_swap_balance_is_parsed() {
+        let account = json!({
+            "code": 0,
+            "data": {
+                "balance": {
+                    "asset": "USDT",
+                    "balance": "1500.0",
+                    "availableMargin": "1200.5",
+                    "usedMargin": "250.0",
+                    "freezedMargin": "49.5"
+                }
+            }
+        });
+
+        let balance = Balance::from_bingx_lookup(&account, "USDT").unwrap();
+        assert_eq!(balance.free, 1200.5);
+        assert_eq!(balance.locked, 299.5);
+
+        // account margined in USDT only
+        let balance = Balance::from_bingx_lookup(&account, "BTC").unwrap();
+        assert_eq!(balance.free, 0.0);
+        assert_eq!(balance.locked, 0.0);
+    }
+}
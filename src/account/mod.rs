@@ -1,2 +1,6 @@
+#[allow(clippy::module_inception)]
 pub mod account;
+pub mod balance;
+pub mod portfolio;
+pub mod sizing;
 pub mod trade;
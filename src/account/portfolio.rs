@@ -0,0 +1,186 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::account::trade::{Fill, OrderSide};
+
+// quantities smaller than this are treated as a flat position
+const QUANTITY_EPSILON: f64 = 1e-12;
+
+/// Net holding of symbol built from fills, quantity is
+/// positive for long positions and negative for short positions
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PortfolioPosition {
+    pub quantity: f64,
+    pub avg_entry_price: f64,
+    pub realized_pnl: f64,
+}
+
+impl PortfolioPosition {
+    pub fn unrealized_pnl(&self, current_price: f64) -> f64 {
+        (current_price - self.avg_entry_price) * self.quantity
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct Pnl {
+    pub realized: f64,
+    pub unrealized: f64,
+}
+
+impl Pnl {
+    pub fn total(&self) -> f64 {
+        self.realized + self.unrealized
+    }
+}
+
+/// Tracks average entry price and realized PnL of each symbol from fills
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Portfolio {
+    positions: HashMap<String, PortfolioPosition>,
+}
+
+impl Portfolio {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record fill of order reducing position by quantity at price,
+    /// quantity is limited to size of open position
+    pub fn close_position(&mut self, symbol: &str, price: f64, quantity: f64) {
+        let position_qty = match self.positions.get(symbol) {
+            Some(position) => position.quantity,
+            None => return,
+        };
+
+        let position_side = if position_qty > 0.0 {
+            OrderSide::Buy
+        } else {
+            OrderSide::Sell
+        };
+
+        let quantity = quantity.min(position_qty.abs());
+        self.record(symbol, &position_side.opposite(), price, quantity);
+    }
+
+    /// Update position of fill symbol, fills against position direction
+    /// realize PnL and fills past flat open position in opposite direction
+    pub fn record_fill(&mut self, fill: &Fill) {
+        self.record(&fill.symbol, &fill.order_side, fill.price, fill.quantity);
+    }
+
+    // fills are recorded by value so positions need no fill time
+    fn record(&mut self, symbol: &str, order_side: &OrderSide, price: f64, quantity: f64) {
+        let position = self.positions.entry(symbol.to_string()).or_default();
+
+        let fill_qty = match order_side {
+            OrderSide::Buy => quantity,
+            OrderSide::Sell => -quantity,
+        };
+
+        let is_increase = position.quantity.abs() < QUANTITY_EPSILON
+            || position.quantity.signum() == fill_qty.signum();
+
+        if is_increase {
+            let total_qty = position.quantity.abs() + quantity;
+            position.avg_entry_price =
+                (position.avg_entry_price * position.quantity.abs() + price * quantity) / total_qty;
+            position.quantity += fill_qty;
+            return;
+        }
+
+        let closed_qty = quantity.min(position.quantity.abs());
+        position.realized_pnl +=
+            (price - position.avg_entry_price) * closed_qty * position.quantity.signum();
+        position.quantity += fill_qty;
+
+        if position.quantity.abs() < QUANTITY_EPSILON {
+            position.quantity = 0.0;
+            position.avg_entry_price = 0.0;
+        } else if quantity > closed_qty {
+            // position flipped, remaining quantity opened at fill price
+            position.avg_entry_price = price;
+        }
+    }
+
+    pub fn position(&self, symbol: &str) -> Option<&PortfolioPosition> {
+        self.positions.get(symbol)
+    }
+
+    /// Realized PnL of closed fills and unrealized PnL of open position
+    /// at current price, eg. last price from market
+    pub fn pnl(&self, symbol: &str, current_price: f64) -> Pnl {
+        match self.positions.get(symbol) {
+            Some(position) => Pnl {
+                realized: position.realized_pnl,
+                unrealized: position.unrealized_pnl(current_price),
+            },
+            None => Pnl {
+                realized: 0.0,
+                unrealized: 0.0,
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn buy_partial_sell_and_full_close_realize_pnl() {
+        let mut portfolio = Portfolio::new();
+        portfolio.record_fill(&Fill::new("BTC-USDT", OrderSide::Buy, 100.0, 2.0));
+        portfolio.record_fill(&Fill::new("BTC-USDT", OrderSide::Buy, 110.0, 2.0));
+
+        let position = portfolio.position("BTC-USDT").unwrap();
+        assert_eq!(position.quantity, 4.0);
+        assert_eq!(position.avg_entry_price, 105.0);
+        let pnl = portfolio.pnl("BTC-USDT", 120.0);
+        assert_eq!(pnl.realized, 0.0);
+        assert_eq!(pnl.unrealized, 60.0);
+
+        // partial sell below entry realizes loss
+        portfolio.close_position("BTC-USDT", 100.0, 1.0);
+        let pnl = portfolio.pnl("BTC-USDT", 100.0);
+        assert_eq!(pnl.realized, -5.0);
+        assert_eq!(pnl.unrealized, -15.0);
+        assert_eq!(
+            portfolio.position("BTC-USDT").unwrap().avg_entry_price,
+            105.0
+        );
+
+        // close is limited to open quantity
+        portfolio.close_position("BTC-USDT", 115.0, 10.0);
+        let position = portfolio.position("BTC-USDT").unwrap();
+        assert_eq!(position.quantity, 0.0);
+        let pnl = portfolio.pnl("BTC-USDT", 200.0);
+        assert_eq!(pnl.realized, 25.0);
+        assert_eq!(pnl.unrealized, 0.0);
+        assert_eq!(pnl.total(), 25.0);
+    }
+
+    #[test]
+    fn short_position_profits_when_price_falls() {
+        let mut portfolio = Portfolio::new();
+        portfolio.record_fill(&Fill::new("ETH-USDT", OrderSide::Sell, 2000.0, 1.5));
+
+        assert_eq!(portfolio.pnl("ETH-USDT", 1900.0).unrealized, 150.0);
+
+        portfolio.close_position("ETH-USDT", 2100.0, 1.5);
+        assert_eq!(portfolio.pnl("ETH-USDT", 2100.0).realized, -150.0);
+        assert_eq!(portfolio.pnl("BTC-USDT", 100.0).total(), 0.0);
+    }
+
+    #[test]
+    fn fill_past_flat_flips_position() {
+        let mut portfolio = Portfolio::new();
+        portfolio.record_fill(&Fill::new("BTC-USDT", OrderSide::Buy, 100.0, 1.0));
+        portfolio.record_fill(&Fill::new("BTC-USDT", OrderSide::Sell, 110.0, 3.0));
+
+        let position = portfolio.position("BTC-USDT").unwrap();
+        assert_eq!(position.quantity, -2.0);
+        assert_eq!(position.avg_entry_price, 110.0);
+        assert_eq!(position.realized_pnl, 10.0);
+    }
+}
@@ -0,0 +1,43 @@
+/// Base quantity to order so that being stopped out loses `risk_pct`
+/// percent of `balance`, eg. balance 1000, risk 1, entry 100, stop 98 is 5,
+/// returns 0 if entry and stop are equal or any input is negative
+pub fn position_size(balance: f64, risk_pct: f64, entry: f64, stop: f64) -> f64 {
+    if balance < 0.0 || risk_pct < 0.0 || entry < 0.0 || stop < 0.0 {
+        return 0.0;
+    }
+
+    // stop can be below entry for long or above entry for short
+    let stop_distance = (entry - stop).abs();
+    if stop_distance == 0.0 {
+        return 0.0;
+    }
+
+    let risk_amount = balance * risk_pct / 100.0;
+
+    risk_amount / stop_distance
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stop_out_loses_risk_percent_of_balance() {
+        assert_eq!(position_size(1000.0, 1.0, 100.0, 98.0), 5.0);
+        assert_eq!(position_size(10_000.0, 2.0, 50.0, 45.0), 40.0);
+        // short with stop above entry
+        assert_eq!(position_size(1000.0, 1.0, 100.0, 102.0), 5.0);
+
+        let qty = position_size(2500.0, 0.5, 40_000.0, 39_500.0);
+        assert!((qty * 500.0 - 12.5).abs() < 1e-9);
+    }
+
+    #[test]
+    fn invalid_inputs_size_to_zero() {
+        assert_eq!(position_size(1000.0, 1.0, 100.0, 100.0), 0.0);
+        assert_eq!(position_size(-1000.0, 1.0, 100.0, 98.0), 0.0);
+        assert_eq!(position_size(1000.0, -1.0, 100.0, 98.0), 0.0);
+        assert_eq!(position_size(1000.0, 1.0, -100.0, 98.0), 0.0);
+        assert_eq!(position_size(1000.0, 1.0, 100.0, -98.0), 0.0);
+    }
+}
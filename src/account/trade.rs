@@ -1,8 +1,12 @@
-use std::fmt::Display;
+use std::{fmt::Display, str::FromStr};
 
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg(test)]
+use crate::utils::clock::{Clock, SystemClock};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum OrderSide {
     Buy,
     Sell,
@@ -17,6 +21,138 @@ impl Display for OrderSide {
     }
 }
 
+impl FromStr for OrderSide {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_uppercase().as_str() {
+            "BUY" => Ok(OrderSide::Buy),
+            "SELL" => Ok(OrderSide::Sell),
+            _ => Err(format!("Unknown order side: {}", s)),
+        }
+    }
+}
+
+impl OrderSide {
+    /// Side of order which flattens position opened with this side
+    pub fn opposite(&self) -> OrderSide {
+        match self {
+            OrderSide::Buy => OrderSide::Sell,
+            OrderSide::Sell => OrderSide::Buy,
+        }
+    }
+}
+
+/// Side of position in hedge mode, one-way mode positions are both
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub enum PositionSide {
+    Long,
+    Short,
+    #[default]
+    Both,
+}
+
+impl Display for PositionSide {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PositionSide::Long => f.write_str("LONG"),
+            PositionSide::Short => f.write_str("SHORT"),
+            PositionSide::Both => f.write_str("BOTH"),
+        }
+    }
+}
+
+impl FromStr for PositionSide {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_uppercase().as_str() {
+            "LONG" => Ok(PositionSide::Long),
+            "SHORT" => Ok(PositionSide::Short),
+            "BOTH" => Ok(PositionSide::Both),
+            _ => Err(format!("Unknown position side: {}", s)),
+        }
+    }
+}
+
+/// Margin shared by all positions (crossed) or held per position (isolated)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum MarginMode {
+    Isolated,
+    Crossed,
+}
+
+impl Display for MarginMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MarginMode::Isolated => f.write_str("ISOLATED"),
+            MarginMode::Crossed => f.write_str("CROSSED"),
+        }
+    }
+}
+
+/// Extra order parameters, default places plain order
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct OrderOptions {
+    // order can only reduce open position, never open or flip one
+    pub reduce_only: bool,
+    // position order applies to, long or short in hedge mode,
+    // defaults to both for one-way mode
+    pub position_side: PositionSide,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum OrderType {
+    Market,
+    Limit,
+    StopMarket,
+    TakeProfitMarket,
+}
+
+impl Display for OrderType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            OrderType::Market => f.write_str("MARKET"),
+            OrderType::Limit => f.write_str("LIMIT"),
+            OrderType::StopMarket => f.write_str("STOP_MARKET"),
+            OrderType::TakeProfitMarket => f.write_str("TAKE_PROFIT_MARKET"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum OrderStatus {
+    New,
+    PartiallyFilled,
+    Filled,
+    Canceled,
+    Rejected,
+    Expired,
+}
+
+impl FromStr for OrderStatus {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_uppercase().as_str() {
+            "NEW" | "PENDING" => Ok(OrderStatus::New),
+            "PARTIALLY_FILLED" => Ok(OrderStatus::PartiallyFilled),
+            "FILLED" => Ok(OrderStatus::Filled),
+            "CANCELED" | "CANCELLED" => Ok(OrderStatus::Canceled),
+            "REJECTED" | "FAILED" => Ok(OrderStatus::Rejected),
+            "EXPIRED" => Ok(OrderStatus::Expired),
+            _ => Err(format!("Unknown order status: {}", s)),
+        }
+    }
+}
+
+impl OrderStatus {
+    /// Order will not change status again
+    pub fn is_terminal(&self) -> bool {
+        !matches!(self, OrderStatus::New | OrderStatus::PartiallyFilled)
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Position {
     pub symbol: String,
@@ -61,3 +197,191 @@ impl Position {
         self.order_id = Some(id.to_string());
     }
 }
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Fill {
+    pub symbol: String,
+    pub order_side: OrderSide,
+    pub price: f64,
+    pub quantity: f64,
+    pub time: u64,
+}
+
+impl Fill {
+    /// Fill at current system time
+    #[cfg(test)]
+    pub fn new(symbol: &str, order_side: OrderSide, price: f64, quantity: f64) -> Self {
+        Self::new_at(symbol, order_side, price, quantity, SystemClock.now())
+    }
+
+    /// Fill at time, eg. exchange time order was filled at
+    pub fn new_at(
+        symbol: &str,
+        order_side: OrderSide,
+        price: f64,
+        quantity: f64,
+        time: u64,
+    ) -> Self {
+        Self {
+            symbol: symbol.to_string(),
+            order_side,
+            price,
+            quantity,
+            time,
+        }
+    }
+
+    /// Fill of executed order, average price and executed quantity are
+    /// found at top level of order response or nested in 'data.order' key.
+    /// None if order has not been filled yet. Fill time is exchange time order was filled at
+    pub fn from_order_response(
+        symbol: &str,
+        order_side: OrderSide,
+        res: &Value,
+        time: u64,
+    ) -> Option<Self> {
+        let order = res
+            .get("data")
+            .and_then(|data| data.get("order"))
+            .unwrap_or(res);
+
+        let price = parse_order_amount(order, "avgPrice")?;
+        let quantity = parse_order_amount(order, "executedQty")?;
+
+        if price <= 0.0 || quantity <= 0.0 {
+            return None;
+        }
+
+        Some(Self::new_at(symbol, order_side, price, quantity, time))
+    }
+}
+
+// amounts are strings in exchange responses and numbers in paper responses
+fn parse_order_amount(order: &Value, key: &str) -> Option<f64> {
+    match order.get(key)? {
+        Value::String(amount) => amount.parse::<f64>().ok(),
+        amount => amount.as_f64(),
+    }
+}
+
+/// Fills of executed orders, used to monitor execution quality
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PositionTracker {
+    fills: Vec<Fill>,
+}
+
+impl PositionTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn record_fill(&mut self, fill: Fill) {
+        self.fills.push(fill);
+    }
+
+    /// Volume weighted average price of fills for symbol since timestamp
+    pub fn recent_fill_vwap(&self, symbol: &str, since_ts: u64) -> Option<f64> {
+        let (total_value, total_qty) = self
+            .fills
+            .iter()
+            .filter(|fill| fill.symbol == symbol && fill.time >= since_ts)
+            .fold((0.0, 0.0), |(value, qty), fill| {
+                (value + fill.price * fill.quantity, qty + fill.quantity)
+            });
+
+        if total_qty > 0.0 {
+            Some(total_value / total_qty)
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_fill(symbol: &str, price: f64, quantity: f64, time: u64) -> Fill {
+        Fill::new_at(symbol, OrderSide::Buy, price, quantity, time)
+    }
+
+    #[test]
+    fn recent_fill_vwap_weights_fills_since_timestamp() {
+        let mut position_tracker = PositionTracker::new();
+        // before since timestamp, ignored
+        position_tracker.record_fill(test_fill("BTC-USDT", 50.0, 10.0, 500));
+        position_tracker.record_fill(test_fill("BTC-USDT", 100.0, 1.0, 1_000));
+        position_tracker.record_fill(test_fill("BTC-USDT", 110.0, 3.0, 2_000));
+        // other symbol, ignored
+        position_tracker.record_fill(test_fill("ETH-USDT", 2_000.0, 5.0, 1_500));
+
+        // (100 * 1 + 110 * 3) / 4
+        assert_eq!(
+            position_tracker.recent_fill_vwap("BTC-USDT", 1_000),
+            Some(107.5)
+        );
+        assert_eq!(position_tracker.recent_fill_vwap("BTC-USDT", 3_000), None);
+        assert_eq!(position_tracker.recent_fill_vwap("SOL-USDT", 0), None);
+    }
+
+    #[test]
+    fn order_type_renders_bingx_strings() {
+        assert_eq!(OrderType::Market.to_string(), "MARKET");
+        assert_eq!(OrderType::Limit.to_string(), "LIMIT");
+        assert_eq!(OrderType::StopMarket.to_string(), "STOP_MARKET");
+        assert_eq!(
+            OrderType::TakeProfitMarket.to_string(),
+            "TAKE_PROFIT_MARKET"
+        );
+    }
+
+    #[test]
+    fn order_side_opposite_and_string_round_trip() {
+        assert_eq!(OrderSide::Buy.opposite(), OrderSide::Sell);
+        assert_eq!(OrderSide::Sell.opposite(), OrderSide::Buy);
+
+        for side in [OrderSide::Buy, OrderSide::Sell] {
+            assert_eq!(side.to_string().parse::<OrderSide>(), Ok(side.clone()));
+            assert_eq!(side.opposite().opposite(), side);
+        }
+
+        assert_eq!("buy".parse::<OrderSide>(), Ok(OrderSide::Buy));
+        assert!("HOLD".parse::<OrderSide>().is_err());
+    }
+
+    #[test]
+    fn position_side_renders_bingx_strings_and_parses() {
+        assert_eq!(PositionSide::Long.to_string(), "LONG");
+        assert_eq!(PositionSide::Short.to_string(), "SHORT");
+        assert_eq!(PositionSide::Both.to_string(), "BOTH");
+
+        for side in [PositionSide::Long, PositionSide::Short, PositionSide::Both] {
+            assert_eq!(side.to_string().parse::<PositionSide>(), Ok(side.clone()));
+        }
+        assert!("FLAT".parse::<PositionSide>().is_err());
+
+        // orders default to one-way mode
+        assert_eq!(OrderOptions::default().position_side, PositionSide::Both);
+    }
+
+    #[test]
+    fn order_status_parses_exchange_strings() {
+        for (status, expected) in [
+            ("NEW", OrderStatus::New),
+            ("PENDING", OrderStatus::New),
+            ("PARTIALLY_FILLED", OrderStatus::PartiallyFilled),
+            ("FILLED", OrderStatus::Filled),
+            ("CANCELLED", OrderStatus::Canceled),
+            ("FAILED", OrderStatus::Rejected),
+            ("expired", OrderStatus::Expired),
+        ] {
+            assert_eq!(status.parse::<OrderStatus>(), Ok(expected));
+        }
+        assert!("UNKNOWN".parse::<OrderStatus>().is_err());
+
+        assert!(!OrderStatus::New.is_terminal());
+        assert!(!OrderStatus::PartiallyFilled.is_terminal());
+        assert!(OrderStatus::Filled.is_terminal());
+        assert!(OrderStatus::Rejected.is_terminal());
+    }
+}
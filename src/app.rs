@@ -1,19 +1,46 @@
 use actix_web::web::Data;
 use dotenv_codegen::dotenv;
+use serde::Serialize;
 
-use std::{collections::HashMap, sync::Arc, time::Duration};
+use std::{fmt, path::PathBuf, sync::Arc, time::Duration};
 
 use crate::{
     account::account::Account,
-    exchange::{api::ExchangeApi, bingx::BingXApi, stream::StreamManager},
-    market::{market::Market, messages::MarketMessage, types::ArcMutex},
-    storage::manager::StorageManager,
-    utils::channel::build_arc_channel,
+    exchange::{
+        api::ExchangeApi,
+        binance::BinanceApi,
+        bingx::{BingXApi, BING_X_RECV_WINDOW, BING_X_REQUESTS_PER_SECOND, BING_X_REQUEST_TIMEOUT},
+        metadata::DEFAULT_METADATA_MAX_AGE,
+        paper::{PaperExchange, DEFAULT_PAPER_BALANCE, DEFAULT_PAPER_REPLAY_DELAY},
+        stream::StreamManager,
+        types::{ApiResult, StreamType},
+    },
+    market::{
+        interval::Interval,
+        market::{
+            Market, MarketConfig, DEFAULT_BACKUP_INTERVAL, DEFAULT_MAX_KLINES,
+            DEFAULT_SERVER_TIME_SYNC_INTERVAL,
+        },
+        messages::MarketMessage,
+        recorder::{MarketRecorder, MarketReplayer},
+        ticker::{DEFAULT_TICKER_HISTORY_LEN, DEFAULT_TICKER_WINDOW},
+        types::{ArcMutex, ArcSender},
+    },
+    storage::manager::{CompressionLevel, StorageManager},
+    strategy::strategy::StrategyRunner,
+    utils::{
+        channel::{build_arc_channel, DEFAULT_CHANNEL_CAPACITY},
+        rate_limit::requests_per_second,
+    },
 };
 
-use tokio::sync::watch::{channel, Receiver, Sender};
+use tracing::{info, warn};
 
-use crate::Message;
+#[cfg(test)]
+use crate::strategy::strategy::Strategy;
+
+// asset positions are margined in, balance is read to validate credentials
+pub const MARGIN_ASSET: &str = "USDT";
 
 pub struct RaderBot {
     pub market: ArcMutex<Market>,
@@ -22,28 +49,219 @@ pub struct RaderBot {
     // pub stream_manager: ArcMutex<StreamManager>,
     pub account: ArcMutex<Account>,
     pub exchange_api: Arc<Box<dyn ExchangeApi>>,
+    strategy_runner: ArcMutex<StrategyRunner>,
+    market_sender: ArcSender<MarketMessage>,
+    market_recorder: Option<MarketRecorder>,
 }
 
 impl RaderBot {
-    pub async fn new() -> Self {
+    /// Create bot from config, error if exchange API config is invalid, eg. empty API keys
+    pub async fn new() -> ApiResult<Self> {
         // create new Arc of exchange API
         let api_key = dotenv!("BINANCE_API_KEY");
         let secret_key = dotenv!("BINANCE_SECRET_KEY");
 
-        // create new channel for stream handler and market to communicate
-        let (market_tx, market_rx) = build_arc_channel::<MarketMessage>();
+        // create new channel for stream handler and market to communicate,
+        // messages are dropped if market falls behind by more than capacity
+        let channel_capacity = std::env::var("MARKET_CHANNEL_CAPACITY")
+            .ok()
+            .and_then(|capacity| capacity.parse::<usize>().ok())
+            .filter(|capacity| *capacity > 0)
+            .unwrap_or(DEFAULT_CHANNEL_CAPACITY);
+        let (market_tx, market_rx) = build_arc_channel::<MarketMessage>(channel_capacity);
 
-        let exchange_api: Arc<Box<dyn ExchangeApi>> = Arc::new(Box::new(BingXApi::new(
-            api_key,
-            secret_key,
-            market_tx.clone(),
-        )));
+        // create new storage manager, files stored under configured directory
+        let mut storage_manager = match std::env::var("STORAGE_DIR") {
+            Ok(storage_dir) => StorageManager::new(PathBuf::from(storage_dir)),
+            Err(_) => StorageManager::default(),
+        };
+        info!(
+            "Storing market data in {}",
+            storage_manager.base_path().display()
+        );
+
+        // set kline file compression levels from config
+        let compression_level = std::env::var("KLINE_COMPRESSION_LEVEL")
+            .ok()
+            .and_then(|level| level.parse().ok())
+            .unwrap_or(CompressionLevel::Default);
+        let archive_compression_level = std::env::var("KLINE_ARCHIVE_COMPRESSION_LEVEL")
+            .ok()
+            .and_then(|level| level.parse().ok())
+            .unwrap_or(CompressionLevel::Best);
+        storage_manager.set_compression_levels(compression_level, archive_compression_level);
+
+        // klines saved before files were namespaced by exchange came from BingX
+        match storage_manager.migrate_legacy_klines("bingx") {
+            Ok(0) => {}
+            Ok(moved) => info!("Moved {moved} kline files into bingx kline directory"),
+            Err(e) => warn!("Unable to move kline files into bingx kline directory: {e}"),
+        }
+
+        // write klines as gzip compressed CSV if enabled in config
+        if let Ok(val) = std::env::var("KLINE_COMPRESSION") {
+            storage_manager.set_compress_klines(val == "true");
+        }
+
+        // only log unparseable messages if enabled in config
+        let dead_letter_log = match std::env::var("DEAD_LETTER_LOG") {
+            Ok(val) if val == "true" => Some(storage_manager.dead_letter_log()),
+            _ => None,
+        };
+
+        // max requests sent to exchange each second
+        let rate_limit = std::env::var("EXCHANGE_RATE_LIMIT")
+            .ok()
+            .and_then(|requests| requests.parse::<u32>().ok())
+            .unwrap_or(BING_X_REQUESTS_PER_SECOND);
+
+        // secs to wait for exchange response before request fails
+        let request_timeout = std::env::var("EXCHANGE_REQUEST_TIMEOUT")
+            .ok()
+            .and_then(|secs| secs.parse::<u64>().ok())
+            .map(Duration::from_secs)
+            .unwrap_or(BING_X_REQUEST_TIMEOUT);
+
+        // max milliseconds after timestamp signed requests are valid for
+        let recv_window = std::env::var("EXCHANGE_RECV_WINDOW")
+            .ok()
+            .and_then(|millis| millis.parse::<u64>().ok())
+            .unwrap_or(BING_X_RECV_WINDOW);
+
+        // max age of cached exchange metadata, eg. symbols and precision filters
+        let metadata_max_age = std::env::var("EXCHANGE_METADATA_MAX_AGE")
+            .ok()
+            .and_then(|secs| secs.parse::<u64>().ok())
+            .map(Duration::from_secs)
+            .unwrap_or(DEFAULT_METADATA_MAX_AGE);
+
+        // replay stored klines with simulated fills if paper exchange
+        // enabled in config, eg. EXCHANGE=paper, trade on Binance spot
+        // testnet if EXCHANGE=binance, otherwise trade on BingX
+        let exchange_api: Arc<Box<dyn ExchangeApi>> = match std::env::var("EXCHANGE") {
+            Ok(exchange) if exchange == "paper" => {
+                let balance = std::env::var("PAPER_BALANCE")
+                    .ok()
+                    .and_then(|balance| balance.parse::<f64>().ok())
+                    .unwrap_or(DEFAULT_PAPER_BALANCE);
+
+                // millis waited between each replayed kline
+                let replay_delay = std::env::var("PAPER_REPLAY_DELAY")
+                    .ok()
+                    .and_then(|millis| millis.parse::<u64>().ok())
+                    .map(Duration::from_millis)
+                    .unwrap_or(DEFAULT_PAPER_REPLAY_DELAY);
+
+                // replay klines stored from live exchange, eg. PAPER_REPLAY_EXCHANGE=bingx
+                let mut replay_storage_manager = storage_manager.clone();
+                replay_storage_manager.set_exchange(
+                    &std::env::var("PAPER_REPLAY_EXCHANGE").unwrap_or_else(|_| "bingx".to_string()),
+                );
+
+                Arc::new(Box::new(PaperExchange::new(
+                    replay_storage_manager,
+                    market_tx.clone(),
+                    balance,
+                    replay_delay,
+                )))
+            }
+            Ok(exchange) if exchange == "binance" => {
+                let binance_api =
+                    BinanceApi::new(api_key, secret_key, market_tx.clone(), dead_letter_log);
+
+                Arc::new(Box::new(binance_api))
+            }
+            _ => {
+                let bingx_builder = BingXApi::builder(api_key, secret_key, market_tx.clone())
+                    .dead_letter_log(dead_letter_log)
+                    .rate_limit(requests_per_second(rate_limit))
+                    .request_timeout(request_timeout)
+                    .recv_window(recv_window)
+                    .metadata_max_age(metadata_max_age);
+
+                // use exchange demo trading hosts if testnet enabled in config,
+                // configured hosts take precedence, eg. http://localhost:8080
+                let bingx_builder = match (
+                    std::env::var("EXCHANGE_HOST"),
+                    std::env::var("EXCHANGE_WS_HOST"),
+                    std::env::var("EXCHANGE_TESTNET"),
+                ) {
+                    (Ok(host), Ok(ws_host), _) => bingx_builder.hosts(&host, &ws_host),
+                    (_, _, Ok(val)) if val == "true" => bingx_builder.testnet(),
+                    _ => bingx_builder,
+                };
 
-        // create new storage manager
-        let storage_manager = StorageManager::default();
+                let bingx_api = bingx_builder.build()?;
+
+                Arc::new(Box::new(bingx_api))
+            }
+        };
+
+        // ticker streams for each configured symbol, eg. BTC-USDT,ETH-USDT
+        let market_config = MarketConfig {
+            streams: std::env::var("MARKET_SYMBOLS")
+                .map(|symbols| {
+                    symbols
+                        .split(',')
+                        .filter(|symbol| !symbol.trim().is_empty())
+                        .map(|symbol| (symbol.trim().to_string(), StreamType::Ticker, None))
+                        .collect()
+                })
+                .unwrap_or_default(),
+            // 0 seconds disables saving klines to disk
+            backup_interval: match std::env::var("KLINE_BACKUP_INTERVAL")
+                .ok()
+                .and_then(|secs| secs.parse::<u64>().ok())
+            {
+                Some(0) => None,
+                Some(secs) => Some(Duration::from_secs(secs)),
+                None => Some(DEFAULT_BACKUP_INTERVAL),
+            },
+            // remove tickers not updated within ttl seconds
+            ticker_ttl: std::env::var("TICKER_TTL")
+                .ok()
+                .and_then(|secs| secs.parse::<u64>().ok())
+                .map(Duration::from_secs),
+            // time window of tickers kept in ticker history
+            ticker_window: std::env::var("TICKER_WINDOW")
+                .ok()
+                .and_then(|secs| secs.parse::<u64>().ok())
+                .map(Duration::from_secs)
+                .unwrap_or(DEFAULT_TICKER_WINDOW),
+            // max number of tickers kept in ticker history
+            ticker_history_len: std::env::var("TICKER_HISTORY_LEN")
+                .ok()
+                .and_then(|len| len.parse::<usize>().ok())
+                .filter(|len| *len > 0)
+                .unwrap_or(DEFAULT_TICKER_HISTORY_LEN),
+            // klines kept in memory for each symbol and interval
+            max_klines: std::env::var("MARKET_MAX_KLINES")
+                .ok()
+                .and_then(|len| len.parse::<usize>().ok())
+                .filter(|len| *len > 0)
+                .unwrap_or(DEFAULT_MAX_KLINES),
+            // 0 seconds disables syncing signing timestamps with exchange server time
+            server_time_sync_interval: match std::env::var("SERVER_TIME_SYNC_INTERVAL")
+                .ok()
+                .and_then(|secs| secs.parse::<u64>().ok())
+            {
+                Some(0) => None,
+                Some(secs) => Some(Duration::from_secs(secs)),
+                None => Some(DEFAULT_SERVER_TIME_SYNC_INTERVAL),
+            },
+        };
 
         // create new market to hold market data
-        let market = Market::new(market_rx.clone(), exchange_api.clone(), storage_manager).await;
+        let market = Market::new(
+            market_rx.clone(),
+            exchange_api.clone(),
+            storage_manager,
+            market_config,
+        )
+        .await;
+
+        // set max age of cached exchange metadata from config
+        market.set_metadata_max_age(metadata_max_age);
 
         let market = ArcMutex::new(market);
 
@@ -51,68 +269,151 @@ impl RaderBot {
 
         let account = ArcMutex::new(account);
 
-        Self {
+        // dispatches market updates to strategies as they are added
+        let mut strategy_runner = StrategyRunner::new(market.clone());
+        strategy_runner.start().await;
+
+        // record market messages to JSON lines file if configured
+        let market_recorder = match std::env::var("MARKET_RECORD_FILE") {
+            Ok(path) => {
+                let mut recorder = MarketRecorder::new(&PathBuf::from(path), exchange_api.clock());
+                match recorder.start(market.lock().await.subscribe()) {
+                    Ok(_) => Some(recorder),
+                    Err(e) => {
+                        warn!("Unable to start market recorder: {e}");
+                        None
+                    }
+                }
+            }
+            Err(_) => None,
+        };
+
+        // replay market messages recorded to JSON lines file if configured,
+        // eg. to debug strategy with messages it received before
+        if let Ok(path) = std::env::var("MARKET_REPLAY_FILE") {
+            // multiplier of recorded timing, replayed without waiting if not set
+            let speed = std::env::var("MARKET_REPLAY_SPEED")
+                .ok()
+                .and_then(|speed| speed.parse::<f64>().ok());
+            let replayer = MarketReplayer::new(&PathBuf::from(path), speed);
+            let market_sender = market_tx.clone();
+
+            tokio::spawn(async move {
+                match replayer.replay(&market_sender).await {
+                    Ok(sent) => info!("Replayed {sent} recorded market messages"),
+                    Err(e) => warn!("Unable to replay market messages: {e}"),
+                }
+            });
+        }
+
+        Ok(Self {
             market,
             // stream_manager,
             account,
             exchange_api: exchange_api.clone(),
-        }
+            strategy_runner: ArcMutex::new(strategy_runner),
+            market_sender: market_tx,
+            market_recorder,
+        })
     }
-}
-
-pub struct WsManager {
-    receivers: HashMap<String, Receiver<String>>,
-}
 
-pub const INTERVAL: Duration = Duration::from_secs(1);
+    /// Add strategy to receive market updates
+    #[cfg(test)]
+    pub async fn add_strategy(&self, strategy: Box<dyn Strategy>) {
+        let mut strategy_runner = self.strategy_runner.lock().await;
+        strategy_runner.add_strategy(strategy).await;
+        strategy_runner.start().await;
+    }
 
-#[derive(Debug)]
-pub struct WsMessage {
-    pub text: String,
-}
+    /// Stop strategies and market streams
+    pub async fn stop(&mut self) {
+        self.strategy_runner.lock().await.stop();
+        self.market.lock().await.shutdown().await;
+    }
 
-impl ToString for Message {
-    fn to_string(&self) -> String {
-        self.int.to_string()
+    /// Number of market messages dropped because market could not keep up
+    pub fn dropped_messages(&self) -> u64 {
+        self.market_sender.dropped_messages()
     }
-}
 
-impl WsManager {
-    pub fn new() -> Self {
-        Self {
-            receivers: HashMap::new(),
+    /// Stop recording market messages, recording file is kept,
+    /// returns false if market was not being recorded
+    pub async fn stop_recording(&mut self) -> bool {
+        match self.market_recorder.take() {
+            Some(mut recorder) => {
+                recorder.stop().await;
+                true
+            }
+            None => false,
         }
     }
 
-    pub fn get_ticker_stream(&self, symbol: &str) -> Receiver<String> {
-        if let Some(rec) = self.receivers.get(symbol) {
-            rec.clone()
-        } else {
-            let (sender, receiver) = channel::<String>("".to_string());
+    /// Check needed streams and API credentials against the exchange,
+    /// returns all issues found without starting any trading
+    pub async fn validate(&self) -> Vec<ValidationIssue> {
+        let mut issues = vec![];
 
-            // spawn new ticker thread
-            self.spawn_ticker_thread(symbol, sender);
-            receiver
+        let needed_streams = self.market.lock().await.needed_streams().await;
+
+        match self.exchange_api.get_symbols().await {
+            Ok(symbols) => {
+                for stream_meta in needed_streams.iter() {
+                    if !symbols.contains(&stream_meta.symbol) {
+                        issues.push(ValidationIssue::UnknownSymbol(
+                            stream_meta.symbol.to_string(),
+                        ));
+                    }
+                }
+            }
+            Err(e) => issues.push(ValidationIssue::ExchangeUnavailable(e.to_string())),
         }
-    }
 
-    pub async fn spawn_ticker_thread(&self, symbol: &str, _sender: Sender<String>) {
-        let _url = format!(
-            "https://open-api.bingx.com/openApi/swap/v2/quote/ticker?symbol={}",
-            symbol
-        );
+        for stream_meta in needed_streams.iter() {
+            if let Some(interval) = &stream_meta.interval {
+                if interval.parse::<Interval>().is_err() {
+                    issues.push(ValidationIssue::UnsupportedInterval(interval.to_string()));
+                }
+            }
+        }
 
-        // tokio::spawn(async move {
-        //     let val = get_ticker(&url).await;
-        //     sender.send(val);
+        // signed request to check API key is valid
+        if let Err(e) = self.exchange_api.get_balance(MARGIN_ASSET).await {
+            issues.push(ValidationIssue::InvalidCredentials(e.to_string()));
+        }
 
-        //     tokio::time::sleep(INTERVAL).await;
-        // });
+        issues
+    }
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub enum ValidationIssue {
+    UnknownSymbol(String),
+    UnsupportedInterval(String),
+    InvalidCredentials(String),
+    ExchangeUnavailable(String),
+}
+
+impl fmt::Display for ValidationIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ValidationIssue::UnknownSymbol(symbol) => {
+                write!(f, "Symbol not found on exchange: {}", symbol)
+            }
+            ValidationIssue::UnsupportedInterval(interval) => {
+                write!(f, "Unsupported kline interval: {}", interval)
+            }
+            ValidationIssue::InvalidCredentials(msg) => {
+                write!(f, "Invalid API credentials: {}", msg)
+            }
+            ValidationIssue::ExchangeUnavailable(msg) => {
+                write!(f, "Unable to reach exchange: {}", msg)
+            }
+        }
     }
 }
+
 pub struct AppState {
     pub bot: ArcMutex<RaderBot>,
-    pub ws_manager: ArcMutex<WsManager>,
 }
 
 impl AppState {
@@ -142,9 +443,117 @@ impl AppState {
     }
 }
 
-pub async fn new_app_state() -> Data<AppState> {
-    let bot = ArcMutex::new(RaderBot::new().await);
-    let ws_manager = ArcMutex::new(WsManager::new());
+pub async fn new_app_state() -> ApiResult<Data<AppState>> {
+    let bot = ArcMutex::new(RaderBot::new().await?);
+
+    Ok(Data::new(AppState { bot }))
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use std::sync::atomic::Ordering;
+    use tempfile::TempDir;
+
+    use super::*;
+    use crate::exchange::{mock::MockExchange, types::StreamType};
+    use crate::market::kline::Kline;
+    use crate::strategy::mock::CountingStrategy;
+
+    async fn mock_bot(symbols: &[&str]) -> (TempDir, RaderBot) {
+        let (market_tx, market_rx) = build_arc_channel::<MarketMessage>(DEFAULT_CHANNEL_CAPACITY);
+        // only BTC-USDT listed and API key rejected by exchange
+        let mut exchange = MockExchange::new();
+        exchange.account = json!({
+            "code": 100001,
+            "msg": "Signature verification failed"
+        });
+        let exchange_api: Arc<Box<dyn ExchangeApi>> = Arc::new(Box::new(exchange));
+
+        let storage_dir = TempDir::new().unwrap();
+        let market = Market::new(
+            market_rx,
+            exchange_api.clone(),
+            StorageManager::new(storage_dir.path().to_path_buf()),
+            MarketConfig::default(),
+        )
+        .await;
+        for symbol in symbols {
+            market
+                .add_needed_stream(symbol, StreamType::Kline, Some(Interval::Min1))
+                .await;
+        }
+
+        let market = ArcMutex::new(market);
+        let account = ArcMutex::new(Account::new(market.clone(), exchange_api.clone()).await);
+
+        let bot = RaderBot {
+            market: market.clone(),
+            account,
+            exchange_api,
+            strategy_runner: ArcMutex::new(StrategyRunner::new(market)),
+            market_sender: market_tx,
+            market_recorder: None,
+        };
+
+        (storage_dir, bot)
+    }
+
+    #[tokio::test]
+    async fn validate_reports_all_issues() {
+        let (_storage_dir, mut bot) = mock_bot(&["DOGE-FOO", "SHIB-FOO"]).await;
+
+        let issues = bot.validate().await;
+        bot.stop().await;
+
+        let unknown_symbols: Vec<&str> = issues
+            .iter()
+            .filter_map(|issue| match issue {
+                ValidationIssue::UnknownSymbol(symbol) => Some(symbol.as_str()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(unknown_symbols, vec!["DOGE-FOO", "SHIB-FOO"]);
+
+        assert!(issues.iter().any(|issue| matches!(
+            issue,
+            ValidationIssue::InvalidCredentials(msg) if msg.contains("100001")
+        )));
+        assert_eq!(issues.len(), 3);
+    }
+
+    fn update_kline(open_time: u64) -> MarketMessage {
+        MarketMessage::UpdateKline(Kline {
+            symbol: "BTC-USDT".to_string(),
+            interval: "1m".to_string(),
+            open_time,
+            close_time: open_time + 59_999,
+            ..Default::default()
+        })
+    }
+
+    #[tokio::test]
+    async fn added_strategy_receives_klines_until_bot_stops() {
+        let (_storage_dir, mut bot) = mock_bot(&["BTC-USDT"]).await;
 
-    Data::new(AppState { bot, ws_manager })
+        let strategy = CountingStrategy::default();
+        let klines = strategy.klines.clone();
+        bot.add_strategy(Box::new(strategy)).await;
+
+        bot.market_sender.send(update_kline(0)).unwrap();
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while klines.load(Ordering::SeqCst) < 1 {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .unwrap();
+
+        bot.stop().await;
+
+        // stopped strategies are not called
+        let _ = bot.market_sender.send(update_kline(60_000));
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(klines.load(Ordering::SeqCst), 1);
+    }
 }
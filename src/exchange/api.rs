@@ -1,41 +1,105 @@
 use async_trait::async_trait;
-use reqwest::{header::HeaderMap, Response};
+use reqwest::{
+    header::{HeaderMap, HeaderValue},
+    Response,
+};
 use serde_json::Value;
+use std::{fmt, time::Duration};
 
-use std::{error::Error, fmt};
+use std::sync::Arc;
 
 use crate::{
-    account::trade::OrderSide,
-    market::{kline::Kline, ticker::Ticker, types::ArcMutex},
+    account::{
+        balance::Balance,
+        trade::{MarginMode, OrderOptions, OrderSide, OrderStatus, OrderType, PositionSide},
+    },
+    market::{interval::Interval, kline::Kline, ticker::Ticker, types::ArcMutex},
+    utils::clock::{Clock, SystemClock},
 };
 
 use super::{
-    stream::{StreamManager, StreamMeta},
-    types::{ApiResult, StreamType},
+    stream::{StreamId, StreamManager, StreamMeta},
+    types::{ApiResult, ExchangeError, StreamType, TimestampUnit},
 };
 
-#[derive(Debug)]
-pub struct ApiError;
-
-impl Error for ApiError {}
-
-impl fmt::Display for ApiError {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "Oh no, something bad went down")
-    }
-}
+// Time waited between order status requests while waiting for fill
+pub const ORDER_STATUS_POLL_INTERVAL: Duration = Duration::from_millis(500);
 
 #[async_trait]
 pub trait ExchangeApi: Send + Sync {
+    // Identifier of exchange, eg. bingx
+    fn name(&self) -> &'static str;
+
     // ---
     // Account methods
     // ---
     async fn get_account(&self) -> ApiResult<Value>;
-    async fn open_position(&self, symbol: &str, side: OrderSide, quantity: f64)
-        -> ApiResult<Value>;
+    async fn get_balance(&self, asset: &str) -> ApiResult<Balance>;
+    async fn open_position(
+        &self,
+        symbol: &str,
+        side: OrderSide,
+        quantity: f64,
+        options: OrderOptions,
+    ) -> ApiResult<Value>;
+    async fn open_limit_order(
+        &self,
+        symbol: &str,
+        side: OrderSide,
+        quantity: f64,
+        price: f64,
+        options: OrderOptions,
+    ) -> ApiResult<Value>;
+    // Price is limit price for limit orders, trigger price for stop orders
+    async fn open_order(
+        &self,
+        symbol: &str,
+        side: OrderSide,
+        order_type: OrderType,
+        quantity: f64,
+        price: Option<f64>,
+        options: OrderOptions,
+    ) -> ApiResult<Value>;
     async fn close_position(&self, position_id: &str) -> ApiResult<Value>;
+    // Flatten open position of symbol, side is side position was opened with
+    async fn close_position_by_symbol(&self, symbol: &str, side: OrderSide) -> ApiResult<Value>;
+    async fn set_leverage(
+        &self,
+        symbol: &str,
+        leverage: u32,
+        side: PositionSide,
+    ) -> ApiResult<Value>;
+    async fn set_margin_mode(&self, symbol: &str, mode: MarginMode) -> ApiResult<Value>;
     async fn all_orders(&self) -> ApiResult<Value>;
     async fn list_open_orders(&self) -> ApiResult<Value>;
+    async fn cancel_order(&self, symbol: &str, order_id: &str) -> ApiResult<Value>;
+    async fn order_status(&self, symbol: &str, order_id: &str) -> ApiResult<OrderStatus>;
+    async fn cancel_all_orders(&self, symbol: &str) -> ApiResult<Value>;
+
+    /// Poll order status until order is filled, canceled or rejected,
+    /// last status is returned if order is still open once timeout elapses
+    /// on exchange clock
+    async fn wait_for_fill(
+        &self,
+        symbol: &str,
+        order_id: &str,
+        timeout: Duration,
+    ) -> ApiResult<OrderStatus> {
+        let clock = self.clock();
+        let deadline = clock.now() + timeout.as_millis() as u64;
+
+        loop {
+            let status = self.order_status(symbol, order_id).await?;
+
+            let remaining = deadline.saturating_sub(clock.now());
+            if status.is_terminal() || remaining == 0 {
+                return Ok(status);
+            }
+
+            let wait = ORDER_STATUS_POLL_INTERVAL.min(Duration::from_millis(remaining));
+            tokio::time::sleep(wait).await;
+        }
+    }
 
     // ---
     // Stream Methods
@@ -44,9 +108,9 @@ pub trait ExchangeApi: Send + Sync {
         &self,
         stream_type: StreamType,
         symbol: &str,
-        interval: Option<&str>,
-    ) -> ApiResult<String>;
-    async fn close_stream(&self, stream_id: &str) -> Option<StreamMeta>;
+        interval: Option<Interval>,
+    ) -> ApiResult<StreamId>;
+    async fn close_stream(&self, stream_id: &StreamId) -> Option<StreamMeta>;
 
     fn get_stream_manager(&self) -> ArcMutex<Box<dyn StreamManager>>;
 
@@ -56,36 +120,123 @@ pub trait ExchangeApi: Send + Sync {
         stream_manager.active_streams().await
     }
 
+    async fn active_streams_for(&self, symbol: &str) -> Vec<StreamMeta> {
+        let stream_manager = self.get_stream_manager();
+        let stream_manager = stream_manager.lock().await;
+        stream_manager.active_streams_for(symbol).await
+    }
+
+    async fn active_streams_of_type(&self, stream_type: StreamType) -> Vec<StreamMeta> {
+        let stream_manager = self.get_stream_manager();
+        let stream_manager = stream_manager.lock().await;
+        stream_manager.active_streams_of_type(stream_type).await
+    }
+
     // --
     // Exchange Methods
     // ---
-    async fn get_kline(&self, symbol: &str, interval: &str) -> ApiResult<Kline>;
+    async fn get_kline(&self, symbol: &str, interval: Interval) -> ApiResult<Kline>;
+    async fn get_klines(
+        &self,
+        symbol: &str,
+        interval: Interval,
+        limit: usize,
+    ) -> ApiResult<Vec<Kline>>;
+    async fn get_klines_range(
+        &self,
+        symbol: &str,
+        interval: Interval,
+        from_ts: u64,
+        to_ts: u64,
+    ) -> ApiResult<Vec<Kline>>;
     async fn get_ticker(&self, symbol: &str) -> ApiResult<Ticker>;
     async fn exchange_info(&self) -> ApiResult<Value>;
+    async fn get_symbols(&self) -> ApiResult<Vec<String>>;
+    async fn get_server_time(&self) -> ApiResult<u64>;
 
     // ---
     // HTTP Methods
     // ---
-    async fn get(
-        &self,
-        endpoint: &str,
-        query_str: Option<&str>,
-    ) -> Result<Response, reqwest::Error>;
-    async fn post(&self, endpoint: &str, query_str: &str) -> Result<Response, reqwest::Error>;
+    async fn get(&self, endpoint: &str, query_str: Option<&str>) -> ApiResult<Response>;
+    async fn post(&self, endpoint: &str, query_str: &str) -> ApiResult<Response>;
+    async fn delete(&self, endpoint: &str, query_str: &str) -> ApiResult<Response>;
 
     // ---
     // API Util methods
     // ---
     async fn handle_response(&self, response: Response) -> ApiResult<Value>;
 
-    fn build_headers(&self, json: bool) -> HeaderMap;
+    fn build_headers(&self, json: bool) -> ApiResult<HeaderMap>;
     fn build_stream_url(
         &self,
         symbol: &str,
         stream_type: StreamType,
-        interval: Option<&str>,
+        interval: Option<Interval>,
     ) -> String;
-    fn sign_query_str(&self, query_str: &str) -> String;
+    fn sign_query_str(&self, query_str: &str) -> ApiResult<String>;
+
+    // Unit of timestamp used in signed requests
+    fn timestamp_unit(&self) -> TimestampUnit;
+
+    // Milliseconds exchange server time is ahead of local time
+    fn time_offset(&self) -> i64;
+    fn set_time_offset(&self, offset: i64);
+
+    /// Measure offset of exchange server time from local time,
+    /// offset is applied to timestamps of signed requests
+    async fn sync_server_time(&self) -> ApiResult<i64> {
+        let request_ts = self.local_ts();
+        let server_ts = self.get_server_time().await?;
+        let response_ts = self.local_ts();
+
+        // assume server time was taken half way through request
+        let local_ts = request_ts + response_ts.saturating_sub(request_ts) / 2;
+        let offset = server_ts as i64 - local_ts as i64;
+
+        self.set_time_offset(offset);
+
+        Ok(offset)
+    }
+
+    /// Source of current time of exchange, replayed exchanges
+    /// use time of data being replayed
+    fn clock(&self) -> Arc<dyn Clock> {
+        Arc::new(SystemClock)
+    }
+
+    /// Local time in milliseconds used for signing, before server offset is applied
+    fn local_ts(&self) -> u64 {
+        self.clock().now()
+    }
+
+    fn signing_ts(&self) -> u64 {
+        let ts = self.local_ts().saturating_add_signed(self.time_offset());
+
+        self.timestamp_unit().convert_millis(ts)
+    }
+}
+
+/// Check credentials when exchange is created so bad keys
+/// fail fast instead of on first signed request
+pub fn validate_credentials(api_key: &str, secret_key: &str) -> ApiResult<()> {
+    for (name, key) in [("API key", api_key), ("Secret key", secret_key)] {
+        if key.trim().is_empty() {
+            return Err(ExchangeError::Auth(format!("{name} is empty")));
+        }
+        // usually caused by copying key with trailing newline
+        if key.trim() != key {
+            return Err(ExchangeError::Auth(format!(
+                "{name} has leading or trailing whitespace"
+            )));
+        }
+    }
+
+    // API key is sent in header
+    HeaderValue::from_str(api_key).map_err(|_| {
+        ExchangeError::Auth("API key contains characters not allowed in header".to_string())
+    })?;
+
+    Ok(())
 }
 
 pub struct QueryStr<'a> {
@@ -96,10 +247,25 @@ impl<'a> QueryStr<'a> {
     pub fn new(params: Vec<(&'a str, &'a str)>) -> Self {
         Self { params }
     }
+
+    /// Append timestamp and signature of query,
+    /// eg. symbol=BTC-USDT&timestamp=1700000000000&signature=ab12
+    pub fn signed(
+        mut self,
+        ts: &'a str,
+        sign: impl FnOnce(&str) -> ApiResult<String>,
+    ) -> ApiResult<String> {
+        self.params.push(("timestamp", ts));
+
+        let query_str = self.to_string();
+        let signature = sign(&query_str)?;
+
+        Ok(format!("{query_str}&signature={signature}"))
+    }
 }
 
-impl<'a> ToString for QueryStr<'a> {
-    fn to_string(&self) -> String {
+impl fmt::Display for QueryStr<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         let str_vec: Vec<String> = self
             .params
             .iter()
@@ -110,6 +276,68 @@ impl<'a> ToString for QueryStr<'a> {
 
         // remove last & from query_str
         query_str.pop();
-        query_str
+        write!(f, "{query_str}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn invalid_credentials_are_auth_errors() {
+        let cases = [
+            ("", "secret_key", "Auth error: API key is empty"),
+            ("api_key", "  ", "Auth error: Secret key is empty"),
+            (
+                "api_key\n",
+                "secret_key",
+                "Auth error: API key has leading or trailing whitespace",
+            ),
+            (
+                "api_\u{7f}key",
+                "secret_key",
+                "Auth error: API key contains characters not allowed in header",
+            ),
+        ];
+
+        for (api_key, secret_key, error) in cases {
+            assert_eq!(
+                validate_credentials(api_key, secret_key)
+                    .unwrap_err()
+                    .to_string(),
+                error
+            );
+        }
+
+        assert!(validate_credentials("api_key", "secret_key").is_ok());
+    }
+
+    #[test]
+    fn signed_query_ends_with_signature_of_query_and_timestamp() {
+        let query_str = QueryStr::new(vec![("symbol", "BTC-USDT"), ("orderId", "42")])
+            .signed("1700000000000", |unsigned| Ok(format!("sig({unsigned})")))
+            .unwrap();
+
+        assert_eq!(
+            query_str,
+            "symbol=BTC-USDT&orderId=42&timestamp=1700000000000\
+            &signature=sig(symbol=BTC-USDT&orderId=42&timestamp=1700000000000)"
+        );
+
+        // timestamp is only param of empty query
+        let query_str = QueryStr::new(vec![])
+            .signed("1700000000000", |_| Ok("ab12".to_string()))
+            .unwrap();
+        assert_eq!(query_str, "timestamp=1700000000000&signature=ab12");
+    }
+
+    #[test]
+    fn signing_error_is_returned() {
+        let result = QueryStr::new(vec![("symbol", "BTC-USDT")]).signed("1700000000000", |_| {
+            Err(ExchangeError::Signature("invalid key length".to_string()))
+        });
+
+        assert!(matches!(result, Err(ExchangeError::Signature(_))));
     }
 }
@@ -8,6 +8,8 @@ use reqwest::{Client, Response};
 use futures_util::StreamExt;
 use serde_json::{json, Value};
 use std::collections::HashMap;
+use std::sync::atomic::{AtomicI64, Ordering};
+use tracing::{error, warn};
 
 use tokio_tungstenite::connect_async;
 use tokio_tungstenite::tungstenite::Message;
@@ -15,17 +17,25 @@ use tokio_tungstenite::tungstenite::Message;
 use hmac::{Hmac, Mac};
 use sha2::Sha256;
 
-use crate::account::trade::OrderSide;
+use crate::account::{
+    balance::Balance,
+    trade::{MarginMode, OrderOptions, OrderSide, OrderStatus, OrderType, PositionSide},
+};
 use crate::exchange::api::{ExchangeApi, QueryStr};
+use crate::exchange::filters::SymbolFilters;
 use crate::exchange::types::ArcEsStreamSync;
 use crate::market::messages::MarketMessage;
 use crate::market::types::{ArcMutex, ArcSender};
-use crate::market::{kline::Kline, ticker::Ticker};
+use crate::market::{interval::Interval, kline::Kline, ticker::Ticker};
+use crate::storage::dead_letter::DeadLetterLog;
 use crate::utils::time::generate_ts;
 
 use super::stream::build_stream_id;
-use super::stream::{StreamManager, StreamMeta};
-use super::types::{ApiResult, StreamType};
+use super::stream::{StreamId, StreamManager, StreamMeta};
+use super::types::{ApiResult, ExchangeError, StreamType, TimestampUnit};
+
+// Max klines returned by single klines request
+const BINANCE_KLINES_LIMIT: usize = 1000;
 
 pub struct BinanceApi {
     ws_host: String,
@@ -34,10 +44,16 @@ pub struct BinanceApi {
     api_key: String,
     secret_key: String,
     stream_manager: ArcMutex<Box<dyn StreamManager>>,
+    time_offset: AtomicI64,
 }
 
 impl BinanceApi {
-    pub fn new(api_key: &str, secret_key: &str, market_sender: ArcSender<MarketMessage>) -> Self {
+    pub fn new(
+        api_key: &str,
+        secret_key: &str,
+        market_sender: ArcSender<MarketMessage>,
+        dead_letter_log: Option<DeadLetterLog>,
+    ) -> Self {
         let _ws_host = "wss://stream.binance.com".to_string();
         let _host = "https://api.binance.com".to_string();
 
@@ -45,8 +61,9 @@ impl BinanceApi {
         let host = "https://testnet.binance.vision".to_string();
         let ws_host = "wss://testnet.binance.vision".to_string();
 
-        let stream_manager: ArcMutex<Box<dyn StreamManager>> =
-            ArcMutex::new(Box::new(BinanceStreamManager::new(market_sender)));
+        let stream_manager: ArcMutex<Box<dyn StreamManager>> = ArcMutex::new(Box::new(
+            BinanceStreamManager::new(market_sender, dead_letter_log),
+        ));
 
         Self {
             ws_host,
@@ -55,26 +72,88 @@ impl BinanceApi {
             api_key: api_key.to_string(),
             secret_key: secret_key.to_string(),
             stream_manager,
+            time_offset: AtomicI64::new(0),
         }
     }
 
     pub fn parse_kline(res_str: &str) -> ApiResult<Kline> {
-        let lookup: HashMap<String, Value> = serde_json::from_str(res_str).unwrap();
+        let lookup: HashMap<String, Value> = serde_json::from_str(res_str)?;
 
         // build kline from hashmap
         Kline::from_binance_lookup(lookup)
     }
 
     pub fn parse_ticker(res_str: &str) -> ApiResult<Ticker> {
-        let lookup: HashMap<String, Value> = serde_json::from_str(res_str).unwrap();
+        let lookup: HashMap<String, Value> = serde_json::from_str(res_str)?;
 
         // build kline from hashmap
         Ticker::from_binance_lookup(lookup)
     }
+
+    /// Precision filters of symbol from exchange info of symbol
+    async fn symbol_filters(&self, symbol: &str) -> ApiResult<SymbolFilters> {
+        let query_str = format!("symbol={symbol}");
+        let res = self.get("/api/v3/exchangeInfo", Some(&query_str)).await?;
+
+        let exchange_info = self.handle_response(res).await?;
+
+        SymbolFilters::from_exchange_info(&exchange_info, symbol)
+    }
+
+    async fn fetch_klines(
+        &self,
+        symbol: &str,
+        interval: &str,
+        query_str: &str,
+    ) -> ApiResult<Vec<Kline>> {
+        let res = self.get("/api/v3/klines", Some(query_str)).await?;
+
+        let data = self.handle_response(res).await?;
+
+        let arr: Vec<Vec<Value>> = serde_json::from_value(data)?;
+
+        let now = self.clock().now();
+        let mut klines = vec![];
+
+        for kline in arr.iter() {
+            let parse_time = |idx: usize| {
+                kline
+                    .get(idx)
+                    .and_then(|val| val.as_u64())
+                    .ok_or_else(|| format!("Unable to parse kline time at index {idx}"))
+            };
+            let parse_price = |idx: usize| -> ApiResult<f64> {
+                let price = kline
+                    .get(idx)
+                    .and_then(|val| val.as_str())
+                    .ok_or_else(|| format!("Unable to parse kline value at index {idx}"))?;
+                Ok(price.parse::<f64>()?)
+            };
+
+            klines.push(Kline {
+                interval: interval.to_string(),
+                symbol: symbol.to_string(),
+                open_time: parse_time(0)?,
+                open: parse_price(1)?,
+                high: parse_price(2)?,
+                low: parse_price(3)?,
+                close: parse_price(4)?,
+                volume: parse_price(5)?,
+                close_time: parse_time(6)?,
+                is_closed: parse_time(6)? < now,
+            });
+        }
+
+        Ok(klines)
+    }
 }
 
 #[async_trait]
 impl ExchangeApi for BinanceApi {
+    fn name(&self) -> &'static str {
+        "binance"
+    }
+
     fn get_stream_manager(&self) -> ArcMutex<Box<dyn StreamManager>> {
         self.stream_manager.clone()
     }
@@ -83,30 +162,104 @@ impl ExchangeApi for BinanceApi {
         symbol: &str,
         side: OrderSide,
         quantity: f64,
+        _options: OrderOptions,
     ) -> ApiResult<Value> {
         let endpoint = "/api/v3/order";
 
         // format qty to 8 decimals
         let _qty = format!("{:.1$}", quantity, 8);
 
-        let ts = &generate_ts().to_string();
+        let ts = &self.signing_ts().to_string();
         let side = &side.to_string();
+        let order_type = &OrderType::Market.to_string();
         let quote_qty = 50.to_string();
 
         let request_body = QueryStr::new(vec![
             ("symbol", symbol),
             ("quoteOrderQty", &quote_qty),
             // ("quantity", &qty),
-            ("type", "MARKET"),
+            ("type", order_type),
             ("side", side),
             ("timestamp", ts),
         ]);
 
-        let signature = self.sign_query_str(&request_body.to_string());
+        let signature = self.sign_query_str(&request_body.to_string())?;
+
+        let query_str = format!("{request_body}&signature={signature}");
+
+        let res = self.post(endpoint, &query_str).await?;
+
+        self.handle_response(res).await
+    }
+
+    async fn open_limit_order(
+        &self,
+        symbol: &str,
+        side: OrderSide,
+        quantity: f64,
+        price: f64,
+        options: OrderOptions,
+    ) -> ApiResult<Value> {
+        self.open_order(
+            symbol,
+            side,
+            OrderType::Limit,
+            quantity,
+            Some(price),
+            options,
+        )
+        .await
+    }
+
+    async fn open_order(
+        &self,
+        symbol: &str,
+        side: OrderSide,
+        order_type: OrderType,
+        quantity: f64,
+        price: Option<f64>,
+        _options: OrderOptions,
+    ) -> ApiResult<Value> {
+        let endpoint = "/api/v3/order";
+
+        // snap qty and price to step and tick size of symbol
+        let filters = self.symbol_filters(symbol).await?;
+        let qty = filters.format_quantity(quantity)?;
+        let price = match (&order_type, price) {
+            (OrderType::Market, _) => None,
+            (_, Some(price)) => Some(filters.format_price(price)?),
+            (_, None) => return Err(format!("Price required for {order_type} order").into()),
+        };
 
-        let query_str = format!("{}&signature={signature}", request_body.to_string());
+        let ts = &self.signing_ts().to_string();
+        let side = &side.to_string();
+        let order_type_str = &order_type.to_string();
 
-        println!("qry_str: {query_str}");
+        let mut params = vec![
+            ("symbol", symbol),
+            ("side", side.as_str()),
+            ("type", order_type_str.as_str()),
+            ("quantity", &qty),
+        ];
+
+        match (&order_type, &price) {
+            (OrderType::Limit, Some(price)) => {
+                params.push(("timeInForce", "GTC"));
+                params.push(("price", price));
+            }
+            (OrderType::StopMarket | OrderType::TakeProfitMarket, Some(price)) => {
+                params.push(("stopPrice", price));
+            }
+            _ => {}
+        }
+
+        params.push(("timestamp", ts));
+
+        let request_body = QueryStr::new(params);
+
+        let signature = self.sign_query_str(&request_body.to_string())?;
+
+        let query_str = format!("{request_body}&signature={signature}");
 
         let res = self.post(endpoint, &query_str).await?;
 
@@ -117,12 +270,63 @@ impl ExchangeApi for BinanceApi {
         Ok(json!({"ok":"ok"}))
     }
 
+    // spot account has balances, not positions to close
+    async fn close_position_by_symbol(&self, _symbol: &str, _side: OrderSide) -> ApiResult<Value> {
+        Err(ExchangeError::Unsupported(
+            "Binance spot has no positions to close".to_string(),
+        ))
+    }
+
+    // leverage and margin mode only apply to futures
+    async fn set_leverage(
+        &self,
+        _symbol: &str,
+        _leverage: u32,
+        _side: PositionSide,
+    ) -> ApiResult<Value> {
+        Err(ExchangeError::Unsupported(
+            "Binance spot has no leverage".to_string(),
+        ))
+    }
+
+    async fn set_margin_mode(&self, _symbol: &str, _mode: MarginMode) -> ApiResult<Value> {
+        Err(ExchangeError::Unsupported(
+            "Binance spot has no margin mode".to_string(),
+        ))
+    }
+
+    async fn order_status(&self, symbol: &str, order_id: &str) -> ApiResult<OrderStatus> {
+        let endpoint = "/api/v3/order";
+        let ts = self.signing_ts().to_string();
+
+        let request_body = QueryStr::new(vec![
+            ("symbol", symbol),
+            ("orderId", order_id),
+            ("timestamp", &ts),
+        ]);
+
+        let signature = self.sign_query_str(&request_body.to_string())?;
+
+        let query_str = format!("{request_body}&signature={signature}");
+
+        let res = self.get(endpoint, Some(&query_str)).await?;
+
+        let data = self.handle_response(res).await?;
+
+        let status = data
+            .get("status")
+            .and_then(|status| status.as_str())
+            .ok_or_else(|| "Missing 'status' key from order response".to_string())?;
+
+        Ok(status.parse()?)
+    }
+
     async fn get_account(&self) -> ApiResult<Value> {
         let endpoint = "/api/v3/account";
-        let ts = generate_ts();
+        let ts = self.signing_ts();
 
         let query_str = format!("timestamp={ts}");
-        let signature = self.sign_query_str(&query_str);
+        let signature = self.sign_query_str(&query_str)?;
         let query_str = format!("{}&signature={signature}", query_str);
 
         let res = self.get(endpoint, Some(&query_str)).await?;
@@ -130,35 +334,65 @@ impl ExchangeApi for BinanceApi {
         self.handle_response(res).await
     }
 
-    async fn get_kline(&self, symbol: &str, interval: &str) -> ApiResult<Kline> {
-        let endpoint = format!("/api/v3/klines?symbol={symbol}&interval={interval}&limit=1");
+    async fn get_balance(&self, asset: &str) -> ApiResult<Balance> {
+        let account = self.get_account().await?;
 
-        let res = self.get(&endpoint, None).await?;
+        Balance::from_account_lookup(&account, asset)
+    }
 
-        let data = self.handle_response(res).await?;
+    async fn get_kline(&self, symbol: &str, interval: Interval) -> ApiResult<Kline> {
+        // klines are in ascending order, last kline is latest
+        self.get_klines(symbol, interval, 1)
+            .await?
+            .pop()
+            .ok_or_else(|| "Empty klines response".to_string().into())
+    }
+
+    async fn get_klines(
+        &self,
+        symbol: &str,
+        interval: Interval,
+        limit: usize,
+    ) -> ApiResult<Vec<Kline>> {
+        let query_str = format!("symbol={symbol}&interval={interval}&limit={limit}");
+
+        self.fetch_klines(symbol, interval.as_str(), &query_str)
+            .await
+    }
+
+    async fn get_klines_range(
+        &self,
+        symbol: &str,
+        interval: Interval,
+        from_ts: u64,
+        to_ts: u64,
+    ) -> ApiResult<Vec<Kline>> {
+        let mut klines: Vec<Kline> = vec![];
+        let mut start_time = from_ts;
+
+        while start_time <= to_ts {
+            let query_str = format!(
+                "symbol={symbol}&interval={interval}&limit={BINANCE_KLINES_LIMIT}&startTime={start_time}&endTime={to_ts}"
+            );
+            let page = self
+                .fetch_klines(symbol, interval.as_str(), &query_str)
+                .await?;
+            let page_len = page.len();
+
+            // skip klines already added from previous page
+            let last_open_time = klines.last().map(|kline| kline.open_time);
+            klines.extend(
+                page.into_iter()
+                    .filter(|kline| last_open_time.is_none_or(|last| kline.open_time > last)),
+            );
+
+            match klines.last() {
+                Some(last) if page_len == BINANCE_KLINES_LIMIT => start_time = last.open_time + 1,
+                _ => break,
+            }
+        }
 
-        // println!("{res:?}");
-
-        let arr: Vec<Vec<Value>> = serde_json::from_value(data).unwrap();
-        let open_time = arr[0][0].as_u64().unwrap();
-        let open = arr[0][1].as_str().unwrap().parse::<f64>().unwrap();
-        let high = arr[0][2].as_str().unwrap().parse::<f64>().unwrap();
-        let low = arr[0][3].as_str().unwrap().parse::<f64>().unwrap();
-        let close = arr[0][4].as_str().unwrap().parse::<f64>().unwrap();
-        let volume = arr[0][5].as_str().unwrap().parse::<f64>().unwrap();
-        let close_time = arr[0][6].as_u64().unwrap();
-
-        Ok(Kline {
-            interval: interval.to_string(),
-            symbol: symbol.to_string(),
-            open_time,
-            open,
-            high,
-            low,
-            close,
-            volume,
-            close_time,
-        })
+        Ok(klines)
     }
 
     async fn get_ticker(&self, _symbol: &str) -> ApiResult<Ticker> {
@@ -167,10 +401,10 @@ impl ExchangeApi for BinanceApi {
 
     async fn all_orders(&self) -> ApiResult<Value> {
         let endpoint = "/api/v3/allOrderList";
-        let ts = generate_ts();
+        let ts = self.signing_ts();
 
         let query_str = format!("timestamp={ts}");
-        let signature = self.sign_query_str(&query_str);
+        let signature = self.sign_query_str(&query_str)?;
         let query_str = format!("{}&signature={signature}", query_str);
 
         let res = self.get(endpoint, Some(&query_str)).await?;
@@ -179,10 +413,10 @@ impl ExchangeApi for BinanceApi {
     }
     async fn list_open_orders(&self) -> ApiResult<Value> {
         let endpoint = "/api/v3/openOrderList";
-        let ts = generate_ts();
+        let ts = self.signing_ts();
 
         let query_str = format!("timestamp={ts}");
-        let signature = self.sign_query_str(&query_str);
+        let signature = self.sign_query_str(&query_str)?;
         let query_str = format!("{}&signature={signature}", query_str);
 
         let res = self.get(endpoint, Some(&query_str)).await?;
@@ -190,6 +424,40 @@ impl ExchangeApi for BinanceApi {
         self.handle_response(res).await
     }
 
+    async fn cancel_order(&self, symbol: &str, order_id: &str) -> ApiResult<Value> {
+        let endpoint = "/api/v3/order";
+        let ts = &self.signing_ts().to_string();
+
+        let request_body = QueryStr::new(vec![
+            ("symbol", symbol),
+            ("orderId", order_id),
+            ("timestamp", ts),
+        ]);
+
+        let signature = self.sign_query_str(&request_body.to_string())?;
+
+        let query_str = format!("{request_body}&signature={signature}");
+
+        let res = self.delete(endpoint, &query_str).await?;
+
+        self.handle_response(res).await
+    }
+
+    async fn cancel_all_orders(&self, symbol: &str) -> ApiResult<Value> {
+        let endpoint = "/api/v3/openOrders";
+        let ts = &self.signing_ts().to_string();
+
+        let request_body = QueryStr::new(vec![("symbol", symbol), ("timestamp", ts)]);
+
+        let signature = self.sign_query_str(&request_body.to_string())?;
+
+        let query_str = format!("{request_body}&signature={signature}");
+
+        let res = self.delete(endpoint, &query_str).await?;
+
+        self.handle_response(res).await
+    }
+
     // ---
     // Stream Methods
     // ---
@@ -197,16 +465,22 @@ impl ExchangeApi for BinanceApi {
         &self,
         stream_type: StreamType,
         symbol: &str,
-        interval: Option<&str>,
-    ) -> ApiResult<String> {
+        interval: Option<Interval>,
+    ) -> ApiResult<StreamId> {
         let url = self.build_stream_url(symbol, stream_type.clone(), interval);
-        let stream_id = build_stream_id(symbol, interval);
+        let stream_id = build_stream_id(symbol, interval.map(|interval| interval.as_str()));
 
-        let interval = interval.map(|s| s.to_owned());
+        let interval = interval.map(|interval| interval.to_string());
 
         // create new StreamMeta
-        let open_stream_meta =
-            StreamMeta::new(&stream_id, &url, symbol, stream_type.clone(), interval);
+        let open_stream_meta = StreamMeta::new_at(
+            stream_id,
+            &url,
+            symbol,
+            stream_type.clone(),
+            interval,
+            self.clock().now(),
+        );
 
         self.stream_manager
             .lock()
@@ -215,7 +489,7 @@ impl ExchangeApi for BinanceApi {
             .await
     }
 
-    async fn close_stream(&self, stream_id: &str) -> Option<StreamMeta> {
+    async fn close_stream(&self, stream_id: &StreamId) -> Option<StreamMeta> {
         self.stream_manager
             .clone()
             .lock()
@@ -227,6 +501,21 @@ impl ExchangeApi for BinanceApi {
     // ---
     // Exchange Methods
     // ---
+    async fn get_server_time(&self) -> ApiResult<u64> {
+        let endpoint = "/api/v3/time";
+
+        let res = self.get(endpoint, None).await?;
+
+        let data = self.handle_response(res).await?;
+
+        let server_time = data
+            .get("serverTime")
+            .and_then(|time| time.as_u64())
+            .ok_or_else(|| "Missing 'serverTime' key from server time response".to_string())?;
+
+        Ok(server_time)
+    }
+
     async fn exchange_info(&self) -> ApiResult<Value> {
         let endpoint = "/api/v3/exchangeInfo";
 
@@ -235,43 +524,82 @@ impl ExchangeApi for BinanceApi {
         self.handle_response(res).await
     }
 
+    async fn get_symbols(&self) -> ApiResult<Vec<String>> {
+        let data = self.exchange_info().await?;
+
+        let symbols = data
+            .get("symbols")
+            .and_then(|symbols| symbols.as_array())
+            .ok_or_else(|| "Missing 'symbols' list from exchange info response".to_string())?;
+
+        let symbols = symbols
+            .iter()
+            .filter(|symbol| {
+                symbol
+                    .get("status")
+                    .is_none_or(|status| status.as_str() == Some("TRADING"))
+            })
+            .filter_map(|symbol| symbol.get("symbol")?.as_str().map(|s| s.to_string()))
+            .collect();
+
+        Ok(symbols)
+    }
+
     // ---
     // API Util methods
     // ---
-    async fn get(
-        &self,
-        endpoint: &str,
-        query_str: Option<&str>,
-    ) -> Result<Response, reqwest::Error> {
-        // let signature = self.sign_query_str(query_str);
+    async fn get(&self, endpoint: &str, query_str: Option<&str>) -> ApiResult<Response> {
         let url = match query_str {
             Some(qs) => format!("{}{}?{}", self.host, endpoint, qs),
             None => format!("{}{}", self.host, endpoint),
         };
 
-        self.client
+        let res = self
+            .client
             .get(&url)
-            .headers(self.build_headers(true))
+            .headers(self.build_headers(true)?)
             .send()
-            .await
+            .await?;
+
+        Ok(res)
     }
 
-    async fn post(&self, endpoint: &str, query_str: &str) -> Result<Response, reqwest::Error> {
+    async fn post(&self, endpoint: &str, query_str: &str) -> ApiResult<Response> {
         let url = format!("{}{}", self.host, endpoint);
         let body = query_str.to_string();
 
-        self.client
+        let res = self
+            .client
             .post(&url)
-            .headers(self.build_headers(true))
+            .headers(self.build_headers(true)?)
             .body(body)
             .send()
-            .await
+            .await?;
+
+        Ok(res)
+    }
+
+    async fn delete(&self, endpoint: &str, query_str: &str) -> ApiResult<Response> {
+        let url = format!("{}{}?{}", self.host, endpoint, query_str);
+
+        let res = self
+            .client
+            .delete(&url)
+            .headers(self.build_headers(true)?)
+            .send()
+            .await?;
+
+        Ok(res)
     }
 
     async fn handle_response(&self, response: Response) -> ApiResult<Value> {
         let data = match &response.headers().get("content-type") {
             Some(header) => {
-                if header.to_str().unwrap().contains("text/html") {
+                let content_type = header
+                    .to_str()
+                    .map_err(|e| ExchangeError::Parsing(e.to_string()))?;
+
+                if content_type.contains("text/html") {
                     json!({"text":response.text().await?})
                 } else {
                     response.json::<serde_json::Value>().await?
@@ -283,7 +611,7 @@ impl ExchangeApi for BinanceApi {
         Ok(data)
     }
 
-    fn build_headers(&self, json: bool) -> HeaderMap {
+    fn build_headers(&self, json: bool) -> ApiResult<HeaderMap> {
         let mut custom_headers = HeaderMap::new();
 
         // custom_headers.insert(USER_AGENT, HeaderValue::from_static("binance-rs"));
@@ -292,16 +620,17 @@ impl ExchangeApi for BinanceApi {
         }
         custom_headers.insert(
             "X-MBX-APIKEY",
-            HeaderValue::from_str(self.api_key.as_str()).expect("Unable to get API key"),
+            HeaderValue::from_str(self.api_key.as_str())
+                .map_err(|_| ExchangeError::Auth("Invalid API key".to_string()))?,
         );
 
-        custom_headers
+        Ok(custom_headers)
     }
 
-    fn sign_query_str(&self, query_str: &str) -> String {
+    fn sign_query_str(&self, query_str: &str) -> ApiResult<String> {
         // Create a new HMAC instance with SHA256
-        let mut hmac =
-            Hmac::<Sha256>::new_from_slice(self.secret_key.as_bytes()).expect("Invalid key length");
+        let mut hmac = Hmac::<Sha256>::new_from_slice(self.secret_key.as_bytes())
+            .map_err(|e| ExchangeError::Signature(e.to_string()))?;
 
         // Update the HMAC with the data
         hmac.update(query_str.as_bytes());
@@ -310,14 +639,26 @@ impl ExchangeApi for BinanceApi {
         let result = hmac.finalize();
 
         // Convert the HMAC value to a string
-        hex::encode(result.into_bytes())
+        Ok(hex::encode(result.into_bytes()))
+    }
+
+    fn timestamp_unit(&self) -> TimestampUnit {
+        TimestampUnit::Milliseconds
+    }
+
+    fn time_offset(&self) -> i64 {
+        self.time_offset.load(Ordering::Relaxed)
+    }
+
+    fn set_time_offset(&self, offset: i64) {
+        self.time_offset.store(offset, Ordering::Relaxed);
     }
 
     fn build_stream_url(
         &self,
         symbol: &str,
         stream_type: StreamType,
-        interval: Option<&str>,
+        interval: Option<Interval>,
     ) -> String {
         let url = match stream_type {
             StreamType::Kline => {
@@ -338,24 +679,33 @@ impl ExchangeApi for BinanceApi {
 }
 
 pub struct BinanceStreamManager {
-    streams: HashMap<String, ArcEsStreamSync>,
+    streams: HashMap<StreamId, ArcEsStreamSync>,
     market_sender: ArcSender<MarketMessage>,
-    stream_metas: ArcMutex<HashMap<String, StreamMeta>>,
+    stream_metas: ArcMutex<HashMap<StreamId, StreamMeta>>,
+    dead_letter_log: Option<DeadLetterLog>,
 }
 
 impl BinanceStreamManager {
-    pub fn new(market_sender: ArcSender<MarketMessage>) -> Self {
+    pub fn new(
+        market_sender: ArcSender<MarketMessage>,
+        dead_letter_log: Option<DeadLetterLog>,
+    ) -> Self {
         Self {
             streams: HashMap::new(),
             market_sender,
             stream_metas: ArcMutex::new(HashMap::new()),
+            dead_letter_log,
         }
     }
 }
 
 #[async_trait]
 impl StreamManager for BinanceStreamManager {
-    async fn open_stream(&mut self, stream_meta: StreamMeta) -> ApiResult<String> {
+    async fn open_stream(&mut self, stream_meta: StreamMeta) -> ApiResult<StreamId> {
+        if self.is_stream_open(&stream_meta.id).await {
+            return Ok(stream_meta.id);
+        }
+
         let (ws_stream, _) = connect_async(stream_meta.url.to_string())
             .await
             .unwrap_or_else(|_| {
@@ -373,12 +723,13 @@ impl StreamManager for BinanceStreamManager {
         stream_metas
             .lock()
             .await
-            .insert(stream_meta.id.to_string(), stream_meta.clone());
+            .insert(stream_meta.id.clone(), stream_meta.clone());
 
         let sync = ArcMutex::new(sync);
         self.streams.insert(stream_meta.id.clone(), sync);
 
         let market_sender = self.market_sender.clone();
+        let dead_letter_log = self.dead_letter_log.clone();
 
         let thread_stream_id = stream_meta.id.clone();
 
@@ -402,6 +753,9 @@ impl StreamManager for BinanceStreamManager {
                                         if let Ok(kline) = kline {
                                             let _ = market_sender
                                                 .send(MarketMessage::UpdateKline(kline));
+                                        } else if let Some(dead_letter_log) = &dead_letter_log {
+                                            let _ = dead_letter_log
+                                                .log(thread_stream_id.as_str(), &text);
                                         }
                                     }
                                     StreamType::Ticker => {
@@ -410,6 +764,9 @@ impl StreamManager for BinanceStreamManager {
                                         if let Ok(ticker) = ticker {
                                             let _ = market_sender
                                                 .send(MarketMessage::UpdateTicker(ticker));
+                                        } else if let Some(dead_letter_log) = &dead_letter_log {
+                                            let _ = dead_letter_log
+                                                .log(thread_stream_id.as_str(), &text);
                                         }
                                     }
                                 }
@@ -427,21 +784,21 @@ impl StreamManager for BinanceStreamManager {
                             // ignore Ping Pong Messages
                         }
                         _ => {
-                            println!("Received unexpected data: {:?}", msg);
+                            warn!("Received unexpected data: {:?}", msg);
                         }
                     },
                     Err(e) => {
                         // Handle error
-                        eprintln!("Error receiving message: {:?}", e);
+                        error!("Error receiving message: {:?}", e);
                     }
                 }
             }
         });
 
-        Ok(stream_meta.id.to_string())
+        Ok(stream_meta.id.clone())
     }
 
-    async fn close_stream(&mut self, stream_id: &str) -> Option<StreamMeta> {
+    async fn close_stream(&mut self, stream_id: &StreamId) -> Option<StreamMeta> {
         let mut infos = self.stream_metas.lock().await;
 
         if let Some(stream_meta) = infos.get_mut(stream_id) {
@@ -457,7 +814,7 @@ impl StreamManager for BinanceStreamManager {
     // ---
     // Accessor methods for trait
     // ---
-    fn stream_metas(&self) -> ArcMutex<HashMap<String, StreamMeta>> {
+    fn stream_metas(&self) -> ArcMutex<HashMap<StreamId, StreamMeta>> {
         self.stream_metas.clone()
     }
 }
@@ -1,165 +1,859 @@
 use async_trait::async_trait;
 
 use futures_util::SinkExt;
-use log::warn;
+use tracing::{debug, info_span, instrument, warn, Instrument};
 
-use reqwest::header::{HeaderMap, HeaderValue, CONTENT_TYPE};
-use reqwest::{Client, Response};
+use reqwest::header::{HeaderMap, HeaderValue, CONTENT_TYPE, RETRY_AFTER};
+use reqwest::{Client, Method, RequestBuilder, Response, StatusCode};
 // use reqwest::Client;
 
 use futures_util::StreamExt;
 use serde_json::{json, Value};
 use std::collections::HashMap;
+use std::sync::{
+    atomic::{AtomicI64, Ordering},
+    Arc,
+};
 
 use std::time::Duration;
+use tokio::net::TcpStream;
+use tokio::sync::mpsc::{unbounded_channel, UnboundedSender};
+use tokio::sync::oneshot;
 use tokio::task::JoinHandle;
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::{connect_async, MaybeTlsStream, WebSocketStream};
+use uuid::Uuid;
 
+use governor::Quota;
 use hmac::{Hmac, Mac};
 use sha2::Sha256;
 
-use crate::account::trade::OrderSide;
-use crate::exchange::api::{ExchangeApi, QueryStr};
+use crate::account::{
+    balance::Balance,
+    trade::{MarginMode, OrderOptions, OrderSide, OrderStatus, OrderType, PositionSide},
+};
+use crate::exchange::api::{validate_credentials, ExchangeApi, QueryStr};
 
 use crate::market::messages::MarketMessage;
 use crate::market::types::{ArcMutex, ArcSender};
-use crate::market::{kline::Kline, ticker::Ticker};
+use crate::market::{interval::Interval, kline::Kline, ticker::Ticker};
 
-use crate::utils::time::generate_ts;
+use crate::storage::dead_letter::DeadLetterLog;
+use crate::utils::clock::{system_clock, Clock};
+use crate::utils::json::parse_gzip_to_string;
+use crate::utils::rate_limit::{build_rate_limiter, requests_per_second, ArcRateLimiter};
 
+use super::filters::SymbolFilters;
+use super::metadata::{Cached, DEFAULT_METADATA_MAX_AGE};
 use super::stream::build_stream_id;
-use super::stream::{StreamManager, StreamMeta};
-use super::types::{ApiResult, StreamType};
+use super::stream::{StreamId, StreamManager, StreamMeta};
+use super::types::{ApiResult, Environment, ExchangeError, StreamType, TimestampUnit};
 
 const BING_X_WS_HOST_URL: &str = "wss://open-api-swap.bingx.com/swap-market";
 const BING_X_HOST_URL: &str = "https://open-api.bingx.com";
 
-pub struct BingXApi {
-    ws_host: String,
+// Max klines returned by single klines request
+const BING_X_KLINES_LIMIT: usize = 1000;
+
+// Default max milliseconds after timestamp a signed request is valid for
+pub const BING_X_RECV_WINDOW: u64 = 5000;
+
+// Default max requests sent to BingX REST API each second
+pub const BING_X_REQUESTS_PER_SECOND: u32 = 10;
+
+// Max times request is retried after rate limited response
+const BING_X_MAX_RETRIES: u32 = 3;
+// Delay before retry if rate limited response has no Retry-After header
+const BING_X_RETRY_BASE_DELAY: Duration = Duration::from_secs(1);
+// Longest Retry-After delay waited before retry, longer delays are returned as error
+const BING_X_MAX_RETRY_DELAY: Duration = Duration::from_secs(10);
+
+// Default time to wait for response before request fails
+pub const BING_X_REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+// Time to wait for web socket to send unsubscriptions and close before it is aborted
+const BING_X_WS_CLOSE_TIMEOUT: Duration = Duration::from_secs(5);
+
+// Testnet hosts
+const BING_X_TESTNET_WS_HOST_URL: &str = "wss://vst-open-api-ws.bingx.com/swap-market";
+const BING_X_TESTNET_HOST_URL: &str = "https://open-api-vst.bingx.com";
+
+/// HTTP client for BingX REST API, cloned into stream tasks so all
+/// requests reuse pooled connections and count towards same rate limit
+#[derive(Clone)]
+pub struct BingXClient {
     host: String,
     client: Client,
+    rate_limiter: ArcRateLimiter,
+}
+
+impl BingXClient {
+    pub fn new(host: &str, request_timeout: Duration, rate_limit: Quota) -> ApiResult<Self> {
+        let client = Client::builder().timeout(request_timeout).build()?;
+
+        Ok(Self {
+            host: host.to_string(),
+            client,
+            rate_limiter: build_rate_limiter(rate_limit),
+        })
+    }
+
+    /// Send unsigned GET request to public market endpoint
+    pub async fn get_public(
+        &self,
+        endpoint: &str,
+        query_str: &QueryStr<'_>,
+    ) -> ApiResult<Response> {
+        let url = format!("{}{}?{}", self.host, endpoint, query_str);
+
+        self.send_with_retry(|| Ok(self.client.get(&url))).await
+    }
+
+    /// Send request once permitted by rate limiter, rate limited responses
+    /// are retried after Retry-After delay up to max retries, delays longer
+    /// than max retry delay are returned to caller as error. Request is
+    /// built again for each attempt so signed requests get fresh timestamp
+    async fn send_with_retry(
+        &self,
+        build_request: impl Fn() -> ApiResult<RequestBuilder>,
+    ) -> ApiResult<Response> {
+        let mut retries = 0;
+        let mut delay = BING_X_RETRY_BASE_DELAY;
+
+        loop {
+            let request = build_request()?;
+
+            self.rate_limiter.until_ready().await;
+            let res = request.send().await?;
+            let status = res.status();
+            debug!(%status, "Received BingX response");
+
+            match check_rate_limit(&res) {
+                // 418 means IP is banned, retrying would only extend ban
+                Err(ExchangeError::RateLimited { retry_after })
+                    if status == StatusCode::TOO_MANY_REQUESTS
+                        && retries < BING_X_MAX_RETRIES
+                        && retry_after.is_none_or(|wait| wait <= BING_X_MAX_RETRY_DELAY) =>
+                {
+                    let wait = retry_after.unwrap_or(delay);
+                    warn!("Rate limited by BingX, retrying in {wait:?}");
+
+                    tokio::time::sleep(wait).await;
+                    retries += 1;
+                    delay *= 2;
+                }
+                Err(e) => return Err(e),
+                Ok(()) => return Ok(res),
+            }
+        }
+    }
+}
+
+pub struct BingXApi {
+    ws_host: String,
+    http: BingXClient,
     api_key: String,
     secret_key: String,
     stream_manager: ArcMutex<Box<dyn StreamManager>>,
+    time_offset: AtomicI64,
+    recv_window: u64,
+    // precision of each symbol, loaded from contracts on first order
+    // and reloaded once older than metadata max age
+    symbol_filters: ArcMutex<Option<Cached<HashMap<String, SymbolFilters>>>>,
+    metadata_max_age: Duration,
+    // source of local time for signed requests
+    clock: Arc<dyn Clock>,
 }
 
-impl BingXApi {
-    pub fn new(api_key: &str, secret_key: &str, market_sender: ArcSender<MarketMessage>) -> Self {
-        let ws_host = BING_X_WS_HOST_URL.to_string();
-        let host = BING_X_HOST_URL.to_string();
-
-        // Testnet hosts
-
-        let stream_manager: ArcMutex<Box<dyn StreamManager>> =
-            ArcMutex::new(Box::new(BingXStreamManager::new(market_sender)));
+/// Configures BingX API, options not set use mainnet defaults
+pub struct BingXApiBuilder {
+    api_key: String,
+    secret_key: String,
+    market_sender: ArcSender<MarketMessage>,
+    dead_letter_log: Option<DeadLetterLog>,
+    environment: Environment,
+    rate_limit: Quota,
+    request_timeout: Duration,
+    recv_window: u64,
+    metadata_max_age: Duration,
+    clock: Arc<dyn Clock>,
+}
 
+impl BingXApiBuilder {
+    pub fn new(api_key: &str, secret_key: &str, market_sender: ArcSender<MarketMessage>) -> Self {
         Self {
-            ws_host,
-            host,
-            client: Client::builder().build().unwrap(),
             api_key: api_key.to_string(),
             secret_key: secret_key.to_string(),
+            market_sender,
+            dead_letter_log: None,
+            environment: Environment::Mainnet,
+            rate_limit: requests_per_second(BING_X_REQUESTS_PER_SECOND),
+            request_timeout: BING_X_REQUEST_TIMEOUT,
+            recv_window: BING_X_RECV_WINDOW,
+            metadata_max_age: DEFAULT_METADATA_MAX_AGE,
+            clock: system_clock(),
+        }
+    }
+
+    /// Log stream messages which could not be parsed
+    pub fn dead_letter_log(mut self, dead_letter_log: Option<DeadLetterLog>) -> Self {
+        self.dead_letter_log = dead_letter_log;
+        self
+    }
+
+    pub fn environment(mut self, environment: Environment) -> Self {
+        self.environment = environment;
+        self
+    }
+
+    /// Use demo trading hosts
+    pub fn testnet(self) -> Self {
+        self.environment(Environment::Testnet)
+    }
+
+    /// Send requests to custom hosts, eg. local mock server
+    pub fn hosts(self, host: &str, ws_host: &str) -> Self {
+        self.environment(Environment::Custom {
+            host: host.to_string(),
+            ws_host: ws_host.to_string(),
+        })
+    }
+
+    pub fn rate_limit(mut self, rate_limit: Quota) -> Self {
+        self.rate_limit = rate_limit;
+        self
+    }
+
+    pub fn request_timeout(mut self, request_timeout: Duration) -> Self {
+        self.request_timeout = request_timeout;
+        self
+    }
+
+    /// Max milliseconds after timestamp signed requests are valid for
+    pub fn recv_window(mut self, recv_window: u64) -> Self {
+        self.recv_window = recv_window;
+        self
+    }
+
+    /// Max age of cached contract precision filters
+    pub fn metadata_max_age(mut self, metadata_max_age: Duration) -> Self {
+        self.metadata_max_age = metadata_max_age;
+        self
+    }
+
+    #[cfg(test)]
+    pub fn clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    pub fn build(self) -> ApiResult<BingXApi> {
+        validate_credentials(&self.api_key, &self.secret_key)?;
+
+        let (ws_host, host) = match &self.environment {
+            Environment::Mainnet => (BING_X_WS_HOST_URL, BING_X_HOST_URL),
+            Environment::Testnet => (BING_X_TESTNET_WS_HOST_URL, BING_X_TESTNET_HOST_URL),
+            Environment::Custom { host, ws_host } => (ws_host.as_str(), host.as_str()),
+        };
+
+        let http = BingXClient::new(host, self.request_timeout, self.rate_limit)?;
+
+        let stream_manager: ArcMutex<Box<dyn StreamManager>> =
+            ArcMutex::new(Box::new(BingXStreamManager::new(
+                http.clone(),
+                self.market_sender,
+                self.dead_letter_log,
+                self.clock.clone(),
+            )));
+
+        Ok(BingXApi {
+            ws_host: ws_host.to_string(),
+            http,
+            api_key: self.api_key,
+            secret_key: self.secret_key,
             stream_manager,
+            time_offset: AtomicI64::new(0),
+            recv_window: self.recv_window,
+            symbol_filters: ArcMutex::new(None),
+            metadata_max_age: self.metadata_max_age,
+            clock: self.clock,
+        })
+    }
+}
+
+impl BingXApi {
+    pub fn builder(
+        api_key: &str,
+        secret_key: &str,
+        market_sender: ArcSender<MarketMessage>,
+    ) -> BingXApiBuilder {
+        BingXApiBuilder::new(api_key, secret_key, market_sender)
+    }
+
+    /// Create API with default options, see `BingXApiBuilder` to configure
+    #[cfg(test)]
+    pub fn new(
+        api_key: &str,
+        secret_key: &str,
+        market_sender: ArcSender<MarketMessage>,
+    ) -> ApiResult<Self> {
+        Self::builder(api_key, secret_key, market_sender).build()
+    }
+
+    /// Set max milliseconds after timestamp signed requests are valid for
+    #[cfg(test)]
+    pub fn set_recv_window(&mut self, recv_window: u64) {
+        self.recv_window = recv_window;
+    }
+
+    /// Replace clock used for timestamps of signed requests and fills,
+    /// streams keep clock API was created with
+    #[cfg(test)]
+    pub fn set_clock(&mut self, clock: Arc<dyn Clock>) {
+        self.clock = clock;
+    }
+
+    /// Precision filters of symbol, filters of all contracts are cached
+    /// when first symbol is requested and reloaded once stale
+    async fn symbol_filters(&self, symbol: &str) -> ApiResult<SymbolFilters> {
+        let max_age = self.metadata_max_age.as_millis() as u64;
+
+        if let Some(cached) = self.symbol_filters.lock().await.as_ref() {
+            if let Some(symbol_filters) = cached.fresh(self.clock.now(), max_age) {
+                return symbol_filters
+                    .get(symbol)
+                    .copied()
+                    .ok_or_else(|| ExchangeError::UnknownSymbol(symbol.to_string()));
+            }
+        }
+
+        let res = self.get("/openApi/swap/v2/quote/contracts", None).await?;
+        let contracts = self.handle_response(res).await?;
+
+        // contracts not trading are left out so orders for them are rejected
+        let trading_contracts: Vec<&Value> = contracts
+            .get("data")
+            .and_then(|contracts| contracts.as_array())
+            .ok_or_else(|| "Missing 'data' list from contracts response".to_string())?
+            .iter()
+            .filter(|contract| is_trading_contract(contract))
+            .collect();
+
+        let contract = trading_contracts
+            .iter()
+            .find(|contract| contract.get("symbol").and_then(|s| s.as_str()) == Some(symbol))
+            .ok_or_else(|| ExchangeError::UnknownSymbol(symbol.to_string()))?;
+        let filters = SymbolFilters::from_symbol_info(contract)?;
+
+        let mut symbol_filters = HashMap::new();
+        for contract in trading_contracts {
+            if let (Some(contract_symbol), Ok(contract_filters)) = (
+                contract.get("symbol").and_then(|s| s.as_str()),
+                SymbolFilters::from_symbol_info(contract),
+            ) {
+                symbol_filters.insert(contract_symbol.to_string(), contract_filters);
+            }
         }
+        *self.symbol_filters.lock().await = Some(Cached::new(symbol_filters, self.clock.now()));
+
+        Ok(filters)
     }
 
-    pub fn parse_kline(res_str: &str, symbol: &str, interval: &str) -> ApiResult<Kline> {
-        let lookup: HashMap<String, Value> = serde_json::from_str(res_str).unwrap();
+    #[cfg(test)]
+    pub fn parse_kline(res_str: &str, symbol: &str, interval: &str, now: u64) -> ApiResult<Kline> {
+        let lookup: HashMap<String, Value> = serde_json::from_str(res_str)?;
 
-        // build kline from hashmap
-        Kline::from_bingx_lookup(lookup, symbol, interval)
+        // build kline from hashmap, response code is checked by kline
+        Kline::from_bingx_lookup(lookup, symbol, interval, now)
+    }
+
+    pub fn parse_klines(
+        res_str: &str,
+        symbol: &str,
+        interval: &str,
+        now: u64,
+    ) -> ApiResult<Vec<Kline>> {
+        let lookup: HashMap<String, Value> = serde_json::from_str(res_str)?;
+
+        // build klines from hashmap, response code is checked by kline
+        Kline::from_bingx_klines_lookup(&lookup, symbol, interval, now)
     }
 
     pub fn parse_ticker(res_str: &str) -> ApiResult<Ticker> {
-        let lookup: HashMap<String, Value> = serde_json::from_str(res_str).unwrap();
+        let lookup: HashMap<String, Value> = serde_json::from_str(res_str)?;
+        Self::check_response_code(&lookup)?;
 
         // build kline from hashmap
         Ticker::from_bingx_lookup(lookup)
     }
+
+    pub fn parse_ws_ticker(res_str: &str) -> ApiResult<Ticker> {
+        let lookup: HashMap<String, Value> = serde_json::from_str(res_str)?;
+        Self::check_response_code(&lookup)?;
+
+        // build ticker from hashmap
+        Ticker::from_bingx_lookup_ws(lookup)
+    }
+
+    pub fn parse_ws_kline(res_str: &str, symbol: &str, interval: &str) -> ApiResult<Kline> {
+        let lookup: HashMap<String, Value> = serde_json::from_str(res_str)?;
+        Self::check_response_code(&lookup)?;
+
+        // build kline from hashmap
+        Kline::from_bingx_lookup_ws(lookup, symbol, interval)
+    }
+
+    /// Return error if BingX response contains non zero error code
+    pub fn check_response_code(lookup: &HashMap<String, Value>) -> ApiResult<()> {
+        let code = lookup
+            .get("code")
+            .and_then(|code| code.as_i64())
+            .unwrap_or(0);
+
+        if code != 0 {
+            let msg = lookup
+                .get("msg")
+                .and_then(|msg| msg.as_str())
+                .unwrap_or("Unknown error")
+                .to_string();
+
+            return Err(ExchangeError::Api { code, msg });
+        }
+
+        Ok(())
+    }
+
+    /// Build query string with receive window, timestamp and signature appended
+    fn signed_query_str(&self, params: &[(&str, &str)]) -> ApiResult<String> {
+        let ts = self.signing_ts().to_string();
+        let recv_window = self.recv_window.to_string();
+
+        let mut params = params.to_vec();
+        params.push(("recvWindow", &recv_window));
+
+        QueryStr::new(params).signed(&ts, |query_str| self.sign_query_str(query_str))
+    }
+
+    /// Send request with timestamp and signature appended to query params,
+    /// params are signed again when request is retried so it stays within
+    /// receive window. POST params are sent as body, others in URL
+    async fn send_signed(
+        &self,
+        method: Method,
+        endpoint: &str,
+        params: &[(&str, &str)],
+    ) -> ApiResult<Response> {
+        let url = format!("{}{}", self.http.host, endpoint);
+
+        self.http
+            .send_with_retry(|| {
+                let query_str = self.signed_query_str(params)?;
+
+                let request = match method {
+                    Method::POST => self.http.client.post(&url).body(query_str),
+                    _ => self
+                        .http
+                        .client
+                        .request(method.clone(), format!("{url}?{query_str}")),
+                };
+
+                Ok(request.headers(self.build_headers(true)?))
+            })
+            .instrument(info_span!(
+                "exchange_request",
+                exchange = "bingx",
+                method = method.as_str(),
+                endpoint
+            ))
+            .await
+    }
+
+    /// Send GET request with timestamp and signature appended to query params
+    pub async fn get_signed(&self, endpoint: &str, params: &[(&str, &str)]) -> ApiResult<Response> {
+        self.send_signed(Method::GET, endpoint, params).await
+    }
+
+    /// Send POST request with timestamp and signature appended to query params
+    pub async fn post_signed(
+        &self,
+        endpoint: &str,
+        params: &[(&str, &str)],
+    ) -> ApiResult<Response> {
+        self.send_signed(Method::POST, endpoint, params).await
+    }
+
+    /// Send DELETE request with timestamp and signature appended to query params
+    pub async fn delete_signed(
+        &self,
+        endpoint: &str,
+        params: &[(&str, &str)],
+    ) -> ApiResult<Response> {
+        self.send_signed(Method::DELETE, endpoint, params).await
+    }
+
+    pub async fn get_position(&self, position_id: &str) -> ApiResult<Value> {
+        let endpoint = "/openApi/swap/v2/user/positions";
+
+        let res = self.get_signed(endpoint, &[]).await?;
+
+        let data = self.handle_response(res).await?;
+
+        let positions = data
+            .get("data")
+            .and_then(|positions| positions.as_array())
+            .ok_or_else(|| "Missing 'data' list from positions response".to_string())?;
+
+        let position = positions
+            .iter()
+            .find(|position| {
+                position.get("positionId").and_then(|id| id.as_str()) == Some(position_id)
+            })
+            .ok_or_else(|| format!("Position with ID {} not found", position_id))?;
+
+        Ok(position.clone())
+    }
+
+    /// Close position with market order in opposite direction. One-way mode
+    /// positions are BOTH side so direction is taken from sign of amount,
+    /// and order is reduce only so it can never flip position
+    async fn flatten_position(&self, position: &Value) -> ApiResult<Value> {
+        let symbol = position
+            .get("symbol")
+            .and_then(|symbol| symbol.as_str())
+            .ok_or_else(|| "Missing 'symbol' key from position".to_string())?;
+        let position_side: PositionSide = position
+            .get("positionSide")
+            .and_then(|side| side.as_str())
+            .ok_or_else(|| "Missing 'positionSide' key from position".to_string())?
+            .parse()?;
+        let amount = position
+            .get("positionAmt")
+            .and_then(|qty| qty.as_str())
+            .ok_or_else(|| "Missing 'positionAmt' key from position".to_string())?
+            .parse::<f64>()?;
+
+        let close_side = position_open_side(&position_side, amount).opposite();
+
+        // hedge mode orders on position side can only reduce,
+        // one-way mode needs flag so order never flips position
+        let options = OrderOptions {
+            reduce_only: position_side == PositionSide::Both,
+            position_side,
+        };
+
+        self.open_order(
+            symbol,
+            close_side,
+            OrderType::Market,
+            amount.abs(),
+            None,
+            options,
+        )
+        .await
+    }
+
+    /// Open position of symbol on side, LONG for buy and SHORT for sell,
+    /// one-way mode positions have BOTH side and are matched by sign of amount
+    pub async fn get_symbol_position(&self, symbol: &str, side: &OrderSide) -> ApiResult<Value> {
+        let endpoint = "/openApi/swap/v2/user/positions";
+
+        let res = self.get_signed(endpoint, &[("symbol", symbol)]).await?;
+
+        let data = self.handle_response(res).await?;
+
+        let positions = data
+            .get("data")
+            .and_then(|positions| positions.as_array())
+            .ok_or_else(|| "Missing 'data' list from positions response".to_string())?;
+
+        let position_side = match side {
+            OrderSide::Buy => PositionSide::Long,
+            OrderSide::Sell => PositionSide::Short,
+        }
+        .to_string();
+
+        let position = positions
+            .iter()
+            .find(|position| {
+                let amount = position
+                    .get("positionAmt")
+                    .and_then(|amount| amount.as_str())
+                    .and_then(|amount| amount.parse::<f64>().ok())
+                    .unwrap_or(0.0);
+
+                match position.get("positionSide").and_then(|side| side.as_str()) {
+                    Some("BOTH") => match side {
+                        OrderSide::Buy => amount > 0.0,
+                        OrderSide::Sell => amount < 0.0,
+                    },
+                    Some(other) => other == position_side && amount != 0.0,
+                    None => false,
+                }
+            })
+            .ok_or_else(|| {
+                ExchangeError::InvalidOrder(format!(
+                    "No open {position_side} position for {symbol}"
+                ))
+            })?;
+
+        Ok(position.clone())
+    }
 }
 
 #[async_trait]
 impl ExchangeApi for BingXApi {
+    fn name(&self) -> &'static str {
+        "bingx"
+    }
+
     // TODO: Remove methods from trait
-    async fn get_kline(&self, symbol: &str, interval: &str) -> ApiResult<Kline> {
-        get_bingx_kline(symbol, interval).await
+    async fn get_kline(&self, symbol: &str, interval: Interval) -> ApiResult<Kline> {
+        // klines are in ascending order, last kline is latest
+        self.get_klines(symbol, interval, 1)
+            .await?
+            .pop()
+            .ok_or_else(|| "Empty 'data' list from klines response".to_string().into())
+    }
+
+    async fn get_klines(
+        &self,
+        symbol: &str,
+        interval: Interval,
+        limit: usize,
+    ) -> ApiResult<Vec<Kline>> {
+        get_bingx_klines(&self.http, symbol, interval, limit, self.clock().now()).await
+    }
+
+    async fn get_klines_range(
+        &self,
+        symbol: &str,
+        interval: Interval,
+        from_ts: u64,
+        to_ts: u64,
+    ) -> ApiResult<Vec<Kline>> {
+        get_bingx_klines_range(
+            &self.http,
+            symbol,
+            interval,
+            from_ts,
+            to_ts,
+            self.clock().now(),
+        )
+        .await
     }
 
     async fn get_ticker(&self, symbol: &str) -> ApiResult<Ticker> {
-        get_bingx_ticker(symbol).await
+        get_bingx_ticker(&self.http, symbol).await
     }
 
+    #[instrument(skip(self), fields(exchange = "bingx"))]
     async fn open_position(
         &self,
         symbol: &str,
         side: OrderSide,
         quantity: f64,
+        options: OrderOptions,
+    ) -> ApiResult<Value> {
+        self.open_order(symbol, side, OrderType::Market, quantity, None, options)
+            .await
+    }
+
+    async fn open_limit_order(
+        &self,
+        symbol: &str,
+        side: OrderSide,
+        quantity: f64,
+        price: f64,
+        options: OrderOptions,
     ) -> ApiResult<Value> {
-        let endpoint = "/api/v3/order";
+        self.open_order(
+            symbol,
+            side,
+            OrderType::Limit,
+            quantity,
+            Some(price),
+            options,
+        )
+        .await
+    }
 
-        // format qty to 8 decimals
-        let _qty = format!("{:.1$}", quantity, 8);
+    #[instrument(skip(self), fields(exchange = "bingx"))]
+    async fn open_order(
+        &self,
+        symbol: &str,
+        side: OrderSide,
+        order_type: OrderType,
+        quantity: f64,
+        price: Option<f64>,
+        options: OrderOptions,
+    ) -> ApiResult<Value> {
+        let endpoint = "/openApi/swap/v2/trade/order";
+
+        // round qty and price down to step and tick size of symbol
+        let filters = self.symbol_filters(symbol).await?;
+        let qty = filters.format_quantity(quantity)?;
+        let price = match (&order_type, price) {
+            (OrderType::Market, _) => None,
+            (_, Some(price)) => Some(filters.format_price(price)?),
+            (_, None) => return Err(format!("Price required for {order_type} order").into()),
+        };
 
-        let ts = &generate_ts().to_string();
         let side = &side.to_string();
-        let quote_qty = 50.to_string();
+        let order_type_str = &order_type.to_string();
 
-        let request_body = QueryStr::new(vec![
+        let position_side = options.position_side.to_string();
+
+        let mut params = vec![
             ("symbol", symbol),
-            ("quoteOrderQty", &quote_qty),
-            // ("quantity", &qty),
-            ("type", "MARKET"),
-            ("side", side),
-            ("timestamp", ts),
-        ]);
+            ("side", side.as_str()),
+            ("type", order_type_str.as_str()),
+            ("quantity", &qty),
+            // BOTH in one-way mode, LONG or SHORT in hedge mode
+            ("positionSide", &position_side),
+        ];
+
+        match (&order_type, &price) {
+            (OrderType::Limit, Some(price)) => {
+                params.push(("timeInForce", "GTC"));
+                params.push(("price", price));
+            }
+            (OrderType::StopMarket | OrderType::TakeProfitMarket, Some(price)) => {
+                params.push(("stopPrice", price));
+            }
+            _ => {}
+        }
+
+        if options.reduce_only {
+            params.push(("reduceOnly", "true"));
+        }
+
+        let res = self.post_signed(endpoint, &params).await?;
+
+        self.handle_response(res).await
+    }
+
+    #[instrument(skip(self), fields(exchange = "bingx"))]
+    async fn close_position(&self, position_id: &str) -> ApiResult<Value> {
+        let position = self.get_position(position_id).await?;
+
+        self.flatten_position(&position).await
+    }
+
+    #[instrument(skip(self), fields(exchange = "bingx"))]
+    async fn close_position_by_symbol(&self, symbol: &str, side: OrderSide) -> ApiResult<Value> {
+        let position = self.get_symbol_position(symbol, &side).await?;
+
+        self.flatten_position(&position).await
+    }
 
-        let signature = self.sign_query_str(&request_body.to_string());
+    #[instrument(skip(self), fields(exchange = "bingx"))]
+    async fn set_leverage(
+        &self,
+        symbol: &str,
+        leverage: u32,
+        side: PositionSide,
+    ) -> ApiResult<Value> {
+        let endpoint = "/openApi/swap/v2/trade/leverage";
 
-        let query_str = format!("{}&signature={signature}", request_body.to_string());
+        let leverage = leverage.to_string();
+        let side = side.to_string();
 
-        let res = self.post(endpoint, &query_str).await?;
+        let res = self
+            .post_signed(
+                endpoint,
+                &[("symbol", symbol), ("side", &side), ("leverage", &leverage)],
+            )
+            .await?;
 
         self.handle_response(res).await
     }
 
-    async fn close_position(&self, _position_id: &str) -> ApiResult<Value> {
-        Ok(json!({"ok":"ok"}))
+    #[instrument(skip(self), fields(exchange = "bingx"))]
+    async fn set_margin_mode(&self, symbol: &str, mode: MarginMode) -> ApiResult<Value> {
+        let endpoint = "/openApi/swap/v2/trade/marginType";
+
+        let margin_type = mode.to_string();
+
+        let res = self
+            .post_signed(
+                endpoint,
+                &[("symbol", symbol), ("marginType", &margin_type)],
+            )
+            .await?;
+
+        self.handle_response(res).await
     }
 
     async fn get_account(&self) -> ApiResult<Value> {
         let endpoint = "/api/v3/account";
-        let ts = generate_ts();
-
-        let query_str = format!("timestamp={ts}");
-        let signature = self.sign_query_str(&query_str);
-        let query_str = format!("{}&signature={signature}", query_str);
 
-        let res = self.get(endpoint, Some(&query_str)).await?;
+        let res = self.get_signed(endpoint, &[]).await?;
 
         self.handle_response(res).await
     }
 
+    async fn get_balance(&self, asset: &str) -> ApiResult<Balance> {
+        let endpoint = "/openApi/swap/v2/user/balance";
+
+        let res = self.get_signed(endpoint, &[]).await?;
+        let account = self.handle_response(res).await?;
+
+        let lookup: HashMap<String, Value> = serde_json::from_value(account.clone())?;
+        Self::check_response_code(&lookup)?;
+
+        Balance::from_bingx_lookup(&account, asset)
+    }
+
     async fn all_orders(&self) -> ApiResult<Value> {
         let endpoint = "/api/v3/allOrderList";
-        let ts = generate_ts();
-
-        let query_str = format!("timestamp={ts}");
-        let signature = self.sign_query_str(&query_str);
-        let query_str = format!("{}&signature={signature}", query_str);
 
-        let res = self.get(endpoint, Some(&query_str)).await?;
+        let res = self.get_signed(endpoint, &[]).await?;
 
         self.handle_response(res).await
     }
 
     async fn list_open_orders(&self) -> ApiResult<Value> {
         let endpoint = "/api/v3/openOrderList";
-        let ts = generate_ts();
 
-        let query_str = format!("timestamp={ts}");
-        let signature = self.sign_query_str(&query_str);
-        let query_str = format!("{}&signature={signature}", query_str);
+        let res = self.get_signed(endpoint, &[]).await?;
+
+        self.handle_response(res).await
+    }
+
+    #[instrument(skip(self), fields(exchange = "bingx"))]
+    async fn cancel_order(&self, symbol: &str, order_id: &str) -> ApiResult<Value> {
+        let endpoint = "/openApi/swap/v2/trade/order";
+
+        let res = self
+            .delete_signed(endpoint, &[("symbol", symbol), ("orderId", order_id)])
+            .await?;
+
+        self.handle_response(res).await
+    }
+
+    async fn order_status(&self, symbol: &str, order_id: &str) -> ApiResult<OrderStatus> {
+        let endpoint = "/openApi/swap/v2/trade/order";
+
+        let res = self
+            .get_signed(endpoint, &[("symbol", symbol), ("orderId", order_id)])
+            .await?;
 
-        let res = self.get(endpoint, Some(&query_str)).await?;
+        let data = self.handle_response(res).await?;
+
+        let status = data
+            .get("data")
+            .and_then(|data| data.get("order"))
+            .and_then(|order| order.get("status"))
+            .and_then(|status| status.as_str())
+            .ok_or_else(|| "Missing 'status' key from order response".to_string())?;
+
+        Ok(status.parse()?)
+    }
+
+    #[instrument(skip(self), fields(exchange = "bingx"))]
+    async fn cancel_all_orders(&self, symbol: &str) -> ApiResult<Value> {
+        let endpoint = "/openApi/swap/v2/trade/allOpenOrders";
+
+        let res = self.delete_signed(endpoint, &[("symbol", symbol)]).await?;
 
         self.handle_response(res).await
     }
@@ -171,16 +865,22 @@ impl ExchangeApi for BingXApi {
         &self,
         stream_type: StreamType,
         symbol: &str,
-        interval: Option<&str>,
-    ) -> ApiResult<String> {
+        interval: Option<Interval>,
+    ) -> ApiResult<StreamId> {
         let url = self.build_stream_url(symbol, stream_type.clone(), interval);
-        let stream_id = build_stream_id(symbol, interval);
+        let stream_id = build_stream_id(symbol, interval.map(|interval| interval.as_str()));
 
-        let interval = interval.map(|s| s.to_owned());
+        let interval = interval.map(|interval| interval.to_string());
 
         // create new StreamMeta
-        let open_stream_meta =
-            StreamMeta::new(&stream_id, &url, symbol, stream_type.clone(), interval);
+        let open_stream_meta = StreamMeta::new_at(
+            stream_id,
+            &url,
+            symbol,
+            stream_type.clone(),
+            interval,
+            self.clock.now(),
+        );
 
         self.stream_manager
             .lock()
@@ -189,7 +889,7 @@ impl ExchangeApi for BingXApi {
             .await
     }
 
-    async fn close_stream(&self, stream_id: &str) -> Option<StreamMeta> {
+    async fn close_stream(&self, stream_id: &StreamId) -> Option<StreamMeta> {
         self.stream_manager
             .clone()
             .lock()
@@ -205,6 +905,22 @@ impl ExchangeApi for BingXApi {
     // ---
     // Exchange Methods
     // ---
+    async fn get_server_time(&self) -> ApiResult<u64> {
+        let endpoint = "/openApi/swap/v2/server/time";
+
+        let res = self.get(endpoint, None).await?;
+
+        let data = self.handle_response(res).await?;
+
+        let server_time = data
+            .get("data")
+            .and_then(|data| data.get("serverTime"))
+            .and_then(|time| time.as_u64())
+            .ok_or_else(|| "Missing 'serverTime' key from server time response".to_string())?;
+
+        Ok(server_time)
+    }
+
     async fn exchange_info(&self) -> ApiResult<Value> {
         let endpoint = "/api/v3/exchangeInfo";
 
@@ -213,43 +929,105 @@ impl ExchangeApi for BingXApi {
         self.handle_response(res).await
     }
 
+    async fn get_symbols(&self) -> ApiResult<Vec<String>> {
+        let endpoint = "/openApi/swap/v2/quote/contracts";
+
+        let res = self.get(endpoint, None).await?;
+
+        let data = self.handle_response(res).await?;
+
+        let contracts = data
+            .get("data")
+            .and_then(|contracts| contracts.as_array())
+            .ok_or_else(|| "Missing 'data' list from contracts response".to_string())?;
+
+        let symbols = contracts
+            .iter()
+            .filter(|contract| is_trading_contract(contract))
+            .filter_map(|contract| contract.get("symbol")?.as_str().map(|s| s.to_string()))
+            .collect();
+
+        Ok(symbols)
+    }
+
     // ---
     // API Util methods
     // ---
-    async fn get(
-        &self,
-        endpoint: &str,
-        query_str: Option<&str>,
-    ) -> Result<Response, reqwest::Error> {
-        // let signature = self.sign_query_str(query_str);
+    async fn get(&self, endpoint: &str, query_str: Option<&str>) -> ApiResult<Response> {
         let url = match query_str {
-            Some(qs) => format!("{}{}?{}", self.host, endpoint, qs),
-            None => format!("{}{}", self.host, endpoint),
+            Some(qs) => format!("{}{}?{}", self.http.host, endpoint, qs),
+            None => format!("{}{}", self.http.host, endpoint),
         };
 
-        self.client
-            .get(&url)
-            .headers(self.build_headers(true))
-            .send()
+        self.http
+            .send_with_retry(|| {
+                Ok(self
+                    .http
+                    .client
+                    .get(&url)
+                    .headers(self.build_headers(true)?))
+            })
+            .instrument(info_span!(
+                "exchange_request",
+                exchange = "bingx",
+                method = "GET",
+                endpoint
+            ))
             .await
     }
 
-    async fn post(&self, endpoint: &str, query_str: &str) -> Result<Response, reqwest::Error> {
-        let url = format!("{}{}", self.host, endpoint);
+    async fn post(&self, endpoint: &str, query_str: &str) -> ApiResult<Response> {
+        let url = format!("{}{}", self.http.host, endpoint);
         let body = query_str.to_string();
 
-        self.client
-            .post(&url)
-            .headers(self.build_headers(true))
-            .body(body)
-            .send()
+        self.http
+            .send_with_retry(|| {
+                Ok(self
+                    .http
+                    .client
+                    .post(&url)
+                    .headers(self.build_headers(true)?)
+                    .body(body.clone()))
+            })
+            .instrument(info_span!(
+                "exchange_request",
+                exchange = "bingx",
+                method = "POST",
+                endpoint
+            ))
+            .await
+    }
+
+    async fn delete(&self, endpoint: &str, query_str: &str) -> ApiResult<Response> {
+        let url = format!("{}{}?{}", self.http.host, endpoint, query_str);
+
+        self.http
+            .send_with_retry(|| {
+                Ok(self
+                    .http
+                    .client
+                    .delete(&url)
+                    .headers(self.build_headers(true)?))
+            })
+            .instrument(info_span!(
+                "exchange_request",
+                exchange = "bingx",
+                method = "DELETE",
+                endpoint
+            ))
             .await
     }
 
     async fn handle_response(&self, response: Response) -> ApiResult<Value> {
+        check_rate_limit(&response)?;
+
         let data = match &response.headers().get("content-type") {
             Some(header) => {
-                if header.to_str().unwrap().contains("text/html") {
+                let content_type = header
+                    .to_str()
+                    .map_err(|e| ExchangeError::Parsing(e.to_string()))?;
+
+                if content_type.contains("text/html") {
                     json!({"text":response.text().await?})
                 } else {
                     response.json::<serde_json::Value>().await?
@@ -261,7 +1039,7 @@ impl ExchangeApi for BingXApi {
         Ok(data)
     }
 
-    fn build_headers(&self, json: bool) -> HeaderMap {
+    fn build_headers(&self, json: bool) -> ApiResult<HeaderMap> {
         let mut custom_headers = HeaderMap::new();
 
         // custom_headers.insert(USER_AGENT, HeaderValue::from_static("binance-rs"));
@@ -269,17 +1047,18 @@ impl ExchangeApi for BingXApi {
             custom_headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
         }
         custom_headers.insert(
-            "X-MBX-APIKEY",
-            HeaderValue::from_str(self.api_key.as_str()).expect("Unable to get API key"),
+            "X-BX-APIKEY",
+            HeaderValue::from_str(self.api_key.as_str())
+                .map_err(|_| ExchangeError::Auth("Invalid API key".to_string()))?,
         );
 
-        custom_headers
+        Ok(custom_headers)
     }
 
-    fn sign_query_str(&self, query_str: &str) -> String {
+    fn sign_query_str(&self, query_str: &str) -> ApiResult<String> {
         // Create a new HMAC instance with SHA256
-        let mut hmac =
-            Hmac::<Sha256>::new_from_slice(self.secret_key.as_bytes()).expect("Invalid key length");
+        let mut hmac = Hmac::<Sha256>::new_from_slice(self.secret_key.as_bytes())
+            .map_err(|e| ExchangeError::Signature(e.to_string()))?;
 
         // Update the HMAC with the data
         hmac.update(query_str.as_bytes());
@@ -288,112 +1067,416 @@ impl ExchangeApi for BingXApi {
         let result = hmac.finalize();
 
         // Convert the HMAC value to a string
-        hex::encode(result.into_bytes())
+        Ok(hex::encode(result.into_bytes()))
+    }
+
+    fn timestamp_unit(&self) -> TimestampUnit {
+        TimestampUnit::Milliseconds
+    }
+
+    fn clock(&self) -> Arc<dyn Clock> {
+        self.clock.clone()
+    }
+
+    fn time_offset(&self) -> i64 {
+        self.time_offset.load(Ordering::Relaxed)
+    }
+
+    fn set_time_offset(&self, offset: i64) {
+        self.time_offset.store(offset, Ordering::Relaxed);
     }
 
     fn build_stream_url(
         &self,
         _symbol: &str,
         _stream_type: StreamType,
-        _interval: Option<&str>,
+        _interval: Option<Interval>,
     ) -> String {
         self.ws_host.to_string()
     }
 }
 
+// Single web socket connection shared by all stream subscriptions
+struct BingXWsConnection {
+    // messages sent to exchange over connection, eg. subscriptions
+    commands: UnboundedSender<Message>,
+    handle: JoinHandle<()>,
+}
+
+// Ticker polling task, loop stops on shutdown signal or when task dropped
+struct BingXPollingTask {
+    shutdown: oneshot::Sender<()>,
+    handle: JoinHandle<()>,
+}
+
+impl BingXPollingTask {
+    fn stop(self) {
+        let _ = self.shutdown.send(());
+        // abort in case task is not waiting on shutdown signal
+        self.handle.abort();
+    }
+}
+
 pub struct BingXStreamManager {
-    ticker_streams: HashMap<String, JoinHandle<()>>,
-    kline_streams: HashMap<String, JoinHandle<()>>,
+    http: BingXClient,
+    // ticker streams polling http endpoint when web socket unavailable
+    polling_streams: HashMap<StreamId, BingXPollingTask>,
+    ws_connection: Option<BingXWsConnection>,
+    // stream of each subscribed data type, eg. BTC-USDT@kline_1m
+    subscriptions: ArcMutex<HashMap<String, StreamMeta>>,
     market_sender: ArcSender<MarketMessage>,
-    stream_metas: ArcMutex<HashMap<String, StreamMeta>>,
+    stream_metas: ArcMutex<HashMap<StreamId, StreamMeta>>,
+    dead_letter_log: Option<DeadLetterLog>,
+    // time of stream messages, same clock as API
+    clock: Arc<dyn Clock>,
 }
 
 impl BingXStreamManager {
-    pub fn new(market_sender: ArcSender<MarketMessage>) -> Self {
+    pub fn new(
+        http: BingXClient,
+        market_sender: ArcSender<MarketMessage>,
+        dead_letter_log: Option<DeadLetterLog>,
+        clock: Arc<dyn Clock>,
+    ) -> Self {
         Self {
-            ticker_streams: HashMap::new(),
-            kline_streams: HashMap::new(),
+            http,
+            polling_streams: HashMap::new(),
+            ws_connection: None,
+            subscriptions: ArcMutex::new(HashMap::new()),
             market_sender,
             stream_metas: ArcMutex::new(HashMap::new()),
+            dead_letter_log,
+            clock,
         }
     }
-}
 
-#[async_trait]
-impl StreamManager for BingXStreamManager {
-    async fn open_stream(&mut self, stream_meta: StreamMeta) -> ApiResult<String> {
-        let stream_metas = self.stream_metas();
+    /// Data type of stream subscription, eg. BTC-USDT@kline_1m or BTC-USDT@ticker
+    fn build_data_type(stream_meta: &StreamMeta) -> ApiResult<String> {
+        match stream_meta.stream_type {
+            StreamType::Kline => {
+                // interval of stream meta is built from Interval when stream is opened
+                let interval = stream_meta
+                    .interval
+                    .as_deref()
+                    .ok_or_else(|| "Missing interval of kline stream".to_string())?;
+
+                Ok(format!(
+                    "{}@{}_{}",
+                    stream_meta.symbol, stream_meta.stream_type, interval
+                ))
+            }
+            StreamType::Ticker => Ok(format!(
+                "{}@{}",
+                stream_meta.symbol, stream_meta.stream_type
+            )),
+        }
+    }
 
-        stream_metas
-            .lock()
-            .await
-            .insert(stream_meta.id.to_string(), stream_meta.clone());
+    /// Subscribe to data type over shared web socket,
+    /// connection is opened if not already open
+    async fn subscribe(&mut self, url: &str, data_type: &str) -> ApiResult<()> {
+        let is_connected = self
+            .ws_connection
+            .as_ref()
+            .is_some_and(|connection| !connection.handle.is_finished());
 
-        // if stream type is ticker, start thread to call http request every 1 second
-        // if stream type is kline, subscribe to normal web socket endpoint
-        match stream_meta.stream_type {
-            StreamType::Ticker => {
-                let market_sender = self.market_sender.clone();
+        if !is_connected {
+            let (ws_stream, _) = connect_async(url.to_string())
+                .await
+                .map_err(|e| ExchangeError::Network(e.to_string()))?;
 
-                let thread_handle = tokio::spawn(async move {
-                    loop {
-                        let ticker = get_bingx_ticker(&stream_meta.symbol).await;
+            self.ws_connection = Some(self.spawn_ws_connection(ws_stream));
+        }
 
-                        if let Ok(ticker) = ticker {
-                            let _ = market_sender.send(MarketMessage::UpdateTicker(ticker));
-                        } else {
-                            warn!("Unable to get ticker from BingX API");
-                        }
+        let connection = self
+            .ws_connection
+            .as_ref()
+            .ok_or_else(|| ExchangeError::Network("BingX web socket not connected".to_string()))?;
 
-                        tokio::time::sleep(Duration::from_secs(1)).await;
+        connection
+            .commands
+            .send(build_subscription_msg("sub", data_type))
+            .map_err(|e| ExchangeError::Network(e.to_string()))
+    }
+
+    // call ticker http endpoint every 1 second,
+    // only used if ticker web socket could not be opened
+    fn spawn_ticker_polling(&self, stream_meta: &StreamMeta) -> BingXPollingTask {
+        let http = self.http.clone();
+        let market_sender = self.market_sender.clone();
+        let dead_letter_log = self.dead_letter_log.clone();
+        let thread_stream_id = stream_meta.id.clone();
+        let symbol = stream_meta.symbol.clone();
+        let stream_metas = self.stream_metas.clone();
+        let clock = self.clock.clone();
+        let (shutdown, mut shutdown_receiver) = oneshot::channel::<()>();
+        let span = info_span!(
+            "ticker_polling",
+            exchange = "bingx",
+            symbol = %symbol,
+            stream_id = %thread_stream_id
+        );
+
+        let handle = tokio::spawn(
+            async move {
+                loop {
+                    // stop waiting on request or sleep as soon as shutdown is signalled
+                    let ticker_str = tokio::select! {
+                        _ = &mut shutdown_receiver => break,
+                        ticker_str = fetch_bingx_ticker_str(&http, &symbol) => ticker_str,
+                    };
+
+                    match ticker_str {
+                        Ok(ticker_str) => match BingXApi::parse_ticker(&ticker_str) {
+                            Ok(ticker) => {
+                                let _ = market_sender.send(MarketMessage::UpdateTicker(ticker));
+                                if let Some(stream_meta) =
+                                    stream_metas.lock().await.get_mut(&thread_stream_id)
+                                {
+                                    stream_meta.record_message(clock.now());
+                                }
+                            }
+                            Err(e) => {
+                                warn!("Unable to parse ticker from BingX API: {e}");
+                                if let Some(dead_letter_log) = &dead_letter_log {
+                                    let _ =
+                                        dead_letter_log.log(thread_stream_id.as_str(), &ticker_str);
+                                }
+                            }
+                        },
+                        Err(_) => warn!("Unable to get ticker from BingX API"),
                     }
-                });
 
-                self.ticker_streams
-                    .insert(stream_meta.id.clone(), thread_handle);
+                    tokio::select! {
+                        _ = &mut shutdown_receiver => break,
+                        _ = tokio::time::sleep(Duration::from_secs(1)) => {}
+                    }
+                }
             }
-            StreamType::Kline => {
-                let market_sender = self.market_sender.clone();
+            .instrument(span),
+        );
+
+        BingXPollingTask { shutdown, handle }
+    }
 
-                let thread_handle = tokio::spawn(async move {
-                    loop {
-                        let kline = get_bingx_kline(
+    // Spawn client web socket to send subscriptions and route
+    // received messages to stream subscribed to message data type
+    fn spawn_ws_connection(&self, mut ws_stream: BingXWsStream) -> BingXWsConnection {
+        let (commands, mut command_receiver) = unbounded_channel::<Message>();
+        let market_sender = self.market_sender.clone();
+        let dead_letter_log = self.dead_letter_log.clone();
+        let subscriptions = self.subscriptions.clone();
+        let stream_metas = self.stream_metas.clone();
+        let clock = self.clock.clone();
+
+        let handle = tokio::spawn(
+            async move {
+                loop {
+                    let result = tokio::select! {
+                        command = command_receiver.recv() => match command {
+                            Some(msg) => {
+                                if let Err(e) = ws_stream.send(msg).await {
+                                    warn!("Unable to send message to BingX web socket: {e}");
+                                }
+                                continue;
+                            }
+                            // manager dropped connection, close once commands are sent
+                            None => {
+                                let _ = ws_stream.close(None).await;
+                                break;
+                            }
+                        },
+                        result = ws_stream.next() => match result {
+                            Some(result) => result,
+                            None => break,
+                        },
+                    };
+
+                    let text = match result {
+                        // BingX sends all data as gzip compressed binary messages
+                        Ok(Message::Binary(data)) => match parse_gzip_to_string(&data) {
+                            Ok(text) => text,
+                            Err(e) => {
+                                warn!("Unable to decode gzip message from BingX: {e}");
+                                continue;
+                            }
+                        },
+                        Ok(Message::Text(text)) => text,
+                        Ok(Message::Ping(data)) => {
+                            // echo payload back to keep connection alive
+                            let _ = ws_stream.send(Message::Pong(data)).await;
+                            continue;
+                        }
+                        Ok(Message::Close(_frame)) => break,
+                        Ok(_) => {
+                            // ignore Pong Messages
+                            continue;
+                        }
+                        Err(e) => {
+                            warn!("Error receiving message from BingX: {e:?}");
+                            continue;
+                        }
+                    };
+
+                    // keep connection alive, respond to heartbeat
+                    if text == "Ping" {
+                        let _ = ws_stream.send(Message::Text("Pong".to_string())).await;
+                        continue;
+                    }
+
+                    // only handle data messages, ignore subscription responses
+                    let data_type = serde_json::from_str::<Value>(&text)
+                        .ok()
+                        .and_then(|json| json.get("dataType")?.as_str().map(str::to_string));
+
+                    let stream_meta = match data_type {
+                        Some(data_type) => subscriptions.lock().await.get(&data_type).cloned(),
+                        None => None,
+                    };
+
+                    let stream_meta = match stream_meta {
+                        Some(stream_meta) => stream_meta,
+                        None => continue,
+                    };
+
+                    let message = match stream_meta.stream_type {
+                        StreamType::Kline => BingXApi::parse_ws_kline(
+                            &text,
                             &stream_meta.symbol,
-                            &stream_meta
-                                .interval
-                                .clone()
-                                .unwrap_or_else(|| "UNKNOWN".to_string()),
+                            stream_meta.interval.as_deref().unwrap_or_default(),
                         )
-                        .await;
-
-                        if let Ok(kline) = kline {
-                            // let ticker = BingXApi::parse_ticker(&ticker_str);
-                            let _ = market_sender.send(MarketMessage::UpdateKline(kline));
-                        } else {
-                            warn!("Unable to get kline from BingX API");
+                        .map(MarketMessage::UpdateKline),
+                        StreamType::Ticker => {
+                            BingXApi::parse_ws_ticker(&text).map(MarketMessage::UpdateTicker)
+                        }
+                    };
+
+                    match message {
+                        Ok(message) => {
+                            let _ = market_sender.send(message);
+                            if let Some(stream_meta) =
+                                stream_metas.lock().await.get_mut(&stream_meta.id)
+                            {
+                                stream_meta.record_message(clock.now());
+                            }
                         }
+                        Err(e) => {
+                            warn!(
+                                "Unable to parse {} from BingX web socket: {e}",
+                                stream_meta.id
+                            );
+                            if let Some(dead_letter_log) = &dead_letter_log {
+                                let _ = dead_letter_log.log(stream_meta.id.as_str(), &text);
+                            }
+                        }
+                    }
+                }
 
-                        tokio::time::sleep(Duration::from_secs(1)).await;
+                // mark all subscribed streams closed so stream monitor can reopen them
+                let mut stream_metas = stream_metas.lock().await;
+                for (_, subscribed_meta) in subscriptions.lock().await.drain() {
+                    if let Some(stream_meta) = stream_metas.get_mut(&subscribed_meta.id) {
+                        stream_meta.status = "closed".to_string();
                     }
-                });
+                }
+            }
+            .instrument(info_span!("ws_connection", exchange = "bingx")),
+        );
 
-                self.kline_streams
-                    .insert(stream_meta.id.clone(), thread_handle);
+        BingXWsConnection { commands, handle }
+    }
+}
+
+#[async_trait]
+impl StreamManager for BingXStreamManager {
+    async fn open_stream(&mut self, stream_meta: StreamMeta) -> ApiResult<StreamId> {
+        if self.is_stream_open(&stream_meta.id).await {
+            return Ok(stream_meta.id);
+        }
+
+        // stream being reopened after closing, stop previous polling task
+        if let Some(polling_task) = self.polling_streams.remove(&stream_meta.id) {
+            polling_task.stop();
+        }
+
+        let data_type = Self::build_data_type(&stream_meta)?;
+
+        // route messages before subscribing so no messages are missed
+        self.subscriptions
+            .lock()
+            .await
+            .insert(data_type.clone(), stream_meta.clone());
+
+        if let Err(e) = self.subscribe(&stream_meta.url, &data_type).await {
+            self.subscriptions.lock().await.remove(&data_type);
+
+            match stream_meta.stream_type {
+                // fall back to polling ticker if web socket unavailable
+                StreamType::Ticker => {
+                    warn!("Unable to open BingX ticker web socket, polling instead: {e}");
+                    let polling_task = self.spawn_ticker_polling(&stream_meta);
+                    self.polling_streams
+                        .insert(stream_meta.id.clone(), polling_task);
+                }
+                StreamType::Kline => return Err(e),
             }
-        };
+        }
+
+        // only add stream meta once stream successfully opened
+        self.stream_metas
+            .lock()
+            .await
+            .insert(stream_meta.id.clone(), stream_meta.clone());
 
-        Ok(stream_meta.id.to_string())
+        Ok(stream_meta.id.clone())
     }
 
-    async fn close_stream(&mut self, stream_id: &str) -> Option<StreamMeta> {
-        // check if stream_id in ticker streams
-        if let Some(sync) = self.ticker_streams.get(stream_id) {
-            let _ = sync.abort();
+    async fn close_stream(&mut self, stream_id: &StreamId) -> Option<StreamMeta> {
+        // stop polling task and remove so handles don't build up over reopens
+        if let Some(polling_task) = self.polling_streams.remove(stream_id) {
+            polling_task.stop();
         }
 
-        // check if stream_id in kline streams
-        if let Some(sync) = self.kline_streams.get(stream_id) {
-            let _ = sync.abort();
+        // unsubscribe from web socket, connection closed once no subscriptions remain
+        let mut subscriptions = self.subscriptions.lock().await;
+        let data_types: Vec<String> = subscriptions
+            .iter()
+            .filter(|(_, meta)| &meta.id == stream_id)
+            .map(|(data_type, _)| data_type.clone())
+            .collect();
+
+        for data_type in data_types {
+            subscriptions.remove(&data_type);
+            if let Some(connection) = &self.ws_connection {
+                let _ = connection
+                    .commands
+                    .send(build_subscription_msg("unsub", &data_type));
+            }
+        }
+
+        let closed_connection = if subscriptions.is_empty() {
+            self.ws_connection.take()
+        } else {
+            None
+        };
+        // connection task locks subscriptions before it exits
+        drop(subscriptions);
+
+        // dropping commands lets task send queued unsubscriptions and close
+        // connection, task is aborted if connection is not closed in time
+        if let Some(BingXWsConnection {
+            commands,
+            mut handle,
+        }) = closed_connection
+        {
+            drop(commands);
+            if tokio::time::timeout(BING_X_WS_CLOSE_TIMEOUT, &mut handle)
+                .await
+                .is_err()
+            {
+                warn!("BingX web socket not closed in time, aborting connection");
+                handle.abort();
+            }
         }
 
         let mut infos = self.stream_metas.lock().await;
@@ -405,187 +1488,1966 @@ impl StreamManager for BingXStreamManager {
         meta
     }
 
-    fn stream_metas(&self) -> ArcMutex<HashMap<String, StreamMeta>> {
+    async fn close_all_streams(&mut self) -> Vec<StreamMeta> {
+        for (_, polling_task) in self.polling_streams.drain() {
+            polling_task.stop();
+        }
+
+        // web socket carries all subscriptions, close connection instead of unsubscribing
+        self.subscriptions.lock().await.clear();
+        if let Some(connection) = self.ws_connection.take() {
+            connection.handle.abort();
+        }
+
+        self.stream_metas
+            .lock()
+            .await
+            .drain()
+            .map(|(_, stream_meta)| stream_meta)
+            .collect()
+    }
+
+    fn stream_metas(&self) -> ArcMutex<HashMap<StreamId, StreamMeta>> {
         self.stream_metas.clone()
     }
+
+    fn clock(&self) -> Arc<dyn Clock> {
+        self.clock.clone()
+    }
 }
 
-pub async fn get_bingx_kline(symbol: &str, interval: &str) -> ApiResult<Kline> {
-    // remove last two letters from interval if interval is {number}min
-    // api accepts interval as {number}m
-    let _interval = if interval.ends_with('n') {
-        let mut interval_copy = interval.to_string();
-        interval_copy.pop();
-        interval_copy.pop();
-        interval_copy
-    } else {
-        interval.to_string()
-    };
-
-    let client = reqwest::Client::new();
-    let query_str = QueryStr::new(vec![("symbol", symbol), ("interval", &_interval)]);
-    let url: String = format!(
-        "{}/openApi/swap/v2/quote/klines?{}",
-        BING_X_HOST_URL,
-        query_str.to_string()
-    );
-
-    let res = client.get(url).send().await?;
-
-    let kline_str = res.json::<Value>().await?.to_string();
-
-    let kline = BingXApi::parse_kline(&kline_str, symbol, interval)?;
-
-    Ok(kline)
+type BingXWsStream = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+// request type is sub or unsub
+fn build_subscription_msg(req_type: &str, data_type: &str) -> Message {
+    let msg = json!({
+        "id": Uuid::new_v4().hyphenated().to_string(),
+        "reqType": req_type,
+        "dataType": data_type,
+    })
+    .to_string();
+
+    Message::Text(msg)
 }
 
-pub async fn get_bingx_ticker(symbol: &str) -> ApiResult<Ticker> {
-    let client = reqwest::Client::new();
-    let query_str = QueryStr::new(vec![("symbol", symbol)]);
-    let url = format!(
-        "{}/openApi/swap/v2/quote/ticker?{}",
-        BING_X_HOST_URL,
-        query_str.to_string()
-    );
+pub async fn get_bingx_klines(
+    http: &BingXClient,
+    symbol: &str,
+    interval: Interval,
+    limit: usize,
+    now: u64,
+) -> ApiResult<Vec<Kline>> {
+    fetch_bingx_klines(http, symbol, interval, limit, None, None, now).await
+}
 
-    let res = client.get(url).send().await?;
+/// Get all klines between from and to timestamps, paginating
+/// klines endpoint as each request returns at most limit klines
+pub async fn get_bingx_klines_range(
+    http: &BingXClient,
+    symbol: &str,
+    interval: Interval,
+    from_ts: u64,
+    to_ts: u64,
+    now: u64,
+) -> ApiResult<Vec<Kline>> {
+    let mut klines: Vec<Kline> = vec![];
+    let mut start_time = from_ts;
+
+    while start_time <= to_ts {
+        let page = fetch_bingx_klines(
+            http,
+            symbol,
+            interval,
+            BING_X_KLINES_LIMIT,
+            Some(start_time),
+            Some(to_ts),
+            now,
+        )
+        .await?;
+
+        let page_len = page.len();
+
+        // skip klines already added from previous page
+        let last_open_time = klines.last().map(|kline| kline.open_time);
+        klines.extend(
+            page.into_iter()
+                .filter(|kline| last_open_time.is_none_or(|last| kline.open_time > last)),
+        );
 
-    let ticker_str = res.json::<Value>().await?.to_string();
+        match klines.last() {
+            Some(last) if page_len == BING_X_KLINES_LIMIT => start_time = last.open_time + 1,
+            _ => break,
+        }
+    }
+
+    Ok(klines)
+}
+
+async fn fetch_bingx_klines(
+    http: &BingXClient,
+    symbol: &str,
+    interval: Interval,
+    limit: usize,
+    start_time: Option<u64>,
+    end_time: Option<u64>,
+    // klines with close time before now are closed
+    now: u64,
+) -> ApiResult<Vec<Kline>> {
+    let limit = limit.to_string();
+    let start_time = start_time.map(|ts| ts.to_string());
+    let end_time = end_time.map(|ts| ts.to_string());
+
+    let mut params = vec![
+        ("symbol", symbol),
+        ("interval", interval.as_str()),
+        ("limit", limit.as_str()),
+    ];
+    if let Some(start_time) = &start_time {
+        params.push(("startTime", start_time));
+    }
+    if let Some(end_time) = &end_time {
+        params.push(("endTime", end_time));
+    }
+
+    let query_str = QueryStr::new(params);
+    let res = http
+        .get_public("/openApi/swap/v2/quote/klines", &query_str)
+        .await?;
+
+    let res_str = res.text().await?;
+
+    // klines are returned in ascending order of open time
+    BingXApi::parse_klines(&res_str, symbol, interval.as_str(), now)
+}
+
+pub async fn get_bingx_ticker(http: &BingXClient, symbol: &str) -> ApiResult<Ticker> {
+    let ticker_str = fetch_bingx_ticker_str(http, symbol).await?;
 
     let ticker = BingXApi::parse_ticker(&ticker_str)?;
 
     Ok(ticker)
 }
 
-// Tungsenite WS implemenation for Kline
-// let (mut org_ws_stream, _) = connect_async(stream_meta.url.to_string())
-//                     .await
-//                     .unwrap_or_else(|_| {
-//                         panic!(
-//                             "Unable to create new kline stream for stream type: {} with symbol: {}",
-//                             stream_meta.stream_type, stream_meta.symbol
-//                         )
-//                     });
-
-//                 // build subscribe message
-//                 let uuid = Uuid::new_v4();
-//                 uuid.hyphenated().to_string();
-//                 let msg =
-//                     json!({"id":uuid,"dataType":"market.kline.BTC-USDT.1min", "reqType": "sub"})
-//                         .to_string();
-//                 // let msg = json!({"id":uuid,"dataType":stream_meta.id.clone(), "reqType": "sub"})
-//                 //     .to_string();
-
-//                 println!("Message sent to websocket:{msg}",);
-//                 // send subscribe message
-
-//                 org_ws_stream
-//                     .send(tokio_tungstenite::tungstenite::Message::Text(msg.clone()))
-//                     .await
-//                     .unwrap_or_else(|_| panic!("Unable to send subscribe message to API: {}", msg));
-
-//                 // Split the Websocket to use sync to close connection
-//                 let (sync, mut ws_stream) = org_ws_stream.split();
-
-//                 let stream_metas = self.stream_metas();
-
-//                 stream_metas
-//                     .lock()
-//                     .await
-//                     .insert(stream_meta.id.to_string(), stream_meta.clone());
-
-//                 let sync = ArcMutex::new(sync);
-//                 self.kline_streams
-//                     .insert(stream_meta.id.clone(), sync.clone());
-
-//                 let market_sender = self.market_sender.clone();
-
-//                 let thread_stream_id = stream_meta.id.clone();
-
-//                 // Spawn client web socket to listen for kline
-//                 tokio::spawn(async move {
-//                     while let Some(result) = ws_stream.next().await {
-//                         match result {
-//                             // Forward message to receiver
-//                             Ok(msg) => match msg {
-//                                 // Handle received message
-//                                 // If text message then can create new Kline
-//                                 Message::Text(text) => {
-//                                     println!("Message received from Web socket API: {text}");
-//                                     // create json from text message
-//                                     let json = serde_json::from_str::<Value>(&text)
-//                                         .expect("Unable to parse JSON from web socket message");
-
-//                                     // get data type from json
-//                                     let data_type = json
-//                                         .get("dataType")
-//                                         .expect("Unable to get dataType from web socket message")
-//                                         .to_string();
-
-//                                     // build regex pattern to match for kline message
-//                                     let pattern = r"^@kline_";
-//                                     let re = Regex::new(pattern).unwrap();
-
-//                                     // check text is kline data message
-//                                     if re.is_match(&data_type) {
-//                                         if let Some(stream_meta) =
-//                                             stream_metas.lock().await.get_mut(&thread_stream_id)
-//                                         {
-//                                             stream_meta.last_update = generate_ts();
-//                                             match stream_meta.stream_type {
-//                                                 StreamType::Kline => {
-//                                                     let kline = BingXApi::parse_kline(
-//                                                         &text,
-//                                                         &stream_meta.symbol,
-//                                                         &stream_meta.interval.clone().unwrap(),
-//                                                     );
-//                                                     let _ = market_sender
-//                                                         .send(MarketMessage::UpdateKline(kline));
-//                                                 }
-//                                                 StreamType::Ticker => {
-//                                                     let ticker = BingXApi::parse_ticker(&text);
-
-//                                                     let _ = market_sender
-//                                                         .send(MarketMessage::UpdateTicker(ticker));
-//                                                 }
-//                                             }
-//                                         };
-//                                     } else {
-//                                         println!("Not kline data message");
-//                                     }
-//                                 }
-
-//                                 Message::Close(_frame) => {
-//                                     if let Some(stream_meta) =
-//                                         stream_metas.lock().await.get(&thread_stream_id)
-//                                     {
-//                                         let mut stream_meta = stream_meta.clone();
-//                                         stream_meta.status = "closed".to_string();
-//                                     };
-//                                 }
-
-//                                 Message::Ping(_data) => {
-//                                     sync.lock().await.send(Message::Pong(vec![123]));
-//                                     // ignore Ping Pong Messages
-//                                 }
-//                                 Message::Pong(_data) => {
-//                                     // ignore Ping Pong Messages
-//                                 }
-//                                 Message::Binary(data) => {
-//                                     let json = parse_gzip_to_json(data);
-
-//                                     if let Ok(json) = json {
-//                                         println!("Binary Gzip data: {:?}", json);
-//                                     }
-//                                 }
-//                                 _ => {
-//                                     println!("Received unexpected data: {:?}", msg);
-//                                 }
-//                             },
-//                             Err(e) => {
-//                                 // Handle error
-//                                 eprintln!("Error receiving message: {:?}", e);
-//                             }
-//                         }
-//                     }
-//                 });
+/// Fetch raw ticker response body from BingX API
+pub async fn fetch_bingx_ticker_str(http: &BingXClient, symbol: &str) -> ApiResult<String> {
+    let query_str = QueryStr::new(vec![("symbol", symbol)]);
+    let res = http
+        .get_public("/openApi/swap/v2/quote/ticker", &query_str)
+        .await?;
+
+    let ticker_str = res.json::<Value>().await?.to_string();
+
+    Ok(ticker_str)
+}
+
+/// Side of order which opened position, one-way mode positions
+/// are BOTH side so side is taken from sign of amount
+fn position_open_side(position_side: &PositionSide, amount: f64) -> OrderSide {
+    match position_side {
+        PositionSide::Long => OrderSide::Buy,
+        PositionSide::Short => OrderSide::Sell,
+        PositionSide::Both if amount < 0.0 => OrderSide::Sell,
+        PositionSide::Both => OrderSide::Buy,
+    }
+}
+
+/// Status 1 is contract trading, status missing from older responses
+fn is_trading_contract(contract: &Value) -> bool {
+    contract
+        .get("status")
+        .is_none_or(|status| status.as_i64() == Some(1))
+}
+
+/// Return rate limited error if response status is 429 too many requests
+/// or 418 banned for ignoring rate limits
+pub fn check_rate_limit(response: &Response) -> ApiResult<()> {
+    let status = response.status().as_u16();
+
+    if status != 429 && status != 418 {
+        return Ok(());
+    }
+
+    // Retry-After header is given in seconds
+    let retry_after = response
+        .headers()
+        .get(RETRY_AFTER)
+        .and_then(|retry_after| retry_after.to_str().ok())
+        .and_then(|secs| secs.trim().parse::<u64>().ok())
+        .map(Duration::from_secs);
+
+    Err(ExchangeError::RateLimited { retry_after })
+}
+
+#[cfg(test)]
+mod tests {
+    use flate2::{write::GzEncoder, Compression};
+    use std::io::Write;
+    use tokio::net::TcpListener;
+    use tokio::sync::oneshot;
+    use tokio_tungstenite::accept_async;
+    use tracing_test::traced_test;
+    use wiremock::matchers::{body_string_contains, header, method, path, query_param};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    use super::*;
+    use crate::market::types::ArcReceiver;
+    use crate::utils::channel::{build_arc_channel, DEFAULT_CHANNEL_CAPACITY};
+    use crate::utils::clock::MockClock;
+    use crate::utils::rate_limit::requests_per_second;
+    use crate::utils::time::generate_ts;
+
+    fn mock_api(server: &MockServer) -> BingXApi {
+        let (market_sender, _) = build_arc_channel(DEFAULT_CHANNEL_CAPACITY);
+
+        BingXApi::builder("api_key", "secret_key", market_sender)
+            .hosts(&server.uri(), "ws://localhost")
+            .build()
+            .unwrap()
+    }
+
+    fn mock_ws_api(server: &MockServer, ws_uri: &str) -> (BingXApi, ArcReceiver<MarketMessage>) {
+        let (market_sender, market_receiver) = build_arc_channel(DEFAULT_CHANNEL_CAPACITY);
+
+        let api = BingXApi::builder("api_key", "secret_key", market_sender)
+            .hosts(&server.uri(), ws_uri)
+            .build()
+            .unwrap();
+
+        (api, market_receiver)
+    }
+
+    // BingX sends all web socket data gzip compressed
+    fn gzip(text: &str) -> Vec<u8> {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(text.as_bytes()).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    // body of last request received by mock server on path
+    async fn mount_contracts(server: &MockServer) {
+        Mock::given(method("GET"))
+            .and(path("/openApi/swap/v2/quote/contracts"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "code": 0,
+                "data": [{"symbol": "BTC-USDT", "quantityPrecision": 4, "pricePrecision": 1}]
+            })))
+            .mount(server)
+            .await;
+    }
+
+    async fn last_request_body(server: &MockServer, endpoint: &str) -> String {
+        let requests = server.received_requests().await.unwrap();
+        let request = requests
+            .iter()
+            .rev()
+            .find(|request| request.url.path() == endpoint)
+            .unwrap();
+
+        String::from_utf8(request.body.clone()).unwrap()
+    }
+
+    // signature appended to query must be HMAC of rest of query with secret key
+    fn assert_signed(query_str: &str) {
+        let (unsigned, signature) = query_str.rsplit_once("&signature=").unwrap();
+
+        let mut hmac = Hmac::<Sha256>::new_from_slice(b"secret_key").unwrap();
+        hmac.update(unsigned.as_bytes());
+
+        assert_eq!(signature, hex::encode(hmac.finalize().into_bytes()));
+    }
+
+    // open BTC-USDT kline stream to web socket server at ws_uri
+    async fn open_kline_stream(
+        ws_uri: &str,
+    ) -> (BingXStreamManager, ArcReceiver<MarketMessage>, StreamId) {
+        let (market_sender, market_receiver) = build_arc_channel(DEFAULT_CHANNEL_CAPACITY);
+        let http = BingXClient::new(
+            BING_X_HOST_URL,
+            BING_X_REQUEST_TIMEOUT,
+            requests_per_second(BING_X_REQUESTS_PER_SECOND),
+        )
+        .unwrap();
+        let mut stream_manager = BingXStreamManager::new(http, market_sender, None, system_clock());
+        let stream_meta = StreamMeta::new(
+            build_stream_id("BTC-USDT", Some("1m")),
+            ws_uri,
+            "BTC-USDT",
+            StreamType::Kline,
+            Some("1m".to_string()),
+        );
+        let stream_id = stream_manager.open_stream(stream_meta).await.unwrap();
+
+        (stream_manager, market_receiver, stream_id)
+    }
+
+    #[tokio::test]
+    async fn kline_stream_sends_update_kline_from_web_socket() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let ws_uri = format!("ws://{}", listener.local_addr().unwrap());
+
+        tokio::spawn(async move {
+            let (tcp_stream, _) = listener.accept().await.unwrap();
+            let mut ws_stream = accept_async(tcp_stream).await.unwrap();
+
+            // reply to subscription with kline for subscribed data type
+            let sub = ws_stream.next().await.unwrap().unwrap();
+            let sub: Value = serde_json::from_str(&sub.into_text().unwrap()).unwrap();
+            let data_type = sub["dataType"].as_str().unwrap().to_string();
+
+            let kline = json!({
+                "code": 0,
+                "dataType": data_type,
+                "s": "BTC-USDT",
+                "data": [{
+                    "T": 1_700_000_000_000_u64,
+                    "o": "100.0",
+                    "h": "110.0",
+                    "l": "90.0",
+                    "c": "105.0",
+                    "v": "12.5"
+                }]
+            });
+            ws_stream
+                .send(Message::Binary(gzip(&kline.to_string())))
+                .await
+                .unwrap();
+
+            // hold connection open until client disconnects
+            while ws_stream.next().await.is_some() {}
+        });
+
+        let (_stream_manager, market_receiver, _stream_id) = open_kline_stream(&ws_uri).await;
+
+        let message =
+            tokio::time::timeout(Duration::from_secs(5), market_receiver.lock().await.recv())
+                .await
+                .unwrap()
+                .unwrap();
+
+        match message {
+            MarketMessage::UpdateKline(kline) => {
+                assert_eq!(kline.symbol, "BTC-USDT");
+                assert_eq!(kline.open_time, 1_700_000_000_000);
+                assert_eq!(kline.close, 105.0);
+            }
+            message => panic!("Expected UpdateKline, received {message:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn ping_is_answered_and_close_marks_stream_closed() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let ws_uri = format!("ws://{}", listener.local_addr().unwrap());
+
+        let (pong_sender, pong_receiver) = oneshot::channel::<Vec<u8>>();
+
+        tokio::spawn(async move {
+            let (tcp_stream, _) = listener.accept().await.unwrap();
+            let mut ws_stream = accept_async(tcp_stream).await.unwrap();
+
+            // wait for subscription before sending heartbeat
+            ws_stream.next().await.unwrap().unwrap();
+            ws_stream
+                .send(Message::Ping(b"heartbeat".to_vec()))
+                .await
+                .unwrap();
+
+            while let Some(Ok(message)) = ws_stream.next().await {
+                if let Message::Pong(data) = message {
+                    pong_sender.send(data).unwrap();
+                    break;
+                }
+            }
+
+            ws_stream.close(None).await.unwrap();
+        });
+
+        let (stream_manager, _market_receiver, stream_id) = open_kline_stream(&ws_uri).await;
+
+        let pong = tokio::time::timeout(Duration::from_secs(5), pong_receiver)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(pong, b"heartbeat".to_vec());
+
+        // stream marked closed once server closes connection
+        let stream_metas = stream_manager.stream_metas();
+        let status = tokio::time::timeout(Duration::from_secs(5), async {
+            loop {
+                let status = stream_metas
+                    .lock()
+                    .await
+                    .get(&stream_id)
+                    .unwrap()
+                    .status
+                    .clone();
+                if status == "closed" {
+                    return status;
+                }
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .unwrap();
+        assert_eq!(status, "closed");
+    }
+
+    #[test]
+    fn bingx_signs_with_millisecond_timestamp() {
+        let (market_sender, _) = build_arc_channel(DEFAULT_CHANNEL_CAPACITY);
+        let api = BingXApi::builder("api_key", "secret_key", market_sender)
+            .clock(Arc::new(MockClock::new(1_700_000_000_123)))
+            .build()
+            .unwrap();
+
+        assert_eq!(api.timestamp_unit(), TimestampUnit::Milliseconds);
+
+        let query_str = api.signed_query_str(&[("symbol", "BTC-USDT")]).unwrap();
+        assert!(query_str.contains("&timestamp=1700000000123&"));
+    }
+
+    #[tokio::test]
+    async fn close_position_by_symbol_sends_reduce_only_flatten_order() {
+        let server = MockServer::start().await;
+        mount_contracts(&server).await;
+        Mock::given(method("GET"))
+            .and(path("/openApi/swap/v2/user/positions"))
+            .and(query_param("symbol", "BTC-USDT"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "code": 0,
+                "data": [{
+                    "positionId": "1003",
+                    "symbol": "BTC-USDT",
+                    "positionSide": "BOTH",
+                    "positionAmt": "-0.25"
+                }]
+            })))
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/openApi/swap/v2/trade/order"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({"code": 0})))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let api = mock_api(&server);
+        api.close_position_by_symbol("BTC-USDT", OrderSide::Sell)
+            .await
+            .unwrap();
+
+        let requests = server.received_requests().await.unwrap();
+        let positions_request = requests
+            .iter()
+            .find(|request| request.url.path() == "/openApi/swap/v2/user/positions")
+            .unwrap();
+        assert_signed(positions_request.url.query().unwrap());
+
+        // short one-way position is bought back and can never flip long
+        let body = last_request_body(&server, "/openApi/swap/v2/trade/order").await;
+        assert!(body.starts_with(
+            "symbol=BTC-USDT&side=BUY&type=MARKET&quantity=0.2500&positionSide=BOTH&reduceOnly=true&"
+        ));
+        assert_signed(&body);
+    }
+
+    #[tokio::test]
+    async fn close_position_by_symbol_without_open_position_is_err() {
+        let server = MockServer::start().await;
+        mount_contracts(&server).await;
+        Mock::given(method("GET"))
+            .and(path("/openApi/swap/v2/user/positions"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "code": 0,
+                "data": [{
+                    "positionId": "1004",
+                    "symbol": "BTC-USDT",
+                    "positionSide": "LONG",
+                    "positionAmt": "0.5"
+                }]
+            })))
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/openApi/swap/v2/trade/order"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({"code": 0})))
+            .expect(0)
+            .mount(&server)
+            .await;
+
+        let api = mock_api(&server);
+        let err = api
+            .close_position_by_symbol("BTC-USDT", OrderSide::Sell)
+            .await
+            .unwrap_err();
+
+        assert_eq!(
+            err.to_string(),
+            "Invalid order: No open SHORT position for BTC-USDT"
+        );
+    }
+
+    #[tokio::test]
+    async fn reduce_only_flag_is_signed_only_when_set() {
+        let server = MockServer::start().await;
+        mount_contracts(&server).await;
+        Mock::given(method("POST"))
+            .and(path("/openApi/swap/v2/trade/order"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({"code": 0})))
+            .mount(&server)
+            .await;
+
+        let api = mock_api(&server);
+        let reduce_only = OrderOptions {
+            reduce_only: true,
+            ..Default::default()
+        };
+        api.open_order(
+            "BTC-USDT",
+            OrderSide::Sell,
+            OrderType::Market,
+            0.1,
+            None,
+            reduce_only,
+        )
+        .await
+        .unwrap();
+
+        let body = last_request_body(&server, "/openApi/swap/v2/trade/order").await;
+        assert!(body.contains("&reduceOnly=true&"));
+        assert_signed(&body);
+
+        api.open_order(
+            "BTC-USDT",
+            OrderSide::Sell,
+            OrderType::Market,
+            0.1,
+            None,
+            OrderOptions::default(),
+        )
+        .await
+        .unwrap();
+
+        let body = last_request_body(&server, "/openApi/swap/v2/trade/order").await;
+        assert!(!body.contains("reduceOnly"));
+        assert_signed(&body);
+
+        // market and limit order methods take same options
+        let reduce_only = || OrderOptions {
+            reduce_only: true,
+            ..Default::default()
+        };
+        api.open_position("BTC-USDT", OrderSide::Sell, 0.1, reduce_only())
+            .await
+            .unwrap();
+
+        let body = last_request_body(&server, "/openApi/swap/v2/trade/order").await;
+        assert!(body.contains("&type=MARKET&"));
+        assert!(body.contains("&reduceOnly=true&"));
+        assert_signed(&body);
+
+        api.open_limit_order("BTC-USDT", OrderSide::Sell, 0.1, 42_000.5, reduce_only())
+            .await
+            .unwrap();
+
+        let body = last_request_body(&server, "/openApi/swap/v2/trade/order").await;
+        assert!(body.contains("&type=LIMIT&"));
+        assert!(body.contains("&reduceOnly=true&"));
+        assert_signed(&body);
+    }
+
+    #[tokio::test]
+    async fn leverage_and_margin_mode_requests_are_signed() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({"code": 0})))
+            .mount(&server)
+            .await;
+
+        let api = mock_api(&server);
+        api.set_leverage("BTC-USDT", 10, PositionSide::Long)
+            .await
+            .unwrap();
+        api.set_margin_mode("BTC-USDT", MarginMode::Isolated)
+            .await
+            .unwrap();
+
+        let leverage_body = last_request_body(&server, "/openApi/swap/v2/trade/leverage").await;
+        assert!(leverage_body.starts_with("symbol=BTC-USDT&side=LONG&leverage=10&"));
+        assert_signed(&leverage_body);
+
+        let margin_body = last_request_body(&server, "/openApi/swap/v2/trade/marginType").await;
+        assert!(margin_body.starts_with("symbol=BTC-USDT&marginType=ISOLATED&"));
+        assert_signed(&margin_body);
+    }
+
+    #[tokio::test]
+    async fn position_side_is_signed_and_defaults_to_both() {
+        let server = MockServer::start().await;
+        mount_contracts(&server).await;
+        Mock::given(method("POST"))
+            .and(path("/openApi/swap/v2/trade/order"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({"code": 0})))
+            .mount(&server)
+            .await;
+
+        let api = mock_api(&server);
+        let short_side = OrderOptions {
+            position_side: PositionSide::Short,
+            ..Default::default()
+        };
+        api.open_order(
+            "BTC-USDT",
+            OrderSide::Sell,
+            OrderType::Market,
+            0.1,
+            None,
+            short_side,
+        )
+        .await
+        .unwrap();
+
+        let body = last_request_body(&server, "/openApi/swap/v2/trade/order").await;
+        assert!(body.contains("&positionSide=SHORT&"));
+        assert_signed(&body);
+
+        // one-way mode by default
+        api.open_position("BTC-USDT", OrderSide::Buy, 0.1, OrderOptions::default())
+            .await
+            .unwrap();
+
+        let body = last_request_body(&server, "/openApi/swap/v2/trade/order").await;
+        assert!(body.contains("&positionSide=BOTH&"));
+        assert_signed(&body);
+    }
+
+    #[test]
+    fn builder_applies_custom_options() {
+        let (market_sender, _) = build_arc_channel(DEFAULT_CHANNEL_CAPACITY);
+        let api = BingXApi::builder("api_key", "secret_key", market_sender)
+            .hosts("http://127.0.0.1:9000", "ws://127.0.0.1:9001")
+            .recv_window(10_000)
+            .request_timeout(Duration::from_secs(3))
+            .clock(Arc::new(MockClock::new(1_700_000_000_000)))
+            .build()
+            .unwrap();
+
+        assert_eq!(api.http.host, "http://127.0.0.1:9000");
+        assert_eq!(api.ws_host, "ws://127.0.0.1:9001");
+        assert_eq!(api.recv_window, 10_000);
+        assert_eq!(
+            api.signed_query_str(&[])
+                .unwrap()
+                .split('&')
+                .take(2)
+                .collect::<Vec<_>>(),
+            vec!["recvWindow=10000", "timestamp=1700000000000"]
+        );
+    }
+
+    #[test]
+    fn new_uses_mainnet_defaults() {
+        let (market_sender, _) = build_arc_channel(DEFAULT_CHANNEL_CAPACITY);
+        let api = BingXApi::new("api_key", "secret_key", market_sender).unwrap();
+
+        assert_eq!(api.http.host, BING_X_HOST_URL);
+        assert_eq!(api.ws_host, BING_X_WS_HOST_URL);
+        assert_eq!(api.recv_window, BING_X_RECV_WINDOW);
+    }
+
+    #[test]
+    fn empty_credentials_fail_at_construction() {
+        for (api_key, secret_key) in [("", "secret_key"), ("api_key", "")] {
+            let (market_sender, _) = build_arc_channel(DEFAULT_CHANNEL_CAPACITY);
+
+            assert!(matches!(
+                BingXApi::new(api_key, secret_key, market_sender),
+                Err(ExchangeError::Auth(_))
+            ));
+        }
+    }
+
+    fn order_status_response(status: &str) -> ResponseTemplate {
+        ResponseTemplate::new(200).set_body_json(json!({
+            "code": 0,
+            "data": {"order": {"orderId": 1_735_950_529_123_455_u64, "status": status}}
+        }))
+    }
+
+    #[tokio::test]
+    async fn wait_for_fill_polls_until_order_filled() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/openApi/swap/v2/trade/order"))
+            .respond_with(order_status_response("NEW"))
+            .up_to_n_times(2)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/openApi/swap/v2/trade/order"))
+            .respond_with(order_status_response("FILLED"))
+            .mount(&server)
+            .await;
+
+        let api = mock_api(&server);
+        let status = api
+            .wait_for_fill("BTC-USDT", "1735950529123455", Duration::from_secs(5))
+            .await
+            .unwrap();
+
+        assert_eq!(status, OrderStatus::Filled);
+
+        let requests = server.received_requests().await.unwrap();
+        assert_eq!(requests.len(), 3);
+        for request in &requests {
+            let query_str = request.url.query().unwrap();
+            assert!(query_str.starts_with("symbol=BTC-USDT&orderId=1735950529123455&"));
+            assert_signed(query_str);
+        }
+    }
+
+    #[tokio::test]
+    async fn wait_for_fill_returns_last_status_on_timeout() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/openApi/swap/v2/trade/order"))
+            .respond_with(order_status_response("PARTIALLY_FILLED"))
+            .mount(&server)
+            .await;
+
+        let api = mock_api(&server);
+        let status = api
+            .wait_for_fill("BTC-USDT", "1735950529123455", Duration::from_millis(100))
+            .await
+            .unwrap();
+
+        assert_eq!(status, OrderStatus::PartiallyFilled);
+    }
+
+    #[tokio::test]
+    async fn wait_for_fill_timeout_follows_exchange_clock() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/openApi/swap/v2/trade/order"))
+            .respond_with(order_status_response("NEW"))
+            .mount(&server)
+            .await;
+
+        let clock = Arc::new(MockClock::new(1_700_000_000_000));
+        let (market_sender, _) = build_arc_channel(DEFAULT_CHANNEL_CAPACITY);
+        let api = BingXApi::builder("api_key", "secret_key", market_sender)
+            .hosts(&server.uri(), "ws://localhost")
+            .clock(clock.clone())
+            .build()
+            .unwrap();
+
+        // order still open once exchange clock passes deadline
+        let advance_clock = tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(300)).await;
+            clock.advance(Duration::from_secs(60));
+        });
+        let status = tokio::time::timeout(
+            Duration::from_secs(5),
+            api.wait_for_fill("BTC-USDT", "1735950529123455", Duration::from_secs(60)),
+        )
+        .await
+        .unwrap()
+        .unwrap();
+        advance_clock.await.unwrap();
+
+        assert_eq!(status, OrderStatus::New);
+    }
+
+    #[test]
+    fn signature_timestamp_follows_injected_clock() {
+        let (market_sender, _) = build_arc_channel(DEFAULT_CHANNEL_CAPACITY);
+        let clock = Arc::new(MockClock::new(1_700_000_000_000));
+        let api = BingXApi::builder("api_key", "secret_key", market_sender)
+            .clock(clock.clone())
+            .build()
+            .unwrap();
+
+        let query_str = api.signed_query_str(&[]).unwrap();
+        assert!(query_str.starts_with("recvWindow=5000&timestamp=1700000000000&signature="));
+
+        clock.advance(Duration::from_secs(2));
+        let advanced_query_str = api.signed_query_str(&[]).unwrap();
+        assert!(
+            advanced_query_str.starts_with("recvWindow=5000&timestamp=1700000002000&signature=")
+        );
+        assert_signed(&advanced_query_str);
+
+        let mut api = api;
+        api.set_clock(Arc::new(MockClock::new(1_600_000_000_000)));
+        let replaced_query_str = api.signed_query_str(&[]).unwrap();
+        assert!(
+            replaced_query_str.starts_with("recvWindow=5000&timestamp=1600000000000&signature=")
+        );
+    }
+
+    #[tokio::test]
+    async fn close_position_sends_opposite_market_order() {
+        let server = MockServer::start().await;
+        mount_contracts(&server).await;
+        Mock::given(method("GET"))
+            .and(path("/openApi/swap/v2/user/positions"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "code": 0,
+                "data": [
+                    {
+                        "positionId": "1001",
+                        "symbol": "ETH-USDT",
+                        "positionSide": "SHORT",
+                        "positionAmt": "2.0"
+                    },
+                    {
+                        "positionId": "1002",
+                        "symbol": "BTC-USDT",
+                        "positionSide": "LONG",
+                        "positionAmt": "0.5"
+                    }
+                ]
+            })))
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/openApi/swap/v2/trade/order"))
+            .and(body_string_contains("symbol=BTC-USDT"))
+            .and(body_string_contains("side=SELL"))
+            .and(body_string_contains("positionSide=LONG"))
+            .and(body_string_contains("type=MARKET"))
+            .and(body_string_contains("quantity=0.5"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "code": 0,
+                "data": {"order": {"orderId": 1_735_950_529_123_455_u64, "symbol": "BTC-USDT"}}
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let api = mock_api(&server);
+        let res = api.close_position("1002").await.unwrap();
+
+        assert_eq!(res["data"]["order"]["orderId"], 1_735_950_529_123_455_u64);
+    }
+
+    #[tokio::test]
+    async fn close_position_sells_one_way_long_position_reduce_only() {
+        let server = MockServer::start().await;
+        mount_contracts(&server).await;
+        Mock::given(method("GET"))
+            .and(path("/openApi/swap/v2/user/positions"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "code": 0,
+                "data": [{
+                    "positionId": "1004",
+                    "symbol": "BTC-USDT",
+                    "positionSide": "BOTH",
+                    "positionAmt": "0.75"
+                }]
+            })))
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/openApi/swap/v2/trade/order"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({"code": 0})))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let api = mock_api(&server);
+        api.close_position("1004").await.unwrap();
+
+        let body = last_request_body(&server, "/openApi/swap/v2/trade/order").await;
+        assert!(body.starts_with(
+            "symbol=BTC-USDT&side=SELL&type=MARKET&quantity=0.7500&positionSide=BOTH&reduceOnly=true&"
+        ));
+        assert_signed(&body);
+    }
+
+    #[tokio::test]
+    async fn close_position_buys_back_one_way_short_position_reduce_only() {
+        let server = MockServer::start().await;
+        mount_contracts(&server).await;
+        Mock::given(method("GET"))
+            .and(path("/openApi/swap/v2/user/positions"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "code": 0,
+                "data": [{
+                    "positionId": "1005",
+                    "symbol": "BTC-USDT",
+                    "positionSide": "BOTH",
+                    "positionAmt": "-0.5"
+                }]
+            })))
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/openApi/swap/v2/trade/order"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({"code": 0})))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let api = mock_api(&server);
+        api.close_position("1005").await.unwrap();
+
+        // opposite of sell which opened short, quantity is absolute amount
+        let body = last_request_body(&server, "/openApi/swap/v2/trade/order").await;
+        assert!(body.starts_with(
+            "symbol=BTC-USDT&side=BUY&type=MARKET&quantity=0.5000&positionSide=BOTH&reduceOnly=true&"
+        ));
+        assert_signed(&body);
+    }
+
+    #[tokio::test]
+    async fn limit_order_query_is_signed_with_price() {
+        let server = MockServer::start().await;
+        mount_contracts(&server).await;
+        Mock::given(method("POST"))
+            .and(path("/openApi/swap/v2/trade/order"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({"code": 0})))
+            .mount(&server)
+            .await;
+
+        let api = mock_api(&server);
+        api.open_limit_order(
+            "BTC-USDT",
+            OrderSide::Buy,
+            0.01,
+            42_000.5,
+            OrderOptions::default(),
+        )
+        .await
+        .unwrap();
+
+        let body = last_request_body(&server, "/openApi/swap/v2/trade/order").await;
+        assert!(body.contains("type=LIMIT"));
+        assert!(body.contains("timeInForce=GTC"));
+        assert!(body.contains("price=42000.5"));
+        assert_signed(&body);
+    }
+
+    #[test]
+    fn parse_error_code_and_malformed_payloads_return_err() {
+        let error_payload = r#"{"code":100400,"msg":"Invalid parameters"}"#;
+        let malformed_payload = "<html>Too many requests</html>";
+
+        assert!(matches!(
+            BingXApi::parse_kline(error_payload, "BTC-USDT", "1m", 0),
+            Err(ExchangeError::Api { code: 100400, .. })
+        ));
+        assert!(matches!(
+            BingXApi::parse_ticker(error_payload),
+            Err(ExchangeError::Api { code: 100400, .. })
+        ));
+
+        assert!(matches!(
+            BingXApi::parse_kline(malformed_payload, "BTC-USDT", "1m", 0),
+            Err(ExchangeError::Json(_))
+        ));
+        assert!(matches!(
+            BingXApi::parse_ticker(malformed_payload),
+            Err(ExchangeError::Json(_))
+        ));
+    }
+
+    #[test]
+    fn testnet_uses_demo_trading_hosts() {
+        let (market_sender, _) = build_arc_channel(DEFAULT_CHANNEL_CAPACITY);
+        let api = BingXApi::builder("api_key", "secret_key", market_sender)
+            .testnet()
+            .build()
+            .unwrap();
+
+        assert_eq!(api.http.host, BING_X_TESTNET_HOST_URL);
+        assert_eq!(
+            api.build_stream_url("BTC-USDT", StreamType::Kline, Some(Interval::Min1)),
+            BING_X_TESTNET_WS_HOST_URL
+        );
+        assert!(api.http.host.contains("open-api-vst.bingx.com"));
+    }
+
+    #[tokio::test]
+    async fn market_order_sends_requested_quantity() {
+        let server = MockServer::start().await;
+        mount_contracts(&server).await;
+        Mock::given(method("POST"))
+            .and(path("/openApi/swap/v2/trade/order"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({"code": 0})))
+            .mount(&server)
+            .await;
+
+        let api = mock_api(&server);
+        api.open_position("BTC-USDT", OrderSide::Buy, 0.1234, OrderOptions::default())
+            .await
+            .unwrap();
+
+        let body = last_request_body(&server, "/openApi/swap/v2/trade/order").await;
+        assert!(body.contains("type=MARKET"));
+        assert!(body.contains("quantity=0.1234"));
+        assert!(!body.contains("quoteOrderQty"));
+        assert_signed(&body);
+    }
+
+    #[tokio::test]
+    async fn symbol_filters_reloaded_once_older_than_max_age() {
+        let server = MockServer::start().await;
+        mount_contracts(&server).await;
+        Mock::given(method("POST"))
+            .and(path("/openApi/swap/v2/trade/order"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({"code": 0})))
+            .mount(&server)
+            .await;
+
+        let clock = Arc::new(MockClock::new(1_700_000_000_000));
+        let (market_sender, _) = build_arc_channel(DEFAULT_CHANNEL_CAPACITY);
+        let api = BingXApi::builder("api_key", "secret_key", market_sender)
+            .hosts(&server.uri(), "ws://localhost")
+            .metadata_max_age(Duration::from_secs(60))
+            .clock(clock.clone())
+            .build()
+            .unwrap();
+
+        let contracts_requests = || async {
+            server
+                .received_requests()
+                .await
+                .unwrap()
+                .iter()
+                .filter(|request| request.url.path() == "/openApi/swap/v2/quote/contracts")
+                .count()
+        };
+        let order = || api.open_position("BTC-USDT", OrderSide::Buy, 0.1, OrderOptions::default());
+
+        // loaded once, then served from cache within max age
+        order().await.unwrap();
+        clock.advance(Duration::from_secs(59));
+        order().await.unwrap();
+        assert_eq!(contracts_requests().await, 1);
+
+        // unknown symbols are rejected from cache
+        assert!(matches!(
+            api.open_position("ETH-USDT", OrderSide::Buy, 0.1, OrderOptions::default())
+                .await,
+            Err(ExchangeError::UnknownSymbol(_))
+        ));
+        assert_eq!(contracts_requests().await, 1);
+
+        clock.advance(Duration::from_secs(1));
+        order().await.unwrap();
+        assert_eq!(contracts_requests().await, 2);
+    }
+
+    #[tokio::test]
+    async fn unknown_or_halted_symbol_rejected_without_order_request() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/openApi/swap/v2/quote/contracts"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "code": 0,
+                "data": [
+                    {"symbol": "BTC-USDT", "quantityPrecision": 4, "pricePrecision": 1, "status": 1},
+                    {"symbol": "LUNA-USDT", "quantityPrecision": 0, "pricePrecision": 4, "status": 0}
+                ]
+            })))
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/openApi/swap/v2/trade/order"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({"code": 0})))
+            .expect(0)
+            .mount(&server)
+            .await;
+
+        let api = mock_api(&server);
+
+        // symbol typo, halted contract and symbol not listed on exchange
+        for symbol in ["BTCUSDT", "LUNA-USDT", "ETH-USDT"] {
+            assert!(matches!(
+                api.open_position(symbol, OrderSide::Buy, 1.0, OrderOptions::default())
+                    .await,
+                Err(ExchangeError::UnknownSymbol(unknown)) if unknown == symbol
+            ));
+            assert!(matches!(
+                api.open_limit_order(symbol, OrderSide::Buy, 1.0, 1.0, OrderOptions::default())
+                    .await,
+                Err(ExchangeError::UnknownSymbol(_))
+            ));
+        }
+    }
+
+    #[tokio::test]
+    async fn open_position_sends_exact_signed_body_and_headers() {
+        let server = MockServer::start().await;
+        mount_contracts(&server).await;
+        Mock::given(method("POST"))
+            .and(path("/openApi/swap/v2/trade/order"))
+            .and(header("X-BX-APIKEY", "api_key"))
+            .and(header("Content-Type", "application/json"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({"code": 0})))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let (market_sender, _) = build_arc_channel(DEFAULT_CHANNEL_CAPACITY);
+        let api = BingXApi::builder("api_key", "secret_key", market_sender)
+            .hosts(&server.uri(), "ws://localhost")
+            .clock(Arc::new(MockClock::new(1_700_000_000_000)))
+            .build()
+            .unwrap();
+
+        api.open_position("BTC-USDT", OrderSide::Sell, 0.5, OrderOptions::default())
+            .await
+            .unwrap();
+
+        let unsigned = "symbol=BTC-USDT&side=SELL&type=MARKET&quantity=0.5000\
+            &positionSide=BOTH&recvWindow=5000&timestamp=1700000000000";
+        let mut hmac = Hmac::<Sha256>::new_from_slice(b"secret_key").unwrap();
+        hmac.update(unsigned.as_bytes());
+        let signature = hex::encode(hmac.finalize().into_bytes());
+
+        assert_eq!(
+            last_request_body(&server, "/openApi/swap/v2/trade/order").await,
+            format!("{unsigned}&signature={signature}")
+        );
+    }
+
+    #[tokio::test]
+    async fn headers_carry_bingx_api_key() {
+        let server = MockServer::start().await;
+        mount_contracts(&server).await;
+        Mock::given(method("POST"))
+            .and(path("/openApi/swap/v2/trade/order"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({"code": 0})))
+            .mount(&server)
+            .await;
+
+        let api = mock_api(&server);
+        let headers = api.build_headers(false).unwrap();
+        assert_eq!(headers.get("X-BX-APIKEY").unwrap(), "api_key");
+        assert!(headers.get("X-MBX-APIKEY").is_none());
+
+        // signed requests carry receive window and timestamp
+        api.open_position("BTC-USDT", OrderSide::Buy, 0.1, OrderOptions::default())
+            .await
+            .unwrap();
+        let body = last_request_body(&server, "/openApi/swap/v2/trade/order").await;
+        assert!(body.contains(&format!("recvWindow={BING_X_RECV_WINDOW}")));
+        assert!(body.contains("timestamp="));
+        assert_signed(&body);
+    }
+
+    #[tokio::test]
+    async fn get_balance_reads_swap_balance_endpoint() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/openApi/swap/v2/user/balance"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "code": 0,
+                "data": {"balance": {
+                    "asset": "USDT",
+                    "availableMargin": "90.5",
+                    "usedMargin": "8.0",
+                    "freezedMargin": "1.5"
+                }}
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let api = mock_api(&server);
+        let balance = api.get_balance("USDT").await.unwrap();
+
+        assert_eq!(balance.asset, "USDT");
+        assert_eq!(balance.free, 90.5);
+        assert_eq!(balance.locked, 9.5);
+    }
+
+    #[tokio::test]
+    async fn signed_get_requests_carry_valid_signature() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({"code": 0})))
+            .mount(&server)
+            .await;
+
+        let api = mock_api(&server);
+        api.get_account().await.unwrap();
+        api.all_orders().await.unwrap();
+        api.list_open_orders().await.unwrap();
+
+        let requests = server.received_requests().await.unwrap();
+        assert_eq!(requests.len(), 3);
+        for request in requests {
+            let query_str = request.url.query().unwrap();
+            assert!(query_str.contains("timestamp="));
+            assert_signed(query_str);
+        }
+    }
+
+    #[tokio::test]
+    async fn cancel_requests_are_signed_with_order_and_symbol() {
+        let server = MockServer::start().await;
+        Mock::given(method("DELETE"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({"code": 0})))
+            .mount(&server)
+            .await;
+
+        let api = mock_api(&server);
+        api.cancel_order("BTC-USDT", "1736011869418901234")
+            .await
+            .unwrap();
+        api.cancel_all_orders("ETH-USDT").await.unwrap();
+
+        let requests = server.received_requests().await.unwrap();
+        assert_eq!(requests.len(), 2);
+
+        let cancel_query = requests[0].url.query().unwrap();
+        assert_eq!(requests[0].url.path(), "/openApi/swap/v2/trade/order");
+        assert!(cancel_query.contains("symbol=BTC-USDT"));
+        assert!(cancel_query.contains("orderId=1736011869418901234"));
+        assert_signed(cancel_query);
+
+        let cancel_all_query = requests[1].url.query().unwrap();
+        assert_eq!(
+            requests[1].url.path(),
+            "/openApi/swap/v2/trade/allOpenOrders"
+        );
+        assert!(cancel_all_query.contains("symbol=ETH-USDT"));
+        assert_signed(cancel_all_query);
+    }
+
+    fn bingx_klines_page(open_times: impl Iterator<Item = u64>) -> Value {
+        let klines: Vec<Value> = open_times
+            .map(|time| {
+                json!({
+                    "open": "100.0",
+                    "high": "110.0",
+                    "low": "90.0",
+                    "close": "105.0",
+                    "volume": "10.0",
+                    "time": time
+                })
+            })
+            .collect();
+
+        json!({"code": 0, "data": klines})
+    }
+
+    #[tokio::test]
+    async fn klines_range_pages_are_stitched_in_order() {
+        const MINUTE: u64 = 60_000;
+        let from_ts = 1_700_000_000_000;
+        let to_ts = from_ts + 1500 * MINUTE;
+        let open_time = |i: u64| from_ts + i * MINUTE;
+
+        let server = MockServer::start().await;
+        // first page is full, so next page is requested
+        // from after last kline of first page
+        Mock::given(method("GET"))
+            .and(path("/openApi/swap/v2/quote/klines"))
+            .and(query_param("startTime", from_ts.to_string()))
+            .respond_with(ResponseTemplate::new(200).set_body_json(bingx_klines_page(
+                (0..BING_X_KLINES_LIMIT as u64).map(open_time),
+            )))
+            .expect(1)
+            .mount(&server)
+            .await;
+        // second page overlaps last kline of first page, returned newest first
+        Mock::given(method("GET"))
+            .and(path("/openApi/swap/v2/quote/klines"))
+            .and(query_param(
+                "startTime",
+                (from_ts + (BING_X_KLINES_LIMIT as u64 - 1) * MINUTE + 1).to_string(),
+            ))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_json(bingx_klines_page((999..1500).rev().map(open_time))),
+            )
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let api = mock_api(&server);
+        let klines = api
+            .get_klines_range("BTC-USDT", Interval::Min1, from_ts, to_ts)
+            .await
+            .unwrap();
+
+        assert_eq!(klines.len(), 1500);
+        assert_eq!(klines[0].open_time, from_ts);
+        assert!(klines
+            .windows(2)
+            .all(|pair| pair[1].open_time - pair[0].open_time == MINUTE));
+
+        for request in server.received_requests().await.unwrap() {
+            let query = request.url.query().unwrap();
+            assert!(query.contains("limit=1000"));
+            assert!(query.contains(&format!("endTime={to_ts}")));
+        }
+    }
+
+    #[tokio::test]
+    async fn klines_error_response_returns_api_error() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/openApi/swap/v2/quote/klines"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_json(json!({"code": 100400, "msg": "Invalid parameters"})),
+            )
+            .expect(2)
+            .mount(&server)
+            .await;
+
+        let api = mock_api(&server);
+
+        assert!(matches!(
+            api.get_kline("BTC-USDT", Interval::Min1).await,
+            Err(ExchangeError::Api { code: 100400, .. })
+        ));
+        assert!(matches!(
+            api.get_klines("BTC-USDT", Interval::Min1, 10).await,
+            Err(ExchangeError::Api { code: 100400, .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn signing_timestamp_is_corrected_by_server_time_offset() {
+        let clock = Arc::new(MockClock::new(1_700_000_000_000));
+
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/openApi/swap/v2/server/time"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "code": 0,
+                "data": {"serverTime": clock.now() + 3000}
+            })))
+            .mount(&server)
+            .await;
+
+        let (market_sender, _) = build_arc_channel(DEFAULT_CHANNEL_CAPACITY);
+        let api = BingXApi::builder("api_key", "secret_key", market_sender)
+            .hosts(&server.uri(), "ws://localhost")
+            .clock(clock.clone())
+            .build()
+            .unwrap();
+
+        let offset = api.sync_server_time().await.unwrap();
+        assert_eq!(offset, 3000);
+        assert_eq!(api.time_offset(), offset);
+
+        assert_eq!(api.signing_ts(), clock.now() + 3000);
+    }
+
+    #[tokio::test]
+    async fn signed_requests_carry_recv_window() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({"code": 0})))
+            .mount(&server)
+            .await;
+
+        let mut api = mock_api(&server);
+        api.get_account().await.unwrap();
+        api.set_recv_window(10_000);
+        api.get_account().await.unwrap();
+
+        let requests = server.received_requests().await.unwrap();
+        let default_query = requests[0].url.query().unwrap();
+        assert!(default_query.contains(&format!("recvWindow={BING_X_RECV_WINDOW}&")));
+        assert_signed(default_query);
+
+        // receive window is signed with rest of query
+        let query_str = requests[1].url.query().unwrap();
+        assert!(query_str.contains("recvWindow=10000&"));
+        assert_signed(query_str);
+    }
+
+    #[tokio::test]
+    async fn stream_health_advances_as_messages_flow() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let ws_uri = format!("ws://{}", listener.local_addr().unwrap());
+        let (send_kline, send_kline_receiver) = oneshot::channel::<()>();
+
+        tokio::spawn(async move {
+            let (tcp_stream, _) = listener.accept().await.unwrap();
+            let mut ws_stream = accept_async(tcp_stream).await.unwrap();
+
+            let sub = ws_stream.next().await.unwrap().unwrap();
+            let sub: Value = serde_json::from_str(&sub.into_text().unwrap()).unwrap();
+            send_kline_receiver.await.unwrap();
+
+            let kline = json!({
+                "code": 0,
+                "dataType": sub["dataType"],
+                "s": "BTC-USDT",
+                "data": [{
+                    "T": 1_700_000_000_000_u64,
+                    "o": "100.0",
+                    "h": "110.0",
+                    "l": "90.0",
+                    "c": "105.0",
+                    "v": "12.5"
+                }]
+            });
+            ws_stream
+                .send(Message::Binary(gzip(&kline.to_string())))
+                .await
+                .unwrap();
+
+            while ws_stream.next().await.is_some() {}
+        });
+
+        let (stream_manager, market_receiver, stream_id) = open_kline_stream(&ws_uri).await;
+
+        let health = stream_manager.stream_health(&stream_id).await.unwrap();
+        assert_eq!(health.message_count, 0);
+
+        let before_ts = generate_ts();
+        send_kline.send(()).unwrap();
+        tokio::time::timeout(Duration::from_secs(5), market_receiver.lock().await.recv())
+            .await
+            .unwrap()
+            .unwrap();
+
+        // message is recorded after it is sent to market
+        let last_update = tokio::time::timeout(Duration::from_secs(5), async {
+            loop {
+                let stream_metas = stream_manager.stream_metas();
+                if let Some(meta) = stream_metas.lock().await.get(&stream_id) {
+                    if meta.message_count == 1 {
+                        return meta.last_update;
+                    }
+                }
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .unwrap();
+        assert!(last_update >= before_ts);
+
+        let health = stream_manager.stream_health(&stream_id).await.unwrap();
+        assert_eq!(health.message_count, 1);
+        assert!(health.age <= generate_ts() - last_update);
+    }
+
+    #[test]
+    fn exchange_is_named_bingx() {
+        let (market_sender, _) = build_arc_channel(DEFAULT_CHANNEL_CAPACITY);
+        let api = BingXApi::new("api_key", "secret_key", market_sender).unwrap();
+
+        assert_eq!(api.name(), "bingx");
+    }
+
+    #[tokio::test]
+    async fn get_klines_returns_all_candles_oldest_first() {
+        let from_ts = 1_700_000_000_000;
+        let open_time = |i: u64| from_ts + i * 60_000;
+
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/openApi/swap/v2/quote/klines"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_json(bingx_klines_page((0..5).rev().map(open_time))),
+            )
+            .mount(&server)
+            .await;
+
+        let api = mock_api(&server);
+        let klines = api.get_klines("BTC-USDT", Interval::Min1, 5).await.unwrap();
+
+        let open_times: Vec<u64> = klines.iter().map(|kline| kline.open_time).collect();
+        let expected: Vec<u64> = (0..5).map(|i| from_ts + i * 60_000).collect();
+        assert_eq!(open_times, expected);
+
+        // single kline is latest kline of response
+        let kline = api.get_kline("BTC-USDT", Interval::Min1).await.unwrap();
+        assert_eq!(kline.open_time, from_ts + 4 * 60_000);
+
+        let requests = server.received_requests().await.unwrap();
+        assert!(requests[0].url.query().unwrap().contains("limit=5"));
+        assert!(requests[1].url.query().unwrap().contains("limit=1"));
+    }
+
+    #[tokio::test]
+    async fn ticker_stream_sends_update_ticker_from_web_socket() {
+        let server = MockServer::start().await;
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let ws_uri = format!("ws://{}", listener.local_addr().unwrap());
+
+        tokio::spawn(async move {
+            let (tcp_stream, _) = listener.accept().await.unwrap();
+            let mut ws_stream = accept_async(tcp_stream).await.unwrap();
+
+            let sub = ws_stream.next().await.unwrap().unwrap();
+            let sub: Value = serde_json::from_str(&sub.into_text().unwrap()).unwrap();
+            assert_eq!(sub["dataType"], "BTC-USDT@ticker");
+
+            let ticker = json!({
+                "code": 0,
+                "dataType": "BTC-USDT@ticker",
+                "data": {
+                    "e": "24hTicker",
+                    "E": 1_672_026_667_803_u64,
+                    "s": "BTC-USDT",
+                    "p": "52.5",
+                    "P": "0.31",
+                    "c": "16880.5",
+                    "h": "16897.5",
+                    "l": "16726.0",
+                    "v": "245870.1692",
+                    "q": "4151395117.73",
+                    "o": "16832.0",
+                    "O": 1_671_940_267_803_u64,
+                    "C": 1_672_026_648_425_u64
+                }
+            });
+            ws_stream
+                .send(Message::Binary(gzip(&ticker.to_string())))
+                .await
+                .unwrap();
+
+            while ws_stream.next().await.is_some() {}
+        });
+
+        let (api, market_receiver) = mock_ws_api(&server, &ws_uri);
+        api.open_stream(StreamType::Ticker, "BTC-USDT", None)
+            .await
+            .unwrap();
+
+        let message =
+            tokio::time::timeout(Duration::from_secs(5), market_receiver.lock().await.recv())
+                .await
+                .unwrap()
+                .unwrap();
+
+        match message {
+            MarketMessage::UpdateTicker(ticker) => {
+                assert_eq!(ticker.symbol, "BTC-USDT");
+                assert_eq!(ticker.last_price, 16_880.5);
+                assert_eq!(ticker.time, 1_672_026_667_803);
+            }
+            message => panic!("Expected UpdateTicker, received {message:?}"),
+        }
+
+        // ticker received over web socket, no HTTP polling
+        assert!(server.received_requests().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn ticker_stream_polls_http_when_web_socket_unavailable() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/openApi/swap/v2/quote/ticker"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "code": 0,
+                "data": {
+                    "symbol": "BTC-USDT",
+                    "lastPrice": "16880.5",
+                    "priceChange": "52.5",
+                    "priceChangePercent": "0.31",
+                    "highPrice": "16897.5",
+                    "lowPrice": "16726.0",
+                    "openPrice": "16832.0",
+                    "volume": "245870.1692",
+                    "quoteVolume": "4151395117.73",
+                    "openTime": 1_671_940_267_803_u64,
+                    "closeTime": 1_672_026_648_425_u64
+                }
+            })))
+            .mount(&server)
+            .await;
+
+        // nothing listening on port once listener is dropped
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let ws_uri = format!("ws://{}", listener.local_addr().unwrap());
+        drop(listener);
+
+        let (api, market_receiver) = mock_ws_api(&server, &ws_uri);
+        api.open_stream(StreamType::Ticker, "BTC-USDT", None)
+            .await
+            .unwrap();
+
+        let message =
+            tokio::time::timeout(Duration::from_secs(5), market_receiver.lock().await.recv())
+                .await
+                .unwrap()
+                .unwrap();
+        assert!(matches!(
+            message,
+            MarketMessage::UpdateTicker(ticker) if ticker.last_price == 16_880.5
+        ));
+        assert!(!server.received_requests().await.unwrap().is_empty());
+
+        api.close_stream(&build_stream_id("BTC-USDT", None)).await;
+    }
+
+    // web socket server forwarding (connection number, request type, data type)
+    // of each request, request type is "closed" once connection is closed
+    async fn spawn_recording_ws_server() -> (
+        String,
+        tokio::sync::mpsc::UnboundedReceiver<(usize, String, String)>,
+    ) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let ws_uri = format!("ws://{}", listener.local_addr().unwrap());
+
+        let (ws_sender, ws_receiver) = tokio::sync::mpsc::unbounded_channel();
+        tokio::spawn(async move {
+            let mut connections = 0;
+            while let Ok((tcp_stream, _)) = listener.accept().await {
+                connections += 1;
+                let connection = connections;
+                let ws_sender = ws_sender.clone();
+
+                tokio::spawn(async move {
+                    let mut ws_stream = accept_async(tcp_stream).await.unwrap();
+                    while let Some(Ok(Message::Text(text))) = ws_stream.next().await {
+                        let req: Value = serde_json::from_str(&text).unwrap();
+                        let _ = ws_sender.send((
+                            connection,
+                            req["reqType"].as_str().unwrap().to_string(),
+                            req["dataType"].as_str().unwrap().to_string(),
+                        ));
+                    }
+                    let _ = ws_sender.send((connection, "closed".to_string(), String::new()));
+                });
+            }
+        });
+
+        (ws_uri, ws_receiver)
+    }
+
+    async fn next_ws_request<T>(ws_receiver: &mut tokio::sync::mpsc::UnboundedReceiver<T>) -> T {
+        tokio::time::timeout(Duration::from_secs(5), ws_receiver.recv())
+            .await
+            .unwrap()
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn streams_share_single_web_socket_connection() {
+        let server = MockServer::start().await;
+        let (ws_uri, mut ws_receiver) = spawn_recording_ws_server().await;
+
+        let (api, _market_receiver) = mock_ws_api(&server, &ws_uri);
+        let kline_id = api
+            .open_stream(StreamType::Kline, "BTC-USDT", Some(Interval::Min1))
+            .await
+            .unwrap();
+        let ticker_id = api
+            .open_stream(StreamType::Ticker, "ETH-USDT", None)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            next_ws_request(&mut ws_receiver).await,
+            (1, "sub".to_string(), "BTC-USDT@kline_1m".to_string())
+        );
+        assert_eq!(
+            next_ws_request(&mut ws_receiver).await,
+            (1, "sub".to_string(), "ETH-USDT@ticker".to_string())
+        );
+
+        // connection kept open while a subscription remains
+        api.close_stream(&kline_id).await;
+        assert_eq!(
+            next_ws_request(&mut ws_receiver).await,
+            (1, "unsub".to_string(), "BTC-USDT@kline_1m".to_string())
+        );
+
+        // last subscription is unsubscribed before connection is closed
+        api.close_stream(&ticker_id).await;
+        assert_eq!(
+            next_ws_request(&mut ws_receiver).await,
+            (1, "unsub".to_string(), "ETH-USDT@ticker".to_string())
+        );
+        let (connection, req_type, _) = next_ws_request(&mut ws_receiver).await;
+        assert_eq!((connection, req_type.as_str()), (1, "closed"));
+    }
+
+    #[tokio::test]
+    async fn requests_share_rate_limit_across_tasks() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({"code": 0})))
+            .mount(&server)
+            .await;
+
+        let http = BingXClient::new(
+            &server.uri(),
+            BING_X_REQUEST_TIMEOUT,
+            requests_per_second(5),
+        )
+        .unwrap();
+
+        // burst of 5 allowed at once, then 1 request every 200ms
+        let start = std::time::Instant::now();
+        let tasks: Vec<_> = (0..10)
+            .map(|_| {
+                let http = http.clone();
+                tokio::spawn(async move { fetch_bingx_ticker_str(&http, "BTC-USDT").await })
+            })
+            .collect();
+        for task in tasks {
+            task.await.unwrap().unwrap();
+        }
+        let elapsed = start.elapsed();
+
+        assert_eq!(server.received_requests().await.unwrap().len(), 10);
+        assert!(elapsed >= Duration::from_millis(900), "{elapsed:?}");
+        assert!(elapsed < Duration::from_secs(3), "{elapsed:?}");
+    }
+
+    #[tokio::test]
+    async fn rate_limited_requests_are_retried_after_delay() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .respond_with(ResponseTemplate::new(429).insert_header("Retry-After", "1"))
+            .up_to_n_times(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({"code": 0})))
+            .mount(&server)
+            .await;
+
+        let api = mock_api(&server);
+        let start = std::time::Instant::now();
+        api.get_account().await.unwrap();
+
+        assert!(start.elapsed() >= Duration::from_secs(1));
+        assert_eq!(server.received_requests().await.unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn rate_limited_error_after_max_retries_or_ban() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/openApi/swap/v2/quote/ticker"))
+            .respond_with(ResponseTemplate::new(429).insert_header("Retry-After", "0"))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/openApi/swap/v2/server/time"))
+            .respond_with(ResponseTemplate::new(418).insert_header("Retry-After", "120"))
+            .mount(&server)
+            .await;
+
+        let api = mock_api(&server);
+        let res = api.get_ticker("BTC-USDT").await;
+        assert!(matches!(
+            res,
+            Err(ExchangeError::RateLimited { retry_after: Some(retry_after) })
+                if retry_after == Duration::ZERO
+        ));
+        assert_eq!(
+            server.received_requests().await.unwrap().len(),
+            1 + BING_X_MAX_RETRIES as usize
+        );
+
+        // banned requests are not retried
+        let res = api.get_server_time().await;
+        assert!(matches!(
+            res,
+            Err(ExchangeError::RateLimited { retry_after: Some(retry_after) })
+                if retry_after == Duration::from_secs(120)
+        ));
+        assert_eq!(
+            server.received_requests().await.unwrap().len(),
+            2 + BING_X_MAX_RETRIES as usize
+        );
+    }
+
+    #[tokio::test]
+    async fn long_retry_after_is_returned_without_waiting() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .respond_with(ResponseTemplate::new(429).insert_header("Retry-After", "3600"))
+            .mount(&server)
+            .await;
+
+        let api = mock_api(&server);
+        let start = std::time::Instant::now();
+        let res = api.get_ticker("BTC-USDT").await;
+
+        assert!(matches!(
+            res,
+            Err(ExchangeError::RateLimited { retry_after: Some(retry_after) })
+                if retry_after == Duration::from_secs(3600)
+        ));
+        assert!(start.elapsed() < BING_X_MAX_RETRY_DELAY);
+        assert_eq!(server.received_requests().await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn request_timeout_returns_error() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_json(json!({"code": 0}))
+                    .set_delay(Duration::from_secs(5)),
+            )
+            .mount(&server)
+            .await;
+
+        let (market_sender, _) = build_arc_channel(DEFAULT_CHANNEL_CAPACITY);
+        let api = BingXApi::builder("api_key", "secret_key", market_sender)
+            .hosts(&server.uri(), "ws://localhost")
+            .request_timeout(Duration::from_millis(200))
+            .build()
+            .unwrap();
+
+        let res = tokio::time::timeout(Duration::from_secs(2), api.get_ticker("BTC-USDT"))
+            .await
+            .expect("request should time out before test timeout");
+        assert!(
+            matches!(res, Err(ExchangeError::Http(ref e)) if e.is_timeout()),
+            "{res:?}"
+        );
+    }
+
+    #[tokio::test]
+    async fn opening_open_stream_again_does_not_subscribe_twice() {
+        let server = MockServer::start().await;
+        let (ws_uri, mut ws_receiver) = spawn_recording_ws_server().await;
+
+        let (api, _market_receiver) = mock_ws_api(&server, &ws_uri);
+        let stream_id = api
+            .open_stream(StreamType::Kline, "BTC-USDT", Some(Interval::Min1))
+            .await
+            .unwrap();
+        let duplicate_id = api
+            .open_stream(StreamType::Kline, "BTC-USDT", Some(Interval::Min1))
+            .await
+            .unwrap();
+        assert_eq!(duplicate_id, stream_id);
+        assert_eq!(api.active_streams().await.len(), 1);
+
+        // request after single kline subscription is for next stream opened
+        api.open_stream(StreamType::Ticker, "ETH-USDT", None)
+            .await
+            .unwrap();
+        assert_eq!(
+            next_ws_request(&mut ws_receiver).await,
+            (1, "sub".to_string(), "BTC-USDT@kline_1m".to_string())
+        );
+        assert_eq!(
+            next_ws_request(&mut ws_receiver).await,
+            (1, "sub".to_string(), "ETH-USDT@ticker".to_string())
+        );
+        assert_eq!(api.active_streams().await.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn close_all_streams_closes_connection_and_clears_metas() {
+        let server = MockServer::start().await;
+        let (ws_uri, mut ws_receiver) = spawn_recording_ws_server().await;
+
+        let (api, _market_receiver) = mock_ws_api(&server, &ws_uri);
+        let mut opened_ids = vec![
+            api.open_stream(StreamType::Kline, "BTC-USDT", Some(Interval::Min1))
+                .await
+                .unwrap(),
+            api.open_stream(StreamType::Kline, "ETH-USDT", Some(Interval::Min5))
+                .await
+                .unwrap(),
+            api.open_stream(StreamType::Ticker, "BTC-USDT", None)
+                .await
+                .unwrap(),
+        ];
+        for _ in 0..3 {
+            let (_, req_type, _) = next_ws_request(&mut ws_receiver).await;
+            assert_eq!(req_type, "sub");
+        }
+
+        let closed_metas = api
+            .get_stream_manager()
+            .lock()
+            .await
+            .close_all_streams()
+            .await;
+
+        let mut closed_ids: Vec<StreamId> = closed_metas.into_iter().map(|meta| meta.id).collect();
+        closed_ids.sort();
+        opened_ids.sort();
+        assert_eq!(closed_ids, opened_ids);
+        assert!(api.active_streams().await.is_empty());
+
+        let (connection, req_type, _) = next_ws_request(&mut ws_receiver).await;
+        assert_eq!((connection, req_type.as_str()), (1, "closed"));
+    }
+
+    #[tokio::test]
+    async fn polling_tasks_do_not_build_up_over_reopens() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/openApi/swap/v2/quote/ticker"))
+            .respond_with(ResponseTemplate::new(500))
+            .mount(&server)
+            .await;
+
+        // nothing listening on port once listener is dropped
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let ws_uri = format!("ws://{}", listener.local_addr().unwrap());
+        drop(listener);
+
+        let (market_sender, _market_receiver) = build_arc_channel(DEFAULT_CHANNEL_CAPACITY);
+        let mut stream_manager = BingXStreamManager::new(
+            mock_api(&server).http.clone(),
+            market_sender,
+            None,
+            Arc::new(MockClock::new(1_700_000_000_000)),
+        );
+        let stream_id = build_stream_id("BTC-USDT", None);
+
+        for _ in 0..100 {
+            let stream_meta = StreamMeta::new_at(
+                stream_id.clone(),
+                &ws_uri,
+                "BTC-USDT",
+                StreamType::Ticker,
+                None,
+                1_700_000_000_000,
+            );
+            stream_manager.open_stream(stream_meta).await.unwrap();
+            assert_eq!(stream_manager.polling_streams.len(), 1);
+
+            stream_manager.close_stream(&stream_id).await;
+            assert!(stream_manager.polling_streams.is_empty());
+        }
+
+        assert!(stream_manager.active_streams().await.is_empty());
+    }
+
+    #[tokio::test]
+    #[traced_test]
+    async fn order_request_is_traced_in_span() {
+        let server = MockServer::start().await;
+        mount_contracts(&server).await;
+        Mock::given(method("POST"))
+            .and(path("/openApi/swap/v2/trade/order"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({"code": 0})))
+            .mount(&server)
+            .await;
+
+        let api = mock_api(&server);
+        api.open_order(
+            "BTC-USDT",
+            OrderSide::Buy,
+            OrderType::Market,
+            0.01,
+            None,
+            OrderOptions::default(),
+        )
+        .await
+        .unwrap();
+
+        assert!(logs_contain("exchange_request"));
+        assert!(logs_contain("method=\"POST\""));
+        assert!(logs_contain("endpoint=\"/openApi/swap/v2/trade/order\""));
+    }
+}
@@ -0,0 +1,204 @@
+use serde_json::Value;
+
+use crate::exchange::types::{ApiResult, ExchangeError};
+
+// allow for float error when value is already multiple of step
+const STEP_EPSILON: f64 = 1e-9;
+// most decimals order values are formatted with
+const MAX_DECIMALS: usize = 12;
+
+/// Allowed precision of order quantity and price for symbol
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SymbolFilters {
+    pub step_size: f64,
+    pub tick_size: f64,
+}
+
+impl SymbolFilters {
+    /// Find filters of symbol in list of symbols from exchange info,
+    /// list is found in 'symbols' key or nested in 'data' key
+    pub fn from_exchange_info(exchange_info: &Value, symbol: &str) -> ApiResult<Self> {
+        let symbols = exchange_info
+            .get("symbols")
+            .or_else(|| exchange_info.get("data"))
+            .and_then(|symbols| symbols.as_array())
+            .ok_or_else(|| "Missing 'symbols' list from exchange info".to_string())?;
+
+        let symbol_info = symbols
+            .iter()
+            .find(|info| info.get("symbol").and_then(|s| s.as_str()) == Some(symbol))
+            .ok_or_else(|| ExchangeError::UnknownSymbol(symbol.to_string()))?;
+
+        Self::from_symbol_info(symbol_info)
+    }
+
+    /// Parse filters from symbol info, sizes are given as stepSize and
+    /// tickSize, in list of filters, or as number of decimals eg. BingX contracts
+    pub fn from_symbol_info(symbol_info: &Value) -> ApiResult<Self> {
+        let step_size = find_size(symbol_info, "stepSize")
+            .or_else(|| precision_to_size(symbol_info, "quantityPrecision"))
+            .ok_or_else(|| "Missing 'stepSize' from symbol info".to_string())?;
+        let tick_size = find_size(symbol_info, "tickSize")
+            .or_else(|| precision_to_size(symbol_info, "pricePrecision"))
+            .ok_or_else(|| "Missing 'tickSize' from symbol info".to_string())?;
+
+        Ok(Self {
+            step_size,
+            tick_size,
+        })
+    }
+
+    /// Round quantity down to multiple of step size
+    pub fn round_quantity(&self, quantity: f64) -> f64 {
+        round_down(quantity, self.step_size)
+    }
+
+    /// Round price down to multiple of tick size
+    pub fn round_price(&self, price: f64) -> f64 {
+        round_down(price, self.tick_size)
+    }
+
+    /// Rounded quantity formatted to decimals of step size,
+    /// error if quantity is smaller than one step
+    pub fn format_quantity(&self, quantity: f64) -> ApiResult<String> {
+        let rounded = self.round_quantity(quantity);
+        if rounded <= 0.0 {
+            return Err(ExchangeError::InvalidOrder(format!(
+                "Quantity {quantity} is below step size {}",
+                self.step_size
+            )));
+        }
+
+        Ok(format!("{:.1$}", rounded, decimals(self.step_size)))
+    }
+
+    /// Rounded price formatted to decimals of tick size,
+    /// error if price is smaller than one tick
+    pub fn format_price(&self, price: f64) -> ApiResult<String> {
+        let rounded = self.round_price(price);
+        if rounded <= 0.0 {
+            return Err(ExchangeError::InvalidOrder(format!(
+                "Price {price} is below tick size {}",
+                self.tick_size
+            )));
+        }
+
+        Ok(format!("{:.1$}", rounded, decimals(self.tick_size)))
+    }
+}
+
+fn round_down(value: f64, step: f64) -> f64 {
+    if step <= 0.0 {
+        return value;
+    }
+
+    (value / step + STEP_EPSILON).floor() * step
+}
+
+// number of decimals of step, eg. 0.001 is 3 decimals and 0.5 is 1
+fn decimals(step: f64) -> usize {
+    if step <= 0.0 {
+        return 0;
+    }
+
+    (0..MAX_DECIMALS)
+        .find(|decimals| {
+            let scaled = step * 10f64.powi(*decimals as i32);
+            (scaled - scaled.round()).abs() < STEP_EPSILON * scaled.max(1.0)
+        })
+        .unwrap_or(MAX_DECIMALS)
+}
+
+// size is found at top level of symbol info or in filters list
+fn find_size(symbol_info: &Value, key: &str) -> Option<f64> {
+    let size = symbol_info.get(key).or_else(|| {
+        symbol_info
+            .get("filters")?
+            .as_array()?
+            .iter()
+            .find_map(|filter| filter.get(key))
+    })?;
+
+    match size {
+        Value::String(size) => size.parse::<f64>().ok(),
+        _ => size.as_f64(),
+    }
+}
+
+fn precision_to_size(symbol_info: &Value, key: &str) -> Option<f64> {
+    let precision = symbol_info.get(key)?.as_i64()?;
+
+    Some(10f64.powi(-precision as i32))
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn assert_close(value: f64, expected: f64) {
+        assert!((value - expected).abs() < 1e-9, "{value} != {expected}");
+    }
+
+    #[test]
+    fn filters_are_parsed_from_exchange_info() {
+        let exchange_info = json!({
+            "symbols": [{
+                "symbol": "BTCUSDT",
+                "filters": [
+                    {"filterType": "PRICE_FILTER", "tickSize": "0.10"},
+                    {"filterType": "LOT_SIZE", "stepSize": "0.001"}
+                ]
+            }]
+        });
+        let filters = SymbolFilters::from_exchange_info(&exchange_info, "BTCUSDT").unwrap();
+        assert_eq!(
+            filters,
+            SymbolFilters {
+                step_size: 0.001,
+                tick_size: 0.1,
+            }
+        );
+
+        // BingX contracts give number of decimals
+        let contracts = json!({
+            "code": 0,
+            "data": [{"symbol": "BTC-USDT", "quantityPrecision": 4, "pricePrecision": 1}]
+        });
+        let filters = SymbolFilters::from_exchange_info(&contracts, "BTC-USDT").unwrap();
+        assert_close(filters.step_size, 0.0001);
+        assert_close(filters.tick_size, 0.1);
+
+        assert!(matches!(
+            SymbolFilters::from_exchange_info(&contracts, "ETH-USDT"),
+            Err(ExchangeError::UnknownSymbol(_))
+        ));
+    }
+
+    #[test]
+    fn quantity_and_price_round_down_to_step() {
+        let filters = SymbolFilters {
+            step_size: 0.001,
+            tick_size: 0.5,
+        };
+
+        assert_close(filters.round_quantity(1.23456), 1.234);
+        assert_close(filters.round_quantity(0.0019), 0.001);
+        // already multiple of step is unchanged despite float error
+        assert_close(filters.round_quantity(0.3), 0.3);
+        assert_close(filters.round_price(100.74), 100.5);
+        assert_close(filters.round_price(100.5), 100.5);
+
+        assert_eq!(filters.format_quantity(1.23456).unwrap(), "1.234");
+        assert_eq!(filters.format_price(100.74).unwrap(), "100.5");
+        assert!(matches!(
+            filters.format_quantity(0.0009),
+            Err(ExchangeError::InvalidOrder(_))
+        ));
+        assert!(matches!(
+            filters.format_price(0.4),
+            Err(ExchangeError::InvalidOrder(_))
+        ));
+    }
+}
@@ -0,0 +1,145 @@
+use serde_json::Value;
+use std::collections::HashSet;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::{
+    exchange::{
+        api::ExchangeApi,
+        types::{ApiResult, ExchangeError},
+    },
+    market::types::ArcMutex,
+    utils::clock::Clock,
+};
+
+pub const DEFAULT_METADATA_MAX_AGE: Duration = Duration::from_secs(60 * 60);
+
+/// Value fetched from exchange with time it was fetched
+pub struct Cached<T> {
+    value: T,
+    fetched_at: u64,
+}
+
+impl<T> Cached<T> {
+    pub fn new(value: T, fetched_at: u64) -> Self {
+        Self { value, fetched_at }
+    }
+
+    /// Cached value if younger than max age milliseconds at now
+    pub fn fresh(&self, now: u64, max_age: u64) -> Option<&T> {
+        (now.saturating_sub(self.fetched_at) < max_age).then_some(&self.value)
+    }
+}
+
+/// Cache of exchange info, refreshed once older than max age
+pub struct MetadataCache {
+    exchange_api: Arc<Box<dyn ExchangeApi>>,
+    clock: Arc<dyn Clock>,
+    // milliseconds, atomic so cache can be shared with market tasks
+    max_age: AtomicU64,
+    cached: ArcMutex<Option<Cached<Value>>>,
+    cached_symbols: ArcMutex<Option<Cached<HashSet<String>>>>,
+}
+
+impl MetadataCache {
+    /// Cache ages follow exchange clock, eg. replayed kline time
+    pub fn new(exchange_api: Arc<Box<dyn ExchangeApi>>) -> Self {
+        Self {
+            clock: exchange_api.clock(),
+            exchange_api,
+            max_age: AtomicU64::new(DEFAULT_METADATA_MAX_AGE.as_millis() as u64),
+            cached: ArcMutex::new(None),
+            cached_symbols: ArcMutex::new(None),
+        }
+    }
+
+    pub fn set_max_age(&self, max_age: Duration) {
+        self.max_age
+            .store(max_age.as_millis() as u64, Ordering::Relaxed);
+    }
+
+    /// Return cached metadata, fetching from exchange if missing or stale
+    pub async fn metadata(&self) -> ApiResult<Value> {
+        if let Some(data) = self.fresh(&*self.cached.lock().await) {
+            return Ok(data);
+        }
+
+        self.refresh_metadata().await
+    }
+
+    /// Force fetch of metadata from exchange, eg. after order rejections
+    pub async fn refresh_metadata(&self) -> ApiResult<Value> {
+        let data = self.exchange_api.exchange_info().await?;
+
+        *self.cached.lock().await = Some(Cached::new(data.clone(), self.clock.now()));
+
+        Ok(data)
+    }
+
+    /// Return cached trading symbols, fetching from exchange if missing or stale
+    pub async fn symbols(&self) -> ApiResult<HashSet<String>> {
+        if let Some(symbols) = self.fresh(&*self.cached_symbols.lock().await) {
+            return Ok(symbols);
+        }
+
+        let symbols: HashSet<String> = self.exchange_api.get_symbols().await?.into_iter().collect();
+
+        *self.cached_symbols.lock().await = Some(Cached::new(symbols.clone(), self.clock.now()));
+
+        Ok(symbols)
+    }
+
+    fn fresh<T: Clone>(&self, cached: &Option<Cached<T>>) -> Option<T> {
+        cached
+            .as_ref()?
+            .fresh(self.clock.now(), self.max_age.load(Ordering::Relaxed))
+            .cloned()
+    }
+
+    /// Check symbol is trading on exchange before opening streams or orders
+    pub async fn validate_symbol(&self, symbol: &str) -> ApiResult<()> {
+        if self.symbols().await?.contains(symbol) {
+            Ok(())
+        } else {
+            Err(ExchangeError::UnknownSymbol(symbol.to_string()))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::Ordering;
+
+    use super::*;
+    use crate::exchange::mock::MockExchange;
+    use crate::utils::clock::BacktestClock;
+
+    #[tokio::test]
+    async fn metadata_refreshes_after_max_age() {
+        let clock = Arc::new(BacktestClock::default());
+        clock.advance_to(1_700_000_000_000);
+        let mut exchange = MockExchange::new();
+        exchange.clock = clock.clone();
+        let requests = exchange.exchange_info_requests.clone();
+        let exchange_info_requests = || requests.load(Ordering::SeqCst);
+
+        let metadata_cache = MetadataCache::new(Arc::new(Box::new(exchange)));
+        metadata_cache.set_max_age(Duration::from_secs(60));
+
+        // fetched once, then served from cache within max age
+        metadata_cache.metadata().await.unwrap();
+        clock.advance_to(1_700_000_059_000);
+        metadata_cache.metadata().await.unwrap();
+        assert_eq!(exchange_info_requests(), 1);
+
+        // stale once max age elapsed
+        clock.advance_to(1_700_000_060_000);
+        metadata_cache.metadata().await.unwrap();
+        assert_eq!(exchange_info_requests(), 2);
+
+        // forced refresh ignores max age
+        metadata_cache.refresh_metadata().await.unwrap();
+        assert_eq!(exchange_info_requests(), 3);
+    }
+}
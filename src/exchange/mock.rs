@@ -0,0 +1,280 @@
+use async_trait::async_trait;
+use reqwest::{header::HeaderMap, Response};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc,
+};
+
+use crate::{
+    account::{
+        balance::Balance,
+        trade::{MarginMode, OrderOptions, OrderSide, OrderStatus, OrderType, PositionSide},
+    },
+    market::{
+        interval::Interval, kline::Kline, messages::MarketMessage, ticker::Ticker, types::ArcMutex,
+    },
+    utils::{
+        channel::{build_arc_channel, DEFAULT_CHANNEL_CAPACITY},
+        clock::{Clock, SystemClock},
+        rate_limit::requests_per_second,
+    },
+};
+
+use super::{
+    api::ExchangeApi,
+    bingx::{
+        BingXApi, BingXClient, BingXStreamManager, BING_X_REQUESTS_PER_SECOND,
+        BING_X_REQUEST_TIMEOUT,
+    },
+    stream::{build_stream_id, StreamId, StreamManager, StreamMeta},
+    types::{ApiResult, ExchangeError, StreamType, TimestampUnit},
+};
+
+/// Exchange used in tests, serves fixed symbols, klines and account response
+/// and counts exchange info requests
+pub struct MockExchange {
+    pub symbols: Vec<String>,
+    pub klines: Vec<Kline>,
+    pub account: Value,
+    pub clock: Arc<dyn Clock>,
+    pub exchange_info_requests: Arc<AtomicUsize>,
+    stream_manager: ArcMutex<Box<dyn StreamManager>>,
+}
+
+impl MockExchange {
+    pub fn new() -> Self {
+        let (market_tx, _market_rx) = build_arc_channel::<MarketMessage>(DEFAULT_CHANNEL_CAPACITY);
+
+        Self {
+            symbols: vec!["BTC-USDT".to_string()],
+            klines: vec![],
+            account: json!({"code": 0}),
+            clock: Arc::new(SystemClock),
+            exchange_info_requests: Arc::new(AtomicUsize::new(0)),
+            stream_manager: ArcMutex::new(Box::new(BingXStreamManager::new(
+                BingXClient::new(
+                    "",
+                    BING_X_REQUEST_TIMEOUT,
+                    requests_per_second(BING_X_REQUESTS_PER_SECOND),
+                )
+                .unwrap(),
+                market_tx,
+                None,
+                Arc::new(SystemClock),
+            ))),
+        }
+    }
+
+    fn unsupported(&self, method: &str) -> ExchangeError {
+        ExchangeError::Unsupported(format!("Mock exchange does not support {method}"))
+    }
+}
+
+#[async_trait]
+impl ExchangeApi for MockExchange {
+    fn name(&self) -> &'static str {
+        "mock"
+    }
+
+    async fn get_account(&self) -> ApiResult<Value> {
+        Ok(self.account.clone())
+    }
+
+    // account response only carries error code, balance is zeroed
+    async fn get_balance(&self, asset: &str) -> ApiResult<Balance> {
+        let lookup: HashMap<String, Value> = serde_json::from_value(self.account.clone())?;
+        BingXApi::check_response_code(&lookup)?;
+
+        Ok(Balance::new(asset, 0.0, 0.0))
+    }
+
+    async fn open_position(
+        &self,
+        _symbol: &str,
+        _side: OrderSide,
+        _quantity: f64,
+        _options: OrderOptions,
+    ) -> ApiResult<Value> {
+        Err(self.unsupported("open_position"))
+    }
+
+    async fn open_limit_order(
+        &self,
+        _symbol: &str,
+        _side: OrderSide,
+        _quantity: f64,
+        _price: f64,
+        _options: OrderOptions,
+    ) -> ApiResult<Value> {
+        Err(self.unsupported("open_limit_order"))
+    }
+
+    async fn open_order(
+        &self,
+        _symbol: &str,
+        _side: OrderSide,
+        _order_type: OrderType,
+        _quantity: f64,
+        _price: Option<f64>,
+        _options: OrderOptions,
+    ) -> ApiResult<Value> {
+        Err(self.unsupported("open_order"))
+    }
+
+    async fn close_position(&self, _position_id: &str) -> ApiResult<Value> {
+        Err(self.unsupported("close_position"))
+    }
+
+    async fn close_position_by_symbol(&self, _symbol: &str, _side: OrderSide) -> ApiResult<Value> {
+        Err(self.unsupported("close_position_by_symbol"))
+    }
+
+    async fn set_leverage(
+        &self,
+        _symbol: &str,
+        _leverage: u32,
+        _side: PositionSide,
+    ) -> ApiResult<Value> {
+        Err(self.unsupported("set_leverage"))
+    }
+
+    async fn set_margin_mode(&self, _symbol: &str, _mode: MarginMode) -> ApiResult<Value> {
+        Err(self.unsupported("set_margin_mode"))
+    }
+
+    async fn all_orders(&self) -> ApiResult<Value> {
+        Err(self.unsupported("all_orders"))
+    }
+
+    async fn list_open_orders(&self) -> ApiResult<Value> {
+        Err(self.unsupported("list_open_orders"))
+    }
+
+    async fn cancel_order(&self, _symbol: &str, _order_id: &str) -> ApiResult<Value> {
+        Err(self.unsupported("cancel_order"))
+    }
+
+    async fn order_status(&self, _symbol: &str, _order_id: &str) -> ApiResult<OrderStatus> {
+        Err(self.unsupported("order_status"))
+    }
+
+    async fn cancel_all_orders(&self, _symbol: &str) -> ApiResult<Value> {
+        Err(self.unsupported("cancel_all_orders"))
+    }
+
+    // streams are not connected, market messages are sent by test
+    async fn open_stream(
+        &self,
+        _stream_type: StreamType,
+        symbol: &str,
+        interval: Option<Interval>,
+    ) -> ApiResult<StreamId> {
+        Ok(build_stream_id(
+            symbol,
+            interval.map(|interval| interval.as_str()),
+        ))
+    }
+
+    async fn close_stream(&self, _stream_id: &StreamId) -> Option<StreamMeta> {
+        None
+    }
+
+    fn get_stream_manager(&self) -> ArcMutex<Box<dyn StreamManager>> {
+        self.stream_manager.clone()
+    }
+
+    async fn get_kline(&self, _symbol: &str, _interval: Interval) -> ApiResult<Kline> {
+        Err(self.unsupported("get_kline"))
+    }
+
+    async fn get_klines(
+        &self,
+        _symbol: &str,
+        _interval: Interval,
+        _limit: usize,
+    ) -> ApiResult<Vec<Kline>> {
+        Ok(self.klines.clone())
+    }
+
+    async fn get_klines_range(
+        &self,
+        _symbol: &str,
+        _interval: Interval,
+        from_ts: u64,
+        to_ts: u64,
+    ) -> ApiResult<Vec<Kline>> {
+        Ok(self
+            .klines
+            .iter()
+            .filter(|kline| kline.open_time >= from_ts && kline.open_time <= to_ts)
+            .cloned()
+            .collect())
+    }
+
+    async fn get_ticker(&self, _symbol: &str) -> ApiResult<Ticker> {
+        Err(self.unsupported("get_ticker"))
+    }
+
+    async fn exchange_info(&self) -> ApiResult<Value> {
+        self.exchange_info_requests.fetch_add(1, Ordering::SeqCst);
+
+        Ok(json!({ "symbols": self.symbols }))
+    }
+
+    async fn get_symbols(&self) -> ApiResult<Vec<String>> {
+        Ok(self.symbols.clone())
+    }
+
+    async fn get_server_time(&self) -> ApiResult<u64> {
+        Ok(self.clock.now())
+    }
+
+    async fn get(&self, _endpoint: &str, _query_str: Option<&str>) -> ApiResult<Response> {
+        Err(self.unsupported("get"))
+    }
+
+    async fn post(&self, _endpoint: &str, _query_str: &str) -> ApiResult<Response> {
+        Err(self.unsupported("post"))
+    }
+
+    async fn delete(&self, _endpoint: &str, _query_str: &str) -> ApiResult<Response> {
+        Err(self.unsupported("delete"))
+    }
+
+    async fn handle_response(&self, _response: Response) -> ApiResult<Value> {
+        Err(self.unsupported("handle_response"))
+    }
+
+    fn build_headers(&self, _json: bool) -> ApiResult<HeaderMap> {
+        Ok(HeaderMap::new())
+    }
+
+    fn build_stream_url(
+        &self,
+        _symbol: &str,
+        _stream_type: StreamType,
+        _interval: Option<Interval>,
+    ) -> String {
+        String::new()
+    }
+
+    fn sign_query_str(&self, _query_str: &str) -> ApiResult<String> {
+        Err(self.unsupported("sign_query_str"))
+    }
+
+    fn timestamp_unit(&self) -> TimestampUnit {
+        TimestampUnit::Milliseconds
+    }
+
+    fn time_offset(&self) -> i64 {
+        0
+    }
+
+    fn set_time_offset(&self, _offset: i64) {}
+
+    fn clock(&self) -> Arc<dyn Clock> {
+        self.clock.clone()
+    }
+}
@@ -1,5 +1,10 @@
 pub mod api;
 pub mod binance;
 pub mod bingx;
+pub mod filters;
+pub mod metadata;
+#[cfg(test)]
+pub mod mock;
+pub mod paper;
 pub mod stream;
 pub mod types;
@@ -0,0 +1,1141 @@
+use async_trait::async_trait;
+use reqwest::{header::HeaderMap, Response};
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use tracing::{info_span, warn, Instrument};
+use uuid::Uuid;
+
+use crate::account::{
+    balance::Balance,
+    trade::{MarginMode, OrderOptions, OrderSide, OrderStatus, OrderType, PositionSide},
+};
+use crate::exchange::api::ExchangeApi;
+use crate::market::market::MarketData;
+use crate::market::messages::MarketMessage;
+use crate::market::types::{ArcMutex, ArcSender};
+use crate::market::{interval::Interval, kline::Kline, ticker::Ticker};
+use crate::storage::manager::StorageManager;
+use crate::utils::clock::{BacktestClock, Clock};
+
+use super::stream::{build_stream_id, StreamId, StreamManager, StreamMeta};
+use super::types::{ApiResult, ExchangeError, StreamType, TimestampUnit};
+
+// Asset balances are held in, all symbols are quoted in this asset
+pub const PAPER_QUOTE_ASSET: &str = "USDT";
+
+// Interval of stored klines replayed as ticker stream
+const PAPER_TICKER_INTERVAL: Interval = Interval::Min1;
+
+pub const DEFAULT_PAPER_BALANCE: f64 = 10_000.0;
+pub const DEFAULT_PAPER_REPLAY_DELAY: Duration = Duration::from_millis(100);
+
+// Status of replay stream once all stored klines are replayed,
+// finished streams are not closed so they are not reopened
+const REPLAY_FINISHED_STATUS: &str = "finished";
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PaperPosition {
+    pub id: String,
+    pub symbol: String,
+    pub side: OrderSide,
+    pub quantity: f64,
+    pub entry_price: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PaperOrder {
+    pub id: String,
+    pub symbol: String,
+    pub side: OrderSide,
+    pub order_type: OrderType,
+    pub quantity: f64,
+    pub price: f64,
+    pub status: String,
+}
+
+/// Simulated account, balance is only changed by order fills
+#[derive(Debug, Clone, Serialize)]
+pub struct PaperAccount {
+    pub balance: f64,
+    // last replayed price of each symbol
+    pub prices: HashMap<String, f64>,
+    pub positions: HashMap<String, PaperPosition>,
+    pub orders: Vec<PaperOrder>,
+    // orders no longer open, eg. filled, canceled or rejected
+    pub closed_orders: Vec<PaperOrder>,
+    // status of every order placed, kept once order is filled or canceled
+    pub order_statuses: HashMap<String, OrderStatus>,
+}
+
+impl PaperAccount {
+    pub fn new(balance: f64) -> Self {
+        Self {
+            balance,
+            prices: HashMap::new(),
+            positions: HashMap::new(),
+            orders: vec![],
+            closed_orders: vec![],
+            order_statuses: HashMap::new(),
+        }
+    }
+
+    pub fn price(&self, symbol: &str) -> ApiResult<f64> {
+        self.prices
+            .get(symbol)
+            .copied()
+            .ok_or_else(|| format!("No replayed price for symbol: {symbol}").into())
+    }
+
+    /// Error if value of new position at price is more than balance
+    fn check_balance(&self, quantity: f64, price: f64) -> ApiResult<()> {
+        let value = quantity * price;
+
+        if value > self.balance {
+            return Err(ExchangeError::InvalidOrder(format!(
+                "Insufficient balance {} for order value {value}",
+                self.balance
+            )));
+        }
+
+        Ok(())
+    }
+
+    /// Fill order at price, buying reduces balance and selling increases it
+    fn fill(&mut self, symbol: &str, side: &OrderSide, quantity: f64, price: f64) {
+        let value = quantity * price;
+
+        match side {
+            OrderSide::Buy => self.balance -= value,
+            OrderSide::Sell => self.balance += value,
+        }
+
+        self.prices.insert(symbol.to_string(), price);
+    }
+
+    fn open_position(&mut self, symbol: &str, side: OrderSide, quantity: f64, price: f64) -> Value {
+        let order = PaperOrder {
+            id: Uuid::new_v4().to_string(),
+            symbol: symbol.to_string(),
+            side: side.clone(),
+            order_type: OrderType::Market,
+            quantity,
+            price,
+            status: "NEW".to_string(),
+        };
+        let res = self.open_position_with_id(order.id.clone(), symbol, side, quantity, price);
+        self.close_order(order, OrderStatus::Filled);
+
+        res
+    }
+
+    // filled orders keep order id so order status can be found from position
+    fn open_position_with_id(
+        &mut self,
+        id: String,
+        symbol: &str,
+        side: OrderSide,
+        quantity: f64,
+        price: f64,
+    ) -> Value {
+        self.fill(symbol, &side, quantity, price);
+        self.order_statuses.insert(id.clone(), OrderStatus::Filled);
+
+        let position = PaperPosition {
+            id,
+            symbol: symbol.to_string(),
+            side,
+            quantity,
+            entry_price: price,
+        };
+        let res = json!({
+            "orderId": position.id,
+            "symbol": position.symbol,
+            "side": position.side.to_string(),
+            "type": OrderType::Market.to_string(),
+            "price": price,
+            "quantity": quantity,
+            "avgPrice": price,
+            "executedQty": quantity,
+            "status": "FILLED",
+        });
+
+        self.positions.insert(position.id.clone(), position);
+
+        res
+    }
+
+    /// Reduce open position of symbol opposite to side by quantity,
+    /// position is removed once fully closed
+    fn reduce_position(
+        &mut self,
+        symbol: &str,
+        side: OrderSide,
+        quantity: f64,
+        price: f64,
+    ) -> ApiResult<Value> {
+        let position = self
+            .positions
+            .values_mut()
+            .find(|position| position.symbol == symbol && position.side == side.opposite())
+            .ok_or_else(|| {
+                ExchangeError::InvalidOrder(format!(
+                    "No open position for reduce only order on {symbol}"
+                ))
+            })?;
+
+        // never fill more than open quantity so position can not flip
+        let quantity = quantity.min(position.quantity);
+        position.quantity -= quantity;
+
+        let position_id = position.id.clone();
+        if position.quantity <= 0.0 {
+            self.positions.remove(&position_id);
+        }
+
+        self.fill(symbol, &side, quantity, price);
+
+        Ok(json!({
+            "orderId": position_id,
+            "symbol": symbol,
+            "side": side.to_string(),
+            "type": OrderType::Market.to_string(),
+            "price": price,
+            "quantity": quantity,
+            "reduceOnly": true,
+            "status": "FILLED",
+        }))
+    }
+
+    /// Add open limit order, order is filled once price reaches order price
+    fn add_order(&mut self, order: PaperOrder) {
+        self.order_statuses
+            .insert(order.id.clone(), OrderStatus::New);
+        self.orders.push(order);
+    }
+
+    /// Remove open order from order book, order status is kept as canceled
+    fn cancel_order(&mut self, order_id: &str) -> Option<PaperOrder> {
+        let index = self.orders.iter().position(|order| order.id == order_id)?;
+
+        let order = self.orders.remove(index);
+
+        Some(self.close_order(order, OrderStatus::Canceled))
+    }
+
+    /// Keep order with final status once it is no longer open
+    fn close_order(&mut self, mut order: PaperOrder, status: OrderStatus) -> PaperOrder {
+        order.status = match status {
+            OrderStatus::Filled => "FILLED",
+            OrderStatus::Canceled => "CANCELED",
+            _ => "REJECTED",
+        }
+        .to_string();
+        self.order_statuses.insert(order.id.clone(), status);
+        self.closed_orders.push(order.clone());
+
+        order
+    }
+
+    /// Update price from replayed kline, open orders with price
+    /// within kline range are filled at order price
+    fn update_kline(&mut self, kline: &Kline) {
+        self.prices.insert(kline.symbol.clone(), kline.close);
+
+        let (filled, open): (Vec<PaperOrder>, Vec<PaperOrder>) =
+            self.orders.drain(..).partition(|order| {
+                order.symbol == kline.symbol
+                    && order.price >= kline.low
+                    && order.price <= kline.high
+            });
+        self.orders = open;
+
+        for order in filled {
+            // balance may have changed since order was placed
+            if let Err(e) = self.check_balance(order.quantity, order.price) {
+                warn!("Rejected limit order {}: {e}", order.id);
+                self.close_order(order, OrderStatus::Rejected);
+                continue;
+            }
+
+            self.open_position_with_id(
+                order.id.clone(),
+                &order.symbol,
+                order.side.clone(),
+                order.quantity,
+                order.price,
+            );
+            self.close_order(order, OrderStatus::Filled);
+        }
+    }
+}
+
+pub struct PaperExchange {
+    storage_manager: StorageManager,
+    stream_manager: ArcMutex<Box<dyn StreamManager>>,
+    account: ArcMutex<PaperAccount>,
+    // advanced to open time of each replayed kline
+    clock: Arc<BacktestClock>,
+}
+
+impl PaperExchange {
+    /// Create exchange replaying klines stored by storage manager,
+    /// replay delay is time waited between each replayed kline
+    pub fn new(
+        storage_manager: StorageManager,
+        market_sender: ArcSender<MarketMessage>,
+        balance: f64,
+        replay_delay: Duration,
+    ) -> Self {
+        let account = ArcMutex::new(PaperAccount::new(balance));
+        let clock = Arc::new(BacktestClock::default());
+
+        let stream_manager = ArcMutex::new(Box::new(PaperStreamManager::new(
+            storage_manager.clone(),
+            market_sender,
+            account.clone(),
+            clock.clone(),
+            replay_delay,
+        )) as Box<dyn StreamManager>);
+
+        Self {
+            storage_manager,
+            stream_manager,
+            account,
+            clock,
+        }
+    }
+
+    #[cfg(test)]
+    pub async fn account(&self) -> PaperAccount {
+        self.account.lock().await.clone()
+    }
+
+    fn stored_klines(&self, symbol: &str, interval: &str) -> ApiResult<Vec<Kline>> {
+        load_stored_klines(&self.storage_manager, symbol, interval)
+    }
+
+    fn unsupported(&self, method: &str) -> ExchangeError {
+        ExchangeError::Unsupported(format!("Paper exchange does not support HTTP {method}"))
+    }
+}
+
+#[async_trait]
+impl ExchangeApi for PaperExchange {
+    fn name(&self) -> &'static str {
+        "paper"
+    }
+
+    // ---
+    // Account methods
+    // ---
+    async fn get_account(&self) -> ApiResult<Value> {
+        let account = self.account.lock().await;
+
+        Ok(json!({
+            "balances": [{
+                "asset": PAPER_QUOTE_ASSET,
+                "free": account.balance.to_string(),
+                "locked": "0",
+            }],
+            "positions": account.positions.values().collect::<Vec<_>>(),
+        }))
+    }
+
+    async fn get_balance(&self, asset: &str) -> ApiResult<Balance> {
+        let account = self.get_account().await?;
+
+        Balance::from_account_lookup(&account, asset)
+    }
+
+    async fn open_position(
+        &self,
+        symbol: &str,
+        side: OrderSide,
+        quantity: f64,
+        options: OrderOptions,
+    ) -> ApiResult<Value> {
+        if options.reduce_only {
+            return self
+                .open_order(symbol, side, OrderType::Market, quantity, None, options)
+                .await;
+        }
+
+        let mut account = self.account.lock().await;
+        let price = account.price(symbol)?;
+        account.check_balance(quantity, price)?;
+
+        Ok(account.open_position(symbol, side, quantity, price))
+    }
+
+    async fn open_limit_order(
+        &self,
+        symbol: &str,
+        side: OrderSide,
+        quantity: f64,
+        price: f64,
+        options: OrderOptions,
+    ) -> ApiResult<Value> {
+        self.open_order(
+            symbol,
+            side,
+            OrderType::Limit,
+            quantity,
+            Some(price),
+            options,
+        )
+        .await
+    }
+
+    async fn open_order(
+        &self,
+        symbol: &str,
+        side: OrderSide,
+        order_type: OrderType,
+        quantity: f64,
+        price: Option<f64>,
+        options: OrderOptions,
+    ) -> ApiResult<Value> {
+        if options.reduce_only {
+            let OrderType::Market = order_type else {
+                return Err(format!(
+                    "Paper exchange only supports reduce only market orders, got {order_type}"
+                )
+                .into());
+            };
+
+            let mut account = self.account.lock().await;
+            let price = account.price(symbol)?;
+
+            return account.reduce_position(symbol, side, quantity, price);
+        }
+
+        if let OrderType::Market = order_type {
+            return self.open_position(symbol, side, quantity, options).await;
+        }
+
+        let price = price.ok_or_else(|| format!("Price required for {order_type} order"))?;
+        self.account.lock().await.check_balance(quantity, price)?;
+
+        let order = PaperOrder {
+            id: Uuid::new_v4().to_string(),
+            symbol: symbol.to_string(),
+            side,
+            order_type,
+            quantity,
+            price,
+            status: "NEW".to_string(),
+        };
+        let res = json!(order);
+
+        self.account.lock().await.add_order(order);
+
+        Ok(res)
+    }
+
+    async fn close_position(&self, position_id: &str) -> ApiResult<Value> {
+        let mut account = self.account.lock().await;
+
+        let symbol = account
+            .positions
+            .get(position_id)
+            .map(|position| position.symbol.clone())
+            .ok_or_else(|| format!("Position not found: {position_id}"))?;
+        // position is kept open if there is no price to close at
+        let price = account.price(&symbol)?;
+        let position = account
+            .positions
+            .remove(position_id)
+            .ok_or_else(|| format!("Position not found: {position_id}"))?;
+
+        // close with opposite side order at current price
+        let side = position.side.opposite();
+        account.fill(&position.symbol, &side, position.quantity, price);
+
+        Ok(json!({
+            "orderId": position.id,
+            "symbol": position.symbol,
+            "side": side.to_string(),
+            "price": price,
+            "quantity": position.quantity,
+            "avgPrice": price,
+            "executedQty": position.quantity,
+            "status": "FILLED",
+        }))
+    }
+
+    async fn close_position_by_symbol(&self, symbol: &str, side: OrderSide) -> ApiResult<Value> {
+        let position_id = self
+            .account
+            .lock()
+            .await
+            .positions
+            .values()
+            .find(|position| position.symbol == symbol && position.side == side)
+            .map(|position| position.id.clone())
+            .ok_or_else(|| {
+                ExchangeError::InvalidOrder(format!("No open {side} position for {symbol}"))
+            })?;
+
+        self.close_position(&position_id).await
+    }
+
+    // paper positions are not margined, settings are accepted and ignored
+    async fn set_leverage(
+        &self,
+        symbol: &str,
+        leverage: u32,
+        side: PositionSide,
+    ) -> ApiResult<Value> {
+        Ok(json!({
+            "symbol": symbol,
+            "leverage": leverage,
+            "side": side.to_string(),
+        }))
+    }
+
+    async fn set_margin_mode(&self, symbol: &str, mode: MarginMode) -> ApiResult<Value> {
+        Ok(json!({
+            "symbol": symbol,
+            "marginType": mode.to_string(),
+        }))
+    }
+
+    // open orders followed by filled, canceled and rejected orders
+    async fn all_orders(&self) -> ApiResult<Value> {
+        let account = self.account.lock().await;
+
+        let orders: Vec<&PaperOrder> = account
+            .orders
+            .iter()
+            .chain(account.closed_orders.iter())
+            .collect();
+
+        Ok(json!(orders))
+    }
+
+    async fn list_open_orders(&self) -> ApiResult<Value> {
+        let account = self.account.lock().await;
+
+        Ok(json!(account.orders))
+    }
+
+    async fn cancel_order(&self, _symbol: &str, order_id: &str) -> ApiResult<Value> {
+        let order = self
+            .account
+            .lock()
+            .await
+            .cancel_order(order_id)
+            .ok_or_else(|| format!("Order not found: {order_id}"))?;
+
+        Ok(json!(order))
+    }
+
+    // paper market orders fill immediately, limit orders stay
+    // open until replayed price reaches order price
+    async fn order_status(&self, _symbol: &str, order_id: &str) -> ApiResult<OrderStatus> {
+        self.account
+            .lock()
+            .await
+            .order_statuses
+            .get(order_id)
+            .cloned()
+            .ok_or_else(|| format!("Order not found: {order_id}").into())
+    }
+
+    async fn cancel_all_orders(&self, symbol: &str) -> ApiResult<Value> {
+        let mut account = self.account.lock().await;
+
+        let order_ids: Vec<String> = account
+            .orders
+            .iter()
+            .filter(|order| order.symbol == symbol)
+            .map(|order| order.id.clone())
+            .collect();
+
+        let canceled: Vec<PaperOrder> = order_ids
+            .iter()
+            .filter_map(|order_id| account.cancel_order(order_id))
+            .collect();
+
+        Ok(json!(canceled))
+    }
+
+    // ---
+    // Stream Methods
+    // ---
+    async fn open_stream(
+        &self,
+        stream_type: StreamType,
+        symbol: &str,
+        interval: Option<Interval>,
+    ) -> ApiResult<StreamId> {
+        let url = self.build_stream_url(symbol, stream_type.clone(), interval);
+        let stream_id = build_stream_id(symbol, interval.map(|interval| interval.as_str()));
+
+        let stream_meta = StreamMeta::new_at(
+            stream_id,
+            &url,
+            symbol,
+            stream_type,
+            interval.map(|interval| interval.to_string()),
+            self.clock.now(),
+        );
+
+        self.stream_manager
+            .lock()
+            .await
+            .open_stream(stream_meta)
+            .await
+    }
+
+    async fn close_stream(&self, stream_id: &StreamId) -> Option<StreamMeta> {
+        self.stream_manager
+            .lock()
+            .await
+            .close_stream(stream_id)
+            .await
+    }
+
+    fn get_stream_manager(&self) -> ArcMutex<Box<dyn StreamManager>> {
+        self.stream_manager.clone()
+    }
+
+    // ---
+    // Exchange Methods
+    // ---
+    async fn get_kline(&self, symbol: &str, interval: Interval) -> ApiResult<Kline> {
+        self.stored_klines(symbol, interval.as_str())?
+            .pop()
+            .ok_or_else(|| format!("No stored klines for {symbol} {interval}").into())
+    }
+
+    async fn get_klines(
+        &self,
+        symbol: &str,
+        interval: Interval,
+        limit: usize,
+    ) -> ApiResult<Vec<Kline>> {
+        let klines = self.stored_klines(symbol, interval.as_str())?;
+        let start = klines.len().saturating_sub(limit);
+
+        Ok(klines[start..].to_vec())
+    }
+
+    async fn get_klines_range(
+        &self,
+        symbol: &str,
+        interval: Interval,
+        from_ts: u64,
+        to_ts: u64,
+    ) -> ApiResult<Vec<Kline>> {
+        let mut klines = self.stored_klines(symbol, interval.as_str())?;
+        klines.retain(|kline| kline.open_time >= from_ts && kline.open_time <= to_ts);
+
+        Ok(klines)
+    }
+
+    async fn get_ticker(&self, symbol: &str) -> ApiResult<Ticker> {
+        let kline = self.get_kline(symbol, PAPER_TICKER_INTERVAL).await?;
+        let mut ticker = ticker_from_kline(&kline);
+        ticker.last_price = self.account.lock().await.price(symbol)?;
+
+        Ok(ticker)
+    }
+
+    async fn exchange_info(&self) -> ApiResult<Value> {
+        let symbols = self.get_symbols().await?;
+
+        Ok(json!({ "symbols": symbols }))
+    }
+
+    // symbols which can be replayed, ie. have stored klines
+    async fn get_symbols(&self) -> ApiResult<Vec<String>> {
+        Ok(self.storage_manager.list_kline_symbols())
+    }
+
+    async fn get_server_time(&self) -> ApiResult<u64> {
+        Ok(self.clock.now())
+    }
+
+    // ---
+    // HTTP Methods
+    // ---
+    async fn get(&self, _endpoint: &str, _query_str: Option<&str>) -> ApiResult<Response> {
+        Err(self.unsupported("GET"))
+    }
+
+    async fn post(&self, _endpoint: &str, _query_str: &str) -> ApiResult<Response> {
+        Err(self.unsupported("POST"))
+    }
+
+    async fn delete(&self, _endpoint: &str, _query_str: &str) -> ApiResult<Response> {
+        Err(self.unsupported("DELETE"))
+    }
+
+    // ---
+    // API Util methods
+    // ---
+    async fn handle_response(&self, response: Response) -> ApiResult<Value> {
+        Ok(response.json::<Value>().await?)
+    }
+
+    fn build_headers(&self, _json: bool) -> ApiResult<HeaderMap> {
+        Ok(HeaderMap::new())
+    }
+
+    fn build_stream_url(
+        &self,
+        symbol: &str,
+        _stream_type: StreamType,
+        interval: Option<Interval>,
+    ) -> String {
+        format!(
+            "paper://{}",
+            build_stream_id(symbol, interval.map(|interval| interval.as_str()))
+        )
+    }
+
+    fn sign_query_str(&self, query_str: &str) -> ApiResult<String> {
+        Ok(query_str.to_string())
+    }
+
+    fn timestamp_unit(&self) -> TimestampUnit {
+        TimestampUnit::Milliseconds
+    }
+
+    // time of replayed klines, so strategies see simulated time
+    fn clock(&self) -> Arc<dyn Clock> {
+        self.clock.clone()
+    }
+
+    // local time is exchange time
+    fn time_offset(&self) -> i64 {
+        0
+    }
+
+    fn set_time_offset(&self, _offset: i64) {}
+}
+
+pub struct PaperStreamManager {
+    storage_manager: StorageManager,
+    market_sender: ArcSender<MarketMessage>,
+    account: ArcMutex<PaperAccount>,
+    clock: Arc<BacktestClock>,
+    replay_delay: Duration,
+    streams: HashMap<StreamId, JoinHandle<()>>,
+    stream_metas: ArcMutex<HashMap<StreamId, StreamMeta>>,
+}
+
+impl PaperStreamManager {
+    pub fn new(
+        storage_manager: StorageManager,
+        market_sender: ArcSender<MarketMessage>,
+        account: ArcMutex<PaperAccount>,
+        clock: Arc<BacktestClock>,
+        replay_delay: Duration,
+    ) -> Self {
+        Self {
+            storage_manager,
+            market_sender,
+            account,
+            clock,
+            replay_delay,
+            streams: HashMap::new(),
+            stream_metas: ArcMutex::new(HashMap::new()),
+        }
+    }
+}
+
+#[async_trait]
+impl StreamManager for PaperStreamManager {
+    async fn open_stream(&mut self, stream_meta: StreamMeta) -> ApiResult<StreamId> {
+        if self.is_stream_open(&stream_meta.id).await {
+            return Ok(stream_meta.id);
+        }
+
+        let interval = stream_meta
+            .interval
+            .clone()
+            .unwrap_or_else(|| PAPER_TICKER_INTERVAL.to_string());
+
+        let klines = load_stored_klines(&self.storage_manager, &stream_meta.symbol, &interval)?;
+
+        if klines.is_empty() {
+            return Err(format!("No stored klines to replay for {}", stream_meta.id).into());
+        }
+
+        let market_sender = self.market_sender.clone();
+        let account = self.account.clone();
+        let clock = self.clock.clone();
+        let replay_delay = self.replay_delay;
+        let stream_type = stream_meta.stream_type.clone();
+        let thread_stream_id = stream_meta.id.clone();
+        let stream_metas = self.stream_metas.clone();
+
+        // replay stored klines in order, stream is finished once all replayed
+        // replay of closed stream is restarted, stop previous replay task
+        // so it can't update meta of new replay
+        if let Some(handle) = self.streams.remove(&stream_meta.id) {
+            handle.abort();
+        }
+
+        // meta inserted before replay starts so first messages are recorded
+        self.stream_metas
+            .lock()
+            .await
+            .insert(stream_meta.id.clone(), stream_meta.clone());
+
+        let span = info_span!(
+            "paper_replay",
+            symbol = %stream_meta.symbol,
+            stream_id = %stream_meta.id
+        );
+
+        let thread_handle = tokio::spawn(
+            async move {
+                for mut kline in klines {
+                    // replayed klines are all complete
+                    kline.is_closed = true;
+                    clock.advance_to_kline(&kline);
+                    account.lock().await.update_kline(&kline);
+
+                    let message = match stream_type {
+                        StreamType::Kline => MarketMessage::UpdateKline(kline),
+                        StreamType::Ticker => {
+                            MarketMessage::UpdateTicker(ticker_from_kline(&kline))
+                        }
+                    };
+                    let _ = market_sender.send(message);
+
+                    if let Some(stream_meta) = stream_metas.lock().await.get_mut(&thread_stream_id)
+                    {
+                        stream_meta.record_message(clock.now());
+                    }
+
+                    tokio::time::sleep(replay_delay).await;
+                }
+
+                if let Some(stream_meta) = stream_metas.lock().await.get_mut(&thread_stream_id) {
+                    stream_meta.status = REPLAY_FINISHED_STATUS.to_string();
+                }
+            }
+            .instrument(span),
+        );
+
+        self.streams.insert(stream_meta.id.clone(), thread_handle);
+
+        Ok(stream_meta.id)
+    }
+
+    async fn close_stream(&mut self, stream_id: &StreamId) -> Option<StreamMeta> {
+        if let Some(handle) = self.streams.remove(stream_id) {
+            handle.abort();
+        }
+
+        self.stream_metas.lock().await.remove(stream_id)
+    }
+
+    fn stream_metas(&self) -> ArcMutex<HashMap<StreamId, StreamMeta>> {
+        self.stream_metas.clone()
+    }
+
+    fn clock(&self) -> Arc<dyn Clock> {
+        self.clock.clone()
+    }
+}
+
+/// Load all stored klines of symbol and interval, oldest first
+pub fn load_stored_klines(
+    storage_manager: &StorageManager,
+    symbol: &str,
+    interval: &str,
+) -> ApiResult<Vec<Kline>> {
+    let kline_key = MarketData::build_kline_key(symbol, interval);
+
+    let mut klines = vec![];
+
+    for (year, month) in storage_manager.list_kline_files(&kline_key) {
+        let filename = MarketData::build_kline_filename_from_year_month(&kline_key, year, month);
+
+        match storage_manager.load_klines(&filename) {
+            Ok(Some(file_klines)) => klines.extend(file_klines),
+            Ok(None) => {}
+            Err(e) => warn!("Unable to load klines from {filename}: {e}"),
+        }
+    }
+
+    klines.sort_by_key(|kline| kline.open_time);
+    klines.dedup_by_key(|kline| kline.open_time);
+
+    Ok(klines)
+}
+
+// replayed ticker covers period of single kline
+fn ticker_from_kline(kline: &Kline) -> Ticker {
+    let price_change = kline.close - kline.open;
+    let percent_change = if kline.open != 0.0 {
+        price_change / kline.open * 100.0
+    } else {
+        0.0
+    };
+
+    Ticker {
+        time: kline.close_time,
+        symbol: kline.symbol.clone(),
+        price_change,
+        percent_change,
+        high: kline.high,
+        low: kline.low,
+        traded_vol: kline.volume,
+        quote_vol: kline.volume * kline.close,
+        last_price: kline.close,
+        open_price: kline.open,
+        open_time: kline.open_time,
+        close_time: kline.close_time,
+        // replayed klines have no order book
+        bid_price: None,
+        ask_price: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::channel::{build_arc_channel, DEFAULT_CHANNEL_CAPACITY};
+
+    const BASE_TS: u64 = 1_700_000_000_000;
+    const MINUTE: u64 = 60_000;
+
+    fn store_klines(storage_manager: &StorageManager, closes: &[f64]) {
+        let klines: Vec<Kline> = closes
+            .iter()
+            .enumerate()
+            .map(|(i, close)| Kline {
+                symbol: "BTC-USDT".to_string(),
+                interval: "1m".to_string(),
+                open_time: BASE_TS + i as u64 * MINUTE,
+                close_time: BASE_TS + (i as u64 + 1) * MINUTE - 1,
+                open: *close,
+                high: *close,
+                low: *close,
+                close: *close,
+                ..Default::default()
+            })
+            .collect();
+
+        storage_manager
+            .save_klines(&klines, &MarketData::build_kline_key("BTC-USDT", "1m"))
+            .unwrap();
+    }
+
+    async fn replay_to_end(exchange: &PaperExchange, stream_id: &StreamId) {
+        let stream_metas = exchange.get_stream_manager().lock().await.stream_metas();
+
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while stream_metas.lock().await.get(stream_id).unwrap().status != REPLAY_FINISHED_STATUS
+            {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn fills_change_balance_at_replayed_price() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage_manager = StorageManager::new(dir.path().to_path_buf());
+        store_klines(&storage_manager, &[100.0, 110.0, 120.0]);
+
+        let (market_sender, market_receiver) = build_arc_channel(DEFAULT_CHANNEL_CAPACITY);
+        let exchange = PaperExchange::new(storage_manager, market_sender, 1_000.0, Duration::ZERO);
+
+        // no price to fill at before replay
+        assert!(exchange
+            .open_position("BTC-USDT", OrderSide::Buy, 1.0, OrderOptions::default())
+            .await
+            .is_err());
+
+        let stream_id = exchange
+            .open_stream(StreamType::Kline, "BTC-USDT", Some(Interval::Min1))
+            .await
+            .unwrap();
+        replay_to_end(&exchange, &stream_id).await;
+
+        let mut receiver = market_receiver.lock().await;
+        for close in [100.0, 110.0, 120.0] {
+            match receiver.recv().await.unwrap() {
+                MarketMessage::UpdateKline(kline) => assert_eq!(kline.close, close),
+                message => panic!("Expected UpdateKline, received {message:?}"),
+            }
+        }
+        assert_eq!(exchange.clock().now(), BASE_TS + 2 * MINUTE);
+
+        let res = exchange
+            .open_position("BTC-USDT", OrderSide::Buy, 2.0, OrderOptions::default())
+            .await
+            .unwrap();
+        let account = exchange.account().await;
+        assert_eq!(account.balance, 760.0);
+        assert_eq!(account.positions.len(), 1);
+
+        let position_id = res["orderId"].as_str().unwrap();
+        exchange.close_position(position_id).await.unwrap();
+        let account = exchange.account().await;
+        assert_eq!(account.balance, 1_000.0);
+        assert!(account.positions.is_empty());
+
+        // order value above balance is rejected
+        assert!(matches!(
+            exchange
+                .open_position("BTC-USDT", OrderSide::Buy, 10.0, OrderOptions::default())
+                .await,
+            Err(ExchangeError::InvalidOrder(_))
+        ));
+    }
+
+    fn test_kline(price: f64) -> Kline {
+        Kline {
+            symbol: "BTC-USDT".to_string(),
+            interval: "1m".to_string(),
+            open: price,
+            high: price,
+            low: price,
+            close: price,
+            ..Default::default()
+        }
+    }
+
+    fn test_order(id: &str, quantity: f64, price: f64) -> PaperOrder {
+        PaperOrder {
+            id: id.to_string(),
+            symbol: "BTC-USDT".to_string(),
+            side: OrderSide::Buy,
+            order_type: OrderType::Limit,
+            quantity,
+            price,
+            status: "NEW".to_string(),
+        }
+    }
+
+    #[test]
+    fn limit_fill_above_balance_is_rejected() {
+        let mut account = PaperAccount::new(1_000.0);
+        account.orders = vec![
+            test_order("first", 6.0, 100.0),
+            test_order("second", 6.0, 100.0),
+        ];
+
+        account.update_kline(&test_kline(100.0));
+
+        // second order no longer fits balance left by first fill
+        assert_eq!(account.balance, 400.0);
+        assert_eq!(account.positions.len(), 1);
+        assert!(account.orders.is_empty());
+        assert_eq!(account.order_statuses["first"], OrderStatus::Filled);
+        assert_eq!(account.order_statuses["second"], OrderStatus::Rejected);
+        assert_eq!(account.closed_orders.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn all_orders_include_filled_and_canceled_orders() {
+        let dir = tempfile::tempdir().unwrap();
+        let (market_sender, _market_receiver) = build_arc_channel(DEFAULT_CHANNEL_CAPACITY);
+        let exchange = PaperExchange::new(
+            StorageManager::new(dir.path().to_path_buf()),
+            market_sender,
+            1_000.0,
+            Duration::ZERO,
+        );
+        exchange
+            .account
+            .lock()
+            .await
+            .prices
+            .insert("BTC-USDT".to_string(), 100.0);
+
+        let limit_order = |price| {
+            exchange.open_limit_order("BTC-USDT", OrderSide::Buy, 1.0, price, Default::default())
+        };
+        limit_order(90.0).await.unwrap();
+        let canceled = limit_order(80.0).await.unwrap();
+        exchange
+            .cancel_order("BTC-USDT", canceled["id"].as_str().unwrap())
+            .await
+            .unwrap();
+        exchange
+            .open_position("BTC-USDT", OrderSide::Buy, 1.0, OrderOptions::default())
+            .await
+            .unwrap();
+
+        let statuses = |orders: Value| -> Vec<String> {
+            orders
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|order| order["status"].as_str().unwrap().to_string())
+                .collect()
+        };
+        assert_eq!(
+            statuses(exchange.all_orders().await.unwrap()),
+            vec!["NEW", "CANCELED", "FILLED"]
+        );
+        assert_eq!(
+            statuses(exchange.list_open_orders().await.unwrap()),
+            vec!["NEW"]
+        );
+    }
+
+    // replay runs on other worker as soon as spawned
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn every_replayed_kline_is_recorded_on_stream_meta() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage_manager = StorageManager::new(dir.path().to_path_buf());
+        store_klines(&storage_manager, &[100.0, 110.0, 120.0]);
+
+        let (market_sender, _market_receiver) = build_arc_channel(DEFAULT_CHANNEL_CAPACITY);
+        let exchange = PaperExchange::new(storage_manager, market_sender, 1_000.0, Duration::ZERO);
+
+        let stream_id = exchange
+            .open_stream(StreamType::Kline, "BTC-USDT", Some(Interval::Min1))
+            .await
+            .unwrap();
+        replay_to_end(&exchange, &stream_id).await;
+
+        let stream_metas = exchange.get_stream_manager().lock().await.stream_metas();
+        let stream_meta = stream_metas.lock().await.get(&stream_id).cloned().unwrap();
+        assert_eq!(stream_meta.message_count, 3);
+        assert_eq!(stream_meta.last_update, BASE_TS + 2 * MINUTE);
+    }
+
+    #[tokio::test]
+    async fn close_position_without_price_keeps_position() {
+        let dir = tempfile::tempdir().unwrap();
+        let (market_sender, _market_receiver) = build_arc_channel(DEFAULT_CHANNEL_CAPACITY);
+        let exchange = PaperExchange::new(
+            StorageManager::new(dir.path().to_path_buf()),
+            market_sender,
+            1_000.0,
+            Duration::ZERO,
+        );
+
+        let position_id = {
+            let mut account = exchange.account.lock().await;
+            account.prices.insert("BTC-USDT".to_string(), 100.0);
+            let res = account.open_position("BTC-USDT", OrderSide::Buy, 1.0, 100.0);
+            account.prices.clear();
+            res["orderId"].as_str().unwrap().to_string()
+        };
+
+        assert!(exchange.close_position(&position_id).await.is_err());
+        assert!(exchange
+            .account()
+            .await
+            .positions
+            .contains_key(&position_id));
+    }
+}
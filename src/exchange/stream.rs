@@ -1,18 +1,42 @@
 use std::collections::HashMap;
+use std::fmt::{self, Display, Formatter};
 
 use serde::Serialize;
 
 use async_trait::async_trait;
 
-use crate::{exchange::types::StreamType, market::types::ArcMutex, utils::time::generate_ts};
+use std::sync::Arc;
+
+use crate::{
+    exchange::types::StreamType,
+    market::types::ArcMutex,
+    utils::clock::{system_clock, Clock},
+};
+
+#[cfg(test)]
+use crate::utils::clock::SystemClock;
 
 use super::types::ApiResult;
 
 #[async_trait]
 pub trait StreamManager: Send + Sync {
-    async fn open_stream(&mut self, stream_meta: StreamMeta) -> ApiResult<String>;
+    async fn open_stream(&mut self, stream_meta: StreamMeta) -> ApiResult<StreamId>;
+
+    async fn close_stream(&mut self, stream_id: &StreamId) -> Option<StreamMeta>;
+
+    /// Close every open stream, returns metas of closed streams
+    async fn close_all_streams(&mut self) -> Vec<StreamMeta> {
+        let stream_ids: Vec<StreamId> = self.stream_metas().lock().await.keys().cloned().collect();
 
-    async fn close_stream(&mut self, stream_id: &str) -> Option<StreamMeta>;
+        let mut closed_metas = vec![];
+        for stream_id in stream_ids {
+            if let Some(stream_meta) = self.close_stream(&stream_id).await {
+                closed_metas.push(stream_meta);
+            }
+        }
+
+        closed_metas
+    }
 
     //
     async fn active_streams(&self) -> Vec<StreamMeta> {
@@ -29,59 +53,208 @@ pub trait StreamManager: Send + Sync {
         metas
     }
 
+    /// Active streams of symbol, eg. ticker and kline streams of BTC-USDT
+    async fn active_streams_for(&self, symbol: &str) -> Vec<StreamMeta> {
+        let mut metas = self.active_streams().await;
+        metas.retain(|meta| meta.symbol == symbol);
+        metas
+    }
+
+    async fn active_streams_of_type(&self, stream_type: StreamType) -> Vec<StreamMeta> {
+        let mut metas = self.active_streams().await;
+        metas.retain(|meta| meta.stream_type == stream_type);
+        metas
+    }
+
     // Need trait method to get Arc of Stream Metas to be used in WebSocket threads
-    fn stream_metas(&self) -> ArcMutex<HashMap<String, StreamMeta>>;
+    fn stream_metas(&self) -> ArcMutex<HashMap<StreamId, StreamMeta>>;
+
+    /// Source of time for stream updates and health, eg. simulated time of replay
+    fn clock(&self) -> Arc<dyn Clock> {
+        system_clock()
+    }
+
+    /// Stream has meta which is not closed, used to avoid opening duplicate streams
+    async fn is_stream_open(&self, stream_id: &StreamId) -> bool {
+        let metas = self.stream_metas();
+        let stream_data = metas.lock().await;
+
+        stream_data
+            .get(stream_id)
+            .is_some_and(|stream_meta| stream_meta.status != "closed")
+    }
+
+    /// Health of open stream, None if stream not open
+    async fn stream_health(&self, stream_id: &StreamId) -> Option<StreamHealth> {
+        let metas = self.stream_metas();
+        let stream_data = metas.lock().await;
+
+        let now = self.clock().now();
+
+        stream_data
+            .get(stream_id)
+            .map(|stream_meta| StreamHealth::from_meta(stream_meta, now))
+    }
+}
+
+/// Identifier of a stream, eg. BTC-USDT@kline_1m
+///
+/// Can only be created with `build_stream_id` so that a raw symbol
+/// can never be passed where a stream id is expected
+#[derive(Serialize, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct StreamId(String);
+
+impl StreamId {
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Display for StreamId {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
 }
 
 #[derive(Serialize, Clone, Debug)]
 pub struct StreamMeta {
-    pub id: String,
+    pub id: StreamId,
     pub url: String,
     pub started_time: u64,
     pub stream_type: StreamType,
     pub last_update: u64,
     pub symbol: String,
     pub interval: Option<String>,
+    pub status: String,
+    // number of market messages emitted by stream
+    pub message_count: u64,
 }
 
 impl StreamMeta {
+    /// Stream started at current system time
+    #[cfg(test)]
     pub fn new(
-        id: &str,
+        id: StreamId,
         url: &str,
         symbol: &str,
         stream_type: StreamType,
         interval: Option<String>,
+    ) -> Self {
+        Self::new_at(id, url, symbol, stream_type, interval, SystemClock.now())
+    }
+
+    /// Stream started at now, eg. time of exchange clock
+    pub fn new_at(
+        id: StreamId,
+        url: &str,
+        symbol: &str,
+        stream_type: StreamType,
+        interval: Option<String>,
+        now: u64,
     ) -> Self {
         Self {
-            id: id.to_string(),
+            id,
             url: url.to_string(),
-            started_time: generate_ts(),
+            started_time: now,
             stream_type,
-            last_update: generate_ts(),
+            last_update: now,
             symbol: symbol.to_string(),
             interval,
+            status: "open".to_string(),
+            message_count: 0,
         }
     }
+
+    /// Record market message emitted by stream at time now
+    pub fn record_message(&mut self, now: u64) {
+        self.last_update = now;
+        self.message_count += 1;
+    }
 }
 
 impl Default for StreamMeta {
     fn default() -> Self {
         Self {
-            id: "unknown".to_string(),
+            id: build_stream_id("unknown", None),
             url: "unknown".to_string(),
             started_time: 42,
             stream_type: StreamType::Ticker,
             last_update: 123,
             symbol: "unknown".to_string(),
             interval: None,
+            status: "open".to_string(),
+            message_count: 0,
+        }
+    }
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct StreamHealth {
+    pub id: StreamId,
+    pub status: String,
+    // milliseconds since stream last emitted a message
+    pub age: u64,
+    pub message_count: u64,
+}
+
+impl StreamHealth {
+    pub fn from_meta(stream_meta: &StreamMeta, now: u64) -> Self {
+        Self {
+            id: stream_meta.id.clone(),
+            status: stream_meta.status.clone(),
+            age: now.saturating_sub(stream_meta.last_update),
+            message_count: stream_meta.message_count,
         }
     }
 }
 
-pub fn build_stream_id(symbol: &str, interval: Option<&str>) -> String {
+pub fn build_stream_id(symbol: &str, interval: Option<&str>) -> StreamId {
     if let Some(interval) = interval {
-        format!("{}@kline_{}", symbol, interval)
+        StreamId(format!("{}@kline_{}", symbol, interval))
     } else {
-        format!("{}@ticker", symbol)
+        StreamId(format!("{}@ticker", symbol))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stream_id_is_built_from_symbol_and_interval() {
+        assert_eq!(
+            build_stream_id("BTC-USDT", None).as_str(),
+            "BTC-USDT@ticker"
+        );
+        assert_eq!(
+            build_stream_id("BTC-USDT", Some("1m")).to_string(),
+            "BTC-USDT@kline_1m"
+        );
+    }
+
+    #[test]
+    fn stream_ids_of_same_symbol_differ_by_stream() {
+        let stream_id = build_stream_id("BTC-USDT", None);
+
+        assert_ne!(stream_id.as_str(), "BTC-USDT");
+        assert_eq!(stream_id, build_stream_id("BTC-USDT", None));
+        assert_ne!(stream_id, build_stream_id("BTC-USDT", Some("1m")));
+    }
+
+    #[test]
+    fn stream_metas_are_keyed_by_stream_id() {
+        let stream_id = build_stream_id("BTC-USDT", None);
+        let stream_meta = StreamMeta::new(
+            stream_id.clone(),
+            "url",
+            "BTC-USDT",
+            StreamType::Ticker,
+            None,
+        );
+
+        let mut stream_metas: HashMap<StreamId, StreamMeta> = HashMap::new();
+        stream_metas.insert(stream_meta.id.clone(), stream_meta);
+
+        assert!(stream_metas.contains_key(&stream_id));
     }
 }
@@ -2,6 +2,8 @@ use futures_util::stream::SplitSink;
 use serde::{Deserialize, Serialize};
 use std::error::Error;
 use std::fmt::{self, Display, Formatter};
+use std::str::FromStr;
+use std::time::Duration;
 use tokio::net::TcpStream;
 use tokio_tungstenite::tungstenite::Message;
 use tokio_tungstenite::{MaybeTlsStream, WebSocketStream};
@@ -10,60 +12,129 @@ use crate::market::types::ArcMutex;
 
 // Custom error types
 #[derive(Debug)]
-pub enum ApiError {
+pub enum ExchangeError {
     Network(String),
     Parsing(String),
-    Reqwest(String),
+    Http(reqwest::Error),
+    Json(serde_json::Error),
+    Api { code: i64, msg: String },
+    Auth(String),
+    Signature(String),
+    // reading or writing stored data failed, eg. kline files
+    Io(std::io::Error),
+    // method not available on exchange, eg. HTTP requests on paper exchange
+    Unsupported(String),
+    // too many requests sent, retry after is taken from Retry-After header
+    RateLimited { retry_after: Option<Duration> },
+    // symbol not listed or not trading on exchange
+    UnknownSymbol(String),
+    // order rejected before sending, eg. quantity rounds to zero
+    InvalidOrder(String),
 }
 
-impl fmt::Display for ApiError {
+impl fmt::Display for ExchangeError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            ApiError::Network(msg) => write!(f, "Network error: {}", msg),
-            ApiError::Parsing(msg) => write!(f, "Parsing error: {}", msg),
-            ApiError::Reqwest(msg) => write!(f, "Reqwest error: {}", msg),
+            ExchangeError::Network(msg) => write!(f, "Network error: {}", msg),
+            ExchangeError::Parsing(msg) => write!(f, "Parsing error: {}", msg),
+            ExchangeError::Http(e) => write!(f, "HTTP error: {}", e),
+            ExchangeError::Json(e) => write!(f, "JSON error: {}", e),
+            ExchangeError::Api { code, msg } => write!(f, "API error code {}: {}", code, msg),
+            ExchangeError::Auth(msg) => write!(f, "Auth error: {}", msg),
+            ExchangeError::Signature(msg) => write!(f, "Signature error: {}", msg),
+            ExchangeError::Io(e) => write!(f, "IO error: {}", e),
+            ExchangeError::Unsupported(msg) => write!(f, "Unsupported: {}", msg),
+            ExchangeError::RateLimited { retry_after } => match retry_after {
+                Some(retry_after) => write!(f, "Rate limited, retry after {:?}", retry_after),
+                None => write!(f, "Rate limited"),
+            },
+            ExchangeError::UnknownSymbol(symbol) => write!(f, "Unknown symbol: {}", symbol),
+            ExchangeError::InvalidOrder(msg) => write!(f, "Invalid order: {}", msg),
         }
     }
 }
 
-impl Error for ApiError {}
+impl Error for ExchangeError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ExchangeError::Http(e) => Some(e),
+            ExchangeError::Json(e) => Some(e),
+            ExchangeError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
 
 // Custom result type
-pub type ApiResult<T> = Result<T, ApiError>;
+pub type ApiResult<T> = Result<T, ExchangeError>;
 
-impl From<std::io::Error> for ApiError {
+impl From<std::io::Error> for ExchangeError {
     fn from(e: std::io::Error) -> Self {
-        ApiError::Network(e.to_string())
+        ExchangeError::Io(e)
     }
 }
 
-impl From<String> for ApiError {
+impl From<String> for ExchangeError {
     fn from(e: String) -> Self {
-        ApiError::Parsing(e)
+        ExchangeError::Parsing(e)
     }
 }
 
-impl From<serde_json::Error> for ApiError {
+impl From<serde_json::Error> for ExchangeError {
     fn from(e: serde_json::Error) -> Self {
-        ApiError::Parsing(e.to_string())
+        ExchangeError::Json(e)
     }
 }
 
-impl From<reqwest::Error> for ApiError {
+impl From<reqwest::Error> for ExchangeError {
     fn from(e: reqwest::Error) -> Self {
-        ApiError::Reqwest(e.to_string())
+        ExchangeError::Http(e)
     }
 }
-impl From<std::num::ParseFloatError> for ApiError {
+
+impl From<std::num::ParseFloatError> for ExchangeError {
     fn from(e: std::num::ParseFloatError) -> Self {
-        ApiError::Reqwest(e.to_string())
+        ExchangeError::Parsing(e.to_string())
+    }
+}
+
+// Exchange environment to connect to, testnet uses demo trading hosts,
+// custom hosts can be given eg. to send requests to a local mock server
+#[derive(Debug, Clone, PartialEq, Default)]
+pub enum Environment {
+    #[default]
+    Mainnet,
+    Testnet,
+    Custom {
+        host: String,
+        ws_host: String,
+    },
+}
+
+// Unit of timestamp expected by exchange when signing requests
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TimestampUnit {
+    Milliseconds,
+    // no supported exchange signs with seconds yet
+    #[cfg(test)]
+    Seconds,
+}
+
+impl TimestampUnit {
+    /// Convert milliseconds timestamp to unit
+    pub fn convert_millis(self, ts: u64) -> u64 {
+        match self {
+            TimestampUnit::Milliseconds => ts,
+            #[cfg(test)]
+            TimestampUnit::Seconds => ts / 1000,
+        }
     }
 }
 
 pub type ArcEsStreamSync = ArcMutex<SplitSink<WebSocketStream<MaybeTlsStream<TcpStream>>, Message>>;
 
 #[allow(non_camel_case_types)]
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
 pub enum StreamType {
     Kline,
     Ticker,
@@ -77,3 +148,98 @@ impl Display for StreamType {
         }
     }
 }
+
+impl FromStr for StreamType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "kline" => Ok(StreamType::Kline),
+            "ticker" => Ok(StreamType::Ticker),
+            _ => Err(format!("Unknown stream type: {}", s)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn timestamp_unit_converts_signing_timestamp() {
+        let ts = 1_700_000_000_123;
+
+        assert_eq!(
+            TimestampUnit::Milliseconds.convert_millis(ts),
+            1_700_000_000_123
+        );
+        assert_eq!(TimestampUnit::Seconds.convert_millis(ts), 1_700_000_000);
+    }
+
+    #[test]
+    fn exchange_error_variants_display() {
+        let http_error = reqwest::Client::new().get("not a url").build().unwrap_err();
+        let json_error = serde_json::from_str::<serde_json::Value>("{").unwrap_err();
+        let io_error = std::io::Error::new(std::io::ErrorKind::NotFound, "missing file");
+
+        assert!(ExchangeError::from(http_error)
+            .to_string()
+            .starts_with("HTTP error: "));
+        assert!(ExchangeError::from(json_error)
+            .to_string()
+            .starts_with("JSON error: "));
+
+        let cases = [
+            (
+                ExchangeError::Network("connection reset".to_string()),
+                "Network error: connection reset",
+            ),
+            (
+                ExchangeError::from("missing key".to_string()),
+                "Parsing error: missing key",
+            ),
+            (
+                ExchangeError::Api {
+                    code: 100400,
+                    msg: "Invalid parameters".to_string(),
+                },
+                "API error code 100400: Invalid parameters",
+            ),
+            (
+                ExchangeError::Auth("API key is empty".to_string()),
+                "Auth error: API key is empty",
+            ),
+            (
+                ExchangeError::Signature("invalid key length".to_string()),
+                "Signature error: invalid key length",
+            ),
+            (ExchangeError::from(io_error), "IO error: missing file"),
+        ];
+
+        for (error, display) in cases {
+            assert_eq!(error.to_string(), display);
+        }
+    }
+
+    #[test]
+    fn stream_type_round_trips_through_string() {
+        for stream_type in [StreamType::Kline, StreamType::Ticker] {
+            assert_eq!(
+                stream_type.to_string().parse::<StreamType>().unwrap(),
+                stream_type
+            );
+        }
+
+        assert_eq!(StreamType::Kline.to_string(), "kline");
+        assert_eq!(StreamType::Ticker.to_string(), "ticker");
+        assert_eq!("KLINE".parse::<StreamType>().unwrap(), StreamType::Kline);
+    }
+
+    #[test]
+    fn unknown_stream_type_is_err() {
+        assert_eq!(
+            "depth".parse::<StreamType>().unwrap_err(),
+            "Unknown stream type: depth"
+        );
+    }
+}
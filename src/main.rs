@@ -1,6 +1,8 @@
 use app::new_app_state;
 use dotenv::dotenv;
 use std::io;
+use tracing::{error, info, warn};
+use tracing_subscriber::EnvFilter;
 
 use actix_files::Files;
 use actix_web::middleware::Logger;
@@ -24,9 +26,15 @@ mod web;
 
 const SERVER_HOST: (&str, u16) = ("127.0.0.1", 3000);
 
-#[derive(Debug)]
-pub struct Message {
-    pub int: String,
+/// Log level set with RUST_LOG, eg. RUST_LOG=info, and structured JSON
+/// logs enabled with LOG_FORMAT=json. Records from log crate are included
+fn init_tracing() {
+    let subscriber = tracing_subscriber::fmt().with_env_filter(EnvFilter::from_default_env());
+
+    match std::env::var("LOG_FORMAT") {
+        Ok(format) if format == "json" => subscriber.json().init(),
+        _ => subscriber.init(),
+    }
 }
 
 // Define the main entry point for the trading bot
@@ -34,14 +42,24 @@ pub struct Message {
 
 async fn main() -> io::Result<()> {
     dotenv().ok();
-    env_logger::init();
+    init_tracing();
 
-    println!(
+    info!(
         "Server listening at {:}:{:}...",
         SERVER_HOST.0, SERVER_HOST.1
     );
 
-    let app_state = new_app_state().await;
+    // invalid exchange config, eg. empty API keys, stops server before serving
+    let app_state = new_app_state().await.map_err(|e| {
+        error!("Unable to start bot: {e}");
+        io::Error::other(e.to_string())
+    })?;
+
+    // Check config against exchange before serving
+    let bot = app_state.get_bot().await;
+    for issue in bot.lock().await.validate().await {
+        warn!("Config validation issue: {issue}");
+    }
 
     // Make new HTTP server
     HttpServer::new(move || {
@@ -58,5 +76,10 @@ async fn main() -> io::Result<()> {
     // .listen(listener)?
     .bind(SERVER_HOST)?
     .run()
-    .await
+    .await?;
+
+    // server stopped, eg. by ctrl-c
+    bot.lock().await.stop().await;
+
+    Ok(())
 }
@@ -0,0 +1,157 @@
+use serde::{Deserialize, Serialize};
+use std::fmt::{self, Display, Formatter};
+use std::str::FromStr;
+use std::time::Duration;
+
+/// Kline intervals supported by exchanges
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Interval {
+    Sec1,
+    Min1,
+    Min3,
+    Min5,
+    Min15,
+    Min30,
+    Hour1,
+    Hour2,
+    Hour4,
+    Hour6,
+    Hour8,
+    Hour12,
+    Day1,
+    Day3,
+    Week1,
+    Month1,
+}
+
+impl Interval {
+    pub const ALL: [Interval; 16] = [
+        Interval::Sec1,
+        Interval::Min1,
+        Interval::Min3,
+        Interval::Min5,
+        Interval::Min15,
+        Interval::Min30,
+        Interval::Hour1,
+        Interval::Hour2,
+        Interval::Hour4,
+        Interval::Hour6,
+        Interval::Hour8,
+        Interval::Hour12,
+        Interval::Day1,
+        Interval::Day3,
+        Interval::Week1,
+        Interval::Month1,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Interval::Sec1 => "1s",
+            Interval::Min1 => "1m",
+            Interval::Min3 => "3m",
+            Interval::Min5 => "5m",
+            Interval::Min15 => "15m",
+            Interval::Min30 => "30m",
+            Interval::Hour1 => "1h",
+            Interval::Hour2 => "2h",
+            Interval::Hour4 => "4h",
+            Interval::Hour6 => "6h",
+            Interval::Hour8 => "8h",
+            Interval::Hour12 => "12h",
+            Interval::Day1 => "1d",
+            Interval::Day3 => "3d",
+            Interval::Week1 => "1w",
+            Interval::Month1 => "1M",
+        }
+    }
+
+    /// Length of interval, months are taken as 30 days
+    pub fn to_duration(self) -> Duration {
+        let minutes = match self {
+            Interval::Sec1 => return Duration::from_secs(1),
+            Interval::Min1 => 1,
+            Interval::Min3 => 3,
+            Interval::Min5 => 5,
+            Interval::Min15 => 15,
+            Interval::Min30 => 30,
+            Interval::Hour1 => 60,
+            Interval::Hour2 => 2 * 60,
+            Interval::Hour4 => 4 * 60,
+            Interval::Hour6 => 6 * 60,
+            Interval::Hour8 => 8 * 60,
+            Interval::Hour12 => 12 * 60,
+            Interval::Day1 => 24 * 60,
+            Interval::Day3 => 3 * 24 * 60,
+            Interval::Week1 => 7 * 24 * 60,
+            Interval::Month1 => 30 * 24 * 60,
+        };
+
+        Duration::from_secs(minutes * 60)
+    }
+}
+
+impl Display for Interval {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Interval {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        // minute intervals may be given as {number}min
+        let normalized = match s.strip_suffix("min") {
+            Some(count) => format!("{count}m"),
+            None => s.to_string(),
+        };
+
+        Interval::ALL
+            .into_iter()
+            .find(|interval| interval.as_str() == normalized)
+            .ok_or_else(|| format!("Unsupported interval: {s}"))
+    }
+}
+
+impl TryFrom<&str> for Interval {
+    type Error = String;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn intervals_parse_and_display_round_trip() {
+        for interval in Interval::ALL {
+            assert_eq!(interval.to_string().parse::<Interval>(), Ok(interval));
+        }
+
+        assert_eq!("15min".parse::<Interval>(), Ok(Interval::Min15));
+        assert_eq!(Interval::try_from("1M"), Ok(Interval::Month1));
+        assert_eq!("1s".parse::<Interval>(), Ok(Interval::Sec1));
+        assert!("2w".parse::<Interval>().is_err());
+        assert!("".parse::<Interval>().is_err());
+    }
+
+    #[test]
+    fn interval_durations() {
+        assert_eq!(Interval::Sec1.to_duration(), Duration::from_secs(1));
+        assert_eq!(Interval::Min1.to_duration(), Duration::from_secs(60));
+        assert_eq!(Interval::Min15.to_duration(), Duration::from_secs(15 * 60));
+        assert_eq!(Interval::Hour4.to_duration(), Duration::from_secs(4 * 3600));
+        assert_eq!(Interval::Day1.to_duration(), Duration::from_secs(86_400));
+        assert_eq!(
+            Interval::Week1.to_duration(),
+            Duration::from_secs(7 * 86_400)
+        );
+        assert_eq!(
+            Interval::Month1.to_duration(),
+            Duration::from_secs(30 * 86_400)
+        );
+    }
+}
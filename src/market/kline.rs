@@ -1,16 +1,20 @@
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use std::collections::HashMap;
+use std::io::{self, Write};
 
 use crate::{
-    exchange::types::ApiResult,
-    market::market::MarketDataSymbol,
+    exchange::{bingx::BingXApi, types::ApiResult},
+    market::market::MarketData,
     utils::{
         number::parse_f64_from_lookup,
-        time::{calculate_kline_open_time, generate_ts},
+        time::{calculate_kline_close_time, interval_to_millis, timestamp_to_datetime},
     },
 };
 
+#[cfg(test)]
+use crate::utils::clock::{Clock, SystemClock};
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct KlineMeta {
     pub symbol: String,
@@ -20,12 +24,12 @@ pub struct KlineMeta {
 }
 
 impl KlineMeta {
-    pub fn new(symbol: &str, interval: &str) -> Self {
+    pub fn new_at(symbol: &str, interval: &str, last_update: u64) -> Self {
         Self {
             symbol: symbol.to_string(),
             interval: interval.to_string(),
             len: 0,
-            last_update: generate_ts(),
+            last_update,
         }
     }
 }
@@ -36,9 +40,15 @@ pub struct KlineData {
 }
 
 impl KlineData {
+    #[cfg(test)]
     pub fn new(symbol: &str, interval: &str) -> Self {
+        Self::new_at(symbol, interval, SystemClock.now())
+    }
+
+    /// Kline data last updated at time, eg. time of market clock
+    pub fn new_at(symbol: &str, interval: &str, last_update: u64) -> Self {
         Self {
-            meta: KlineMeta::new(symbol, interval),
+            meta: KlineMeta::new_at(symbol, interval, last_update),
             klines: vec![],
         }
     }
@@ -68,10 +78,321 @@ impl KlineData {
         }
     }
 
+    /// Remove oldest klines so at most max len klines are kept,
+    /// returns removed klines in ascending order
+    pub fn drain_oldest(&mut self, max_len: usize) -> Vec<Kline> {
+        if self.klines.len() <= max_len {
+            return vec![];
+        }
+
+        let excess = self.klines.len() - max_len;
+        self.meta.len = self.meta.len.saturating_sub(excess as u64);
+
+        self.klines.drain(..excess).collect()
+    }
+
     pub fn clear_klines(&mut self) {
         self.klines = vec![];
         self.meta.len = 0;
     }
+
+    /// Write klines as CSV for analysis tools eg. pandas, timestamps
+    /// are ISO-8601, not the storage format, see `write_klines` for that
+    pub fn write_csv<W: Write>(&self, w: W) -> io::Result<()> {
+        let mut writer = csv::Writer::from_writer(w);
+
+        writer.write_record([
+            "open_time",
+            "close_time",
+            "symbol",
+            "interval",
+            "open",
+            "high",
+            "low",
+            "close",
+            "volume",
+        ])?;
+
+        for kline in &self.klines {
+            writer.write_record([
+                timestamp_to_datetime(kline.open_time).to_rfc3339(),
+                timestamp_to_datetime(kline.close_time).to_rfc3339(),
+                kline.symbol.to_string(),
+                kline.interval.to_string(),
+                kline.open.to_string(),
+                kline.high.to_string(),
+                kline.low.to_string(),
+                kline.close.to_string(),
+                kline.volume.to_string(),
+            ])?;
+        }
+
+        writer.flush()
+    }
+
+    pub fn to_csv_string(&self) -> String {
+        let mut buf = vec![];
+
+        // writing to vec can not fail
+        let _ = self.write_csv(&mut buf);
+
+        String::from_utf8_lossy(&buf).to_string()
+    }
+
+    pub fn closes(&self) -> Vec<f64> {
+        self.klines.iter().map(|kline| kline.close).collect()
+    }
+
+    /// Aggregate klines into higher timeframe, target interval
+    /// must be a multiple of current interval
+    pub fn resample(&self, target_interval: &str) -> ApiResult<KlineData> {
+        let source_millis = interval_to_millis(&self.meta.interval)
+            .ok_or_else(|| format!("Unsupported interval: {}", self.meta.interval))?;
+        let target_millis = interval_to_millis(target_interval)
+            .ok_or_else(|| format!("Unsupported interval: {}", target_interval))?;
+
+        if target_millis < source_millis || target_millis % source_millis != 0 {
+            return Err(format!(
+                "Interval {} is not a multiple of {}",
+                target_interval, self.meta.interval
+            )
+            .into());
+        }
+
+        let mut kline_data =
+            KlineData::new_at(&self.meta.symbol, target_interval, self.meta.last_update);
+
+        for kline in &self.klines {
+            let bucket_open_time = bucket_open_time(kline.open_time, target_millis);
+
+            match kline_data.klines.last_mut() {
+                Some(last) if last.open_time == bucket_open_time => {
+                    last.high = last.high.max(kline.high);
+                    last.low = last.low.min(kline.low);
+                    last.close = kline.close;
+                    last.volume += kline.volume;
+                    last.is_closed = kline.is_closed && kline.close_time >= last.close_time;
+                }
+                _ => {
+                    kline_data.add_kline(Kline {
+                        symbol: kline.symbol.clone(),
+                        interval: target_interval.to_string(),
+                        open: kline.open,
+                        high: kline.high,
+                        low: kline.low,
+                        close: kline.close,
+                        volume: kline.volume,
+                        open_time: bucket_open_time,
+                        close_time: bucket_open_time + target_millis - 1,
+                        is_closed: kline.is_closed
+                            && kline.close_time >= bucket_open_time + target_millis - 1,
+                    });
+                }
+            }
+        }
+
+        Ok(kline_data)
+    }
+
+    /// Volume weighted average of typical price (high + low + close) / 3
+    /// over all klines, None if there is no volume
+    pub fn vwap(&self) -> Option<f64> {
+        let (price_volume, volume) = self.klines.iter().fold((0.0, 0.0), |(pv, v), kline| {
+            (pv + kline.typical_price() * kline.volume, v + kline.volume)
+        });
+
+        if volume == 0.0 {
+            return None;
+        }
+
+        Some(price_volume / volume)
+    }
+
+    /// Simple moving average of close prices aligned to klines,
+    /// first period - 1 values are None
+    pub fn sma(&self, period: usize) -> Vec<Option<f64>> {
+        let closes = self.closes();
+        let mut sma = vec![None; closes.len()];
+
+        if period == 0 || period > closes.len() {
+            return sma;
+        }
+
+        let mut sum: f64 = closes[..period].iter().sum();
+        sma[period - 1] = Some(sum / period as f64);
+
+        for i in period..closes.len() {
+            sum += closes[i] - closes[i - period];
+            sma[i] = Some(sum / period as f64);
+        }
+
+        sma
+    }
+
+    /// Bollinger bands of close prices as (lower, middle, upper) aligned to klines,
+    /// middle is SMA and bands are k population standard deviations away
+    pub fn bollinger(&self, period: usize, k: f64) -> Vec<Option<(f64, f64, f64)>> {
+        let closes = self.closes();
+
+        self.sma(period)
+            .into_iter()
+            .enumerate()
+            .map(|(i, middle)| {
+                let middle = middle?;
+                let window = &closes[i + 1 - period..=i];
+                let variance = window
+                    .iter()
+                    .map(|close| (close - middle).powi(2))
+                    .sum::<f64>()
+                    / period as f64;
+                let width = k * variance.sqrt();
+
+                Some((middle - width, middle, middle + width))
+            })
+            .collect()
+    }
+
+    /// Exponential moving average of close prices aligned to klines,
+    /// seeded with SMA of first period closes
+    pub fn ema(&self, period: usize) -> Vec<Option<f64>> {
+        ema(&self.closes(), period)
+    }
+
+    /// Moving average convergence divergence of close prices as
+    /// (macd, signal, histogram) aligned to klines, macd is fast EMA - slow EMA
+    /// and signal is EMA of macd, first slow + signal - 2 values are None
+    pub fn macd(&self, fast: usize, slow: usize, signal: usize) -> Vec<Option<(f64, f64, f64)>> {
+        let mut macd = vec![None; self.klines.len()];
+
+        if fast == 0 || slow == 0 || signal == 0 {
+            return macd;
+        }
+
+        let fast_ema = self.ema(fast);
+        let slow_ema = self.ema(slow);
+
+        // macd line starts once slowest EMA has value
+        let start = fast.max(slow) - 1;
+        let macd_line: Vec<f64> = fast_ema
+            .iter()
+            .zip(slow_ema.iter())
+            .skip(start)
+            .filter_map(|(fast, slow)| Some((*fast)? - (*slow)?))
+            .collect();
+
+        for (i, signal_value) in ema(&macd_line, signal).into_iter().enumerate() {
+            if let Some(signal_value) = signal_value {
+                let macd_value = macd_line[i];
+                macd[start + i] = Some((macd_value, signal_value, macd_value - signal_value));
+            }
+        }
+
+        macd
+    }
+
+    /// Relative strength index of close prices using Wilder's smoothing,
+    /// first period values are None as period price changes are needed
+    pub fn rsi(&self, period: usize) -> Vec<Option<f64>> {
+        let closes = self.closes();
+        let mut rsi = vec![None; closes.len()];
+
+        if period == 0 || period >= closes.len() {
+            return rsi;
+        }
+
+        let mut avg_gain = 0.0;
+        let mut avg_loss = 0.0;
+
+        for i in 1..=period {
+            let change = closes[i] - closes[i - 1];
+            avg_gain += change.max(0.0);
+            avg_loss += (-change).max(0.0);
+        }
+
+        avg_gain /= period as f64;
+        avg_loss /= period as f64;
+        rsi[period] = Some(rsi_value(avg_gain, avg_loss));
+
+        for i in (period + 1)..closes.len() {
+            let change = closes[i] - closes[i - 1];
+            avg_gain = (avg_gain * (period - 1) as f64 + change.max(0.0)) / period as f64;
+            avg_loss = (avg_loss * (period - 1) as f64 + (-change).max(0.0)) / period as f64;
+            rsi[i] = Some(rsi_value(avg_gain, avg_loss));
+        }
+
+        rsi
+    }
+
+    /// Average true range using Wilder's smoothing, first period values
+    /// are None as true range needs close of previous kline
+    pub fn atr(&self, period: usize) -> Vec<Option<f64>> {
+        let mut atr = vec![None; self.klines.len()];
+
+        if period == 0 || period >= self.klines.len() {
+            return atr;
+        }
+
+        let true_ranges: Vec<f64> = self
+            .klines
+            .windows(2)
+            .map(|pair| pair[1].true_range(&pair[0]))
+            .collect();
+
+        let mut prev = true_ranges[..period].iter().sum::<f64>() / period as f64;
+        atr[period] = Some(prev);
+
+        for i in (period + 1)..self.klines.len() {
+            prev = (prev * (period - 1) as f64 + true_ranges[i - 1]) / period as f64;
+            atr[i] = Some(prev);
+        }
+
+        atr
+    }
+}
+
+// Unix epoch is a Thursday, weekly klines open on Monday 00:00 UTC
+const WEEK_MILLIS: u64 = 7 * 24 * 60 * 60 * 1000;
+const WEEK_OPEN_OFFSET: u64 = 4 * 24 * 60 * 60 * 1000;
+
+// open time of bucket containing timestamp, buckets of whole weeks
+// are aligned to Monday and shorter buckets to the epoch
+fn bucket_open_time(timestamp: u64, bucket_millis: u64) -> u64 {
+    let offset = if bucket_millis % WEEK_MILLIS == 0 {
+        WEEK_OPEN_OFFSET
+    } else {
+        0
+    };
+
+    let into_bucket = (timestamp + bucket_millis - offset % bucket_millis) % bucket_millis;
+    timestamp.saturating_sub(into_bucket)
+}
+
+/// Exponential moving average of values, seeded with SMA of first period values
+fn ema(values: &[f64], period: usize) -> Vec<Option<f64>> {
+    let mut ema = vec![None; values.len()];
+
+    if period == 0 || period > values.len() {
+        return ema;
+    }
+
+    let k = 2.0 / (period as f64 + 1.0);
+    let mut prev = values[..period].iter().sum::<f64>() / period as f64;
+    ema[period - 1] = Some(prev);
+
+    for i in period..values.len() {
+        prev = (values[i] - prev) * k + prev;
+        ema[i] = Some(prev);
+    }
+
+    ema
+}
+
+fn rsi_value(avg_gain: f64, avg_loss: f64) -> f64 {
+    if avg_loss == 0.0 {
+        return 100.0;
+    }
+
+    100.0 - 100.0 / (1.0 + avg_gain / avg_loss)
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -85,6 +406,9 @@ pub struct Kline {
     pub volume: f64,
     pub open_time: u64,
     pub close_time: u64,
+    // kline is final and will not be updated, missing in older stored klines
+    #[serde(default)]
+    pub is_closed: bool,
 }
 
 impl Default for Kline {
@@ -99,6 +423,7 @@ impl Default for Kline {
             close: 42.2,
             volume: 42.2,
             close_time: 42,
+            is_closed: false,
         }
     }
 }
@@ -167,6 +492,12 @@ impl Kline {
 
         let volume = parse_f64_from_lookup("v", &_kline)?;
 
+        // x is true once kline is closed
+        let is_closed = _kline
+            .get("x")
+            .and_then(|is_closed| is_closed.as_bool())
+            .unwrap_or(false);
+
         Ok(Self {
             interval: interval.to_string(),
             symbol: symbol.to_string(),
@@ -177,30 +508,120 @@ impl Kline {
             close,
             volume,
             close_time,
+            is_closed,
         })
     }
 
+    /// Build latest kline of BingX klines response, kline is
+    /// closed if its close time is before now
+    #[cfg(test)]
     pub fn from_bingx_lookup(
         lookup: HashMap<String, Value>,
         symbol: &str,
         interval: &str,
+        now: u64,
     ) -> ApiResult<Self> {
+        // klines are in ascending order, last kline is latest
+        Self::from_bingx_klines_lookup(&lookup, symbol, interval, now)?
+            .pop()
+            .ok_or_else(|| "Empty 'data' list in data kline lookup".to_string().into())
+    }
+
+    /// Build klines of BingX klines response in ascending order of open time,
+    /// error is returned if response code is not zero
+    pub fn from_bingx_klines_lookup(
+        lookup: &HashMap<String, Value>,
+        symbol: &str,
+        interval: &str,
+        now: u64,
+    ) -> ApiResult<Vec<Self>> {
         // {
         //     "open": "float64",
         //     "close": "float64",
         //     "high": "float64",
         //     "low": "float64",
         //     "volume": "float64",
-        //     "time": "int64"
+        //     "time": "int64" // open time of kline
         //   }
+        //
+        // data is sent as single kline object, list of kline objects
+        // or list of kline arrays, eg. {"code":0,"data":[[...],...]}
+
+        BingXApi::check_response_code(lookup)?;
 
         let data = lookup.get("data").ok_or_else(|| {
             // Create an error message or construct an error type
             "Missing 'data' key from data kline lookup".to_string()
         })?;
-        let data: HashMap<String, Value> = serde_json::from_value(data.to_owned())?;
 
-        let close_time = data
+        let mut klines = match data {
+            Value::Array(klines_data) => klines_data
+                .iter()
+                .map(|kline_data| Self::from_bingx_value(kline_data, symbol, interval, now))
+                .collect::<ApiResult<Vec<Kline>>>()?,
+            _ => vec![Self::from_bingx_value(data, symbol, interval, now)?],
+        };
+
+        // order of klines in list is not guaranteed
+        klines.sort_by_key(|kline| kline.open_time);
+
+        Ok(klines)
+    }
+
+    /// Build kline from single kline object or array within BingX response data
+    pub fn from_bingx_value(
+        data: &Value,
+        symbol: &str,
+        interval: &str,
+        now: u64,
+    ) -> ApiResult<Self> {
+        match data {
+            Value::Array(values) => Self::from_bingx_array(values, symbol, interval, now),
+            _ => {
+                let data: HashMap<String, Value> = serde_json::from_value(data.to_owned())?;
+                Self::from_bingx_data(data, symbol, interval, now)
+            }
+        }
+    }
+
+    /// Build kline from kline array within BingX response data,
+    /// eg. [open_time, open, high, low, close, volume, ...]
+    pub fn from_bingx_array(
+        values: &[Value],
+        symbol: &str,
+        interval: &str,
+        now: u64,
+    ) -> ApiResult<Self> {
+        let open_time = values
+            .first()
+            .and_then(|open_time| open_time.as_u64())
+            .ok_or_else(|| "Missing open time from data kline array".to_string())?;
+
+        let close_time = calculate_kline_close_time(open_time, interval);
+
+        Ok(Self {
+            interval: interval.to_string(),
+            symbol: symbol.to_string(),
+            open_time,
+            open: parse_f64_from_array(1, values)?,
+            high: parse_f64_from_array(2, values)?,
+            low: parse_f64_from_array(3, values)?,
+            close: parse_f64_from_array(4, values)?,
+            volume: parse_f64_from_array(5, values)?,
+            close_time,
+            is_closed: close_time < now,
+        })
+    }
+
+    /// Build kline from single kline object within BingX response data
+    pub fn from_bingx_data(
+        data: HashMap<String, Value>,
+        symbol: &str,
+        interval: &str,
+        now: u64,
+    ) -> ApiResult<Self> {
+        // time is open time of kline
+        let open_time = data
             .get("time")
             .ok_or_else(|| {
                 // Create an error message or construct an error type
@@ -212,7 +633,7 @@ impl Kline {
                 "Unable to parse as u64".to_string()
             })?;
 
-        let open_time = calculate_kline_open_time(close_time, interval);
+        let close_time = calculate_kline_close_time(open_time, interval);
 
         let open = parse_f64_from_lookup("open", &data)?;
         let close = parse_f64_from_lookup("close", &data)?;
@@ -232,35 +653,57 @@ impl Kline {
             close,
             volume,
             close_time,
+            is_closed: close_time < now,
         })
     }
 
-    pub fn from_bingx_lookup_ws(lookup: HashMap<String, Value>) -> ApiResult<Self> {
+    pub fn from_bingx_lookup_ws(
+        lookup: HashMap<String, Value>,
+        symbol: &str,
+        interval: &str,
+    ) -> ApiResult<Self> {
         // {
         //     "code": 0,
-        //     "data": {
-        //       "T": 1649832779999,  //k line time
+        //     "data": [{
+        //       "T": 1702880460000,  //k line open time
         //       "c": "54564.31",
         //       "h": "54711.73",
         //       "l": "54418.27",
         //       "o": "54577.41",
         //       "v": "1607.0727000000002"
-        //     },
+        //     }],
         //     "s": "BTC-USDT" //trading pair
         //     "dataType": "BTC-USDT@kline_1m"
         //   }
-        let data: HashMap<String, Value> =
-            serde_json::from_value(lookup.get("data").unwrap().to_owned()).unwrap();
+        let data = lookup.get("data").ok_or_else(|| {
+            // Create an error message or construct an error type
+            "Missing 'data' key from data kline lookup".to_string()
+        })?;
 
-        let data_type = data.get("dataType").unwrap().as_str().unwrap();
-        // BTC-USDT@kline_1m
-        let split = data_type.split('_');
-        let interval = split.last().unwrap().to_string();
-        let symbol = lookup.get("s").unwrap().as_str().unwrap();
+        // data can be sent as a list of klines, use the latest kline
+        let data = match data {
+            Value::Array(klines) => klines.last().ok_or_else(|| {
+                // Create an error message or construct an error type
+                "Empty 'data' list in data kline lookup".to_string()
+            })?,
+            _ => data,
+        };
+        let data: HashMap<String, Value> = serde_json::from_value(data.to_owned())?;
 
-        let close_time = data.get("T").unwrap().as_u64().unwrap();
+        // T is open time of kline
+        let open_time = data
+            .get("T")
+            .ok_or_else(|| {
+                // Create an error message or construct an error type
+                "Missing 'T' key from data kline lookup".to_string()
+            })?
+            .as_u64()
+            .ok_or_else(|| {
+                // Create an error message or construct an error type
+                "Unable to 'as_u64' from 'T' key in data kline lookup".to_string()
+            })?;
 
-        let open_time = calculate_kline_open_time(close_time, &interval);
+        let close_time = calculate_kline_close_time(open_time, interval);
 
         let open = parse_f64_from_lookup("o", &data)?;
         let close = parse_f64_from_lookup("c", &data)?;
@@ -271,7 +714,7 @@ impl Kline {
         let volume = parse_f64_from_lookup("v", &data)?;
 
         Ok(Self {
-            interval,
+            interval: interval.to_string(),
             symbol: symbol.to_string(),
             open_time,
             open,
@@ -280,13 +723,77 @@ impl Kline {
             close,
             volume,
             close_time,
+            // web socket klines have no closed flag,
+            // kline is closed once kline with next open time is received
+            is_closed: false,
         })
     }
+
+    /// Average of high, low and close price
+    pub fn typical_price(&self) -> f64 {
+        (self.high + self.low + self.close) / 3.0
+    }
+
+    /// Largest of kline range and gaps from previous close,
+    /// includes moves which happened between klines
+    pub fn true_range(&self, prev: &Kline) -> f64 {
+        (self.high - self.low)
+            .max((self.high - prev.close).abs())
+            .max((self.low - prev.close).abs())
+    }
 }
 
-impl MarketDataSymbol for Kline {
-    fn symbol(&self) -> String {
-        self.symbol.to_string()
+// values in kline arrays are sent as strings or numbers
+fn parse_f64_from_array(index: usize, values: &[Value]) -> ApiResult<f64> {
+    let value = values
+        .get(index)
+        .ok_or_else(|| format!("Missing index {index} from data kline array"))?;
+
+    match value {
+        Value::String(value) => Ok(value.parse::<f64>()?),
+        _ => value
+            .as_f64()
+            .ok_or_else(|| format!("Unable to parse index {index} of data kline array").into()),
+    }
+}
+
+/// Tracks latest kline of each symbol and interval to find when klines close,
+/// each closed kline is returned only once
+#[derive(Debug, Default)]
+pub struct ClosedKlineTracker {
+    latest_klines: HashMap<String, Kline>,
+}
+
+impl ClosedKlineTracker {
+    /// Klines closed by update, previous kline is closed
+    /// once kline with later open time is received
+    pub fn update(&mut self, kline: &Kline) -> Vec<Kline> {
+        let kline_key = MarketData::build_kline_key(&kline.symbol, &kline.interval);
+        let mut closed_klines = vec![];
+
+        match self.latest_klines.get(&kline_key) {
+            // older kline received out of order
+            Some(latest) if kline.open_time < latest.open_time => return closed_klines,
+            // kline already closed
+            Some(latest) if kline.open_time == latest.open_time && latest.is_closed => {
+                return closed_klines
+            }
+            Some(latest) if kline.open_time == latest.open_time => {}
+            Some(latest) if !latest.is_closed => {
+                let mut closed_kline = latest.clone();
+                closed_kline.is_closed = true;
+                closed_klines.push(closed_kline);
+            }
+            _ => {}
+        }
+
+        if kline.is_closed {
+            closed_klines.push(kline.clone());
+        }
+
+        self.latest_klines.insert(kline_key, kline.clone());
+
+        closed_klines
     }
 }
 
@@ -305,3 +812,539 @@ pub struct BinanceKline {
     pub taker_buy_quote_volume: f64,
     pub ignore: u8,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::exchange::types::ExchangeError;
+
+    const MINUTE: u64 = 60_000;
+    const TOLERANCE: f64 = 1e-6;
+
+    // Wilder RSI reference closes
+    const REFERENCE_CLOSES: [f64; 25] = [
+        44.34, 44.09, 44.15, 43.61, 44.33, 44.83, 45.10, 45.42, 45.84, 46.08, 45.89, 46.03, 45.61,
+        46.28, 46.28, 46.00, 46.03, 46.41, 46.22, 45.64, 46.21, 46.25, 45.71, 46.45, 45.78,
+    ];
+
+    fn assert_values_close(values: &[Option<f64>], expected: &[Option<f64>]) {
+        assert_eq!(values.len(), expected.len());
+
+        for (i, (value, expected)) in values.iter().zip(expected.iter()).enumerate() {
+            match (value, expected) {
+                (Some(value), Some(expected)) => assert!(
+                    (value - expected).abs() < TOLERANCE,
+                    "value {i}: {value} != {expected}"
+                ),
+                (None, None) => {}
+                _ => panic!("value {i}: {value:?} != {expected:?}"),
+            }
+        }
+    }
+
+    fn kline_data_from_closes(closes: &[f64]) -> KlineData {
+        let mut kline_data = KlineData::new("BTC-USDT", "1m");
+
+        for (i, close) in closes.iter().enumerate() {
+            let open_time = i as u64 * MINUTE;
+            kline_data.add_kline(Kline {
+                symbol: "BTC-USDT".to_string(),
+                interval: "1m".to_string(),
+                open: *close,
+                high: *close,
+                low: *close,
+                close: *close,
+                volume: 1.0,
+                open_time,
+                close_time: open_time + MINUTE - 1,
+                is_closed: true,
+            });
+        }
+
+        kline_data
+    }
+
+    #[test]
+    fn sma_of_known_series() {
+        let kline_data = kline_data_from_closes(&[1.0, 2.0, 3.0, 4.0, 5.0]);
+
+        assert_eq!(
+            kline_data.sma(3),
+            vec![None, None, Some(2.0), Some(3.0), Some(4.0)]
+        );
+        assert_eq!(kline_data.sma(0), vec![None; 5]);
+        assert_eq!(kline_data.sma(6), vec![None; 5]);
+    }
+
+    #[test]
+    fn ema_matches_reference_values() {
+        let kline_data = kline_data_from_closes(&REFERENCE_CLOSES);
+
+        let mut expected = vec![None; 9];
+        expected.extend(
+            [
+                44.779,
+                44.981,
+                45.171727273,
+                45.251413223,
+                45.438429001,
+                45.59144191,
+                45.665725199,
+                45.731956981,
+                45.85523753,
+                45.921557979,
+                45.870365619,
+                45.932117325,
+                45.989914175,
+                45.939020688,
+                46.031926018,
+                45.986121287,
+            ]
+            .map(Some),
+        );
+        assert_values_close(&kline_data.ema(10), &expected);
+
+        // not enough closes for first average
+        assert_eq!(kline_data_from_closes(&[1.0, 2.0]).ema(3), vec![None; 2]);
+    }
+
+    #[test]
+    fn rsi_matches_reference_values() {
+        let kline_data = kline_data_from_closes(&REFERENCE_CLOSES);
+
+        let mut expected = vec![None; 14];
+        expected.extend(
+            [
+                70.464135021,
+                66.249618554,
+                66.480941835,
+                69.346853163,
+                66.294712659,
+                57.91502067,
+                62.88071831,
+                63.208788718,
+                56.01158479,
+                62.339929311,
+                54.670971378,
+            ]
+            .map(Some),
+        );
+        assert_values_close(&kline_data.rsi(14), &expected);
+
+        // period price changes needed for first value
+        assert_eq!(
+            kline_data_from_closes(&REFERENCE_CLOSES[..14]).rsi(14),
+            vec![None; 14]
+        );
+    }
+
+    #[test]
+    fn resample_aggregates_5m_klines_into_1h() {
+        let five_minutes = 5 * MINUTE;
+        let mut kline_data = KlineData::new("BTC-USDT", "5m");
+        for i in 0..12 {
+            let open_time = i * five_minutes;
+            kline_data.add_kline(Kline {
+                symbol: "BTC-USDT".to_string(),
+                interval: "5m".to_string(),
+                open: 100.0 + i as f64,
+                high: 105.0 + i as f64,
+                low: 95.0 - i as f64,
+                close: 101.0 + i as f64,
+                volume: 2.0,
+                open_time,
+                close_time: open_time + five_minutes - 1,
+                is_closed: true,
+            });
+        }
+
+        let resampled = kline_data.resample("1h").unwrap();
+        assert_eq!(resampled.klines.len(), 1);
+
+        let kline = &resampled.klines[0];
+        assert_eq!(kline.interval, "1h");
+        assert_eq!(kline.open_time, 0);
+        assert_eq!(kline.close_time, 60 * MINUTE - 1);
+        assert_eq!(kline.open, 100.0);
+        assert_eq!(kline.high, 116.0);
+        assert_eq!(kline.low, 84.0);
+        assert_eq!(kline.close, 112.0);
+        assert_eq!(kline.volume, 24.0);
+
+        // target must be multiple of source interval
+        assert!(kline_data.resample("3m").is_err());
+        assert!(kline_data.resample("1m").is_err());
+    }
+
+    fn candle(high: f64, low: f64, close: f64) -> Kline {
+        Kline {
+            high,
+            low,
+            close,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn typical_price_of_known_candle() {
+        assert_eq!(candle(12.0, 8.0, 10.0).typical_price(), 10.0);
+        assert!((candle(48.70, 47.79, 48.16).typical_price() - 48.216_666_666_7).abs() < TOLERANCE);
+    }
+
+    #[test]
+    fn true_range_includes_gaps_from_previous_close() {
+        // range inside previous close, high - low
+        assert!(
+            (candle(48.70, 47.79, 48.16).true_range(&candle(0.0, 0.0, 48.0)) - 0.91).abs()
+                < TOLERANCE
+        );
+        // gap up, high - previous close
+        assert!(
+            (candle(52.0, 51.0, 51.5).true_range(&candle(0.0, 0.0, 49.0)) - 3.0).abs() < TOLERANCE
+        );
+        // gap down, previous close - low
+        assert!(
+            (candle(46.0, 45.5, 45.8).true_range(&candle(0.0, 0.0, 48.0)) - 2.5).abs() < TOLERANCE
+        );
+    }
+
+    #[test]
+    fn atr_matches_reference_values() {
+        // (high, low, close) of ATR reference candles
+        let candles = [
+            (48.70, 47.79, 48.16),
+            (48.72, 48.14, 48.61),
+            (48.90, 48.39, 48.75),
+            (48.87, 48.37, 48.63),
+            (48.82, 48.24, 48.74),
+            (49.05, 48.64, 49.03),
+            (49.20, 48.94, 49.07),
+            (49.35, 48.86, 49.32),
+            (49.92, 49.50, 49.91),
+            (50.19, 49.87, 50.13),
+            (50.12, 49.20, 49.53),
+            (49.66, 48.90, 49.50),
+            (49.88, 49.43, 49.75),
+            (50.19, 49.73, 50.03),
+            (50.36, 49.26, 50.31),
+        ];
+        let mut kline_data = kline_data_from_closes(&[0.0; 15]);
+        for (kline, (high, low, close)) in kline_data.klines.iter_mut().zip(candles) {
+            *kline = Kline {
+                high,
+                low,
+                close,
+                ..kline.clone()
+            };
+        }
+
+        let mut expected = vec![None; 5];
+        expected.extend(
+            [
+                0.516,
+                0.4648,
+                0.46984,
+                0.495872,
+                0.4606976,
+                0.55455808,
+                0.595646464,
+                0.566517171,
+                0.545213737,
+                0.65617099,
+            ]
+            .map(Some),
+        );
+        assert_values_close(&kline_data.atr(5), &expected);
+    }
+
+    #[test]
+    fn atr_is_none_without_enough_klines() {
+        // first average needs period true ranges, so period + 1 klines
+        let kline_data = kline_data_from_closes(&[1.0, 2.0, 3.0]);
+
+        assert_eq!(kline_data.atr(3), vec![None; 3]);
+        assert_eq!(kline_data.atr(0), vec![None; 3]);
+        assert_eq!(KlineData::new("BTC-USDT", "1m").atr(14), vec![]);
+        assert!(kline_data.atr(2)[2].is_some());
+    }
+
+    #[test]
+    fn bollinger_bands_match_reference_values() {
+        let kline_data = kline_data_from_closes(&REFERENCE_CLOSES);
+        let bands = kline_data.bollinger(20, 2.0);
+
+        assert_eq!(bands.len(), REFERENCE_CLOSES.len());
+        assert!(bands[..19].iter().all(|band| band.is_none()));
+
+        // (index, lower, middle, upper) with population standard deviation
+        for (i, lower, middle, upper) in [
+            (19, 43.702_671_778, 45.409, 47.115_328_222),
+            (22, 44.276_603_757, 45.6885, 47.100_396_243),
+            (24, 45.069_978_992, 45.903, 46.736_021_008),
+        ] {
+            let band = bands[i].unwrap();
+            assert!((band.0 - lower).abs() < TOLERANCE);
+            assert!((band.1 - middle).abs() < TOLERANCE);
+            assert!((band.2 - upper).abs() < TOLERANCE);
+        }
+
+        // period longer than series
+        assert_eq!(
+            kline_data_from_closes(&[1.0, 2.0]).bollinger(3, 2.0),
+            vec![None; 2]
+        );
+    }
+
+    #[test]
+    fn macd_matches_reference_values_and_warmup() {
+        let kline_data = kline_data_from_closes(&REFERENCE_CLOSES);
+        let macd = kline_data.macd(5, 10, 4);
+
+        // macd line from slow EMA, signal after 4 macd values, 10 + 4 - 2
+        assert_eq!(macd.len(), REFERENCE_CLOSES.len());
+        assert!(macd[..12].iter().all(|value| value.is_none()));
+        assert!(macd[12..].iter().all(|value| value.is_some()));
+
+        // (index, macd, signal, histogram)
+        for (i, macd_line, signal_line, histogram) in [
+            (12, 0.457_721_665, 0.599_332_617, -0.141_610_952),
+            (18, 0.252_522_450, 0.308_979_724, -0.056_457_274),
+            (24, 0.035_581_247, 0.085_244_292, -0.049_663_045),
+        ] {
+            let value = macd[i].unwrap();
+            assert!((value.0 - macd_line).abs() < TOLERANCE);
+            assert!((value.1 - signal_line).abs() < TOLERANCE);
+            assert!((value.2 - histogram).abs() < TOLERANCE);
+        }
+
+        // not enough closes for signal line
+        assert_eq!(
+            kline_data_from_closes(&REFERENCE_CLOSES[..12]).macd(5, 10, 4),
+            vec![None; 12]
+        );
+    }
+
+    #[test]
+    fn vwap_weights_typical_price_by_volume() {
+        let mut kline_data = kline_data_from_closes(&[0.0, 0.0]);
+        // typical price (12 + 8 + 10) / 3 = 10
+        kline_data.klines[0].high = 12.0;
+        kline_data.klines[0].low = 8.0;
+        kline_data.klines[0].close = 10.0;
+        kline_data.klines[0].volume = 1.0;
+        // typical price (22 + 18 + 20) / 3 = 20
+        kline_data.klines[1].high = 22.0;
+        kline_data.klines[1].low = 18.0;
+        kline_data.klines[1].close = 20.0;
+        kline_data.klines[1].volume = 3.0;
+
+        // (10 * 1 + 20 * 3) / 4
+        assert_eq!(kline_data.vwap(), Some(17.5));
+    }
+
+    #[test]
+    fn vwap_is_none_without_volume() {
+        let mut kline_data = kline_data_from_closes(&[10.0, 20.0]);
+        for kline in kline_data.klines.iter_mut() {
+            kline.volume = 0.0;
+        }
+
+        assert_eq!(kline_data.vwap(), None);
+        assert_eq!(KlineData::new("BTC-USDT", "1m").vwap(), None);
+    }
+
+    #[test]
+    fn csv_export_has_header_and_iso_timestamps() {
+        let mut kline_data = kline_data_from_closes(&[100.5]);
+        // 2023-11-14T22:13:00Z
+        kline_data.klines[0].open_time = 1_699_999_980_000;
+        kline_data.klines[0].close_time = 1_699_999_980_000 + MINUTE - 1;
+        kline_data.klines[0].high = 101.25;
+        kline_data.klines[0].low = 99.0;
+        kline_data.klines[0].volume = 12.5;
+
+        let csv = kline_data.to_csv_string();
+        let mut lines = csv.lines();
+
+        assert_eq!(
+            lines.next(),
+            Some("open_time,close_time,symbol,interval,open,high,low,close,volume")
+        );
+        assert_eq!(
+            lines.next(),
+            Some(
+                "2023-11-14T22:13:00+00:00,2023-11-14T22:13:59.999+00:00,\
+                BTC-USDT,1m,100.5,101.25,99,100.5,12.5"
+            )
+        );
+        assert_eq!(lines.next(), None);
+    }
+
+    fn bingx_lookup(payload: &str) -> HashMap<String, Value> {
+        serde_json::from_str(payload).unwrap()
+    }
+
+    #[test]
+    fn bingx_lookup_uses_latest_kline_of_array_data() {
+        let payload = r#"{
+            "code": 0,
+            "msg": "",
+            "data": [
+                [1700000060000, "35050.4", "35120.0", "35010.5", "35100.1", "98.7", 1700000119999],
+                [1700000000000, "35000.1", "35100.2", "34900.3", "35050.4", "123.4", 1700000059999]
+            ]
+        }"#;
+
+        let kline =
+            Kline::from_bingx_lookup(bingx_lookup(payload), "BTC-USDT", "1m", 1_700_000_100_000)
+                .unwrap();
+
+        assert_eq!(kline.symbol, "BTC-USDT");
+        assert_eq!(kline.open_time, 1_700_000_060_000);
+        assert_eq!(kline.close_time, 1_700_000_119_999);
+        assert_eq!(kline.open, 35_050.4);
+        assert_eq!(kline.high, 35_120.0);
+        assert_eq!(kline.low, 35_010.5);
+        assert_eq!(kline.close, 35_100.1);
+        assert_eq!(kline.volume, 98.7);
+        // latest kline is still open at now
+        assert!(!kline.is_closed);
+    }
+
+    #[test]
+    fn bingx_lookup_errors_are_returned_not_panicked() {
+        let error_payload = r#"{"code": 100400, "msg": "Invalid parameters", "data": []}"#;
+        assert!(matches!(
+            Kline::from_bingx_lookup(bingx_lookup(error_payload), "BTC-USDT", "1m", 0),
+            Err(ExchangeError::Api { code: 100400, .. })
+        ));
+
+        for payload in [
+            r#"{"code": 0, "data": []}"#,
+            r#"{"code": 0}"#,
+            r#"{"code": 0, "data": [["not a time", "1.0"]]}"#,
+        ] {
+            assert!(Kline::from_bingx_lookup(bingx_lookup(payload), "BTC-USDT", "1m", 0).is_err());
+        }
+    }
+
+    #[test]
+    fn close_time_of_1m_kline_is_last_millisecond_of_minute() {
+        let values: Vec<Value> =
+            serde_json::from_str(r#"[1672026600000, "100.0", "101.0", "99.0", "100.5", "10.0"]"#)
+                .unwrap();
+        let kline = Kline::from_bingx_array(&values, "BTC-USDT", "1m", 0).unwrap();
+        assert_eq!(kline.open_time, 1_672_026_600_000);
+        assert_eq!(kline.close_time, 1_672_026_659_999);
+        assert_eq!(kline.close_time - kline.open_time + 1, MINUTE);
+    }
+
+    // object klines response of BingX swap API, time is minute aligned
+    #[test]
+    fn bingx_kline_time_is_open_time() {
+        let payload = r#"{
+            "code": 0,
+            "msg": "",
+            "data": [
+                {
+                    "open": "16838.0",
+                    "close": "16837.9",
+                    "high": "16838.0",
+                    "low": "16837.9",
+                    "volume": "1.00",
+                    "time": 1672026600000
+                }
+            ]
+        }"#;
+
+        let kline =
+            Kline::from_bingx_lookup(bingx_lookup(payload), "BTC-USDT", "15m", 1_672_027_499_999)
+                .unwrap();
+        assert_eq!(kline.open_time, 1_672_026_600_000);
+        assert_eq!(kline.close_time, 1_672_027_499_999);
+        assert_eq!(kline.open, 16_838.0);
+        assert_eq!(kline.close, 16_837.9);
+        assert!(!kline.is_closed);
+
+        // closed once now is past close time
+        let kline =
+            Kline::from_bingx_lookup(bingx_lookup(payload), "BTC-USDT", "15m", 1_672_027_500_000)
+                .unwrap();
+        assert!(kline.is_closed);
+    }
+
+    // kline message of BingX swap web socket, T is minute aligned
+    #[test]
+    fn bingx_ws_kline_time_is_open_time() {
+        let payload = r#"{
+            "code": 0,
+            "dataType": "BTC-USDT@kline_1m",
+            "s": "BTC-USDT",
+            "data": [
+                {
+                    "c": "42932.3",
+                    "o": "42935.5",
+                    "h": "42937.2",
+                    "l": "42930.0",
+                    "v": "6.8911",
+                    "T": 1702880460000
+                }
+            ]
+        }"#;
+
+        let kline = Kline::from_bingx_lookup_ws(bingx_lookup(payload), "BTC-USDT", "1m").unwrap();
+        assert_eq!(kline.open_time, 1_702_880_460_000);
+        assert_eq!(kline.close_time, 1_702_880_519_999);
+        assert_eq!(kline.open, 42_935.5);
+        assert_eq!(kline.close, 42_932.3);
+    }
+
+    fn update_kline(open_time: u64, close: f64, is_closed: bool) -> Kline {
+        Kline {
+            symbol: "BTC-USDT".to_string(),
+            interval: "1m".to_string(),
+            open_time,
+            close_time: open_time + MINUTE - 1,
+            close,
+            is_closed,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn closed_kline_is_emitted_once() {
+        let mut tracker = ClosedKlineTracker::default();
+
+        for close in [100.0, 101.0, 102.0] {
+            assert!(tracker.update(&update_kline(0, close, false)).is_empty());
+        }
+
+        let closed = tracker.update(&update_kline(0, 103.0, true));
+        assert_eq!(closed.len(), 1);
+        assert_eq!(closed[0].close, 103.0);
+
+        // repeated close and late updates are ignored
+        assert!(tracker.update(&update_kline(0, 103.0, true)).is_empty());
+        assert!(tracker.update(&update_kline(0, 104.0, false)).is_empty());
+        assert!(tracker
+            .update(&update_kline(MINUTE, 104.0, false))
+            .is_empty());
+    }
+
+    #[test]
+    fn kline_is_closed_when_next_kline_opens() {
+        let mut tracker = ClosedKlineTracker::default();
+        tracker.update(&update_kline(0, 100.0, false));
+        tracker.update(&update_kline(0, 101.0, false));
+
+        // exchange never sent close flag, next kline closes previous
+        let closed = tracker.update(&update_kline(MINUTE, 102.0, false));
+        assert_eq!(closed.len(), 1);
+        assert_eq!(closed[0].open_time, 0);
+        assert_eq!(closed[0].close, 101.0);
+        assert!(closed[0].is_closed);
+
+        // out of order kline does not close anything
+        assert!(tracker.update(&update_kline(0, 99.0, false)).is_empty());
+    }
+}
@@ -1,10 +1,15 @@
-use futures::StreamExt;
+use tracing::{info, warn};
 
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
 
-use std::time::{Duration, SystemTime};
+use std::io;
+use std::time::{Duration, Instant};
 use std::{collections::HashMap, sync::Arc};
 
+use tokio::sync::broadcast;
+use tokio::task::JoinHandle;
+
 // use tokio::time::{self, Duration};
 
 use crate::exchange::stream::build_stream_id;
@@ -12,47 +17,89 @@ use crate::exchange::types::{ApiResult, StreamType};
 use crate::{
     exchange::{
         api::ExchangeApi,
-        stream::{StreamManager, StreamMeta},
+        metadata::MetadataCache,
+        stream::{StreamId, StreamMeta},
     },
     market::{
-        kline::{Kline, KlineData, KlineMeta},
+        interval::Interval,
+        kline::{ClosedKlineTracker, Kline, KlineData, KlineMeta},
         messages::MarketMessage,
-        ticker::{Ticker, TickerData, TickerMeta},
+        ticker::{Ticker, TickerData, DEFAULT_TICKER_HISTORY_LEN, DEFAULT_TICKER_WINDOW},
         types::ArcReceiver,
     },
     storage::manager::StorageManager,
     utils::{
+        clock::{system_clock, Clock},
         kline::generate_kline_filenames_in_range,
-        time::{generate_ts, timestamp_to_datetime},
+        time::{interval_to_millis, timestamp_to_datetime},
     },
 };
 
 use super::types::ArcMutex;
 
-pub trait MarketDataSymbol {
-    fn symbol(&self) -> String;
-}
-
 #[derive(Serialize, Deserialize, Clone)]
 pub struct MarketData {
     all_klines: HashMap<String, KlineData>,
     all_tickers: HashMap<String, TickerData>,
     storage_manager: StorageManager,
-    last_backup: SystemTime,
+    // source of time for backups and ticker expiry, replaced in tests
+    #[serde(skip, default = "system_clock")]
+    clock: Arc<dyn Clock>,
+    // milliseconds timestamp of last backup
+    last_backup: u64,
+    // None disables saving klines to disk
+    backup_interval: Option<Duration>,
+    // tickers not updated within ttl are removed, None keeps tickers forever
+    ticker_ttl: Option<Duration>,
+    // length of ticker history kept for each symbol
+    ticker_window: Duration,
+    ticker_history_len: usize,
+    // klines kept in memory for each key, oldest are saved and dropped
+    // so memory does not depend on backup interval, or discarded when
+    // there is no backup interval
+    max_klines: usize,
 }
 
-const BACKUP_INTERVAL: u64 = 20;
+pub const DEFAULT_BACKUP_INTERVAL: Duration = Duration::from_secs(20);
+pub const DEFAULT_MAX_KLINES: usize = 10_000;
 
 impl MarketData {
-    pub fn new(storage_manager: StorageManager) -> Self {
+    /// Market data timed by clock, eg. exchange clock so replayed
+    /// klines are backed up and expired at replayed time
+    pub fn new(
+        storage_manager: StorageManager,
+        backup_interval: Option<Duration>,
+        clock: Arc<dyn Clock>,
+    ) -> Self {
         Self {
             storage_manager,
             all_klines: HashMap::new(),
             all_tickers: HashMap::new(),
-            last_backup: SystemTime::now(),
+            last_backup: clock.now(),
+            clock,
+            backup_interval,
+            ticker_ttl: None,
+            ticker_window: DEFAULT_TICKER_WINDOW,
+            ticker_history_len: DEFAULT_TICKER_HISTORY_LEN,
+            max_klines: DEFAULT_MAX_KLINES,
         }
     }
 
+    /// Set max number of klines kept in memory for each symbol and interval
+    pub fn set_max_klines(&mut self, max_klines: usize) {
+        self.max_klines = max_klines.max(1);
+    }
+
+    /// Set time window and max number of tickers kept in ticker history
+    pub fn set_ticker_history(&mut self, window: Duration, max_len: usize) {
+        self.ticker_window = window;
+        self.ticker_history_len = max_len;
+    }
+
+    pub fn set_ticker_ttl(&mut self, ticker_ttl: Option<Duration>) {
+        self.ticker_ttl = ticker_ttl;
+    }
+
     pub fn add_kline(&mut self, kline: Kline) {
         // get kline key eg. BTCUSDT@kline_1m
         let kline_key = Self::build_kline_key(&kline.symbol, &kline.interval);
@@ -60,66 +107,154 @@ impl MarketData {
         // add new kline to data if key found for kline symbol
         if let Some(kline_data) = self.all_klines.get_mut(&kline_key) {
             kline_data.add_kline(kline);
+
+            // once over max, oldest klines are saved in one batch down to half
+            // of max, so file isn't rewritten for every new kline,
+            // latest kline is always kept
+            if kline_data.klines.len() > self.max_klines {
+                let keep = (self.max_klines / 2).max(1);
+                let excess = kline_data.klines.len() - keep;
+
+                // klines are never saved without backups, so oldest are discarded
+                if self.backup_interval.is_some() {
+                    // memory is bounded even if disk is failing, unsaved klines are lost
+                    if let Err(e) = self
+                        .storage_manager
+                        .save_klines(&kline_data.klines[..excess], &kline_key)
+                    {
+                        warn!(
+                            "Unable to save {excess} klines dropped from memory for {kline_key}: {e}"
+                        );
+                    }
+                }
+
+                kline_data.drain_oldest(keep);
+            }
         } else {
             // create new key for new kline eg. ETHUSDT@kline_1h
             let klines = vec![kline.clone()];
             let new_kline_data = KlineData {
-                meta: KlineMeta::new(&kline.symbol, &kline.interval),
+                meta: KlineMeta::new_at(&kline.symbol, &kline.interval, self.clock.now()),
                 klines,
             };
             self.all_klines
                 .insert(kline_key.to_string(), new_kline_data);
         }
 
-        // Save klines to disk if last backup more than 1 minute
-        let time_elapsed = SystemTime::now()
-            .duration_since(self.last_backup)
-            .unwrap_or(Duration::from_secs(0));
+        let backup_interval = match self.backup_interval {
+            Some(backup_interval) => backup_interval,
+            None => return,
+        };
 
-        if time_elapsed >= Duration::from_secs(BACKUP_INTERVAL) {
-            for (key, kline_data) in self.all_klines.iter() {
-                let klines: Vec<Kline> = kline_data.klines.clone();
+        // Save klines to disk if last backup more than backup interval
+        let time_elapsed = self.clock.now().saturating_sub(self.last_backup);
 
-                self.storage_manager
-                    .save_klines(&klines, key)
-                    .expect("Unable to save Klines");
-            }
+        if time_elapsed >= backup_interval.as_millis() as u64 {
+            for (key, kline_data) in self.all_klines.iter_mut() {
+                // only clear klines once saved, keep in memory to retry on next backup
+                match self.storage_manager.save_klines(&kline_data.klines, key) {
+                    Ok(_) => kline_data.clear_klines(),
+                    Err(e) => warn!("Unable to save klines for {key}: {e}"),
+                }
 
-            // Clear tickers from ticker_data
-            for (_k, kline_data) in self.all_klines.iter_mut() {
-                kline_data.clear_klines();
+                if let Err(e) = self
+                    .storage_manager
+                    .archive_completed_months(key, self.clock.now())
+                {
+                    warn!("Unable to archive klines for {key}: {e}");
+                }
             }
 
+            // tickers of stopped streams are evicted even if no tickers arrive
+            self.evict_expired_tickers();
+
             // Update the last backup time
-            self.last_backup = SystemTime::now();
+            self.last_backup = self.clock.now();
         }
     }
 
+    pub fn storage_manager(&self) -> &StorageManager {
+        &self.storage_manager
+    }
+
+    /// Save klines directly to storage, eg. klines backfilled from exchange
+    pub fn save_klines(
+        &self,
+        symbol: &str,
+        interval: Interval,
+        klines: &[Kline],
+    ) -> io::Result<()> {
+        let kline_key = Self::build_kline_key(symbol, interval.as_str());
+
+        self.storage_manager.save_klines(klines, &kline_key)
+    }
+
+    /// Interval &str shim for save_klines
+    #[cfg(test)]
+    pub fn save_klines_str(
+        &self,
+        symbol: &str,
+        interval: &str,
+        klines: &[Kline],
+    ) -> io::Result<()> {
+        let interval = interval
+            .parse()
+            .map_err(|e: String| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+
+        self.save_klines(symbol, interval, klines)
+    }
+
     pub fn update_ticker(&mut self, ticker: Ticker) {
         let ticker_key = Self::build_ticker_key(&ticker.symbol);
-        let now = generate_ts();
+        let now = self.clock.now();
 
         if let Some(ticker_data) = self.all_tickers.get_mut(&ticker_key) {
             ticker_data.update_ticker(ticker, now);
         } else {
-            let new_ticker_data = TickerData {
-                meta: TickerMeta::new(&ticker.symbol),
+            let symbol = ticker.symbol.clone();
+            let new_ticker_data = TickerData::new_at(
+                &symbol,
                 ticker,
-            };
+                now,
+                self.ticker_window,
+                self.ticker_history_len,
+            );
             self.all_tickers
                 .insert(ticker_key.to_string(), new_ticker_data);
         }
+
+        self.evict_expired_tickers();
+    }
+
+    /// Remove tickers of streams which have stopped updating for longer than ttl
+    pub fn evict_expired_tickers(&mut self) {
+        let now = self.clock.now();
+
+        if let Some(ticker_ttl) = self.ticker_ttl {
+            let ttl = ticker_ttl.as_millis() as u64;
+            self.all_tickers
+                .retain(|_, ticker_data| now.saturating_sub(ticker_data.meta.last_update) <= ttl);
+        }
+    }
+
+    fn is_ticker_expired(&self, ticker_data: &TickerData) -> bool {
+        self.ticker_ttl.is_some_and(|ticker_ttl| {
+            self.clock
+                .now()
+                .saturating_sub(ticker_data.meta.last_update)
+                > ticker_ttl.as_millis() as u64
+        })
     }
 
     pub fn kline_data(
         &mut self,
         symbol: &str,
-        interval: &str,
+        interval: Interval,
         from_ts: Option<u64>,
         to_ts: Option<u64>,
         limit: Option<usize>,
-    ) -> Option<KlineData> {
-        let kline_key = Self::build_kline_key(symbol, interval);
+    ) -> ApiResult<Option<KlineData>> {
+        let kline_key = Self::build_kline_key(symbol, interval.as_str());
 
         let in_mem_kline = match self.all_klines.get(&kline_key) {
             Some(kline_data) => kline_data.klines.clone(),
@@ -129,76 +264,201 @@ impl MarketData {
         // create filtered klines to hold all klines which are filtered
         let mut filtered_klines: Vec<Kline> = Vec::new();
 
-        let filenames = match from_ts {
-            Some(from_ts) => match to_ts {
+        let filenames = match (from_ts, limit) {
+            (Some(from_ts), _) => match to_ts {
                 Some(to_ts) => Some(generate_kline_filenames_in_range(
                     &kline_key, from_ts, to_ts,
                 )),
                 None => Some(generate_kline_filenames_in_range(
                     &kline_key,
                     from_ts,
-                    generate_ts(),
+                    self.clock.now(),
                 )),
             },
-            None => None,
+            // count back from latest month on disk until limit klines are loaded
+            (None, Some(_)) => Some(
+                self.storage_manager
+                    .list_kline_files(&kline_key)
+                    .into_iter()
+                    .map(|(year, month)| {
+                        Self::build_kline_filename_from_year_month(&kline_key, year, month)
+                    })
+                    .collect(),
+            ),
+            (None, None) => None,
         };
 
         if let Some(filenames) = filenames {
-            for kline_filename in filenames {
-                if let Some(klines) = self.storage_manager.load_klines(&kline_filename) {
-                    filtered_klines.extend_from_slice(&klines);
+            // load most recent files first, older files are not
+            // needed once enough klines in range are loaded for limit
+            let mut loaded_in_range = 0;
+
+            for kline_filename in filenames.iter().rev() {
+                if limit.is_some_and(|limit| loaded_in_range >= limit) {
+                    break;
+                }
+
+                if let Some(klines) = self.storage_manager.load_klines(kline_filename)? {
+                    loaded_in_range += klines
+                        .iter()
+                        .filter(|kline| {
+                            from_ts.is_none_or(|from_ts| kline.open_time >= from_ts)
+                                && to_ts.is_none_or(|to_ts| kline.open_time <= to_ts)
+                        })
+                        .count();
+                    filtered_klines.extend(klines);
                 }
             }
         };
 
         filtered_klines.extend_from_slice(&in_mem_kline);
 
+        // Remove duplicate klines with same open_time, in mem klines are added
+        // after klines loaded from disk so the most recently updated kline is kept
+        let mut unique_klines: HashMap<u64, Kline> = HashMap::new();
+        for kline in filtered_klines {
+            unique_klines.insert(kline.open_time, kline);
+        }
+        let mut filtered_klines: Vec<Kline> = unique_klines.into_values().collect();
+
         // filtered by from_ts and to_ts
         if let Some(from_ts) = from_ts {
             filtered_klines.retain(|kline| kline.open_time >= from_ts);
-            if let Some(to_ts) = to_ts {
-                filtered_klines.retain(|kline| kline.open_time <= to_ts);
-            }
+        }
+        if let Some(to_ts) = to_ts {
+            filtered_klines.retain(|kline| kline.open_time <= to_ts);
         }
 
         // Sort the klines by open_time in descending order
-        filtered_klines.sort_by(|a, b| a.open_time.cmp(&b.open_time));
+        filtered_klines.sort_by_key(|kline| kline.open_time);
 
         // append in mem klines
 
         // Limit the number of data points returned
+        // keep the most recent klines, ie. the tail of the sorted vector
         if let Some(limit) = limit {
-            filtered_klines = filtered_klines[..limit].to_vec();
+            let start = filtered_klines.len().saturating_sub(limit);
+            filtered_klines = filtered_klines[start..].to_vec();
         }
 
         // Create a new KlineData object to hold the filtered klines
         let filtered_kline_data = KlineData {
             meta: KlineMeta {
                 symbol: symbol.to_string(),
-                interval: interval.to_string(),
+                interval: interval.as_str().to_string(),
                 len: filtered_klines.len() as u64,
-                last_update: generate_ts(),
+                last_update: self.clock.now(),
             },
             klines: filtered_klines,
         };
 
         if filtered_kline_data.meta.len == 0 {
-            None
+            Ok(None)
         } else {
-            Some(filtered_kline_data)
+            Ok(Some(filtered_kline_data))
         }
     }
 
-    /// return last 20 seconds of tickers for given symbol
+    /// Missing (start, end) open time ranges of klines between from and to
+    /// timestamps, expected open times are aligned to first stored kline
+    pub fn find_kline_gaps(
+        &mut self,
+        symbol: &str,
+        interval: Interval,
+        from_ts: u64,
+        to_ts: u64,
+    ) -> ApiResult<Vec<(u64, u64)>> {
+        // month intervals are not fixed length so gaps can't be found
+        let interval_ms = match interval_to_millis(interval.as_str()) {
+            Some(interval_ms) => interval_ms,
+            None => return Ok(vec![]),
+        };
+
+        let open_times: Vec<u64> =
+            match self.kline_data(symbol, interval, Some(from_ts), Some(to_ts), None)? {
+                Some(kline_data) => kline_data
+                    .klines
+                    .iter()
+                    .map(|kline| kline.open_time)
+                    .collect(),
+                None => return Ok(vec![(from_ts, to_ts)]),
+            };
+
+        // first expected open time on or after from_ts
+        let offset = open_times[0] % interval_ms;
+        let mut expected = from_ts + (offset + interval_ms - from_ts % interval_ms) % interval_ms;
+
+        let mut gaps = vec![];
+        for open_time in open_times {
+            if open_time > expected {
+                gaps.push((expected, open_time - interval_ms));
+            }
+            expected = expected.max(open_time + interval_ms);
+        }
+
+        if expected <= to_ts {
+            let last_expected = expected + (to_ts - expected) / interval_ms * interval_ms;
+            gaps.push((expected, last_expected));
+        }
+
+        Ok(gaps)
+    }
+
+    /// Kline with latest open time, klines on disk are only
+    /// loaded if no klines for symbol and interval are in memory
+    pub fn latest_kline(&self, symbol: &str, interval: Interval) -> Option<Kline> {
+        let kline_key = Self::build_kline_key(symbol, interval.as_str());
+
+        if let Some(kline) = self
+            .all_klines
+            .get(&kline_key)
+            .and_then(|kline_data| kline_data.klines.iter().max_by_key(|k| k.open_time))
+        {
+            return Some(kline.clone());
+        }
+
+        // most recent kline file holds latest kline
+        let (year, month) = *self.storage_manager.list_kline_files(&kline_key).last()?;
+        let filename = Self::build_kline_filename_from_year_month(&kline_key, year, month);
+
+        match self.storage_manager.load_klines(&filename) {
+            Ok(klines) => klines?.into_iter().max_by_key(|kline| kline.open_time),
+            Err(e) => {
+                warn!("Unable to load klines from {filename}: {e}");
+                None
+            }
+        }
+    }
+
+    /// return tickers within ticker window for given symbol,
+    /// expired tickers not yet evicted are not returned
     pub fn ticker_data(&self, symbol: &str) -> Option<TickerData> {
         let ticker_key = Self::build_ticker_key(symbol);
         if let Some(ticker_data) = self.all_tickers.get(&ticker_key) {
+            if self.is_ticker_expired(ticker_data) {
+                return None;
+            }
             return Some(ticker_data.clone());
         }
 
         None
     }
 
+    /// Return ticker data only if updated within max age
+    pub fn ticker_data_fresh(&self, symbol: &str, max_age: Duration) -> Option<TickerData> {
+        let ticker_data = self.ticker_data(symbol)?;
+        let age = self
+            .clock
+            .now()
+            .saturating_sub(ticker_data.meta.last_update);
+
+        if age > max_age.as_millis() as u64 {
+            return None;
+        }
+
+        Some(ticker_data)
+    }
+
     pub fn build_kline_key(symbol: &str, interval: &str) -> String {
         format!("{}@kline_{}", symbol, interval)
     }
@@ -222,12 +482,118 @@ impl MarketData {
     }
 }
 
+// number of messages kept for slow subscribers before oldest are skipped
+const MARKET_BROADCAST_CAPACITY: usize = 1000;
+
+pub const DEFAULT_SERVER_TIME_SYNC_INTERVAL: Duration = Duration::from_secs(10 * 60);
+
+const RECONNECT_BASE_DELAY: Duration = Duration::from_secs(1);
+const RECONNECT_MAX_DELAY: Duration = Duration::from_secs(60);
+
+/// Reconnect state of a needed stream which is not active
+#[derive(Debug, Clone)]
+pub struct StreamBackoff {
+    pub delay: Duration,
+    pub next_attempt: Instant,
+}
+
+impl StreamBackoff {
+    pub fn new(now: Instant) -> Self {
+        Self {
+            delay: RECONNECT_BASE_DELAY,
+            next_attempt: now,
+        }
+    }
+
+    /// Schedule next reconnect attempt and double delay up to max delay
+    pub fn record_attempt(&mut self, now: Instant) {
+        self.next_attempt = now + self.delay;
+        self.delay = (self.delay * 2).min(RECONNECT_MAX_DELAY);
+    }
+}
+
+/// Needed streams which are not active and whose backoff delay has passed,
+/// backoff of streams returned is advanced for the reopen attempt
+fn streams_to_reopen(
+    needed_streams: Vec<StreamMeta>,
+    active_streams: &[StreamMeta],
+    stream_backoffs: &mut HashMap<StreamId, StreamBackoff>,
+    now: Instant,
+) -> Vec<StreamMeta> {
+    let mut reopen_streams = vec![];
+
+    for needed_stream_meta in needed_streams {
+        let active_stream_meta = active_streams
+            .iter()
+            .find(|&meta| meta.id == needed_stream_meta.id && meta.status != "closed");
+
+        match active_stream_meta {
+            Some(meta) => {
+                // reset backoff once stream has received a message
+                if meta.last_update > meta.started_time {
+                    stream_backoffs.remove(&meta.id);
+                }
+            }
+            None => {
+                let backoff = stream_backoffs
+                    .entry(needed_stream_meta.id.clone())
+                    .or_insert_with(|| StreamBackoff::new(now));
+
+                // wait for backoff delay before trying to reopen stream
+                if now < backoff.next_attempt {
+                    continue;
+                }
+
+                backoff.record_attempt(now);
+                reopen_streams.push(needed_stream_meta);
+            }
+        }
+    }
+
+    reopen_streams
+}
+
+/// Streams needed by market when started, each stream
+/// given as (symbol, stream type, interval)
+#[derive(Debug, Clone)]
+pub struct MarketConfig {
+    pub streams: Vec<(String, StreamType, Option<Interval>)>,
+    pub backup_interval: Option<Duration>,
+    pub ticker_ttl: Option<Duration>,
+    pub ticker_window: Duration,
+    pub ticker_history_len: usize,
+    pub max_klines: usize,
+    // None does not sync signing timestamps with exchange server time
+    pub server_time_sync_interval: Option<Duration>,
+}
+
+impl Default for MarketConfig {
+    fn default() -> Self {
+        Self {
+            streams: vec![],
+            backup_interval: Some(DEFAULT_BACKUP_INTERVAL),
+            ticker_ttl: None,
+            ticker_window: DEFAULT_TICKER_WINDOW,
+            ticker_history_len: DEFAULT_TICKER_HISTORY_LEN,
+            max_klines: DEFAULT_MAX_KLINES,
+            server_time_sync_interval: None,
+        }
+    }
+}
+
 pub struct Market {
     market_receiver: ArcReceiver<MarketMessage>,
     // stream_manager: ArcMutex<StreamManager>,
     data: ArcMutex<MarketData>,
     exchange_api: Arc<Box<dyn ExchangeApi>>,
     needed_streams: ArcMutex<Vec<StreamMeta>>,
+    stream_backoffs: ArcMutex<HashMap<StreamId, StreamBackoff>>,
+    // shared with stream monitor so reopened streams are validated
+    metadata_cache: Arc<MetadataCache>,
+    // handles of background tasks, aborted on shutdown
+    task_handles: ArcMutex<Vec<JoinHandle<()>>>,
+    // fan out of market messages to subscribers, eg. strategies
+    market_broadcast: broadcast::Sender<MarketMessage>,
 }
 
 impl Market {
@@ -235,17 +601,37 @@ impl Market {
         // stream_manager: ArcMutex<StreamManager>,
         market_receiver: ArcReceiver<MarketMessage>,
         exchange_api: Arc<Box<dyn ExchangeApi>>,
-        storage_manager: StorageManager,
+        mut storage_manager: StorageManager,
+        config: MarketConfig,
     ) -> Self {
+        info!("Starting market on exchange: {}", exchange_api.name());
+
+        // keep klines of each exchange in separate files
+        storage_manager.set_exchange(exchange_api.name());
+
+        // time of market data follows exchange, eg. replayed kline time
+        let mut market_data = MarketData::new(
+            storage_manager,
+            config.backup_interval,
+            exchange_api.clock(),
+        );
+        market_data.set_ticker_ttl(config.ticker_ttl);
+        market_data.set_ticker_history(config.ticker_window, config.ticker_history_len);
+        market_data.set_max_klines(config.max_klines);
+
         let mut _self = Self {
-            data: ArcMutex::new(MarketData::new(storage_manager)),
+            data: ArcMutex::new(market_data),
             market_receiver,
             // stream_manager,
+            metadata_cache: Arc::new(MetadataCache::new(exchange_api.clone())),
             exchange_api,
             needed_streams: ArcMutex::new(vec![]),
+            stream_backoffs: ArcMutex::new(HashMap::new()),
+            task_handles: ArcMutex::new(vec![]),
+            market_broadcast: broadcast::channel(MARKET_BROADCAST_CAPACITY).0,
         };
 
-        _self.init().await;
+        _self.init(config).await;
 
         _self
     }
@@ -257,31 +643,70 @@ impl Market {
     pub async fn last_price(&self, symbol: &str) -> Option<f64> {
         let ticker = self.data.lock().await.ticker_data(symbol);
 
-        ticker.map(|ticker| ticker.ticker.last_price)
+        ticker.and_then(|ticker| ticker.last_price())
     }
 
     pub async fn kline_data(
         &self,
         symbol: &str,
-        interval: &str,
+        interval: Interval,
         from_ts: Option<u64>,
         to_ts: Option<u64>,
         limit: Option<usize>,
-    ) -> Option<KlineData> {
+    ) -> ApiResult<Option<KlineData>> {
         self.data
             .lock()
             .await
             .kline_data(symbol, interval, from_ts, to_ts, limit)
     }
 
+    pub async fn latest_kline(&self, symbol: &str, interval: Interval) -> Option<Kline> {
+        self.data.lock().await.latest_kline(symbol, interval)
+    }
+
     pub async fn ticker_data(&self, symbol: &str) -> Option<TickerData> {
         self.data.lock().await.ticker_data(symbol)
     }
 
+    pub async fn ticker_data_fresh(&self, symbol: &str, max_age: Duration) -> Option<TickerData> {
+        self.data.lock().await.ticker_data_fresh(symbol, max_age)
+    }
+
     pub async fn market_data(&self) -> MarketData {
         self.data.lock().await.clone()
     }
 
+    /// Storage manager klines of market exchange are stored with
+    pub async fn storage_manager(&self) -> StorageManager {
+        self.data.lock().await.storage_manager().clone()
+    }
+
+    /// Receive each market message as it arrives from exchange streams
+    pub fn subscribe(&self) -> broadcast::Receiver<MarketMessage> {
+        self.market_broadcast.subscribe()
+    }
+
+    // ---
+    // Exchange Metadata Methods
+    // ---
+
+    #[cfg(test)]
+    pub fn exchange_api(&self) -> Arc<Box<dyn ExchangeApi>> {
+        self.exchange_api.clone()
+    }
+
+    pub async fn exchange_info(&self) -> ApiResult<Value> {
+        self.metadata_cache.metadata().await
+    }
+
+    pub async fn refresh_metadata(&self) -> ApiResult<Value> {
+        self.metadata_cache.refresh_metadata().await
+    }
+
+    pub fn set_metadata_max_age(&self, max_age: Duration) {
+        self.metadata_cache.set_max_age(max_age);
+    }
+
     // ---
     // Stream Methods
     // ---
@@ -290,47 +715,220 @@ impl Market {
         self.exchange_api.active_streams().await
     }
 
+    pub async fn active_streams_for(&self, symbol: &str) -> Vec<StreamMeta> {
+        self.exchange_api.active_streams_for(symbol).await
+    }
+
+    pub async fn active_streams_of_type(&self, stream_type: StreamType) -> Vec<StreamMeta> {
+        self.exchange_api.active_streams_of_type(stream_type).await
+    }
+
     pub async fn open_stream(
         &self,
         stream_type: StreamType,
         symbol: &str,
-        interval: Option<&str>,
-    ) -> ApiResult<String> {
-        self.exchange_api
-            .open_stream(stream_type, symbol, interval)
+        interval: Option<Interval>,
+    ) -> ApiResult<StreamId> {
+        open_validated_stream(
+            &self.exchange_api,
+            &self.metadata_cache,
+            stream_type,
+            symbol,
+            interval,
+        )
+        .await
+    }
+
+    /// Backfill history of kline data before opening live kline stream,
+    /// returns once warmup klines are added to market data
+    pub async fn subscribe_with_warmup(
+        &self,
+        symbol: &str,
+        interval: Interval,
+        warmup_bars: usize,
+    ) -> ApiResult<StreamId> {
+        let stream_id = build_stream_id(symbol, Some(interval.as_str()));
+
+        // duplicate subscription is no-op, return existing stream
+        if let Some(active_stream) = self
+            .active_streams()
+            .await
+            .into_iter()
+            .find(|meta| meta.id == stream_id && meta.status != "closed")
+        {
+            self.add_needed_stream(symbol, StreamType::Kline, Some(interval))
+                .await;
+            return Ok(active_stream.id);
+        }
+
+        let mut klines = self
+            .exchange_api
+            .get_klines(symbol, interval, warmup_bars)
+            .await?;
+        // klines are only appended to market data in open time order
+        klines.sort_by_key(|kline| kline.open_time);
+
+        {
+            let mut market_data = self.data.lock().await;
+            for kline in klines {
+                market_data.add_kline(kline);
+            }
+        }
+
+        self.add_needed_stream(symbol, StreamType::Kline, Some(interval))
+            .await;
+
+        self.open_stream(StreamType::Kline, symbol, Some(interval))
+            .await
+    }
+
+    /// Fetch klines between from and to timestamps from exchange
+    /// and save to storage, returns number of klines saved
+    pub async fn backfill_klines(
+        &self,
+        symbol: &str,
+        interval: Interval,
+        from_ts: u64,
+        to_ts: u64,
+    ) -> ApiResult<usize> {
+        let mut klines = self
+            .exchange_api
+            .get_klines_range(symbol, interval, from_ts, to_ts)
+            .await?;
+        klines.sort_by_key(|kline| kline.open_time);
+        klines.dedup_by_key(|kline| kline.open_time);
+
+        self.data
+            .lock()
+            .await
+            .save_klines(symbol, interval, &klines)?;
+
+        Ok(klines.len())
+    }
+
+    /// Fetch klines missing from storage between from and to timestamps,
+    /// returns number of klines saved
+    pub async fn backfill_gaps(
+        &self,
+        symbol: &str,
+        interval: Interval,
+        from_ts: u64,
+        to_ts: u64,
+    ) -> ApiResult<usize> {
+        let gaps = self
+            .data
+            .lock()
             .await
+            .find_kline_gaps(symbol, interval, from_ts, to_ts)?;
+
+        let mut saved = 0;
+        for (gap_start, gap_end) in gaps {
+            info!(
+                "Backfilling {symbol} {} klines from {gap_start} to {gap_end}",
+                interval.as_str()
+            );
+            saved += self
+                .backfill_klines(symbol, interval, gap_start, gap_end)
+                .await?;
+        }
+
+        Ok(saved)
     }
 
-    pub async fn close_stream(&self, stream_id: &str) -> Option<StreamMeta> {
+    pub async fn close_stream(&self, stream_id: &StreamId) -> Option<StreamMeta> {
         self.exchange_api.close_stream(stream_id).await
     }
 
+    pub async fn needed_streams(&self) -> Vec<StreamMeta> {
+        self.needed_streams.lock().await.clone()
+    }
+
+    /// Stop background tasks and close all active streams,
+    /// returns once all tasks have finished
+    pub async fn shutdown(&self) {
+        let task_handles: Vec<JoinHandle<()>> = self.task_handles.lock().await.drain(..).collect();
+
+        for handle in task_handles {
+            handle.abort();
+            // aborted task returns cancelled error, only completion is needed
+            let _ = handle.await;
+        }
+
+        self.exchange_api
+            .get_stream_manager()
+            .lock()
+            .await
+            .close_all_streams()
+            .await;
+    }
+
     // ---
     // Init methods
     // ---
 
-    async fn init(&self) {
-        // Add initial needed streams
-        self.add_needed_stream("BTC-USDT", StreamType::Ticker, None)
-            .await;
+    async fn init(&self, config: MarketConfig) {
+        // Add initial needed streams, default to BTC ticker if none configured
+        if config.streams.is_empty() {
+            self.add_needed_stream("BTC-USDT", StreamType::Ticker, None)
+                .await;
+        }
+
+        for (symbol, stream_type, interval) in config.streams {
+            self.add_needed_stream(&symbol, stream_type, interval).await;
+        }
+
+        let mut task_handles = vec![
+            self.init_market_receivers().await,
+            self.init_active_stream_monitor().await,
+        ];
+
+        if let Some(sync_interval) = config.server_time_sync_interval {
+            task_handles.push(self.init_server_time_sync(sync_interval).await);
+        }
+
+        self.task_handles.lock().await.extend(task_handles);
+    }
+
+    // periodically correct signing timestamps for drift of local clock
+    async fn init_server_time_sync(&self, sync_interval: Duration) -> JoinHandle<()> {
+        let exchange_api = self.exchange_api.clone();
 
-        self.init_market_receivers().await;
-        self.init_active_stream_monitor().await;
+        tokio::spawn(async move {
+            loop {
+                if let Err(e) = exchange_api.sync_server_time().await {
+                    warn!("Unable to sync {} server time: {e}", exchange_api.name());
+                }
+
+                tokio::time::sleep(sync_interval).await;
+            }
+        })
     }
 
-    async fn init_market_receivers(&self) {
+    async fn init_market_receivers(&self) -> JoinHandle<()> {
         let market_receiver = self.market_receiver.clone();
         let market_data = self.data.clone();
+        let market_broadcast = self.market_broadcast.clone();
 
         // let active_streams = self.active_streams.clone();
 
         // spawn thread to handle stream_manager messages
         tokio::spawn(async move {
+            let mut closed_kline_tracker = ClosedKlineTracker::default();
+
             while let Some(message) = market_receiver.lock().await.recv().await {
-                // println!("{message:?}");
+                // send to subscribers before storing, error only if no subscribers
+                let _ = market_broadcast.send(message.clone());
 
                 match message {
                     MarketMessage::UpdateKline(kline) => {
+                        for closed_kline in closed_kline_tracker.update(&kline) {
+                            let _ = market_broadcast.send(MarketMessage::KlineClosed(closed_kline));
+                        }
+                        market_data.lock().await.add_kline(kline);
+                    }
+                    MarketMessage::KlineClosed(kline) => {
+                        // already sent to subscribers, only keep tracker up to date
+                        let _ = closed_kline_tracker.update(&kline);
                         market_data.lock().await.add_kline(kline);
                     }
                     MarketMessage::UpdateTicker(ticker) => {
@@ -338,69 +936,1355 @@ impl Market {
                     }
                 }
             }
-        });
+        })
     }
 
-    async fn init_active_stream_monitor(&self) {
-        let stream_manager = self.exchange_api.get_stream_manager();
+    async fn init_active_stream_monitor(&self) -> JoinHandle<()> {
         let exchange_api = self.exchange_api.clone();
+        let metadata_cache = self.metadata_cache.clone();
         let needed_streams = self.needed_streams.clone();
+        let stream_backoffs = self.stream_backoffs.clone();
 
         tokio::spawn(async move {
             loop {
-                tokio::time::sleep(Duration::from_secs(3)).await;
-                let active_streams = stream_manager.lock().await.active_streams().await;
-                for needed_stream_meta in needed_streams.lock().await.iter() {
-                    let active_stream_meta = active_streams
-                        .iter()
-                        .find(|&meta| meta.symbol == needed_stream_meta.symbol);
-
-                    match active_stream_meta {
-                        Some(_meta) => {
-                            continue;
-                        }
-                        None => {
-                            let need_stream = needed_stream_meta.clone();
-
-                            let _ = exchange_api
-                                .open_stream(
-                                    needed_stream_meta.stream_type.clone(),
-                                    &needed_stream_meta.symbol,
-                                    need_stream.interval.as_deref(),
-                                )
-                                .await;
-                        }
+                tokio::time::sleep(Duration::from_secs(1)).await;
+                let active_streams = exchange_api.active_streams().await;
+                let needed_streams = needed_streams.lock().await.clone();
+
+                // find streams to reopen, locks are released before streams are
+                // opened so market is not blocked while exchange is reached
+                let reopen_streams = streams_to_reopen(
+                    needed_streams,
+                    &active_streams,
+                    &mut *stream_backoffs.lock().await,
+                    Instant::now(),
+                );
+
+                for stream_meta in reopen_streams {
+                    // interval of needed stream is built from Interval when stream is added
+                    let interval = stream_meta
+                        .interval
+                        .as_deref()
+                        .and_then(|interval| interval.parse::<Interval>().ok());
+
+                    if let Err(e) = open_validated_stream(
+                        &exchange_api,
+                        &metadata_cache,
+                        stream_meta.stream_type.clone(),
+                        &stream_meta.symbol,
+                        interval,
+                    )
+                    .await
+                    {
+                        warn!(
+                            "Unable to open {} stream {}: {e}",
+                            exchange_api.name(),
+                            stream_meta.id
+                        );
                     }
                 }
             }
-        });
+        })
     }
 
     pub async fn add_needed_stream(
         &self,
         symbol: &str,
         stream_type: StreamType,
-        interval: Option<&str>,
-    ) {
+        interval: Option<Interval>,
+    ) -> StreamId {
         let mut needed_streams = self.needed_streams.lock().await;
+        let stream_id = build_stream_id(symbol, interval.map(|interval| interval.as_str()));
+
+        // stream already needed, avoid processing same data twice
+        if needed_streams.iter().any(|meta| meta.id == stream_id) {
+            return stream_id;
+        }
+
         let url = self
             .exchange_api
-            .build_stream_url(symbol, stream_type, interval);
-        let stream_id = build_stream_id(symbol, interval);
-        let btc_stream_meta = StreamMeta::new(&stream_id, &url, symbol, StreamType::Ticker, None);
-
-        needed_streams.push(btc_stream_meta);
+            .build_stream_url(symbol, stream_type.clone(), interval);
+        let stream_meta = StreamMeta::new_at(
+            stream_id.clone(),
+            &url,
+            symbol,
+            stream_type,
+            interval.map(|interval| interval.to_string()),
+            self.exchange_api.clock().now(),
+        );
+
+        needed_streams.push(stream_meta);
+
+        stream_id
     }
 
     pub async fn remove_needed_stream(
         &self,
         symbol: &str,
         _stream_type: StreamType,
-        interval: Option<&str>,
+        interval: Option<Interval>,
     ) {
-        let mut needed_streams = self.needed_streams.lock().await;
-        let stream_id = build_stream_id(symbol, interval);
+        let stream_id = build_stream_id(symbol, interval.map(|interval| interval.as_str()));
+
+        self.needed_streams
+            .lock()
+            .await
+            .retain(|x| x.id != stream_id);
+
+        // close live stream so data stops once stream no longer needed
+        let active_stream = self
+            .active_streams()
+            .await
+            .into_iter()
+            .find(|meta| meta.id == stream_id);
+
+        if let Some(active_stream) = active_stream {
+            self.close_stream(&active_stream.id).await;
+        }
+    }
+
+    // ---
+    // Interval &str shims, parse interval before delegating
+    // ---
+
+    pub async fn kline_data_str(
+        &self,
+        symbol: &str,
+        interval: &str,
+        from_ts: Option<u64>,
+        to_ts: Option<u64>,
+        limit: Option<usize>,
+    ) -> ApiResult<Option<KlineData>> {
+        self.kline_data(symbol, interval.parse()?, from_ts, to_ts, limit)
+            .await
+    }
+
+    pub async fn subscribe_with_warmup_str(
+        &self,
+        symbol: &str,
+        interval: &str,
+        warmup_bars: usize,
+    ) -> ApiResult<StreamId> {
+        self.subscribe_with_warmup(symbol, interval.parse()?, warmup_bars)
+            .await
+    }
+
+    pub async fn backfill_klines_str(
+        &self,
+        symbol: &str,
+        interval: &str,
+        from_ts: u64,
+        to_ts: u64,
+    ) -> ApiResult<usize> {
+        self.backfill_klines(symbol, interval.parse()?, from_ts, to_ts)
+            .await
+    }
+
+    pub async fn add_needed_stream_str(
+        &self,
+        symbol: &str,
+        stream_type: StreamType,
+        interval: Option<&str>,
+    ) -> ApiResult<StreamId> {
+        let interval = interval.map(str::parse).transpose()?;
+
+        Ok(self.add_needed_stream(symbol, stream_type, interval).await)
+    }
+
+    pub async fn remove_needed_stream_str(
+        &self,
+        symbol: &str,
+        stream_type: StreamType,
+        interval: Option<&str>,
+    ) -> ApiResult<()> {
+        let interval = interval.map(str::parse).transpose()?;
+        self.remove_needed_stream(symbol, stream_type, interval)
+            .await;
+
+        Ok(())
+    }
+}
+
+// symbol is checked against exchange symbols before stream is opened
+async fn open_validated_stream(
+    exchange_api: &Arc<Box<dyn ExchangeApi>>,
+    metadata_cache: &MetadataCache,
+    stream_type: StreamType,
+    symbol: &str,
+    interval: Option<Interval>,
+) -> ApiResult<StreamId> {
+    metadata_cache.validate_symbol(symbol).await?;
+
+    exchange_api
+        .open_stream(stream_type, symbol, interval)
+        .await
+}
+
+#[cfg(test)]
+mod tests {
+    use futures_util::{SinkExt, StreamExt};
+    use serde_json::json;
+    use tempfile::TempDir;
+    use tokio::net::TcpListener;
+    use tokio::sync::oneshot;
+    use tokio_tungstenite::{accept_async, tungstenite::Message};
+
+    use super::*;
+    use crate::exchange::bingx::BingXApi;
+    use crate::exchange::mock::MockExchange;
+    use crate::exchange::paper::PaperExchange;
+    use crate::exchange::types::ExchangeError;
+    use crate::utils::channel::{build_arc_channel, DEFAULT_CHANNEL_CAPACITY};
+    use crate::utils::clock::MockClock;
+
+    // 2023-11-14T22:13:00Z, start of minute
+    const BASE_TS: u64 = 1_700_000_000_000 - 1_700_000_000_000 % MINUTE;
+    const MINUTE: u64 = 60_000;
+
+    fn test_market_data() -> (TempDir, MarketData) {
+        let dir = tempfile::tempdir().unwrap();
+        let market_data = MarketData::new(
+            StorageManager::new(dir.path().to_path_buf()),
+            None,
+            system_clock(),
+        );
+
+        (dir, market_data)
+    }
+
+    fn test_market_data_with_backup(
+        backup_interval: Option<Duration>,
+    ) -> (TempDir, Arc<MockClock>, MarketData) {
+        let dir = tempfile::tempdir().unwrap();
+        let clock = Arc::new(MockClock::new(BASE_TS));
+        let market_data = MarketData::new(
+            StorageManager::new(dir.path().to_path_buf()),
+            backup_interval,
+            clock.clone(),
+        );
+
+        (dir, clock, market_data)
+    }
+
+    fn memory_kline_len(market_data: &MarketData, symbol: &str) -> usize {
+        market_data
+            .all_klines
+            .get(&MarketData::build_kline_key(symbol, "1m"))
+            .map_or(0, |kline_data| kline_data.klines.len())
+    }
+
+    // market on BingX exchange storing klines in temp directory
+    async fn test_market() -> (TempDir, Market) {
+        let config = MarketConfig {
+            backup_interval: None,
+            ..MarketConfig::default()
+        };
+
+        test_market_with_config(config).await
+    }
+
+    async fn test_market_with_config(config: MarketConfig) -> (TempDir, Market) {
+        let dir = tempfile::tempdir().unwrap();
+        let storage_manager = StorageManager::new(dir.path().to_path_buf());
+        let (market_sender, market_receiver) = build_arc_channel(DEFAULT_CHANNEL_CAPACITY);
+
+        let exchange_api: Arc<Box<dyn ExchangeApi>> = Arc::new(Box::new(
+            BingXApi::new("api_key", "secret_key", market_sender).unwrap(),
+        ));
+
+        let market = Market::new(market_receiver, exchange_api, storage_manager, config).await;
+
+        (dir, market)
+    }
+
+    fn test_kline(open_time: u64) -> Kline {
+        Kline {
+            symbol: "BTC-USDT".to_string(),
+            interval: "1m".to_string(),
+            open_time,
+            close_time: open_time + 59_999,
+            ..Default::default()
+        }
+    }
+
+    fn store_replay_klines(dir: &TempDir, symbol: &str, len: u64) {
+        let klines: Vec<Kline> = (0..len)
+            .map(|i| Kline {
+                symbol: symbol.to_string(),
+                ..test_kline(BASE_TS + i * MINUTE)
+            })
+            .collect();
+
+        StorageManager::new(dir.path().to_path_buf())
+            .save_klines(&klines, &MarketData::build_kline_key(symbol, "1m"))
+            .unwrap();
+    }
+
+    fn open_times(kline_data: &KlineData) -> Vec<u64> {
+        kline_data
+            .klines
+            .iter()
+            .map(|kline| kline.open_time)
+            .collect()
+    }
+
+    #[test]
+    fn kline_data_limit_returns_latest_klines() {
+        let (_dir, mut market_data) = test_market_data();
+        for open_time in 1..=10 {
+            market_data.add_kline(test_kline(open_time));
+        }
+
+        let kline_data = market_data
+            .kline_data("BTC-USDT", Interval::Min1, None, None, Some(3))
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(open_times(&kline_data), vec![8, 9, 10]);
+    }
+
+    #[test]
+    fn kline_data_limit_counts_back_across_months_on_disk() {
+        let (_dir, mut market_data) = test_market_data();
+        // 2023-11-01T00:00:00Z, klines either side of month boundary
+        let month_start = 1_698_796_800_000;
+        let disk_klines: Vec<Kline> = (0..6)
+            .map(|i| test_kline(month_start - 3 * MINUTE + i * MINUTE))
+            .collect();
+        market_data
+            .save_klines("BTC-USDT", Interval::Min1, &disk_klines)
+            .unwrap();
+
+        let kline_data = market_data
+            .kline_data("BTC-USDT", Interval::Min1, None, None, Some(5))
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(
+            open_times(&kline_data),
+            vec![
+                month_start - 2 * MINUTE,
+                month_start - MINUTE,
+                month_start,
+                month_start + MINUTE,
+                month_start + 2 * MINUTE,
+            ]
+        );
+    }
+
+    #[test]
+    fn kline_data_dedupes_disk_and_memory_klines() {
+        let (_dir, mut market_data) = test_market_data();
+        let disk_klines: Vec<Kline> = (0..3).map(|i| test_kline(BASE_TS + i * MINUTE)).collect();
+        market_data
+            .storage_manager
+            .save_klines(&disk_klines, &MarketData::build_kline_key("BTC-USDT", "1m"))
+            .unwrap();
+
+        // last saved kline is updated in memory after backup flush
+        let mut updated_kline = test_kline(BASE_TS + 2 * MINUTE);
+        updated_kline.close = 2.0;
+        market_data.add_kline(updated_kline);
+        market_data.add_kline(test_kline(BASE_TS + 3 * MINUTE));
+
+        let kline_data = market_data
+            .kline_data(
+                "BTC-USDT",
+                Interval::Min1,
+                Some(BASE_TS),
+                Some(BASE_TS + 3 * MINUTE),
+                None,
+            )
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(
+            open_times(&kline_data),
+            vec![
+                BASE_TS,
+                BASE_TS + MINUTE,
+                BASE_TS + 2 * MINUTE,
+                BASE_TS + 3 * MINUTE
+            ]
+        );
+        assert_eq!(kline_data.klines[2].close, 2.0);
+    }
+
+    fn test_stream_meta(symbol: &str, interval: &str) -> StreamMeta {
+        StreamMeta::new_at(
+            build_stream_id(symbol, Some(interval)),
+            "ws://localhost",
+            symbol,
+            StreamType::Kline,
+            Some(interval.to_string()),
+            BASE_TS,
+        )
+    }
+
+    #[test]
+    fn stream_backoff_grows_on_failures_and_resets_after_message() {
+        let needed_stream = test_stream_meta("BTC-USDT", "1m");
+        let mut stream_backoffs = HashMap::new();
+        let mut now = Instant::now();
+
+        // stream fails to open every attempt, delay doubles each time
+        let mut delays = vec![];
+        for _ in 0..8 {
+            let reopen =
+                streams_to_reopen(vec![needed_stream.clone()], &[], &mut stream_backoffs, now);
+            assert_eq!(reopen.len(), 1);
+
+            let backoff = stream_backoffs.get(&needed_stream.id).unwrap();
+            delays.push(backoff.next_attempt - now);
+
+            // not reopened again before delay has passed
+            let reopen = streams_to_reopen(
+                vec![needed_stream.clone()],
+                &[],
+                &mut stream_backoffs,
+                now + Duration::from_millis(500),
+            );
+            assert!(reopen.is_empty());
+
+            now = stream_backoffs.get(&needed_stream.id).unwrap().next_attempt;
+        }
+        assert_eq!(
+            delays,
+            [1, 2, 4, 8, 16, 32, 60, 60]
+                .map(Duration::from_secs)
+                .to_vec()
+        );
+
+        // open stream without messages keeps backoff
+        let mut active_stream = needed_stream.clone();
+        streams_to_reopen(
+            vec![needed_stream.clone()],
+            &[active_stream.clone()],
+            &mut stream_backoffs,
+            now,
+        );
+        assert!(stream_backoffs.contains_key(&needed_stream.id));
+
+        // backoff reset once stream receives message
+        active_stream.last_update = active_stream.started_time + 1;
+        let reopen = streams_to_reopen(
+            vec![needed_stream.clone()],
+            &[active_stream],
+            &mut stream_backoffs,
+            now,
+        );
+        assert!(reopen.is_empty());
+        assert!(!stream_backoffs.contains_key(&needed_stream.id));
+
+        // next failure starts from base delay
+        streams_to_reopen(vec![needed_stream.clone()], &[], &mut stream_backoffs, now);
+        assert_eq!(
+            stream_backoffs.get(&needed_stream.id).unwrap().next_attempt - now,
+            RECONNECT_BASE_DELAY
+        );
+    }
+
+    #[tokio::test]
+    async fn add_needed_stream_keeps_stream_type_and_interval() {
+        let (_dir, market) = test_market().await;
+
+        market
+            .add_needed_stream("BTC-USDT", StreamType::Kline, Some(Interval::Min1))
+            .await;
+        let needed_streams = market.needed_streams().await;
+
+        let stream_id = build_stream_id("BTC-USDT", Some("1m"));
+        let stream_meta = needed_streams
+            .iter()
+            .find(|meta| meta.id == stream_id)
+            .unwrap();
+        assert!(matches!(stream_meta.stream_type, StreamType::Kline));
+        assert_eq!(stream_meta.interval.as_deref(), Some("1m"));
+        assert_eq!(stream_meta.symbol, "BTC-USDT");
+    }
+
+    #[tokio::test]
+    async fn str_interval_shims_parse_interval() {
+        let (_dir, market) = test_market().await;
+
+        let stream_id = market
+            .add_needed_stream_str("BTC-USDT", StreamType::Kline, Some("1m"))
+            .await
+            .unwrap();
+        assert_eq!(stream_id, build_stream_id("BTC-USDT", Some("1m")));
+
+        let result = market
+            .add_needed_stream_str("BTC-USDT", StreamType::Kline, Some("2w"))
+            .await;
+        assert!(matches!(result, Err(ExchangeError::Parsing(_))));
+
+        market
+            .data
+            .lock()
+            .await
+            .save_klines_str("BTC-USDT", "1m", &[test_kline(BASE_TS)])
+            .unwrap();
+        let kline_data = market
+            .kline_data_str(
+                "BTC-USDT",
+                "1m",
+                Some(BASE_TS),
+                Some(BASE_TS + MINUTE),
+                None,
+            )
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(open_times(&kline_data), vec![BASE_TS]);
+    }
+
+    #[tokio::test]
+    async fn remove_needed_stream_closes_live_stream() {
+        let (_dir, market) = test_market().await;
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let ws_uri = format!("ws://{}", listener.local_addr().unwrap());
+        let (closed_sender, closed_receiver) = oneshot::channel::<()>();
+
+        // send klines until client closes connection
+        tokio::spawn(async move {
+            let (tcp_stream, _) = listener.accept().await.unwrap();
+            let mut ws_stream = accept_async(tcp_stream).await.unwrap();
+            ws_stream.next().await.unwrap().unwrap();
+
+            for i in 0.. {
+                let kline = json!({
+                    "code": 0,
+                    "dataType": "ETH-USDT@kline_1m",
+                    "s": "ETH-USDT",
+                    "data": [{
+                        "T": BASE_TS + i * MINUTE + 59_999,
+                        "o": "100.0",
+                        "h": "110.0",
+                        "l": "90.0",
+                        "c": "105.0",
+                        "v": "12.5"
+                    }]
+                });
+                if ws_stream
+                    .send(Message::Text(kline.to_string()))
+                    .await
+                    .is_err()
+                {
+                    break;
+                }
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+
+            closed_sender.send(()).unwrap();
+        });
+
+        market
+            .add_needed_stream("ETH-USDT", StreamType::Kline, Some(Interval::Min1))
+            .await;
+        let stream_id = build_stream_id("ETH-USDT", Some("1m"));
+        let stream_meta = StreamMeta::new(
+            stream_id.clone(),
+            &ws_uri,
+            "ETH-USDT",
+            StreamType::Kline,
+            Some("1m".to_string()),
+        );
+        market
+            .exchange_api
+            .get_stream_manager()
+            .lock()
+            .await
+            .open_stream(stream_meta)
+            .await
+            .unwrap();
+
+        // wait for stream to send data
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while market
+                .kline_data("ETH-USDT", Interval::Min1, None, None, None)
+                .await
+                .unwrap()
+                .is_none()
+            {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .unwrap();
+
+        market
+            .remove_needed_stream("ETH-USDT", StreamType::Kline, Some(Interval::Min1))
+            .await;
+        assert!(!market
+            .active_streams()
+            .await
+            .iter()
+            .any(|meta| meta.id == stream_id));
+
+        // stream task aborted, connection to exchange closed
+        tokio::time::timeout(Duration::from_secs(5), closed_receiver)
+            .await
+            .unwrap()
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn subscribe_with_warmup_adds_history_before_live_klines() {
+        let dir = tempfile::tempdir().unwrap();
+        let (market_sender, market_receiver) = build_arc_channel(DEFAULT_CHANNEL_CAPACITY);
+        let mut exchange = MockExchange::new();
+        exchange.klines = (0..5).map(|i| test_kline(BASE_TS + i * MINUTE)).collect();
+        let exchange_api: Arc<Box<dyn ExchangeApi>> = Arc::new(Box::new(exchange));
+        let market = Market::new(
+            market_receiver,
+            exchange_api,
+            StorageManager::new(dir.path().to_path_buf()),
+            MarketConfig::default(),
+        )
+        .await;
+
+        market
+            .subscribe_with_warmup("BTC-USDT", Interval::Min1, 5)
+            .await
+            .unwrap();
+
+        let warmup_open_times: Vec<u64> = (0..5).map(|i| BASE_TS + i * MINUTE).collect();
+        let kline_data = market
+            .kline_data("BTC-USDT", Interval::Min1, None, None, None)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(open_times(&kline_data), warmup_open_times);
+        let stream_id = build_stream_id("BTC-USDT", Some("1m"));
+        assert!(market
+            .needed_streams()
+            .await
+            .iter()
+            .any(|meta| meta.id == stream_id));
+
+        // live kline from stream is added after warmup klines
+        market_sender
+            .send(MarketMessage::UpdateKline(test_kline(BASE_TS + 5 * MINUTE)))
+            .unwrap();
+        let kline_data = tokio::time::timeout(Duration::from_secs(5), async {
+            loop {
+                let kline_data = market
+                    .kline_data("BTC-USDT", Interval::Min1, None, None, None)
+                    .await
+                    .unwrap()
+                    .unwrap();
+                if kline_data.klines.len() > 5 {
+                    return kline_data;
+                }
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .unwrap();
+        let mut expected_open_times = warmup_open_times;
+        expected_open_times.push(BASE_TS + 5 * MINUTE);
+        assert_eq!(open_times(&kline_data), expected_open_times);
+    }
+
+    #[tokio::test]
+    async fn duplicate_subscription_returns_existing_stream() {
+        let (_dir, market) = test_market().await;
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let ws_uri = format!("ws://{}", listener.local_addr().unwrap());
+
+        // keep connection open until client closes it
+        tokio::spawn(async move {
+            let (tcp_stream, _) = listener.accept().await.unwrap();
+            let mut ws_stream = accept_async(tcp_stream).await.unwrap();
+            while ws_stream.next().await.is_some() {}
+        });
+
+        let stream_id = market
+            .add_needed_stream("ETH-USDT", StreamType::Kline, Some(Interval::Min1))
+            .await;
+        let duplicate_id = market
+            .add_needed_stream("ETH-USDT", StreamType::Kline, Some(Interval::Min1))
+            .await;
+        assert_eq!(duplicate_id, stream_id);
+
+        let stream_meta = StreamMeta::new(
+            stream_id.clone(),
+            &ws_uri,
+            "ETH-USDT",
+            StreamType::Kline,
+            Some("1m".to_string()),
+        );
+        market
+            .exchange_api
+            .get_stream_manager()
+            .lock()
+            .await
+            .open_stream(stream_meta)
+            .await
+            .unwrap();
+
+        // stream already open, no warmup klines requested from exchange
+        let subscribed_id = market
+            .subscribe_with_warmup("ETH-USDT", Interval::Min1, 5)
+            .await
+            .unwrap();
+        let duplicate_subscribed_id = market
+            .subscribe_with_warmup("ETH-USDT", Interval::Min1, 5)
+            .await
+            .unwrap();
+        assert_eq!(subscribed_id, stream_id);
+        assert_eq!(duplicate_subscribed_id, stream_id);
+
+        let needed_streams = market.needed_streams().await;
+        let active_streams = market.active_streams().await;
+        assert_eq!(
+            needed_streams
+                .iter()
+                .filter(|meta| meta.id == stream_id)
+                .count(),
+            1
+        );
+        assert_eq!(
+            active_streams
+                .iter()
+                .filter(|meta| meta.id == stream_id)
+                .count(),
+            1
+        );
+    }
+
+    #[tokio::test]
+    async fn configured_streams_are_needed() {
+        let config = MarketConfig {
+            streams: vec![
+                ("BTC-USDT".to_string(), StreamType::Ticker, None),
+                (
+                    "ETH-USDT".to_string(),
+                    StreamType::Kline,
+                    Some(Interval::Min1),
+                ),
+                (
+                    "SOL-USDT".to_string(),
+                    StreamType::Kline,
+                    Some(Interval::Hour1),
+                ),
+            ],
+            backup_interval: None,
+            ..MarketConfig::default()
+        };
+        let (_dir, market) = test_market_with_config(config).await;
+
+        let needed_streams = market.needed_streams().await;
+
+        let needed: Vec<(&str, Option<&str>)> = needed_streams
+            .iter()
+            .map(|meta| (meta.symbol.as_str(), meta.interval.as_deref()))
+            .collect();
+        assert_eq!(
+            needed,
+            vec![
+                ("BTC-USDT", None),
+                ("ETH-USDT", Some("1m")),
+                ("SOL-USDT", Some("1h")),
+            ]
+        );
+        assert!(matches!(needed_streams[0].stream_type, StreamType::Ticker));
+        assert!(matches!(needed_streams[1].stream_type, StreamType::Kline));
+        assert!(matches!(needed_streams[2].stream_type, StreamType::Kline));
+    }
+
+    #[tokio::test]
+    async fn btc_ticker_is_needed_when_no_streams_configured() {
+        let (_dir, market) = test_market().await;
+
+        let needed_streams = market.needed_streams().await;
+
+        assert_eq!(needed_streams.len(), 1);
+        assert_eq!(needed_streams[0].symbol, "BTC-USDT");
+        assert!(matches!(needed_streams[0].stream_type, StreamType::Ticker));
+    }
+
+    #[test]
+    fn klines_flushed_once_backup_interval_elapsed() {
+        let (_dir, clock, mut market_data) =
+            test_market_data_with_backup(Some(Duration::from_secs(60)));
+        let kline_key = MarketData::build_kline_key("BTC-USDT", "1m");
+        let filename = MarketData::build_kline_filename(&kline_key, BASE_TS);
+
+        market_data.add_kline(test_kline(BASE_TS));
+        clock.advance(Duration::from_secs(59));
+        market_data.add_kline(test_kline(BASE_TS + MINUTE));
+
+        // not flushed before backup interval
+        assert_eq!(memory_kline_len(&market_data, "BTC-USDT"), 2);
+        assert!(market_data
+            .storage_manager
+            .load_klines(&filename)
+            .unwrap()
+            .is_none());
+
+        clock.advance(Duration::from_secs(1));
+        market_data.add_kline(test_kline(BASE_TS + 2 * MINUTE));
+
+        assert_eq!(memory_kline_len(&market_data, "BTC-USDT"), 0);
+        let saved = market_data
+            .storage_manager
+            .load_klines(&filename)
+            .unwrap()
+            .unwrap();
+        assert_eq!(saved.len(), 3);
+    }
+
+    #[test]
+    fn klines_never_flushed_without_backup_interval() {
+        let (_dir, clock, mut market_data) = test_market_data_with_backup(None);
+        let kline_key = MarketData::build_kline_key("BTC-USDT", "1m");
+        let filename = MarketData::build_kline_filename(&kline_key, BASE_TS);
+
+        for i in 0..3 {
+            market_data.add_kline(test_kline(BASE_TS + i * MINUTE));
+            clock.advance(Duration::from_secs(60 * 60));
+        }
+
+        assert_eq!(memory_kline_len(&market_data, "BTC-USDT"), 3);
+        assert!(market_data
+            .storage_manager
+            .load_klines(&filename)
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn failed_backup_keeps_klines_until_saved() {
+        let dir = tempfile::tempdir().unwrap();
+        // storage root is a file, so saving klines of every key fails
+        let storage_root = dir.path().join("storage");
+        std::fs::write(&storage_root, "").unwrap();
+        let clock = Arc::new(MockClock::new(BASE_TS));
+        let mut market_data = MarketData::new(
+            StorageManager::new(storage_root),
+            Some(Duration::from_secs(60)),
+            clock.clone(),
+        );
+
+        market_data.add_kline(Kline {
+            symbol: "ETH-USDT".to_string(),
+            ..test_kline(BASE_TS)
+        });
+        market_data.add_kline(test_kline(BASE_TS));
+
+        clock.advance(Duration::from_secs(60));
+        market_data.add_kline(test_kline(BASE_TS + MINUTE));
+
+        assert_eq!(memory_kline_len(&market_data, "ETH-USDT"), 1);
+        assert_eq!(memory_kline_len(&market_data, "BTC-USDT"), 2);
+
+        // klines kept after failed backup are saved once storage works
+        market_data.storage_manager = StorageManager::new(dir.path().join("data"));
+        clock.advance(Duration::from_secs(60));
+        market_data.add_kline(test_kline(BASE_TS + 2 * MINUTE));
+
+        assert_eq!(memory_kline_len(&market_data, "ETH-USDT"), 0);
+        assert_eq!(memory_kline_len(&market_data, "BTC-USDT"), 0);
+
+        let kline_key = MarketData::build_kline_key("BTC-USDT", "1m");
+        let saved = market_data
+            .storage_manager
+            .load_klines(&MarketData::build_kline_filename(&kline_key, BASE_TS))
+            .unwrap()
+            .unwrap();
+        assert_eq!(saved.len(), 3);
+    }
+
+    #[test]
+    fn memory_klines_capped_and_saved_with_long_backup_interval() {
+        let (_dir, _clock, mut market_data) =
+            test_market_data_with_backup(Some(Duration::from_secs(24 * 60 * 60)));
+        market_data.set_max_klines(10);
+
+        for i in 0..100 {
+            market_data.add_kline(test_kline(BASE_TS + i * MINUTE));
+            assert!(memory_kline_len(&market_data, "BTC-USDT") <= 10);
+        }
+
+        // klines dropped from memory were saved before backup interval
+        let kline_data = market_data
+            .kline_data(
+                "BTC-USDT",
+                Interval::Min1,
+                Some(BASE_TS),
+                Some(BASE_TS + 99 * MINUTE),
+                None,
+            )
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            open_times(&kline_data),
+            (0..100).map(|i| BASE_TS + i * MINUTE).collect::<Vec<u64>>()
+        );
+    }
+
+    #[test]
+    fn memory_klines_capped_without_backup_interval() {
+        let (_dir, _clock, mut market_data) = test_market_data_with_backup(None);
+        market_data.set_max_klines(10);
+
+        for i in 0..100 {
+            market_data.add_kline(test_kline(BASE_TS + i * MINUTE));
+            assert!(memory_kline_len(&market_data, "BTC-USDT") <= 10);
+        }
+
+        // oldest klines discarded, latest always kept
+        assert!(market_data.storage_manager.list_kline_symbols().is_empty());
+        assert_eq!(
+            market_data
+                .latest_kline("BTC-USDT", Interval::Min1)
+                .unwrap()
+                .open_time,
+            BASE_TS + 99 * MINUTE
+        );
+    }
+
+    #[test]
+    fn memory_klines_capped_when_saving_fails() {
+        let dir = tempfile::tempdir().unwrap();
+        // storage root is a file, so kline directories can't be created
+        let storage_root = dir.path().join("storage");
+        std::fs::write(&storage_root, "").unwrap();
+        let mut market_data = MarketData::new(
+            StorageManager::new(storage_root),
+            Some(Duration::from_secs(24 * 60 * 60)),
+            Arc::new(MockClock::new(BASE_TS)),
+        );
+        market_data.set_max_klines(10);
+
+        for i in 0..100 {
+            market_data.add_kline(test_kline(BASE_TS + i * MINUTE));
+            assert!(memory_kline_len(&market_data, "BTC-USDT") <= 10);
+        }
+    }
+
+    #[test]
+    fn latest_kline_kept_with_max_klines_of_one() {
+        let (_dir, _clock, mut market_data) = test_market_data_with_backup(None);
+        market_data.set_max_klines(1);
+
+        for i in 0..3 {
+            market_data.add_kline(test_kline(BASE_TS + i * MINUTE));
+            assert_eq!(memory_kline_len(&market_data, "BTC-USDT"), 1);
+        }
+
+        assert_eq!(
+            market_data
+                .latest_kline("BTC-USDT", Interval::Min1)
+                .unwrap()
+                .open_time,
+            BASE_TS + 2 * MINUTE
+        );
+    }
+
+    #[test]
+    fn kline_files_read_from_disk_once_for_two_year_range() {
+        let (dir, mut market_data) = test_market_data();
+        let kline_key = MarketData::build_kline_key("BTC-USDT", "1m");
+
+        // one kline at start of each month of 2022 and 2023
+        let month_open_times: Vec<u64> = (2022..=2023)
+            .flat_map(|year| (1..=12).map(move |month| (year, month)))
+            .map(|(year, month)| {
+                chrono::NaiveDate::from_ymd_opt(year, month, 1)
+                    .unwrap()
+                    .and_hms_opt(0, 0, 0)
+                    .unwrap()
+                    .and_utc()
+                    .timestamp_millis() as u64
+            })
+            .collect();
+        let klines: Vec<Kline> = month_open_times
+            .iter()
+            .map(|open_time| test_kline(*open_time))
+            .collect();
+        market_data
+            .storage_manager
+            .save_klines(&klines, &kline_key)
+            .unwrap();
+
+        let from_ts = month_open_times[0];
+        let to_ts = *month_open_times.last().unwrap() + MINUTE;
+        let kline_data = market_data
+            .kline_data("BTC-USDT", Interval::Min1, Some(from_ts), Some(to_ts), None)
+            .unwrap()
+            .unwrap();
+        assert_eq!(open_times(&kline_data), month_open_times);
+
+        // files removed from disk, so klines can only come from files already read
+        std::fs::remove_dir_all(dir.path().join("market/klines")).unwrap();
+
+        let kline_data = market_data
+            .kline_data("BTC-USDT", Interval::Min1, Some(from_ts), Some(to_ts), None)
+            .unwrap()
+            .unwrap();
+        assert_eq!(open_times(&kline_data), month_open_times);
+    }
+
+    #[test]
+    fn kline_data_returns_storage_errors() {
+        let (dir, mut market_data) = test_market_data();
+
+        // kline directory replaced by file, so kline files can't be opened
+        let kline_dir = dir.path().join("market/klines");
+        std::fs::create_dir_all(kline_dir.parent().unwrap()).unwrap();
+        std::fs::write(&kline_dir, "not a directory").unwrap();
+
+        let result = market_data.kline_data(
+            "BTC-USDT",
+            Interval::Min1,
+            Some(BASE_TS),
+            Some(BASE_TS + MINUTE),
+            None,
+        );
+        assert!(matches!(result, Err(ExchangeError::Io(_))));
+    }
+
+    #[test]
+    fn kline_data_is_none_without_klines() {
+        let (_dir, mut market_data) = test_market_data();
+
+        let kline_data = market_data
+            .kline_data(
+                "BTC-USDT",
+                Interval::Min1,
+                Some(BASE_TS),
+                Some(BASE_TS + MINUTE),
+                None,
+            )
+            .unwrap();
+        assert!(kline_data.is_none());
+    }
+
+    fn test_ticker(symbol: &str) -> Ticker {
+        Ticker {
+            symbol: symbol.to_string(),
+            last_price: 100.0,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn stale_ticker_is_not_fresh() {
+        let (_dir, clock, mut market_data) = test_market_data_with_backup(None);
+        let max_age = Duration::from_secs(10);
+
+        market_data.update_ticker(test_ticker("BTC-USDT"));
+        assert!(market_data.ticker_data_fresh("BTC-USDT", max_age).is_some());
+
+        clock.advance(Duration::from_secs(10));
+        assert!(market_data.ticker_data_fresh("BTC-USDT", max_age).is_some());
+
+        clock.advance(Duration::from_secs(1));
+        assert!(market_data.ticker_data_fresh("BTC-USDT", max_age).is_none());
+        // stale ticker still returned when no max age is given
+        assert!(market_data.ticker_data("BTC-USDT").is_some());
+    }
+
+    #[test]
+    fn expired_tickers_are_evicted_on_update() {
+        let (_dir, clock, mut market_data) = test_market_data_with_backup(None);
+        market_data.set_ticker_ttl(Some(Duration::from_secs(60)));
+
+        market_data.update_ticker(test_ticker("BTC-USDT"));
+        clock.advance(Duration::from_secs(61));
+        assert!(market_data.ticker_data("BTC-USDT").is_none());
+
+        market_data.update_ticker(test_ticker("ETH-USDT"));
+        assert!(!market_data
+            .all_tickers
+            .contains_key(&MarketData::build_ticker_key("BTC-USDT")));
+        assert!(market_data.ticker_data("ETH-USDT").is_some());
+    }
+
+    #[tokio::test]
+    async fn shutdown_stops_background_tasks_and_streams() {
+        let (_dir, market) = test_market().await;
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let ws_uri = format!("ws://{}", listener.local_addr().unwrap());
+
+        // keep connection open until client closes it
+        tokio::spawn(async move {
+            let (tcp_stream, _) = listener.accept().await.unwrap();
+            let mut ws_stream = accept_async(tcp_stream).await.unwrap();
+            while ws_stream.next().await.is_some() {}
+        });
+
+        let stream_meta = StreamMeta::new(
+            build_stream_id("ETH-USDT", Some("1m")),
+            &ws_uri,
+            "ETH-USDT",
+            StreamType::Kline,
+            Some("1m".to_string()),
+        );
+        market
+            .exchange_api
+            .get_stream_manager()
+            .lock()
+            .await
+            .open_stream(stream_meta)
+            .await
+            .unwrap();
+        assert_eq!(market.active_streams().await.len(), 1);
+
+        let abort_handles: Vec<_> = market
+            .task_handles
+            .lock()
+            .await
+            .iter()
+            .map(|handle| handle.abort_handle())
+            .collect();
+        assert_eq!(abort_handles.len(), 2);
+        assert!(abort_handles.iter().all(|handle| !handle.is_finished()));
+
+        market.shutdown().await;
+
+        assert!(abort_handles.iter().all(|handle| handle.is_finished()));
+        assert!(market.task_handles.lock().await.is_empty());
+        assert!(market.active_streams().await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn subscribers_receive_every_market_message() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage_manager = StorageManager::new(dir.path().to_path_buf());
+        let (market_sender, market_receiver) = build_arc_channel(DEFAULT_CHANNEL_CAPACITY);
+        let exchange_api: Arc<Box<dyn ExchangeApi>> = Arc::new(Box::new(MockExchange::new()));
+        let config = MarketConfig {
+            backup_interval: None,
+            ..MarketConfig::default()
+        };
+        let market = Market::new(market_receiver, exchange_api, storage_manager, config).await;
+        let mut market_broadcast = market.subscribe();
+
+        let ticker = Ticker {
+            symbol: "ETH-USDT".to_string(),
+            last_price: 2_000.0,
+            ..Default::default()
+        };
+        let kline = Kline {
+            symbol: "ETH-USDT".to_string(),
+            ..test_kline(BASE_TS)
+        };
+        market_sender
+            .send(MarketMessage::UpdateTicker(ticker))
+            .unwrap();
+        market_sender
+            .send(MarketMessage::UpdateKline(kline))
+            .unwrap();
+
+        let received = tokio::time::timeout(Duration::from_secs(5), async {
+            let mut received = vec![];
+            while received.len() < 2 {
+                // ignore messages of default BTC-USDT stream
+                match market_broadcast.recv().await.unwrap() {
+                    MarketMessage::UpdateTicker(ticker) if ticker.symbol == "ETH-USDT" => {
+                        received.push(MarketMessage::UpdateTicker(ticker))
+                    }
+                    MarketMessage::UpdateKline(kline) if kline.symbol == "ETH-USDT" => {
+                        received.push(MarketMessage::UpdateKline(kline))
+                    }
+                    _ => {}
+                }
+            }
+            received
+        })
+        .await
+        .unwrap();
+        market.shutdown().await;
+
+        assert!(matches!(
+            &received[0],
+            MarketMessage::UpdateTicker(ticker) if ticker.last_price == 2_000.0
+        ));
+        assert!(matches!(
+            &received[1],
+            MarketMessage::UpdateKline(kline) if kline.open_time == BASE_TS
+        ));
+    }
+
+    #[tokio::test]
+    async fn kline_closed_sent_once_to_subscribers() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage_manager = StorageManager::new(dir.path().to_path_buf());
+        let (market_sender, market_receiver) = build_arc_channel(DEFAULT_CHANNEL_CAPACITY);
+        let exchange_api: Arc<Box<dyn ExchangeApi>> = Arc::new(Box::new(MockExchange::new()));
+        let config = MarketConfig {
+            backup_interval: None,
+            ..MarketConfig::default()
+        };
+        let market = Market::new(market_receiver, exchange_api, storage_manager, config).await;
+        let mut market_broadcast = market.subscribe();
+
+        let kline = |open_time, close, is_closed| Kline {
+            symbol: "ETH-USDT".to_string(),
+            close,
+            is_closed,
+            ..test_kline(open_time)
+        };
+        // in progress updates, close flag sent twice, then next kline opens
+        for update in [
+            kline(BASE_TS, 100.0, false),
+            kline(BASE_TS, 101.0, false),
+            kline(BASE_TS, 102.0, true),
+            kline(BASE_TS, 102.0, true),
+            kline(BASE_TS + MINUTE, 103.0, false),
+        ] {
+            market_sender
+                .send(MarketMessage::UpdateKline(update))
+                .unwrap();
+        }
+
+        let closed = tokio::time::timeout(Duration::from_secs(5), async {
+            let mut closed = vec![];
+            loop {
+                // ignore messages of default BTC-USDT stream
+                match market_broadcast.recv().await.unwrap() {
+                    MarketMessage::KlineClosed(kline) if kline.symbol == "ETH-USDT" => {
+                        closed.push(kline)
+                    }
+                    MarketMessage::UpdateKline(kline)
+                        if kline.symbol == "ETH-USDT" && kline.open_time == BASE_TS + MINUTE =>
+                    {
+                        break
+                    }
+                    _ => {}
+                }
+            }
+            closed
+        })
+        .await
+        .unwrap();
+        market.shutdown().await;
+
+        assert_eq!(closed.len(), 1);
+        assert_eq!(closed[0].open_time, BASE_TS);
+        assert_eq!(closed[0].close, 102.0);
+    }
+
+    #[test]
+    fn latest_kline_is_freshest_in_memory_then_on_disk() {
+        let (dir, mut market_data) = test_market_data();
+        assert!(market_data
+            .latest_kline("BTC-USDT", Interval::Min1)
+            .is_none());
+
+        // added out of order, latest is highest open time
+        for open_time in [BASE_TS + 2 * MINUTE, BASE_TS + 5 * MINUTE, BASE_TS] {
+            market_data.add_kline(test_kline(open_time));
+        }
+        assert_eq!(
+            market_data
+                .latest_kline("BTC-USDT", Interval::Min1)
+                .unwrap()
+                .open_time,
+            BASE_TS + 5 * MINUTE
+        );
+
+        // no klines in memory for ETH, latest stored kline is loaded
+        store_replay_klines(&dir, "ETH-USDT", 10);
+        let kline = market_data
+            .latest_kline("ETH-USDT", Interval::Min1)
+            .unwrap();
+        assert_eq!(kline.symbol, "ETH-USDT");
+        assert_eq!(kline.open_time, BASE_TS + 9 * MINUTE);
+    }
+
+    #[test]
+    fn gaps_in_kline_series_are_reported() {
+        let (_dir, mut market_data) = test_market_data();
+        let from_ts = BASE_TS - 2 * MINUTE;
+        let to_ts = BASE_TS + 9 * MINUTE;
+
+        assert_eq!(
+            market_data
+                .find_kline_gaps("BTC-USDT", Interval::Min1, from_ts, to_ts)
+                .unwrap(),
+            vec![(from_ts, to_ts)]
+        );
+
+        for i in [0, 1, 2, 5, 6] {
+            market_data.add_kline(test_kline(BASE_TS + i * MINUTE));
+        }
+
+        assert_eq!(
+            market_data
+                .find_kline_gaps("BTC-USDT", Interval::Min1, from_ts, to_ts)
+                .unwrap(),
+            vec![
+                (from_ts, BASE_TS - MINUTE),
+                (BASE_TS + 3 * MINUTE, BASE_TS + 4 * MINUTE),
+                (BASE_TS + 7 * MINUTE, BASE_TS + 9 * MINUTE),
+            ]
+        );
+
+        // no gaps within stored klines
+        assert!(market_data
+            .find_kline_gaps("BTC-USDT", Interval::Min1, BASE_TS, BASE_TS + 2 * MINUTE)
+            .unwrap()
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn active_streams_filtered_by_symbol_and_type() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage_manager = StorageManager::new(dir.path().to_path_buf());
+        let (market_sender, market_receiver) = build_arc_channel(DEFAULT_CHANNEL_CAPACITY);
+        store_replay_klines(&dir, "BTC-USDT", 1_000);
+        store_replay_klines(&dir, "ETH-USDT", 1_000);
+
+        let exchange_api: Arc<Box<dyn ExchangeApi>> = Arc::new(Box::new(PaperExchange::new(
+            storage_manager.clone(),
+            market_sender,
+            10_000.0,
+            Duration::from_millis(10),
+        )));
+        let config = MarketConfig {
+            backup_interval: None,
+            ..MarketConfig::default()
+        };
+        let market = Market::new(market_receiver, exchange_api, storage_manager, config).await;
+
+        let btc_kline = market
+            .open_stream(StreamType::Kline, "BTC-USDT", Some(Interval::Min1))
+            .await
+            .unwrap();
+        let btc_ticker = market
+            .open_stream(StreamType::Ticker, "BTC-USDT", None)
+            .await
+            .unwrap();
+        let eth_kline = market
+            .open_stream(StreamType::Kline, "ETH-USDT", Some(Interval::Min1))
+            .await
+            .unwrap();
+
+        let stream_ids = |metas: Vec<StreamMeta>| {
+            let mut ids: Vec<StreamId> = metas.into_iter().map(|meta| meta.id).collect();
+            ids.sort();
+            ids
+        };
 
-        needed_streams.retain(|x| x.id != stream_id);
+        assert_eq!(market.active_streams().await.len(), 3);
+        assert_eq!(
+            stream_ids(market.active_streams_for("BTC-USDT").await),
+            vec![btc_kline.clone(), btc_ticker.clone()]
+        );
+        assert_eq!(
+            stream_ids(market.active_streams_of_type(StreamType::Kline).await),
+            vec![btc_kline, eth_kline]
+        );
+        assert_eq!(
+            stream_ids(market.active_streams_of_type(StreamType::Ticker).await),
+            vec![btc_ticker]
+        );
+        assert!(market.active_streams_for("SOL-USDT").await.is_empty());
+
+        market.shutdown().await;
     }
 }
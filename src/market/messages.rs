@@ -1,7 +1,11 @@
+use serde::{Deserialize, Serialize};
+
 use crate::market::{kline::Kline, ticker::Ticker};
 
-#[derive(Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub enum MarketMessage {
     UpdateTicker(Ticker),
     UpdateKline(Kline),
+    // sent once when kline is complete, in progress updates are sent as UpdateKline
+    KlineClosed(Kline),
 }
@@ -1,5 +1,9 @@
+pub mod interval;
 pub mod kline;
+#[allow(clippy::module_inception)]
 pub mod market;
 pub mod messages;
+pub mod patterns;
+pub mod recorder;
 pub mod ticker;
 pub mod types;
@@ -0,0 +1,209 @@
+use serde::{Deserialize, Serialize};
+
+use crate::market::kline::{Kline, KlineData};
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum Pattern {
+    Doji,
+    Hammer,
+    ShootingStar,
+    BullishEngulfing,
+    BearishEngulfing,
+}
+
+/// Thresholds used when detecting candlestick patterns
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PatternConfig {
+    // max body size as ratio of high - low range for doji
+    pub doji_body_ratio: f64,
+    // min size of long shadow as ratio of body for hammer and shooting star
+    pub shadow_body_ratio: f64,
+    // max size of short shadow as ratio of high - low range for hammer and shooting star
+    pub short_shadow_ratio: f64,
+}
+
+impl Default for PatternConfig {
+    fn default() -> Self {
+        Self {
+            doji_body_ratio: 0.1,
+            shadow_body_ratio: 2.0,
+            short_shadow_ratio: 0.1,
+        }
+    }
+}
+
+fn body(kline: &Kline) -> f64 {
+    (kline.close - kline.open).abs()
+}
+
+fn range(kline: &Kline) -> f64 {
+    kline.high - kline.low
+}
+
+fn upper_shadow(kline: &Kline) -> f64 {
+    kline.high - kline.open.max(kline.close)
+}
+
+fn lower_shadow(kline: &Kline) -> f64 {
+    kline.open.min(kline.close) - kline.low
+}
+
+fn is_bullish(kline: &Kline) -> bool {
+    kline.close > kline.open
+}
+
+fn is_bearish(kline: &Kline) -> bool {
+    kline.close < kline.open
+}
+
+pub fn is_doji(kline: &Kline, config: &PatternConfig) -> bool {
+    let range = range(kline);
+
+    range > 0.0 && body(kline) <= range * config.doji_body_ratio
+}
+
+pub fn is_hammer(kline: &Kline, config: &PatternConfig) -> bool {
+    let body = body(kline);
+
+    body > 0.0
+        && lower_shadow(kline) >= body * config.shadow_body_ratio
+        && upper_shadow(kline) <= range(kline) * config.short_shadow_ratio
+}
+
+pub fn is_shooting_star(kline: &Kline, config: &PatternConfig) -> bool {
+    let body = body(kline);
+
+    body > 0.0
+        && upper_shadow(kline) >= body * config.shadow_body_ratio
+        && lower_shadow(kline) <= range(kline) * config.short_shadow_ratio
+}
+
+/// Bearish candle followed by bullish candle whose body covers previous body
+pub fn is_bullish_engulfing(prev: &Kline, cur: &Kline) -> bool {
+    is_bearish(prev) && is_bullish(cur) && cur.open <= prev.close && cur.close >= prev.open
+}
+
+/// Bullish candle followed by bearish candle whose body covers previous body
+pub fn is_bearish_engulfing(prev: &Kline, cur: &Kline) -> bool {
+    is_bullish(prev) && is_bearish(cur) && cur.open >= prev.close && cur.close <= prev.open
+}
+
+/// Detect patterns in kline data, returns open time of
+/// kline the pattern completes on with detected pattern
+pub fn detect_patterns(kline_data: &KlineData, config: &PatternConfig) -> Vec<(u64, Pattern)> {
+    let mut patterns = vec![];
+
+    for (i, kline) in kline_data.klines.iter().enumerate() {
+        if is_doji(kline, config) {
+            patterns.push((kline.open_time, Pattern::Doji));
+        } else if is_hammer(kline, config) {
+            patterns.push((kline.open_time, Pattern::Hammer));
+        } else if is_shooting_star(kline, config) {
+            patterns.push((kline.open_time, Pattern::ShootingStar));
+        }
+
+        if i == 0 {
+            continue;
+        }
+
+        let prev = &kline_data.klines[i - 1];
+
+        if is_bullish_engulfing(prev, kline) {
+            patterns.push((kline.open_time, Pattern::BullishEngulfing));
+        } else if is_bearish_engulfing(prev, kline) {
+            patterns.push((kline.open_time, Pattern::BearishEngulfing));
+        }
+    }
+
+    patterns
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn candle(open: f64, high: f64, low: f64, close: f64) -> Kline {
+        Kline {
+            open,
+            high,
+            low,
+            close,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn doji_body_within_configured_ratio() {
+        let config = PatternConfig::default();
+
+        assert!(is_doji(&candle(100.0, 105.0, 95.0, 100.5), &config));
+        assert!(!is_doji(&candle(100.0, 105.0, 95.0, 103.0), &config));
+        // flat candle has no range
+        assert!(!is_doji(&candle(100.0, 100.0, 100.0, 100.0), &config));
+
+        let loose_config = PatternConfig {
+            doji_body_ratio: 0.5,
+            ..PatternConfig::default()
+        };
+        assert!(is_doji(&candle(100.0, 105.0, 95.0, 103.0), &loose_config));
+    }
+
+    #[test]
+    fn hammer_and_shooting_star() {
+        let config = PatternConfig::default();
+        let hammer = candle(100.0, 101.2, 90.0, 101.0);
+        let shooting_star = candle(101.0, 111.0, 99.9, 100.0);
+
+        assert!(is_hammer(&hammer, &config));
+        assert!(!is_shooting_star(&hammer, &config));
+        assert!(is_shooting_star(
+            &candle(101.0, 111.0, 99.9, 100.0),
+            &config
+        ));
+        assert!(!is_hammer(&shooting_star, &config));
+        // long body with short shadows
+        assert!(!is_hammer(&candle(100.0, 111.0, 99.0, 110.0), &config));
+    }
+
+    #[test]
+    fn engulfing_candles() {
+        let bearish = candle(105.0, 106.0, 99.0, 100.0);
+        let bullish = candle(99.0, 108.0, 98.0, 107.0);
+
+        assert!(is_bullish_engulfing(&bearish, &bullish));
+        assert!(!is_bearish_engulfing(&bearish, &bullish));
+
+        let bullish_prev = candle(100.0, 106.0, 99.0, 105.0);
+        let bearish_cur = candle(106.0, 107.0, 98.0, 99.0);
+        assert!(is_bearish_engulfing(&bullish_prev, &bearish_cur));
+        assert!(!is_bullish_engulfing(&bullish_prev, &bearish_cur));
+
+        // body does not cover previous body
+        let small_bullish = candle(101.0, 104.0, 100.0, 103.0);
+        assert!(!is_bullish_engulfing(&bearish, &small_bullish));
+    }
+
+    #[test]
+    fn detect_patterns_returns_open_time_of_pattern() {
+        let mut kline_data = KlineData::new("BTC-USDT", "1m");
+        let candles = [
+            candle(105.0, 106.0, 99.0, 100.0),
+            candle(99.0, 108.0, 98.0, 107.0),
+            candle(107.0, 110.0, 104.0, 107.2),
+        ];
+        for (i, candle) in candles.into_iter().enumerate() {
+            kline_data.add_kline(Kline {
+                open_time: i as u64 * 60_000,
+                ..candle
+            });
+        }
+
+        assert_eq!(
+            detect_patterns(&kline_data, &PatternConfig::default()),
+            vec![
+                (60_000, Pattern::BullishEngulfing),
+                (120_000, Pattern::Doji)
+            ]
+        );
+    }
+}
@@ -0,0 +1,323 @@
+use serde::{Deserialize, Serialize};
+use std::{
+    fs::OpenOptions,
+    io,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
+use tokio::{
+    io::{AsyncWriteExt, BufWriter},
+    sync::{
+        broadcast::{
+            self,
+            error::{RecvError, TryRecvError},
+        },
+        oneshot,
+    },
+    task::JoinHandle,
+};
+use tracing::{info_span, warn, Instrument};
+
+use crate::{
+    market::{messages::MarketMessage, types::ArcSender},
+    utils::clock::Clock,
+};
+
+/// Market message with time it was recorded, one per line in recording file
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RecordedMessage {
+    pub ts: u64,
+    pub message: MarketMessage,
+}
+
+/// Records market broadcast to JSON lines file to replay later,
+/// eg. to debug strategy with messages it received
+pub struct MarketRecorder {
+    path: PathBuf,
+    // timestamps recorded messages, eg. exchange clock when backtesting
+    clock: Arc<dyn Clock>,
+    handle: Option<JoinHandle<()>>,
+    // signals recording task to flush and stop
+    shutdown: Option<oneshot::Sender<()>>,
+}
+
+impl MarketRecorder {
+    pub fn new(path: &Path, clock: Arc<dyn Clock>) -> Self {
+        Self {
+            path: path.to_path_buf(),
+            clock,
+            handle: None,
+            shutdown: None,
+        }
+    }
+
+    /// Append every message received to recording file until stopped
+    pub fn start(&mut self, mut receiver: broadcast::Receiver<MarketMessage>) -> io::Result<()> {
+        if self.handle.is_some() {
+            return Ok(());
+        }
+
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        // opened before task starts so open errors are returned to caller
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        let mut writer = BufWriter::new(tokio::fs::File::from_std(file));
+
+        let (shutdown, mut shutdown_receiver) = oneshot::channel::<()>();
+        let span = info_span!("market_recorder", path = %self.path.display());
+
+        let clock = self.clock.clone();
+        self.handle = Some(tokio::spawn(
+            async move {
+                loop {
+                    // shutdown checked first, queued messages are drained below
+                    let message = tokio::select! {
+                        biased;
+                        _ = &mut shutdown_receiver => break,
+                        message = receiver.recv() => match message {
+                            Ok(message) => message,
+                            Err(RecvError::Lagged(skipped)) => {
+                                warn!("Market recorder not keeping up, skipped messages: {skipped}");
+                                continue;
+                            }
+                            Err(RecvError::Closed) => break,
+                        },
+                    };
+
+                    write_message(&mut writer, clock.as_ref(), message).await;
+
+                    // burst of messages is flushed once all received
+                    if receiver.is_empty() {
+                        if let Err(e) = writer.flush().await {
+                            warn!("Unable to flush market recording: {e}");
+                        }
+                    }
+                }
+
+                // write messages received before shutdown
+                loop {
+                    match receiver.try_recv() {
+                        Ok(message) => write_message(&mut writer, clock.as_ref(), message).await,
+                        Err(TryRecvError::Lagged(skipped)) => {
+                            warn!("Market recorder not keeping up, skipped messages: {skipped}");
+                        }
+                        Err(TryRecvError::Empty | TryRecvError::Closed) => break,
+                    }
+                }
+
+                if let Err(e) = writer.flush().await {
+                    warn!("Unable to flush market recording: {e}");
+                }
+            }
+            .instrument(span),
+        ));
+        self.shutdown = Some(shutdown);
+
+        Ok(())
+    }
+
+    /// Stop recording once buffered messages are written to file
+    pub async fn stop(&mut self) {
+        if let Some(shutdown) = self.shutdown.take() {
+            let _ = shutdown.send(());
+        }
+
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.await;
+        }
+    }
+}
+
+async fn write_message(
+    writer: &mut BufWriter<tokio::fs::File>,
+    clock: &dyn Clock,
+    message: MarketMessage,
+) {
+    let recorded = RecordedMessage {
+        ts: clock.now(),
+        message,
+    };
+
+    let mut line = match serde_json::to_string(&recorded) {
+        Ok(line) => line,
+        Err(e) => {
+            warn!("Unable to record market message: {e}");
+            return;
+        }
+    };
+    line.push('\n');
+
+    if let Err(e) = writer.write_all(line.as_bytes()).await {
+        warn!("Unable to record market message: {e}");
+    }
+}
+
+/// Re-emits recorded market messages in order, speed multiplies
+/// original timing, eg. 10.0 replays ten times faster
+pub struct MarketReplayer {
+    path: PathBuf,
+    // None replays without waiting between messages
+    speed: Option<f64>,
+}
+
+impl MarketReplayer {
+    pub fn new(path: &Path, speed: Option<f64>) -> Self {
+        Self {
+            path: path.to_path_buf(),
+            speed: speed.filter(|speed| *speed > 0.0),
+        }
+    }
+
+    pub async fn read_messages(&self) -> io::Result<Vec<RecordedMessage>> {
+        let contents = tokio::fs::read_to_string(&self.path).await?;
+
+        let mut messages = vec![];
+        for line in contents.lines() {
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            messages.push(serde_json::from_str::<RecordedMessage>(line)?);
+        }
+
+        Ok(messages)
+    }
+
+    /// Send recorded messages to market sender, returns number of messages sent
+    pub async fn replay(&self, market_sender: &ArcSender<MarketMessage>) -> io::Result<usize> {
+        let messages = self.read_messages().await?;
+
+        let mut last_ts = None;
+        let mut sent = 0;
+
+        for recorded in messages {
+            if let (Some(speed), Some(last_ts)) = (self.speed, last_ts) {
+                let delay = recorded.ts.saturating_sub(last_ts) as f64 / speed;
+                tokio::time::sleep(Duration::from_millis(delay as u64)).await;
+            }
+            last_ts = Some(recorded.ts);
+
+            match market_sender.send(recorded.message) {
+                Ok(_) => sent += 1,
+                Err(e) => warn!("Unable to replay market message: {e}"),
+            }
+        }
+
+        Ok(sent)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Instant;
+
+    use tempfile::TempDir;
+
+    use super::*;
+    use crate::{
+        market::{kline::Kline, ticker::Ticker},
+        utils::{
+            channel::{build_arc_channel, DEFAULT_CHANNEL_CAPACITY},
+            clock::MockClock,
+        },
+    };
+
+    fn test_messages() -> Vec<MarketMessage> {
+        let kline = Kline {
+            symbol: "BTC-USDT".to_string(),
+            interval: "1m".to_string(),
+            open_time: 1_700_000_000_000,
+            ..Default::default()
+        };
+
+        vec![
+            MarketMessage::UpdateTicker(Ticker {
+                symbol: "BTC-USDT".to_string(),
+                last_price: 100.0,
+                ..Default::default()
+            }),
+            MarketMessage::UpdateKline(kline.clone()),
+            MarketMessage::KlineClosed(kline),
+        ]
+    }
+
+    // variant and symbol of message, market messages are not comparable
+    fn message_key(message: &MarketMessage) -> (&'static str, String) {
+        match message {
+            MarketMessage::UpdateTicker(ticker) => ("ticker", ticker.symbol.clone()),
+            MarketMessage::UpdateKline(kline) => ("kline", kline.symbol.clone()),
+            MarketMessage::KlineClosed(kline) => ("closed", kline.symbol.clone()),
+        }
+    }
+
+    #[tokio::test]
+    async fn recorded_messages_are_replayed_in_order() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("recordings").join("market.jsonl");
+
+        let (broadcast_sender, broadcast_receiver) = broadcast::channel(16);
+        let clock = Arc::new(MockClock::new(1_700_000_000_000));
+        let mut recorder = MarketRecorder::new(&path, clock.clone());
+        recorder.start(broadcast_receiver).unwrap();
+        for message in test_messages() {
+            broadcast_sender.send(message).unwrap();
+            clock.advance(Duration::from_secs(1));
+        }
+        // messages still queued when stopped are recorded
+        recorder.stop().await;
+
+        let replayer = MarketReplayer::new(&path, None);
+        let recorded: Vec<u64> = replayer
+            .read_messages()
+            .await
+            .unwrap()
+            .iter()
+            .map(|recorded| recorded.ts)
+            .collect();
+        // recorder task only runs once stopped, all timed by injected clock
+        assert_eq!(recorded, vec![clock.now(); 3]);
+
+        let (market_sender, market_receiver) = build_arc_channel(DEFAULT_CHANNEL_CAPACITY);
+        assert_eq!(replayer.replay(&market_sender).await.unwrap(), 3);
+
+        let mut market_receiver = market_receiver.lock().await;
+        let mut replayed = vec![];
+        while let Ok(message) = market_receiver.try_recv() {
+            replayed.push(message_key(&message));
+        }
+        let expected: Vec<_> = test_messages().iter().map(message_key).collect();
+        assert_eq!(replayed, expected);
+    }
+
+    #[tokio::test]
+    async fn replay_speed_scales_recorded_timing() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("market.jsonl");
+
+        // messages recorded 100ms then 200ms apart
+        let lines: Vec<String> = test_messages()
+            .into_iter()
+            .zip([0, 100, 300])
+            .map(|(message, ts)| serde_json::to_string(&RecordedMessage { ts, message }).unwrap())
+            .collect();
+        std::fs::write(&path, lines.join("\n")).unwrap();
+
+        let (market_sender, _market_receiver) = build_arc_channel(DEFAULT_CHANNEL_CAPACITY);
+
+        // ten times faster, 300ms of recording replayed in 30ms
+        let started = Instant::now();
+        let sent = MarketReplayer::new(&path, Some(10.0))
+            .replay(&market_sender)
+            .await
+            .unwrap();
+
+        assert_eq!(sent, 3);
+        assert!(started.elapsed() >= Duration::from_millis(30));
+        assert!(started.elapsed() < Duration::from_millis(300));
+    }
+}
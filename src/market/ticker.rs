@@ -1,13 +1,13 @@
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
+use std::time::Duration;
 
 use serde_json::Value;
 
 use crate::{
     exchange::types::ApiResult,
-    market::market::MarketDataSymbol,
     utils::{
-        number::{generate_random_id, parse_f64_from_lookup},
+        number::{generate_random_id, parse_f64_from_lookup, parse_optional_f64_from_lookup},
         time::generate_ts,
     },
 };
@@ -19,35 +19,70 @@ pub struct TickerMeta {
 }
 
 impl TickerMeta {
-    pub fn new(symbol: &str) -> Self {
+    pub fn new_at(symbol: &str, last_update: u64) -> Self {
         Self {
             symbol: symbol.to_string(),
-            last_update: generate_ts(),
+            last_update,
         }
     }
 }
 
+pub const DEFAULT_TICKER_WINDOW: Duration = Duration::from_secs(20);
+pub const DEFAULT_TICKER_HISTORY_LEN: usize = 100;
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct TickerData {
     pub meta: TickerMeta,
-    pub ticker: Ticker,
+    // tickers with time received, oldest first
+    pub tickers: VecDeque<(u64, Ticker)>,
+    window: Duration,
+    max_len: usize,
 }
 
 impl TickerData {
-    pub fn new(symbol: &str, ticker: Ticker) -> Self {
-        Self {
-            meta: TickerMeta::new(symbol),
-            ticker,
-        }
+    /// Ticker data with first ticker received at update time
+    pub fn new_at(
+        symbol: &str,
+        ticker: Ticker,
+        update_time: u64,
+        window: Duration,
+        max_len: usize,
+    ) -> Self {
+        let mut ticker_data = Self {
+            meta: TickerMeta::new_at(symbol, update_time),
+            tickers: VecDeque::new(),
+            window,
+            max_len,
+        };
+
+        ticker_data.update_ticker(ticker, update_time);
+
+        ticker_data
     }
 
     pub fn update_ticker(&mut self, ticker: Ticker, update_time: u64) {
-        self.ticker = ticker;
-
-        // increment len of tickers on meta
+        self.tickers.push_back((update_time, ticker));
         self.meta.last_update = update_time;
 
-        // return true ticker added
+        // remove tickers outside of window, latest ticker is always kept
+        let window = self.window.as_millis() as u64;
+        while self.tickers.len() > 1
+            && (self.tickers.len() > self.max_len
+                || self
+                    .tickers
+                    .front()
+                    .is_some_and(|(time, _)| update_time.saturating_sub(*time) > window))
+        {
+            self.tickers.pop_front();
+        }
+    }
+
+    pub fn latest(&self) -> Option<&Ticker> {
+        self.tickers.back().map(|(_, ticker)| ticker)
+    }
+
+    pub fn last_price(&self) -> Option<f64> {
+        self.latest().map(|ticker| ticker.last_price)
     }
 }
 
@@ -66,9 +101,24 @@ pub struct Ticker {
     pub open_price: f64,
     pub open_time: u64,
     pub close_time: u64,
+    // best bid and ask, not sent in all ticker payloads
+    #[serde(default)]
+    pub bid_price: Option<f64>,
+    #[serde(default)]
+    pub ask_price: Option<f64>,
 }
 
 impl Ticker {
+    /// Difference between best ask and best bid
+    pub fn spread(&self) -> Option<f64> {
+        Some(self.ask_price? - self.bid_price?)
+    }
+
+    /// Price halfway between best bid and best ask
+    pub fn mid_price(&self) -> Option<f64> {
+        Some((self.bid_price? + self.ask_price?) / 2.0)
+    }
+
     pub fn from_binance_lookup(lookup: HashMap<String, Value>) -> ApiResult<Self> {
         let symbol = lookup
             .get("s")
@@ -167,6 +217,8 @@ impl Ticker {
             quote_vol,
             open_time,
             close_time,
+            bid_price: parse_optional_f64_from_lookup("b", &lookup),
+            ask_price: parse_optional_f64_from_lookup("a", &lookup),
         })
     }
 
@@ -175,7 +227,7 @@ impl Ticker {
             // Create an error message or construct an error type
             "Missing 'data' key from data ticker lookup".to_string()
         })?;
-        let data: HashMap<String, Value> = serde_json::from_value(data.to_owned()).unwrap();
+        let data: HashMap<String, Value> = serde_json::from_value(data.to_owned())?;
 
         let symbol = data
             .get("symbol")
@@ -240,7 +292,9 @@ impl Ticker {
         //       "quoteVolume": "4151395117.73",
         //       "openPrice": "16832.0",
         //       "openTime": 1672026667803,
-        //       "closeTime": 1672026648425
+        //       "closeTime": 1672026648425,
+        //       "bidPrice": "16880.4",
+        //       "askPrice": "16880.6"
         //     }
         //   }
 
@@ -257,6 +311,71 @@ impl Ticker {
             quote_vol,
             open_time,
             close_time,
+            bid_price: parse_optional_f64_from_lookup("bidPrice", &data),
+            ask_price: parse_optional_f64_from_lookup("askPrice", &data),
+        })
+    }
+
+    pub fn from_bingx_lookup_ws(lookup: HashMap<String, Value>) -> ApiResult<Self> {
+        // {
+        //     "code": 0,
+        //     "dataType": "BTC-USDT@ticker",
+        //     "data": {
+        //       "e": "24hTicker",
+        //       "E": 1672026667803, //event time
+        //       "s": "BTC-USDT",
+        //       "p": "52.5",
+        //       "P": "0.31",
+        //       "c": "16880.5",
+        //       "h": "16897.5",
+        //       "l": "16726.0",
+        //       "v": "245870.1692",
+        //       "q": "4151395117.73",
+        //       "o": "16832.0",
+        //       "O": 1672026667803,
+        //       "C": 1672026648425
+        //     }
+        //   }
+        let data = lookup.get("data").ok_or_else(|| {
+            // Create an error message or construct an error type
+            "Missing 'data' key from data ticker lookup".to_string()
+        })?;
+        let data: HashMap<String, Value> = serde_json::from_value(data.to_owned())?;
+
+        let symbol = data
+            .get("s")
+            .and_then(|symbol| symbol.as_str())
+            .ok_or_else(|| "Missing 's' key from data ticker lookup".to_string())?;
+
+        let time = data
+            .get("E")
+            .and_then(|time| time.as_u64())
+            .unwrap_or_else(generate_ts);
+
+        let open_time = data
+            .get("O")
+            .and_then(|time| time.as_u64())
+            .ok_or_else(|| "Missing 'O' key from data ticker lookup".to_string())?;
+        let close_time = data
+            .get("C")
+            .and_then(|time| time.as_u64())
+            .ok_or_else(|| "Missing 'C' key from data ticker lookup".to_string())?;
+
+        Ok(Self {
+            time,
+            symbol: symbol.to_string(),
+            last_price: parse_f64_from_lookup("c", &data)?,
+            price_change: parse_f64_from_lookup("p", &data)?,
+            percent_change: parse_f64_from_lookup("P", &data)?,
+            open_price: parse_f64_from_lookup("o", &data)?,
+            high: parse_f64_from_lookup("h", &data)?,
+            low: parse_f64_from_lookup("l", &data)?,
+            traded_vol: parse_f64_from_lookup("v", &data)?,
+            quote_vol: parse_f64_from_lookup("q", &data)?,
+            open_time,
+            close_time,
+            bid_price: None,
+            ask_price: None,
         })
     }
 }
@@ -277,12 +396,117 @@ impl Default for Ticker {
             quote_vol: 42.2,
             open_time: 42,
             close_time: 42,
+            bid_price: None,
+            ask_price: None,
         }
     }
 }
 
-impl MarketDataSymbol for Ticker {
-    fn symbol(&self) -> String {
-        self.symbol.to_string()
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn test_ticker(last_price: f64) -> Ticker {
+        Ticker {
+            symbol: "BTC-USDT".to_string(),
+            last_price,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn tickers_older_than_window_are_evicted() {
+        let mut ticker_data = TickerData::new_at(
+            "BTC-USDT",
+            test_ticker(100.0),
+            0,
+            Duration::from_secs(20),
+            DEFAULT_TICKER_HISTORY_LEN,
+        );
+        let start = ticker_data.meta.last_update;
+
+        for (time, price) in [(5_000, 101.0), (10_000, 102.0), (25_000, 103.0)] {
+            ticker_data.update_ticker(test_ticker(price), start + time);
+        }
+
+        let times: Vec<u64> = ticker_data
+            .tickers
+            .iter()
+            .map(|(time, _)| *time - start)
+            .collect();
+        assert_eq!(times, vec![5_000, 10_000, 25_000]);
+        assert_eq!(ticker_data.last_price(), Some(103.0));
+        assert_eq!(ticker_data.meta.last_update, start + 25_000);
+
+        // latest ticker kept even when gap exceeds window
+        ticker_data.update_ticker(test_ticker(104.0), start + 100_000);
+        assert_eq!(ticker_data.tickers.len(), 1);
+        assert_eq!(ticker_data.last_price(), Some(104.0));
+    }
+
+    #[test]
+    fn ticker_history_is_capped_at_max_len() {
+        let mut ticker_data =
+            TickerData::new_at("BTC-USDT", test_ticker(100.0), 0, DEFAULT_TICKER_WINDOW, 3);
+        let start = ticker_data.meta.last_update;
+
+        for i in 1..5 {
+            ticker_data.update_ticker(test_ticker(100.0 + i as f64), start + i * 1_000);
+        }
+
+        let prices: Vec<f64> = ticker_data
+            .tickers
+            .iter()
+            .map(|(_, ticker)| ticker.last_price)
+            .collect();
+        assert_eq!(prices, vec![102.0, 103.0, 104.0]);
+    }
+
+    // rest ticker response, bid and ask are merged into data when given
+    fn bingx_ticker_lookup(bid_ask: Option<(&str, &str)>) -> HashMap<String, Value> {
+        let mut data = json!({
+            "symbol": "BTC-USDT",
+            "priceChange": "52.5",
+            "priceChangePercent": "0.31",
+            "lastPrice": "16880.5",
+            "highPrice": "16897.5",
+            "lowPrice": "16726.0",
+            "volume": "245870.1692",
+            "quoteVolume": "4151395117.73",
+            "openPrice": "16832.0",
+            "openTime": 1_672_026_667_803_u64,
+            "closeTime": 1_672_026_648_425_u64
+        });
+        if let Some((bid, ask)) = bid_ask {
+            data["bidPrice"] = json!(bid);
+            data["askPrice"] = json!(ask);
+        }
+
+        HashMap::from([("code".to_string(), json!(0)), ("data".to_string(), data)])
+    }
+
+    #[test]
+    fn bingx_ticker_with_bid_and_ask() {
+        let ticker =
+            Ticker::from_bingx_lookup(bingx_ticker_lookup(Some(("16880.4", "16880.6")))).unwrap();
+
+        assert_eq!(ticker.last_price, 16_880.5);
+        assert_eq!(ticker.bid_price, Some(16_880.4));
+        assert_eq!(ticker.ask_price, Some(16_880.6));
+        assert!((ticker.spread().unwrap() - 0.2).abs() < 1e-9);
+        assert!((ticker.mid_price().unwrap() - 16_880.5).abs() < 1e-9);
+    }
+
+    #[test]
+    fn bingx_ticker_without_bid_and_ask() {
+        let ticker = Ticker::from_bingx_lookup(bingx_ticker_lookup(None)).unwrap();
+
+        assert_eq!(ticker.last_price, 16_880.5);
+        assert_eq!(ticker.bid_price, None);
+        assert_eq!(ticker.ask_price, None);
+        assert_eq!(ticker.spread(), None);
+        assert_eq!(ticker.mid_price(), None);
     }
 }
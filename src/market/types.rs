@@ -1,15 +1,59 @@
 use std::ops::Deref;
 
-use std::sync::Arc;
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc,
+};
 
 use futures_util::lock::Mutex;
 
 use serde::Serialize;
+use tracing::warn;
 
-use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender};
+use tokio::sync::mpsc::{error::TrySendError, Receiver, Sender};
 
-pub type ArcSender<T> = Arc<UnboundedSender<T>>;
-pub type ArcReceiver<T> = Arc<Mutex<UnboundedReceiver<T>>>;
+pub type ArcSender<T> = Arc<BoundedSender<T>>;
+pub type ArcReceiver<T> = Arc<Mutex<Receiver<T>>>;
+
+/// Sender of bounded channel which never waits for receiver,
+/// messages are dropped and counted when channel is full or closed
+#[derive(Debug)]
+pub struct BoundedSender<T> {
+    sender: Sender<T>,
+    dropped_messages: AtomicU64,
+}
+
+impl<T> BoundedSender<T> {
+    pub fn new(sender: Sender<T>) -> Self {
+        Self {
+            sender,
+            dropped_messages: AtomicU64::new(0),
+        }
+    }
+
+    pub fn send(&self, message: T) -> Result<(), TrySendError<T>> {
+        let res = self.sender.try_send(message);
+
+        if let Err(e) = &res {
+            let dropped = self.dropped_messages.fetch_add(1, Ordering::Relaxed) + 1;
+            match e {
+                TrySendError::Full(_) => {
+                    warn!("Channel full, receiver not keeping up, dropped messages: {dropped}")
+                }
+                TrySendError::Closed(_) => {
+                    warn!("Channel closed, dropped messages: {dropped}")
+                }
+            }
+        }
+
+        res
+    }
+
+    /// Number of messages dropped since channel created
+    pub fn dropped_messages(&self) -> u64 {
+        self.dropped_messages.load(Ordering::Relaxed)
+    }
+}
 
 #[derive(Debug)]
 pub struct ArcMutex<T>(Arc<Mutex<T>>);
@@ -0,0 +1,68 @@
+use serde_json::json;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use crate::utils::time::generate_ts;
+
+/// Append only log of raw payloads which could not be parsed,
+/// each entry is written as a single JSON line
+#[derive(Clone, Debug)]
+pub struct DeadLetterLog {
+    file_path: PathBuf,
+}
+
+impl DeadLetterLog {
+    pub fn new(file_path: impl AsRef<Path>) -> Self {
+        Self {
+            file_path: file_path.as_ref().to_path_buf(),
+        }
+    }
+
+    pub fn log(&self, context: &str, payload: &str) -> io::Result<()> {
+        if let Some(parent) = self.file_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let mut file = OpenOptions::new()
+            .append(true)
+            .create(true)
+            .open(&self.file_path)?;
+
+        let entry = json!({
+            "time": generate_ts(),
+            "context": context,
+            "payload": payload,
+        });
+
+        writeln!(file, "{}", entry)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::Value;
+
+    use super::*;
+    use crate::exchange::bingx::BingXApi;
+
+    #[test]
+    fn malformed_payload_is_written_to_dead_letter_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let dead_letter_log = DeadLetterLog::new(dir.path().join("logs/dead_letter.log"));
+
+        let payload = r#"{"data": {"lastPrice": "not a number"}}"#;
+        assert!(BingXApi::parse_ticker(payload).is_err());
+        dead_letter_log.log("BTC-USDT@ticker", payload).unwrap();
+
+        let contents = fs::read_to_string(dir.path().join("logs/dead_letter.log")).unwrap();
+        let entries: Vec<Value> = contents
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0]["context"], "BTC-USDT@ticker");
+        assert_eq!(entries[0]["payload"], payload);
+    }
+}
@@ -0,0 +1,96 @@
+use std::collections::{HashMap, VecDeque};
+
+use crate::market::kline::Kline;
+
+// number of month files kept in memory, 2 years of klines for a single key
+pub const KLINE_CACHE_CAPACITY: usize = 24;
+
+/// Least recently used cache of klines parsed from kline files, keyed by filename
+#[derive(Debug)]
+pub struct KlineFileCache {
+    capacity: usize,
+    entries: HashMap<String, Vec<Kline>>,
+    // filenames ordered from least to most recently used
+    order: VecDeque<String>,
+}
+
+impl KlineFileCache {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            entries: HashMap::new(),
+            order: VecDeque::new(),
+        }
+    }
+
+    pub fn get(&mut self, filename: &str) -> Option<Vec<Kline>> {
+        let klines = self.entries.get(filename)?.clone();
+        self.touch(filename);
+
+        Some(klines)
+    }
+
+    pub fn insert(&mut self, filename: &str, klines: Vec<Kline>) {
+        if self.capacity == 0 {
+            return;
+        }
+
+        if self.entries.insert(filename.to_string(), klines).is_some() {
+            self.touch(filename);
+            return;
+        }
+
+        self.order.push_back(filename.to_string());
+
+        // evict least recently used file
+        if self.order.len() > self.capacity {
+            if let Some(evicted) = self.order.pop_front() {
+                self.entries.remove(&evicted);
+            }
+        }
+    }
+
+    /// Remove file from cache, should be called whenever file is written to
+    pub fn invalidate(&mut self, filename: &str) {
+        if self.entries.remove(filename).is_some() {
+            self.order.retain(|name| name != filename);
+        }
+    }
+
+    fn touch(&mut self, filename: &str) {
+        if let Some(idx) = self.order.iter().position(|name| name == filename) {
+            if let Some(name) = self.order.remove(idx) {
+                self.order.push_back(name);
+            }
+        }
+    }
+}
+
+impl Default for KlineFileCache {
+    fn default() -> Self {
+        Self::new(KLINE_CACHE_CAPACITY)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn least_recently_used_file_is_evicted() {
+        let mut cache = KlineFileCache::new(2);
+        cache.insert("2023-01.csv", vec![Kline::default()]);
+        cache.insert("2023-02.csv", vec![]);
+
+        // read makes file most recently used
+        assert!(cache.get("2023-01.csv").is_some());
+        cache.insert("2023-03.csv", vec![]);
+
+        assert!(cache.get("2023-01.csv").is_some());
+        assert!(cache.get("2023-02.csv").is_none());
+        assert!(cache.get("2023-03.csv").is_some());
+
+        cache.invalidate("2023-01.csv");
+        assert!(cache.get("2023-01.csv").is_none());
+    }
+}
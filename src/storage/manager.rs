@@ -1,123 +1,640 @@
 use csv::ReaderBuilder;
 use directories::UserDirs;
+use flate2::read::MultiGzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::fs;
 use std::fs::{File, OpenOptions};
-use std::io::{self};
+use std::io::{self, BufRead, BufReader, Read, Seek, SeekFrom, Write};
 use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use tracing::warn;
 
 use crate::market::{kline::Kline, market::MarketData};
+use crate::storage::dead_letter::DeadLetterLog;
+use crate::storage::kline_cache::KlineFileCache;
+use crate::utils::time::generate_ts;
+
+// bytes read from end of kline file to find last kline, longer than a kline row
+const KLINE_TAIL_BYTES: u64 = 4096;
+
+// Kline files start with marker of schema version, eg. #kline_schema_version=2,
+// version 1 files have no marker or header and columns in order of kline fields
+const KLINE_SCHEMA_MARKER: &str = "#kline_schema_version=";
+const KLINE_SCHEMA_VERSION: u32 = 2;
+
+// columns which have no default and must be in header of kline files
+const KLINE_REQUIRED_COLUMNS: [&str; 9] = [
+    "symbol",
+    "interval",
+    "open",
+    "high",
+    "low",
+    "close",
+    "volume",
+    "open_time",
+    "close_time",
+];
+
+// Parquet schema matching order of kline fields
+#[cfg(feature = "parquet")]
+const KLINE_PARQUET_SCHEMA: &str = "
+    message kline {
+        REQUIRED BYTE_ARRAY symbol (UTF8);
+        REQUIRED BYTE_ARRAY interval (UTF8);
+        REQUIRED DOUBLE open;
+        REQUIRED DOUBLE high;
+        REQUIRED DOUBLE low;
+        REQUIRED DOUBLE close;
+        REQUIRED DOUBLE volume;
+        REQUIRED INT64 open_time;
+        REQUIRED INT64 close_time;
+        REQUIRED BOOLEAN is_closed;
+    }
+";
+
+#[cfg(feature = "parquet")]
+fn parquet_to_io(e: parquet::errors::ParquetError) -> io::Error {
+    io::Error::other(e)
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+pub enum CompressionLevel {
+    Fast,
+    Default,
+    Best,
+}
+
+impl CompressionLevel {
+    pub fn to_compression(self) -> Compression {
+        match self {
+            CompressionLevel::Fast => Compression::fast(),
+            CompressionLevel::Default => Compression::default(),
+            CompressionLevel::Best => Compression::best(),
+        }
+    }
+}
+
+impl FromStr for CompressionLevel {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "fast" => Ok(CompressionLevel::Fast),
+            "default" => Ok(CompressionLevel::Default),
+            "best" => Ok(CompressionLevel::Best),
+            _ => Err(format!("Unknown compression level: {}", s)),
+        }
+    }
+}
 
 #[derive(Serialize, Deserialize, Clone)]
 pub struct StorageManager {
-    app_directory: PathBuf,
+    // all files are stored relative to data directory
     data_directory: PathBuf,
+    compression_level: CompressionLevel,
+    archive_compression_level: CompressionLevel,
+    compress_klines: bool,
+    // kline files are stored in directory of exchange they came from
+    exchange: Option<String>,
+    #[serde(skip)]
+    kline_cache: Arc<Mutex<KlineFileCache>>,
 }
 
 impl StorageManager {
-    pub fn new(data_directory: impl AsRef<Path>) -> Self {
-        let app_directory = Self::create_app_directory();
-        let data_directory = app_directory.join(data_directory);
+    /// Create storage manager storing all files under base path,
+    /// base path is created if it does not exist
+    pub fn new(base_path: PathBuf) -> Self {
+        let data_directory = base_path;
 
         if !data_directory.exists() {
             fs::create_dir_all(&data_directory).expect("Failed to create data directory");
         }
 
         Self {
-            app_directory,
             data_directory,
+            compression_level: CompressionLevel::Default,
+            archive_compression_level: CompressionLevel::Best,
+            compress_klines: false,
+            exchange: None,
+            kline_cache: Arc::new(Mutex::new(KlineFileCache::default())),
+        }
+    }
+
+    /// Set compression level used for live kline writes and
+    /// the level used when archiving old kline files
+    pub fn set_compression_levels(&mut self, live: CompressionLevel, archive: CompressionLevel) {
+        self.compression_level = live;
+        self.archive_compression_level = archive;
+    }
+
+    /// Write klines as gzip compressed CSV files (.csv.gz)
+    /// using live compression level
+    pub fn set_compress_klines(&mut self, compress_klines: bool) {
+        self.compress_klines = compress_klines;
+    }
+
+    pub fn base_path(&self) -> &Path {
+        &self.data_directory
+    }
+
+    /// Namespace kline files by exchange so klines of same
+    /// symbol from different exchanges are kept in separate files
+    pub fn set_exchange(&mut self, exchange: &str) {
+        self.exchange = Some(exchange.to_string());
+    }
+
+    /// Directory kline files are stored in, eg. market/klines/bingx
+    pub fn kline_directory(&self) -> PathBuf {
+        let mut kline_dir = self.data_directory.join("market");
+        kline_dir.push("klines");
+
+        if let Some(exchange) = &self.exchange {
+            kline_dir.push(exchange);
         }
+
+        kline_dir
     }
 
+    /// Move kline files saved before files were namespaced by exchange
+    /// into directory of exchange, files already in exchange directory
+    /// are not replaced. Returns number of files moved
+    pub fn migrate_legacy_klines(&self, exchange: &str) -> io::Result<usize> {
+        let legacy_dir = self.data_directory.join("market").join("klines");
+        let exchange_dir = legacy_dir.join(exchange);
+
+        let entries = match fs::read_dir(&legacy_dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(0),
+            Err(e) => return Err(e),
+        };
+
+        let mut moved = 0;
+        for entry in entries {
+            let entry = entry?;
+            // exchange directories are not legacy files
+            if !entry.file_type()?.is_file() {
+                continue;
+            }
+
+            let new_path = exchange_dir.join(entry.file_name());
+            if new_path.exists() {
+                warn!(
+                    "Not moving legacy kline file {}, file exists in {}",
+                    entry.path().display(),
+                    exchange_dir.display()
+                );
+                continue;
+            }
+
+            fs::create_dir_all(&exchange_dir)?;
+            fs::rename(entry.path(), new_path)?;
+            moved += 1;
+        }
+
+        Ok(moved)
+    }
+
+    // cached klines are keyed by path so exchanges sharing cache don't collide
+    fn cache_key(&self, filename: &str) -> String {
+        self.kline_directory()
+            .join(filename)
+            .to_string_lossy()
+            .to_string()
+    }
+
+    /// Merge klines into kline files, each file is rewritten to a temporary
+    /// file and renamed into place so a crash never leaves a partial file
     pub fn save_klines(&self, klines: &[Kline], kline_key: &str) -> io::Result<()> {
+        // cached klines are stale once file is written to
+        for kline in klines {
+            self.invalidate_cached_klines(&MarketData::build_kline_filename(
+                kline_key,
+                kline.open_time,
+            ));
+        }
+
         // Build market directory and subdirectory for klines
-        let mut market_dir = self.data_directory.join("market");
-        market_dir.push("klines");
+        let market_dir = self.kline_directory();
         std::fs::create_dir_all(&market_dir)?;
 
+        let mut klines_by_file: BTreeMap<String, Vec<&Kline>> = BTreeMap::new();
         for kline in klines {
-            // Build file path
-            let kline_filename = MarketData::build_kline_filename(kline_key, kline.open_time);
-            let file_path = market_dir.join(kline_filename);
+            let mut kline_filename = MarketData::build_kline_filename(kline_key, kline.open_time);
+            if self.compress_klines {
+                kline_filename.push_str(".gz");
+            }
+            klines_by_file
+                .entry(kline_filename)
+                .or_default()
+                .push(kline);
+        }
 
-            let file_exists = file_path.exists();
+        // each file is read and rewritten with new klines merged in,
+        // gzip files can't be appended to
+        for (filename, new_klines) in klines_by_file {
+            let file_path = market_dir.join(filename);
 
-            let file = OpenOptions::new()
-                .append(true)
-                .create(true)
-                .open(&file_path)?;
+            // plain files are appended to when new klines all come after last
+            // saved kline, only overlapping klines need file to be merged
+            if !self.compress_klines {
+                let mut new_klines = new_klines.clone();
+                new_klines.sort_by_key(|kline| kline.open_time);
+                let after_last_kline = match read_last_kline(&file_path) {
+                    Ok(Some(last_kline)) => new_klines[0].open_time > last_kline.open_time,
+                    _ => false,
+                };
 
-            let mut writer = csv::WriterBuilder::new()
-                .has_headers(false)
-                .from_writer(file);
-
-            if file_exists {
-                // Read the existing klines from the file
-                let mut reader = csv::ReaderBuilder::new()
-                    .has_headers(false)
-                    .from_path(&file_path)?;
-
-                // Read existing klines into a vector
-                let existing_klines: Vec<Kline> =
-                    reader.deserialize().collect::<Result<Vec<Kline>, _>>()?;
-
-                if let Some((last_index, last_existing_kline)) =
-                    existing_klines.iter().enumerate().last()
-                {
-                    if last_existing_kline.open_time == kline.open_time {
-                        // Overwrite the last entry with the new kline
-                        let mut overwrite_file = File::create(&file_path)?;
-                        let mut overwrite_writer = csv::WriterBuilder::new()
-                            .has_headers(false)
-                            .from_writer(&mut overwrite_file);
-
-                        // Write existing klines excluding the last entry
-                        for existing_kline in &existing_klines[..last_index] {
-                            overwrite_writer.serialize(existing_kline)?;
-                        }
-
-                        // Write the new kline
-                        overwrite_writer.serialize(kline)?;
-                    } else {
-                        // Append the new kline to the existing file
-                        writer.serialize(kline)?;
-                    }
-                } else {
-                    // Append the new kline to the existing file
-                    writer.serialize(kline)?;
+                if after_last_kline {
+                    append_klines(&file_path, &new_klines)?;
+                    continue;
                 }
-            } else {
-                // Serialize and write the kline to the file
-                writer.serialize(kline)?;
             }
+
+            let file_read = match open_kline_file(&file_path)? {
+                Some(file) if self.compress_klines => Some(read_klines(MultiGzDecoder::new(file))),
+                Some(file) => Some(read_klines(file)),
+                None => None,
+            };
+
+            // corrupt file is moved aside so it doesn't fail every save
+            let file_klines = match file_read {
+                Some(Ok(file_klines)) => file_klines,
+                Some(Err(e)) => {
+                    quarantine_kline_file(&file_path, &e)?;
+                    vec![]
+                }
+                None => vec![],
+            };
+
+            // merge by open time so klines saved out of order, eg. backfilled
+            // history, are kept sorted, new klines replace klines with same open time
+            let mut merged_klines: BTreeMap<u64, Kline> = file_klines
+                .into_iter()
+                .map(|kline| (kline.open_time, kline))
+                .collect();
+            for kline in new_klines {
+                merged_klines.insert(kline.open_time, kline.clone());
+            }
+            let file_klines: Vec<Kline> = merged_klines.into_values().collect();
+
+            write_file_atomic(&file_path, |file| {
+                if self.compress_klines {
+                    // gzip files can't be appended to, so whole file is rewritten
+                    let encoder = GzEncoder::new(file, self.compression_level.to_compression());
+                    write_klines(encoder, &file_klines)?.finish()
+                } else {
+                    write_klines(file, &file_klines)
+                }
+            })?;
         }
 
         Ok(())
     }
 
-    pub fn load_klines(&self, filename: &str) -> Option<Vec<Kline>> {
-        let mut market_dir = self.data_directory.join("market");
-        market_dir.push("klines");
+    /// Compress kline file with archive compression level, original file is removed.
+    /// Should only be used on kline files which are no longer written to
+    pub fn archive_klines(&self, filename: &str) -> io::Result<()> {
+        let market_dir = self.kline_directory();
         let file_path = market_dir.join(filename);
+        let archive_path = market_dir.join(format!("{filename}.gz"));
 
-        if let Ok(file) = fs::File::open(file_path) {
-            let mut reader = ReaderBuilder::new().has_headers(false).from_reader(file);
+        self.invalidate_cached_klines(filename);
 
-            let mut klines: Vec<Kline> = Vec::new();
+        let mut file = File::open(&file_path)?;
 
-            for result in reader.deserialize() {
-                if let Ok(kline) = result {
-                    klines.push(kline);
-                } else {
-                    // Handle error while deserializing kline
-                    return None;
+        // merge with klines already archived, eg. if compression
+        // was changed part way through month
+        let archived_klines = match open_kline_file(&archive_path)? {
+            Some(archive_file) => Some(read_klines(MultiGzDecoder::new(archive_file))?),
+            None => None,
+        };
+
+        write_file_atomic(&archive_path, |archive_file| {
+            let mut encoder = GzEncoder::new(
+                archive_file,
+                self.archive_compression_level.to_compression(),
+            );
+
+            match archived_klines {
+                Some(mut klines) => {
+                    klines.extend(read_klines(&mut file)?);
+                    klines.sort_by_key(|kline| kline.open_time);
+                    klines.dedup_by_key(|kline| kline.open_time);
+                    encoder = write_klines(encoder, &klines)?;
+                }
+                None => {
+                    io::copy(&mut file, &mut encoder)?;
                 }
             }
 
-            Some(klines)
-        } else {
-            None
+            encoder.finish()
+        })?;
+
+        fs::remove_file(file_path)
+    }
+
+    /// Archive plain kline files of months before month of timestamp,
+    /// files of past months are no longer written to by live klines
+    pub fn archive_completed_months(&self, kline_key: &str, timestamp: u64) -> io::Result<()> {
+        let market_dir = self.kline_directory();
+        let current_month = MarketData::build_kline_filename(kline_key, timestamp);
+
+        if !market_dir.exists() {
+            return Ok(());
         }
+
+        for entry in fs::read_dir(&market_dir)? {
+            let filename = entry?.file_name().to_string_lossy().to_string();
+
+            // filenames are zero padded so order of names is order of months
+            if !filename.starts_with(&format!("{kline_key}-"))
+                || !filename.ends_with(".csv")
+                || filename >= current_month
+            {
+                continue;
+            }
+
+            self.archive_klines(&filename)?;
+        }
+
+        Ok(())
+    }
+
+    /// Load klines from file, recently loaded files are served from cache.
+    /// Returns None if file does not exist, error if file could not be read
+    pub fn load_klines(&self, filename: &str) -> io::Result<Option<Vec<Kline>>> {
+        if let Some(klines) = self
+            .kline_cache
+            .lock()
+            .ok()
+            .and_then(|mut cache| cache.get(&self.cache_key(filename)))
+        {
+            return Ok(Some(klines));
+        }
+
+        let klines = match self.read_kline_file(filename)? {
+            Some(klines) => klines,
+            None => return Ok(None),
+        };
+
+        if let Ok(mut cache) = self.kline_cache.lock() {
+            cache.insert(&self.cache_key(filename), klines.clone());
+        }
+
+        Ok(Some(klines))
+    }
+
+    fn invalidate_cached_klines(&self, filename: &str) {
+        if let Ok(mut cache) = self.kline_cache.lock() {
+            cache.invalidate(&self.cache_key(filename));
+        }
+    }
+
+    fn read_kline_file(&self, filename: &str) -> io::Result<Option<Vec<Kline>>> {
+        let market_dir = self.kline_directory();
+        let file_path = market_dir.join(filename);
+        let archive_path = market_dir.join(format!("{filename}.gz"));
+
+        // klines may be in compressed file, plain file or both
+        // if compression was changed part way through month
+        let compressed_klines = match open_kline_file(&archive_path)? {
+            Some(file) => read_klines_or_skip(MultiGzDecoder::new(file), &archive_path),
+            None => None,
+        };
+        let plain_klines = match open_kline_file(&file_path)? {
+            Some(file) => read_klines_or_skip(file, &file_path),
+            None => None,
+        };
+
+        let klines = match (compressed_klines, plain_klines) {
+            (Some(mut klines), Some(plain_klines)) => {
+                klines.extend(plain_klines);
+                klines.sort_by_key(|kline| kline.open_time);
+                klines.dedup_by_key(|kline| kline.open_time);
+                Some(klines)
+            }
+            (Some(klines), None) | (None, Some(klines)) => Some(klines),
+            (None, None) => None,
+        };
+
+        Ok(klines)
+    }
+
+    /// Year and month of kline files saved on disk for kline key, in ascending order
+    pub fn list_kline_files(&self, kline_key: &str) -> Vec<(u32, u32)> {
+        let market_dir = self.kline_directory();
+
+        let entries = match fs::read_dir(market_dir) {
+            Ok(entries) => entries,
+            Err(_) => return vec![],
+        };
+
+        let prefix = format!("{kline_key}-");
+
+        let mut year_months: Vec<(u32, u32)> = entries
+            .filter_map(|entry| entry.ok())
+            .filter_map(|entry| {
+                let filename = entry.file_name().to_string_lossy().to_string();
+
+                // filename format eg. BTCUSDT@kline_1m-2023-06.csv(.gz)
+                let year_month = filename.strip_prefix(&prefix)?;
+                let year_month = year_month.strip_suffix(".gz").unwrap_or(year_month);
+                let year_month = year_month.strip_suffix(".csv")?;
+
+                let (year, month) = year_month.split_once('-')?;
+
+                Some((year.parse().ok()?, month.parse().ok()?))
+            })
+            .collect();
+
+        year_months.sort();
+        year_months.dedup();
+
+        year_months
+    }
+
+    /// Symbols with kline files saved on disk, in ascending order
+    pub fn list_kline_symbols(&self) -> Vec<String> {
+        let entries = match fs::read_dir(self.kline_directory()) {
+            Ok(entries) => entries,
+            Err(_) => return vec![],
+        };
+
+        let mut symbols: Vec<String> = entries
+            .filter_map(|entry| entry.ok())
+            .filter_map(|entry| {
+                let filename = entry.file_name().to_string_lossy().to_string();
+
+                // filename format eg. BTCUSDT@kline_1m-2023-06.csv(.gz)
+                let (symbol, _) = filename.split_once("@kline_")?;
+
+                Some(symbol.to_string())
+            })
+            .collect();
+
+        symbols.sort();
+        symbols.dedup();
+
+        symbols
+    }
+
+    /// Min and max open time of klines saved on disk for kline key
+    pub fn available_range(&self, kline_key: &str) -> Option<(u64, u64)> {
+        let year_months = self.list_kline_files(kline_key);
+
+        let (first_year, first_month) = year_months.first()?;
+        let (last_year, last_month) = year_months.last()?;
+
+        let first_klines = self
+            .load_klines(&MarketData::build_kline_filename_from_year_month(
+                kline_key,
+                *first_year,
+                *first_month,
+            ))
+            .ok()??;
+        let last_klines = self
+            .load_klines(&MarketData::build_kline_filename_from_year_month(
+                kline_key,
+                *last_year,
+                *last_month,
+            ))
+            .ok()??;
+
+        let min_open_time = first_klines.iter().map(|kline| kline.open_time).min()?;
+        let max_open_time = last_klines.iter().map(|kline| kline.open_time).max()?;
+
+        Some((min_open_time, max_open_time))
+    }
+
+    /// Save klines to parquet file in market klines directory,
+    /// existing file with same name is overwritten
+    #[cfg(feature = "parquet")]
+    pub fn save_klines_parquet(&self, klines: &[Kline], filename: &str) -> io::Result<()> {
+        self.write_klines_parquet(klines, filename, KLINE_PARQUET_SCHEMA)
+    }
+
+    // columns after last column of schema are not written
+    #[cfg(feature = "parquet")]
+    fn write_klines_parquet(
+        &self,
+        klines: &[Kline],
+        filename: &str,
+        schema: &str,
+    ) -> io::Result<()> {
+        use parquet::data_type::{BoolType, ByteArray, ByteArrayType, DoubleType, Int64Type};
+        use parquet::file::{properties::WriterProperties, writer::SerializedFileWriter};
+        use parquet::schema::parser::parse_message_type;
+
+        let market_dir = self.kline_directory();
+        std::fs::create_dir_all(&market_dir)?;
+        let file = File::create(market_dir.join(filename))?;
+
+        let schema = Arc::new(parse_message_type(schema).map_err(parquet_to_io)?);
+        let props = Arc::new(WriterProperties::builder().build());
+        let mut writer = SerializedFileWriter::new(file, schema, props).map_err(parquet_to_io)?;
+        let mut row_group = writer.next_row_group().map_err(parquet_to_io)?;
+
+        let mut column_idx = 0;
+        while let Some(mut column) = row_group.next_column().map_err(parquet_to_io)? {
+            // columns are written in order of kline fields
+            let result = match column_idx {
+                0 | 1 => {
+                    let values: Vec<ByteArray> = klines
+                        .iter()
+                        .map(|kline| match column_idx {
+                            0 => ByteArray::from(kline.symbol.as_str()),
+                            _ => ByteArray::from(kline.interval.as_str()),
+                        })
+                        .collect();
+                    column
+                        .typed::<ByteArrayType>()
+                        .write_batch(&values, None, None)
+                }
+                2..=6 => {
+                    let values: Vec<f64> = klines
+                        .iter()
+                        .map(|kline| match column_idx {
+                            2 => kline.open,
+                            3 => kline.high,
+                            4 => kline.low,
+                            5 => kline.close,
+                            _ => kline.volume,
+                        })
+                        .collect();
+                    column
+                        .typed::<DoubleType>()
+                        .write_batch(&values, None, None)
+                }
+                7 | 8 => {
+                    let values: Vec<i64> = klines
+                        .iter()
+                        .map(|kline| match column_idx {
+                            7 => kline.open_time as i64,
+                            _ => kline.close_time as i64,
+                        })
+                        .collect();
+                    column.typed::<Int64Type>().write_batch(&values, None, None)
+                }
+                _ => {
+                    let values: Vec<bool> = klines.iter().map(|kline| kline.is_closed).collect();
+                    column.typed::<BoolType>().write_batch(&values, None, None)
+                }
+            };
+
+            result.map_err(parquet_to_io)?;
+            column.close().map_err(parquet_to_io)?;
+            column_idx += 1;
+        }
+
+        row_group.close().map_err(parquet_to_io)?;
+        writer.close().map_err(parquet_to_io)?;
+
+        Ok(())
+    }
+
+    #[cfg(feature = "parquet")]
+    pub fn load_klines_parquet(&self, filename: &str) -> Option<Vec<Kline>> {
+        use parquet::file::reader::{FileReader, SerializedFileReader};
+        use parquet::record::RowAccessor;
+
+        let market_dir = self.kline_directory();
+        let file = File::open(market_dir.join(filename)).ok()?;
+
+        let reader = SerializedFileReader::new(file).ok()?;
+
+        let mut klines: Vec<Kline> = Vec::new();
+
+        for row in reader.get_row_iter(None).ok()? {
+            let row = row.ok()?;
+
+            klines.push(Kline {
+                symbol: row.get_string(0).ok()?.to_string(),
+                interval: row.get_string(1).ok()?.to_string(),
+                open: row.get_double(2).ok()?,
+                high: row.get_double(3).ok()?,
+                low: row.get_double(4).ok()?,
+                close: row.get_double(5).ok()?,
+                volume: row.get_double(6).ok()?,
+                open_time: row.get_long(7).ok()? as u64,
+                close_time: row.get_long(8).ok()? as u64,
+                // files saved before is_closed column only held closed klines
+                is_closed: if row.len() > 9 {
+                    row.get_bool(9).ok()?
+                } else {
+                    true
+                },
+            });
+        }
+
+        Some(klines)
+    }
+
+    pub fn dead_letter_log(&self) -> DeadLetterLog {
+        DeadLetterLog::new(self.data_directory.join("dead_letter.log"))
     }
 
     fn create_app_directory() -> PathBuf {
@@ -133,19 +650,604 @@ impl StorageManager {
     }
 }
 
+fn read_klines(file: impl Read) -> io::Result<Vec<Kline>> {
+    let mut reader = BufReader::new(file);
+
+    let mut first_line = String::new();
+    reader.read_line(&mut first_line)?;
+
+    let version = match first_line.trim_end().strip_prefix(KLINE_SCHEMA_MARKER) {
+        Some(version) => version.parse::<u32>().map_err(|_| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Invalid kline schema version: {version}"),
+            )
+        })?,
+        // version 1 files have no marker or header, first line is a kline
+        None => 1,
+    };
+
+    match version {
+        1 => {
+            // missing trailing columns are filled with defaults
+            let mut csv_reader = ReaderBuilder::new()
+                .has_headers(false)
+                .flexible(true)
+                .from_reader(io::Cursor::new(first_line).chain(reader));
+
+            collect_klines(csv_reader.deserialize())
+        }
+        KLINE_SCHEMA_VERSION => {
+            let mut csv_reader = ReaderBuilder::new().has_headers(true).from_reader(reader);
+
+            let headers = csv_reader.headers()?;
+            if let Some(column) = KLINE_REQUIRED_COLUMNS
+                .iter()
+                .find(|column| !headers.iter().any(|header| header == **column))
+            {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("Missing '{column}' column from kline file header"),
+                ));
+            }
+
+            collect_klines(csv_reader.deserialize())
+        }
+        _ => Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("Unsupported kline schema version: {version}"),
+        )),
+    }
+}
+
+// last row can be cut short if process stops while it is appended,
+// it is dropped instead of failing read of whole file
+fn collect_klines(
+    records: impl Iterator<Item = Result<Kline, csv::Error>>,
+) -> io::Result<Vec<Kline>> {
+    let mut records = records.peekable();
+    let mut klines = vec![];
+
+    while let Some(record) = records.next() {
+        match record {
+            Ok(kline) => klines.push(kline),
+            Err(e) if records.peek().is_none() => {
+                warn!("Dropping incomplete last kline row: {e}");
+            }
+            Err(e) => return Err(e.into()),
+        }
+    }
+
+    Ok(klines)
+}
+
+// last kline is read from end of file with header from start of file,
+// so whole file isn't read to find where to append
+fn read_last_kline(file_path: &Path) -> io::Result<Option<Kline>> {
+    let mut reader = BufReader::new(File::open(file_path)?);
+
+    // version 2 files have marker and header row, version 1 have neither
+    let mut head = String::new();
+    reader.read_line(&mut head)?;
+    if head.starts_with(KLINE_SCHEMA_MARKER) {
+        reader.read_line(&mut head)?;
+    } else {
+        head.clear();
+    }
+
+    let mut file = reader.into_inner();
+    let len = file.metadata()?.len();
+    file.seek(SeekFrom::Start(len.saturating_sub(KLINE_TAIL_BYTES)))?;
+    let mut tail = vec![];
+    file.read_to_end(&mut tail)?;
+    let tail = String::from_utf8_lossy(&tail);
+
+    let last_line = match tail.lines().rev().find(|line| !line.trim().is_empty()) {
+        Some(line) if !head.lines().any(|head_line| head_line == line) => line,
+        _ => return Ok(None),
+    };
+
+    let klines = read_klines(io::Cursor::new(format!("{head}{last_line}\n")))?;
+    Ok(klines.into_iter().last())
+}
+
+// rows are written with single write and synced, file isn't rewritten
+fn append_klines(file_path: &Path, klines: &[&Kline]) -> io::Result<()> {
+    let mut writer = csv::WriterBuilder::new()
+        .has_headers(false)
+        .from_writer(vec![]);
+
+    for kline in klines {
+        writer.serialize(kline)?;
+    }
+
+    let rows = writer
+        .into_inner()
+        .map_err(|e| io::Error::other(e.to_string()))?;
+
+    let mut file = OpenOptions::new().append(true).open(file_path)?;
+    file.write_all(&rows)?;
+    file.sync_all()
+}
+
+// unreadable file is renamed so it can be inspected, new file is started in its place
+fn quarantine_kline_file(file_path: &Path, error: &io::Error) -> io::Result<()> {
+    let mut quarantine_path = file_path.as_os_str().to_owned();
+    quarantine_path.push(format!(".corrupt-{}", generate_ts()));
+    let quarantine_path = PathBuf::from(quarantine_path);
+
+    warn!(
+        "Moving unreadable kline file {} to {}: {error}",
+        file_path.display(),
+        quarantine_path.display()
+    );
+
+    fs::rename(file_path, quarantine_path)
+}
+
+// corrupt file is skipped rather than failing load of all files
+fn read_klines_or_skip(file: impl Read, file_path: &Path) -> Option<Vec<Kline>> {
+    match read_klines(file) {
+        Ok(klines) => Some(klines),
+        Err(e) => {
+            warn!(
+                "Skipping unreadable kline file {}: {e}",
+                file_path.display()
+            );
+            None
+        }
+    }
+}
+
+/// Write klines as CSV with schema version marker and header row
+pub fn write_klines<W: Write>(mut file: W, klines: &[Kline]) -> io::Result<W> {
+    writeln!(file, "{KLINE_SCHEMA_MARKER}{KLINE_SCHEMA_VERSION}")?;
+
+    // header row is written from kline field names
+    let mut writer = csv::WriterBuilder::new()
+        .has_headers(true)
+        .from_writer(file);
+
+    for kline in klines {
+        writer.serialize(kline)?;
+    }
+
+    writer
+        .into_inner()
+        .map_err(|e| io::Error::other(e.to_string()))
+}
+
+// write to temporary file then rename, rename replaces file atomically
+// so file is either the previous or new version, never partially written
+fn write_file_atomic(
+    file_path: &Path,
+    write: impl FnOnce(File) -> io::Result<File>,
+) -> io::Result<()> {
+    let mut tmp_path = file_path.as_os_str().to_owned();
+    tmp_path.push(".tmp");
+    let tmp_path = PathBuf::from(tmp_path);
+
+    let res = File::create(&tmp_path)
+        .and_then(write)
+        .and_then(|file| file.sync_all());
+
+    if let Err(e) = res {
+        let _ = fs::remove_file(&tmp_path);
+        return Err(e);
+    }
+
+    fs::rename(&tmp_path, file_path)
+}
+
+// missing file is not an error, None is returned instead
+fn open_kline_file(file_path: &Path) -> io::Result<Option<File>> {
+    match File::open(file_path) {
+        Ok(file) => Ok(Some(file)),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
 impl Default for StorageManager {
+    // default data directory is ~/.raderbot/default
     fn default() -> Self {
-        // Create the default data directory path
         let app_directory = Self::create_app_directory();
-        let data_directory = app_directory.join("default");
 
-        if !data_directory.exists() {
-            fs::create_dir_all(&data_directory).expect("Failed to create data directory");
+        Self::new(app_directory.join("default"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::TempDir;
+
+    use super::*;
+
+    const BASE_TS: u64 = 1_700_000_000_000 - 1_700_000_000_000 % 60_000;
+    const MINUTE: u64 = 60_000;
+
+    fn test_klines(len: u64) -> Vec<Kline> {
+        test_klines_from(BASE_TS, len)
+    }
+
+    // klines each minute from open time
+    fn test_klines_from(open_time: u64, len: u64) -> Vec<Kline> {
+        (0..len)
+            .map(|i| {
+                // vary prices so rows are not trivially compressible
+                let price = 100.0 + ((i * 7919) % 1000) as f64 / 7.0;
+                Kline {
+                    symbol: "BTC-USDT".to_string(),
+                    interval: "1m".to_string(),
+                    open: price,
+                    high: price + 1.5,
+                    low: price - 1.25,
+                    close: price + 0.5,
+                    volume: ((i * 104_729) % 10_000) as f64 / 3.0,
+                    open_time: open_time + i * MINUTE,
+                    close_time: open_time + (i + 1) * MINUTE - 1,
+                    is_closed: true,
+                }
+            })
+            .collect()
+    }
+
+    // all stored fields of klines are equal
+    fn assert_same_klines(loaded: &[Kline], klines: &[Kline]) {
+        assert_eq!(loaded.len(), klines.len());
+
+        for (loaded, kline) in loaded.iter().zip(klines.iter()) {
+            assert_eq!(loaded.symbol, kline.symbol);
+            assert_eq!(loaded.interval, kline.interval);
+            assert_eq!(loaded.open, kline.open);
+            assert_eq!(loaded.high, kline.high);
+            assert_eq!(loaded.low, kline.low);
+            assert_eq!(loaded.close, kline.close);
+            assert_eq!(loaded.volume, kline.volume);
+            assert_eq!(loaded.open_time, kline.open_time);
+            assert_eq!(loaded.close_time, kline.close_time);
+            assert_eq!(loaded.is_closed, kline.is_closed);
         }
+    }
 
-        Self {
-            app_directory,
-            data_directory,
+    fn compressed_storage_manager(level: CompressionLevel) -> (TempDir, StorageManager) {
+        let dir = TempDir::new().unwrap();
+        let mut storage_manager = StorageManager::new(dir.path().to_path_buf());
+        storage_manager.set_compress_klines(true);
+        storage_manager.set_compression_levels(level, CompressionLevel::Best);
+
+        (dir, storage_manager)
+    }
+
+    fn archive_storage_manager(level: CompressionLevel) -> (TempDir, StorageManager) {
+        let dir = TempDir::new().unwrap();
+        let mut storage_manager = StorageManager::new(dir.path().to_path_buf());
+        storage_manager.set_compression_levels(CompressionLevel::Default, level);
+
+        (dir, storage_manager)
+    }
+
+    #[test]
+    fn compression_levels_reload_klines_and_differ_in_size() {
+        let klines = test_klines(300);
+        let kline_key = MarketData::build_kline_key("BTC-USDT", "1m");
+        let filename = MarketData::build_kline_filename(&kline_key, BASE_TS);
+
+        let (_fast_dir, fast) = archive_storage_manager(CompressionLevel::Fast);
+        let (_best_dir, best) = archive_storage_manager(CompressionLevel::Best);
+        for storage_manager in [&fast, &best] {
+            storage_manager.save_klines(&klines, &kline_key).unwrap();
+            storage_manager.archive_klines(&filename).unwrap();
         }
+
+        let file_size = |storage_manager: &StorageManager| {
+            let path = storage_manager
+                .data_directory
+                .join("market")
+                .join("klines")
+                .join(format!("{filename}.gz"));
+            fs::metadata(path).unwrap().len()
+        };
+        assert!(file_size(&best) < file_size(&fast));
+
+        for storage_manager in [&fast, &best] {
+            let loaded = storage_manager.load_klines(&filename).unwrap().unwrap();
+            assert_same_klines(&loaded, &klines);
+        }
+    }
+
+    #[cfg(feature = "parquet")]
+    #[test]
+    fn parquet_round_trip_keeps_klines() {
+        let dir = TempDir::new().unwrap();
+        let storage_manager = StorageManager::new(dir.path().to_path_buf());
+        let mut klines = test_klines(1_000);
+        // latest kline is still open
+        klines.last_mut().unwrap().is_closed = false;
+
+        storage_manager
+            .save_klines_parquet(&klines, "BTC-USDT@kline_1m.parquet")
+            .unwrap();
+        let loaded = storage_manager
+            .load_klines_parquet("BTC-USDT@kline_1m.parquet")
+            .unwrap();
+
+        assert_same_klines(&loaded, &klines);
+    }
+
+    #[cfg(feature = "parquet")]
+    #[test]
+    fn parquet_without_is_closed_column_loads_closed_klines() {
+        let dir = TempDir::new().unwrap();
+        let storage_manager = StorageManager::new(dir.path().to_path_buf());
+        let klines = test_klines(10);
+
+        // schema of files saved before klines had is_closed
+        let legacy_schema = KLINE_PARQUET_SCHEMA.replace("REQUIRED BOOLEAN is_closed;", "");
+        storage_manager
+            .write_klines_parquet(&klines, "BTC-USDT@kline_1m.parquet", &legacy_schema)
+            .unwrap();
+        let loaded = storage_manager
+            .load_klines_parquet("BTC-USDT@kline_1m.parquet")
+            .unwrap();
+
+        assert_eq!(loaded.len(), klines.len());
+        assert!(loaded.iter().all(|kline| kline.is_closed));
+    }
+
+    #[test]
+    fn compressed_klines_are_smaller_and_load_identically() {
+        let klines = test_klines(300);
+        let kline_key = MarketData::build_kline_key("BTC-USDT", "1m");
+        let filename = MarketData::build_kline_filename(&kline_key, BASE_TS);
+
+        let plain_dir = TempDir::new().unwrap();
+        let plain = StorageManager::new(plain_dir.path().to_path_buf());
+        let (compressed_dir, compressed) = compressed_storage_manager(CompressionLevel::Default);
+        plain.save_klines(&klines, &kline_key).unwrap();
+        compressed.save_klines(&klines, &kline_key).unwrap();
+
+        let plain_path = plain_dir.path().join("market/klines").join(&filename);
+        let compressed_klines_dir = compressed_dir.path().join("market/klines");
+        let compressed_path = compressed_klines_dir.join(format!("{filename}.gz"));
+        assert!(!compressed_klines_dir.join(&filename).exists());
+        assert!(
+            fs::metadata(compressed_path).unwrap().len() < fs::metadata(plain_path).unwrap().len()
+        );
+
+        // same filename is read whether file is compressed or not
+        assert_same_klines(&plain.load_klines(&filename).unwrap().unwrap(), &klines);
+        assert_same_klines(
+            &compressed.load_klines(&filename).unwrap().unwrap(),
+            &klines,
+        );
+    }
+
+    #[test]
+    fn available_kline_files_and_range() {
+        // 2023-10-15T00:00:00Z
+        let october_ts = 1_697_328_000_000;
+        let dir = TempDir::new().unwrap();
+        let storage_manager = StorageManager::new(dir.path().to_path_buf());
+        let kline_key = MarketData::build_kline_key("BTC-USDT", "1m");
+
+        storage_manager
+            .save_klines(&test_klines_from(october_ts, 10), &kline_key)
+            .unwrap();
+        storage_manager
+            .save_klines(&test_klines(20), &kline_key)
+            .unwrap();
+        // files of other keys are not listed
+        storage_manager
+            .save_klines(
+                &test_klines(5),
+                &MarketData::build_kline_key("BTC-USDT", "5m"),
+            )
+            .unwrap();
+
+        assert_eq!(
+            storage_manager.list_kline_files(&kline_key),
+            vec![(2023, 10), (2023, 11)]
+        );
+        assert_eq!(
+            storage_manager.available_range(&kline_key),
+            Some((october_ts, BASE_TS + 19 * MINUTE))
+        );
+
+        let missing_key = MarketData::build_kline_key("ETH-USDT", "1m");
+        assert!(storage_manager.list_kline_files(&missing_key).is_empty());
+        assert_eq!(storage_manager.available_range(&missing_key), None);
+    }
+
+    #[test]
+    fn exchanges_store_same_symbol_in_separate_files() {
+        let dir = TempDir::new().unwrap();
+        let mut bingx = StorageManager::new(dir.path().to_path_buf());
+        bingx.set_exchange("bingx");
+        let mut binance = StorageManager::new(dir.path().to_path_buf());
+        binance.set_exchange("binance");
+
+        let kline_key = MarketData::build_kline_key("BTC-USDT", "1m");
+        let filename = MarketData::build_kline_filename(&kline_key, BASE_TS);
+        let bingx_klines = test_klines(10);
+        let binance_klines = test_klines_from(BASE_TS + 10 * MINUTE, 5);
+        bingx.save_klines(&bingx_klines, &kline_key).unwrap();
+        binance.save_klines(&binance_klines, &kline_key).unwrap();
+
+        assert_ne!(bingx.kline_directory(), binance.kline_directory());
+        assert!(dir
+            .path()
+            .join("market/klines/bingx")
+            .join(&filename)
+            .exists());
+        assert!(dir
+            .path()
+            .join("market/klines/binance")
+            .join(&filename)
+            .exists());
+        assert_same_klines(
+            &bingx.load_klines(&filename).unwrap().unwrap(),
+            &bingx_klines,
+        );
+        assert_same_klines(
+            &binance.load_klines(&filename).unwrap().unwrap(),
+            &binance_klines,
+        );
+    }
+
+    #[test]
+    fn legacy_kline_files_are_moved_to_exchange_directory() {
+        let dir = TempDir::new().unwrap();
+        let legacy = StorageManager::new(dir.path().to_path_buf());
+        let kline_key = MarketData::build_kline_key("BTC-USDT", "1m");
+        let filename = MarketData::build_kline_filename(&kline_key, BASE_TS);
+        let klines = test_klines(10);
+        legacy.save_klines(&klines, &kline_key).unwrap();
+
+        let mut bingx = StorageManager::new(dir.path().to_path_buf());
+        bingx.set_exchange("bingx");
+        assert_eq!(bingx.migrate_legacy_klines("bingx").unwrap(), 1);
+
+        assert!(!legacy.kline_directory().join(&filename).exists());
+        assert_same_klines(&bingx.load_klines(&filename).unwrap().unwrap(), &klines);
+        assert_eq!(bingx.migrate_legacy_klines("bingx").unwrap(), 0);
+    }
+
+    #[test]
+    fn files_are_created_under_base_path() {
+        let dir = TempDir::new().unwrap();
+        let base_path = dir.path().join("data").join("raderbot");
+        let storage_manager = StorageManager::new(base_path.clone());
+
+        // missing base path is created
+        assert!(base_path.is_dir());
+        assert_eq!(storage_manager.base_path(), base_path.as_path());
+        assert!(storage_manager.kline_directory().starts_with(&base_path));
+
+        let kline_key = MarketData::build_kline_key("BTC-USDT", "1m");
+        storage_manager
+            .save_klines(&test_klines(10), &kline_key)
+            .unwrap();
+        storage_manager
+            .dead_letter_log()
+            .log("BTC-USDT@ticker", "not a ticker")
+            .unwrap();
+
+        let filename = MarketData::build_kline_filename(&kline_key, BASE_TS);
+        assert!(base_path.join("market/klines").join(filename).is_file());
+        assert!(base_path.join("dead_letter.log").is_file());
+    }
+
+    #[test]
+    fn failed_write_keeps_previous_file() {
+        let dir = TempDir::new().unwrap();
+        let storage_manager = StorageManager::new(dir.path().to_path_buf());
+        let kline_key = MarketData::build_kline_key("BTC-USDT", "1m");
+        let filename = MarketData::build_kline_filename(&kline_key, BASE_TS);
+        let klines = test_klines(10);
+        storage_manager.save_klines(&klines, &kline_key).unwrap();
+
+        // process dies part way through writing new file
+        let file_path = storage_manager.kline_directory().join(&filename);
+        let res = write_file_atomic(&file_path, |mut file| {
+            file.write_all(b"BTC-USDT,1m,100.5,101.0,99.5,100.75,12.5,17000")?;
+            Err(io::Error::other("killed mid write"))
+        });
+        assert!(res.is_err());
+
+        let mut tmp_path = file_path.as_os_str().to_owned();
+        tmp_path.push(".tmp");
+        assert!(!PathBuf::from(tmp_path).exists());
+        assert_same_klines(
+            &storage_manager.load_klines(&filename).unwrap().unwrap(),
+            &klines,
+        );
+    }
+
+    #[test]
+    fn corrupt_kline_file_is_skipped_on_load() {
+        let dir = TempDir::new().unwrap();
+        let storage_manager = StorageManager::new(dir.path().to_path_buf());
+        let kline_key = MarketData::build_kline_key("BTC-USDT", "1m");
+        let filename = MarketData::build_kline_filename(&kline_key, BASE_TS);
+
+        fs::create_dir_all(storage_manager.kline_directory()).unwrap();
+        fs::write(
+            storage_manager.kline_directory().join(&filename),
+            "not,a,kline\n1,2\n3,4\n",
+        )
+        .unwrap();
+
+        assert!(storage_manager.load_klines(&filename).unwrap().is_none());
+    }
+
+    #[test]
+    fn corrupt_version_2_kline_file_is_skipped_on_load() {
+        let dir = TempDir::new().unwrap();
+        let storage_manager = StorageManager::new(dir.path().to_path_buf());
+        let kline_key = MarketData::build_kline_key("BTC-USDT", "1m");
+        let filename = MarketData::build_kline_filename(&kline_key, BASE_TS);
+
+        fs::create_dir_all(storage_manager.kline_directory()).unwrap();
+        fs::write(
+            storage_manager.kline_directory().join(&filename),
+            format!("{KLINE_SCHEMA_MARKER}{KLINE_SCHEMA_VERSION}\nnot,a,kline,header\n1,2\n3,4\n"),
+        )
+        .unwrap();
+
+        assert!(storage_manager.load_klines(&filename).unwrap().is_none());
+    }
+
+    #[test]
+    fn version_1_kline_file_loads_with_default_fields() {
+        let dir = TempDir::new().unwrap();
+        let storage_manager = StorageManager::new(dir.path().to_path_buf());
+        let kline_key = MarketData::build_kline_key("BTC-USDT", "1m");
+        let filename = MarketData::build_kline_filename(&kline_key, BASE_TS);
+
+        // version 1 files have no marker, header or is_closed column
+        let close_time = BASE_TS + MINUTE - 1;
+        fs::create_dir_all(storage_manager.kline_directory()).unwrap();
+        fs::write(
+            storage_manager.kline_directory().join(&filename),
+            format!("BTC-USDT,1m,100.5,101.0,99.5,100.75,12.5,{BASE_TS},{close_time}\n"),
+        )
+        .unwrap();
+
+        let loaded = storage_manager.load_klines(&filename).unwrap().unwrap();
+
+        assert_eq!(loaded.len(), 1);
+        assert_eq!(loaded[0].symbol, "BTC-USDT");
+        assert_eq!(loaded[0].close, 100.75);
+        assert_eq!(loaded[0].open_time, BASE_TS);
+        assert_eq!(loaded[0].close_time, close_time);
+        assert!(!loaded[0].is_closed);
+    }
+
+    #[test]
+    fn saved_kline_file_has_schema_marker_and_header() {
+        let dir = TempDir::new().unwrap();
+        let storage_manager = StorageManager::new(dir.path().to_path_buf());
+        let kline_key = MarketData::build_kline_key("BTC-USDT", "1m");
+        let filename = MarketData::build_kline_filename(&kline_key, BASE_TS);
+        let klines = test_klines(3);
+
+        storage_manager.save_klines(&klines, &kline_key).unwrap();
+
+        let contents =
+            fs::read_to_string(storage_manager.kline_directory().join(&filename)).unwrap();
+        let mut lines = contents.lines();
+        assert_eq!(
+            lines.next(),
+            Some(format!("{KLINE_SCHEMA_MARKER}{KLINE_SCHEMA_VERSION}").as_str())
+        );
+        assert_eq!(
+            lines.next(),
+            Some("symbol,interval,open,high,low,close,volume,open_time,close_time,is_closed")
+        );
+
+        let loaded = storage_manager.load_klines(&filename).unwrap().unwrap();
+        assert_same_klines(&loaded, &klines);
+        assert!(loaded.iter().all(|kline| kline.is_closed));
     }
 }
@@ -1 +1,3 @@
+pub mod dead_letter;
+pub mod kline_cache;
 pub mod manager;
@@ -0,0 +1,27 @@
+use async_trait::async_trait;
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc,
+};
+
+use crate::market::{kline::Kline, market::Market};
+
+use super::strategy::Strategy;
+
+/// Counts kline updates and closed klines it is called with
+#[derive(Default)]
+pub struct CountingStrategy {
+    pub klines: Arc<AtomicUsize>,
+    pub closed_klines: Arc<AtomicUsize>,
+}
+
+#[async_trait]
+impl Strategy for CountingStrategy {
+    async fn on_kline(&mut self, _kline: &Kline, _market: &Market) {
+        self.klines.fetch_add(1, Ordering::SeqCst);
+    }
+
+    async fn on_kline_closed(&mut self, _kline: &Kline, _market: &Market) {
+        self.closed_klines.fetch_add(1, Ordering::SeqCst);
+    }
+}
@@ -1 +1,4 @@
+#[cfg(test)]
+pub mod mock;
+#[allow(clippy::module_inception)]
 pub mod strategy;
@@ -1 +1,235 @@
-pub struct Strategy {}
+use async_trait::async_trait;
+use tokio::{sync::broadcast::error::RecvError, task::JoinHandle};
+use tracing::warn;
+
+use crate::market::{
+    kline::Kline, market::Market, messages::MarketMessage, ticker::Ticker, types::ArcMutex,
+};
+
+/// Trading strategy which reacts to market updates, orders are
+/// placed through exchange API of market, see `Market::exchange_api`.
+/// Market is locked while strategy handles update, so strategies
+/// should return quickly to not block web routes and stream monitor
+#[async_trait]
+pub trait Strategy: Send + Sync {
+    /// Called on every kline update, including in progress klines
+    async fn on_kline(&mut self, kline: &Kline, market: &Market);
+
+    async fn on_ticker(&mut self, _ticker: &Ticker, _market: &Market) {}
+
+    /// Called once when kline is complete, most strategies
+    /// should only act on closed klines
+    async fn on_kline_closed(&mut self, _kline: &Kline, _market: &Market) {}
+}
+
+/// Dispatches market messages to registered strategies
+pub struct StrategyRunner {
+    market: ArcMutex<Market>,
+    strategies: ArcMutex<Vec<Box<dyn Strategy>>>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl StrategyRunner {
+    pub fn new(market: ArcMutex<Market>) -> Self {
+        Self {
+            market,
+            strategies: ArcMutex::new(vec![]),
+            handle: None,
+        }
+    }
+
+    #[cfg(test)]
+    pub async fn add_strategy(&self, strategy: Box<dyn Strategy>) {
+        self.strategies.lock().await.push(strategy);
+    }
+
+    /// Subscribe to market messages and dispatch to strategies until stopped
+    pub async fn start(&mut self) {
+        if self.handle.is_some() {
+            return;
+        }
+
+        let mut market_receiver = self.market.lock().await.subscribe();
+        let market = self.market.clone();
+        let strategies = self.strategies.clone();
+
+        self.handle = Some(tokio::spawn(async move {
+            loop {
+                let message = match market_receiver.recv().await {
+                    Ok(message) => message,
+                    Err(RecvError::Lagged(skipped)) => {
+                        warn!("Strategy runner not keeping up, skipped messages: {skipped}");
+                        continue;
+                    }
+                    Err(RecvError::Closed) => break,
+                };
+
+                let market = market.lock().await;
+                for strategy in strategies.lock().await.iter_mut() {
+                    match &message {
+                        MarketMessage::UpdateKline(kline) => {
+                            strategy.on_kline(kline, &market).await
+                        }
+                        MarketMessage::KlineClosed(kline) => {
+                            strategy.on_kline_closed(kline, &market).await
+                        }
+                        MarketMessage::UpdateTicker(ticker) => {
+                            strategy.on_ticker(ticker, &market).await
+                        }
+                    }
+                }
+            }
+        }));
+    }
+
+    pub fn stop(&mut self) {
+        if let Some(handle) = self.handle.take() {
+            handle.abort();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::{atomic::Ordering, Arc},
+        time::Duration,
+    };
+
+    use tempfile::TempDir;
+    use tokio::sync::mpsc::{unbounded_channel, UnboundedSender};
+
+    use super::*;
+    use crate::{
+        exchange::{api::ExchangeApi, paper::PaperExchange, types::StreamType},
+        market::{
+            interval::Interval,
+            market::{MarketConfig, MarketData},
+        },
+        storage::manager::StorageManager,
+        strategy::mock::CountingStrategy,
+        utils::{
+            channel::{build_arc_channel, DEFAULT_CHANNEL_CAPACITY},
+            time::generate_ts,
+        },
+    };
+
+    // 2023-11-14T22:13:00Z, start of minute
+    const BASE_TS: u64 = 1_700_000_000_000 - 1_700_000_000_000 % MINUTE;
+    const MINUTE: u64 = 60_000;
+
+    /// Sends open time of each kline with time strategy sees when handling it
+    struct ClockStrategy {
+        sender: UnboundedSender<(u64, u64)>,
+    }
+
+    #[async_trait]
+    impl Strategy for ClockStrategy {
+        async fn on_kline(&mut self, kline: &Kline, market: &Market) {
+            let now = market.exchange_api().clock().now();
+            let _ = self.sender.send((kline.open_time, now));
+        }
+    }
+
+    fn test_klines(len: u64) -> Vec<Kline> {
+        (0..len)
+            .map(|i| Kline {
+                symbol: "BTC-USDT".to_string(),
+                interval: "1m".to_string(),
+                open_time: BASE_TS + i * MINUTE,
+                close_time: BASE_TS + (i + 1) * MINUTE - 1,
+                ..Default::default()
+            })
+            .collect()
+    }
+
+    // market on paper exchange replaying stored klines
+    async fn replay_market(klines: &[Kline]) -> (TempDir, ArcMutex<Market>) {
+        let dir = tempfile::tempdir().unwrap();
+        let storage_manager = StorageManager::new(dir.path().to_path_buf());
+        storage_manager
+            .save_klines(klines, &MarketData::build_kline_key("BTC-USDT", "1m"))
+            .unwrap();
+
+        let (market_sender, market_receiver) = build_arc_channel(DEFAULT_CHANNEL_CAPACITY);
+        let exchange_api: Arc<Box<dyn ExchangeApi>> = Arc::new(Box::new(PaperExchange::new(
+            storage_manager.clone(),
+            market_sender,
+            10_000.0,
+            Duration::from_millis(100),
+        )));
+        let config = MarketConfig {
+            backup_interval: None,
+            ..MarketConfig::default()
+        };
+
+        let market = Market::new(market_receiver, exchange_api, storage_manager, config).await;
+
+        (dir, ArcMutex::new(market))
+    }
+
+    #[tokio::test]
+    async fn strategy_sees_replayed_kline_time() {
+        let klines = test_klines(3);
+        let (_dir, market) = replay_market(&klines).await;
+
+        let (sender, mut receiver) = unbounded_channel();
+        let mut strategy_runner = StrategyRunner::new(market.clone());
+        strategy_runner
+            .add_strategy(Box::new(ClockStrategy { sender }))
+            .await;
+        strategy_runner.start().await;
+
+        let exchange_api = market.lock().await.exchange_api();
+        exchange_api
+            .open_stream(StreamType::Kline, "BTC-USDT", Some(Interval::Min1))
+            .await
+            .unwrap();
+
+        for kline in klines.iter() {
+            let (open_time, now) = tokio::time::timeout(Duration::from_secs(5), receiver.recv())
+                .await
+                .unwrap()
+                .unwrap();
+
+            assert_eq!(open_time, kline.open_time);
+            assert_eq!(now, kline.open_time);
+            assert!(now < generate_ts());
+        }
+
+        strategy_runner.stop();
+        market.lock().await.shutdown().await;
+    }
+
+    #[tokio::test]
+    async fn runner_dispatches_klines_to_strategy() {
+        let (_dir, market) = replay_market(&test_klines(3)).await;
+
+        let strategy = CountingStrategy::default();
+        let klines = strategy.klines.clone();
+        let closed_klines = strategy.closed_klines.clone();
+        let mut strategy_runner = StrategyRunner::new(market.clone());
+        strategy_runner.add_strategy(Box::new(strategy)).await;
+        strategy_runner.start().await;
+
+        let exchange_api = market.lock().await.exchange_api();
+        exchange_api
+            .open_stream(StreamType::Kline, "BTC-USDT", Some(Interval::Min1))
+            .await
+            .unwrap();
+
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while closed_klines.load(Ordering::SeqCst) < 3 {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .unwrap();
+        strategy_runner.stop();
+        market.lock().await.shutdown().await;
+
+        // replayed klines are complete, each is closed once
+        assert_eq!(klines.load(Ordering::SeqCst), 3);
+        assert_eq!(closed_klines.load(Ordering::SeqCst), 3);
+    }
+}
@@ -1,29 +1,51 @@
 use std::sync::Arc;
 
 use futures_util::lock::Mutex;
-use serde_json::Value;
-use tokio::sync::mpsc::unbounded_channel;
+use tokio::sync::mpsc::channel;
 
-use crate::market::types::{ArcReceiver, ArcSender};
+use crate::market::types::{ArcReceiver, ArcSender, BoundedSender};
 
-pub fn build_arc_channel<T>() -> (ArcSender<T>, ArcReceiver<T>) {
-    let (sender, receiver) = unbounded_channel::<T>();
+pub const DEFAULT_CHANNEL_CAPACITY: usize = 1000;
+
+pub fn build_arc_channel<T>(capacity: usize) -> (ArcSender<T>, ArcReceiver<T>) {
+    let (sender, receiver) = channel::<T>(capacity);
 
     let receiver = Arc::new(Mutex::new(receiver));
-    let sender = Arc::new(sender);
+    let sender = Arc::new(BoundedSender::new(sender));
 
     (sender, receiver)
 }
 
-pub async fn get_ticker(url: &str) -> String {
-    let client = reqwest::Client::new();
-
-    let response = client.get(url).send().await;
-
-    let response = match response {
-        Ok(res) => res.json::<Value>().await.unwrap().to_string(),
-        Err(e) => format!("{e:?}"),
-    };
-
-    response
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn full_channel_drops_messages_instead_of_blocking() {
+        let (sender, receiver) = build_arc_channel::<u32>(2);
+
+        assert!(sender.send(1).is_ok());
+        assert!(sender.send(2).is_ok());
+        assert!(sender.send(3).is_err());
+        assert!(sender.send(4).is_err());
+        assert_eq!(sender.dropped_messages(), 2);
+
+        let mut receiver = receiver.lock().await;
+        assert_eq!(receiver.recv().await, Some(1));
+
+        // space freed once receiver catches up
+        assert!(sender.send(5).is_ok());
+        assert_eq!(sender.dropped_messages(), 2);
+        assert_eq!(receiver.recv().await, Some(2));
+        assert_eq!(receiver.recv().await, Some(5));
+    }
+
+    #[tokio::test]
+    async fn closed_channel_counts_dropped_messages() {
+        let (sender, receiver) = build_arc_channel::<u32>(DEFAULT_CHANNEL_CAPACITY);
+        drop(receiver);
+
+        assert!(sender.send(1).is_err());
+        assert_eq!(sender.dropped_messages(), 1);
+    }
 }
@@ -0,0 +1,117 @@
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc,
+};
+#[cfg(test)]
+use std::time::Duration;
+
+use crate::{market::kline::Kline, utils::time::generate_ts};
+
+/// Source of current time in milliseconds
+pub trait Clock: Send + Sync {
+    fn now(&self) -> u64;
+}
+
+/// Wall clock time
+#[derive(Default, Debug, Clone)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> u64 {
+        generate_ts()
+    }
+}
+
+pub fn system_clock() -> Arc<dyn Clock> {
+    Arc::new(SystemClock)
+}
+
+/// Clock which only moves when set or advanced, used to control time in tests
+#[cfg(test)]
+#[derive(Default, Debug)]
+pub struct MockClock {
+    now: AtomicU64,
+}
+
+#[cfg(test)]
+impl MockClock {
+    pub fn new(start_ts: u64) -> Self {
+        Self {
+            now: AtomicU64::new(start_ts),
+        }
+    }
+
+    pub fn set(&self, ts: u64) {
+        self.now.store(ts, Ordering::SeqCst);
+    }
+
+    pub fn advance(&self, duration: Duration) {
+        self.now
+            .fetch_add(duration.as_millis() as u64, Ordering::SeqCst);
+    }
+}
+
+#[cfg(test)]
+impl Clock for MockClock {
+    fn now(&self) -> u64 {
+        self.now.load(Ordering::SeqCst)
+    }
+}
+
+/// Deterministic clock used when replaying klines, time only moves
+/// when advanced by kline timestamps and never goes backwards
+#[derive(Default, Debug)]
+pub struct BacktestClock {
+    now: AtomicU64,
+}
+
+impl BacktestClock {
+    pub fn advance_to(&self, ts: u64) {
+        self.now.fetch_max(ts, Ordering::SeqCst);
+    }
+
+    /// Set simulated time to open time of kline being replayed
+    pub fn advance_to_kline(&self, kline: &Kline) {
+        self.advance_to(kline.open_time);
+    }
+}
+
+impl Clock for BacktestClock {
+    fn now(&self) -> u64 {
+        self.now.load(Ordering::SeqCst)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn backtest_clock_follows_replayed_klines_without_going_back() {
+        let clock = BacktestClock::default();
+        let kline = |open_time| Kline {
+            open_time,
+            ..Default::default()
+        };
+
+        clock.advance_to_kline(&kline(1_700_000_060_000));
+        assert_eq!(clock.now(), 1_700_000_060_000);
+
+        // out of order kline does not move time backwards
+        clock.advance_to_kline(&kline(1_700_000_000_000));
+        assert_eq!(clock.now(), 1_700_000_060_000);
+    }
+
+    #[test]
+    fn mock_clock_only_moves_when_advanced_or_set() {
+        let clock = MockClock::new(1_700_000_000_000);
+        assert_eq!(clock.now(), 1_700_000_000_000);
+        assert_eq!(clock.now(), 1_700_000_000_000);
+
+        clock.advance(Duration::from_secs(90));
+        assert_eq!(clock.now(), 1_700_000_090_000);
+
+        clock.set(1_600_000_000_000);
+        assert_eq!(clock.now(), 1_600_000_000_000);
+    }
+}
@@ -12,9 +12,5 @@ pub fn sign_hmac(secret: &str, message: &str) -> String {
     let result = hmac.finalize();
 
     // Convert the HMAC value to a string
-    let hmac_string = hex::encode(result.into_bytes());
-
-    println!("HMAC: {}", hmac_string);
-
-    hmac_string
+    hex::encode(result.into_bytes())
 }
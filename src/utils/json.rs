@@ -1,13 +1,10 @@
 use flate2::read::GzDecoder;
-use serde_json::Value;
 use std::io::Read;
 
-pub fn parse_gzip_to_json(gzip_data: Vec<u8>) -> Result<Value, Box<dyn std::error::Error>> {
-    let mut decoder = GzDecoder::new(gzip_data.as_slice());
-    let mut json_string = String::new();
-    decoder.read_to_string(&mut json_string)?;
+pub fn parse_gzip_to_string(gzip_data: &[u8]) -> Result<String, Box<dyn std::error::Error>> {
+    let mut decoder = GzDecoder::new(gzip_data);
+    let mut decoded = String::new();
+    decoder.read_to_string(&mut decoded)?;
 
-    let json: Value = serde_json::from_str(&json_string)?;
-
-    Ok(json)
+    Ok(decoded)
 }
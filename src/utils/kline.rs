@@ -1,16 +1,15 @@
 use chrono::Datelike;
 
-use serde::{Deserialize, Serialize};
-
 use std::fs::File;
 
-use std::io::BufRead;
+use tracing::debug;
 
 use crate::{
     market::{
         kline::{BinanceKline, Kline},
         market::MarketData,
     },
+    storage::manager::write_klines,
     utils::{csv::has_header, time::timestamp_to_datetime},
 };
 use csv::Reader;
@@ -21,7 +20,7 @@ pub fn load_binance_klines(
     interval: &str,
 ) -> Vec<Kline> {
     let filepath_str = file_path.as_os_str().to_str().unwrap();
-    println!("filepath: {filepath_str}");
+    debug!("Loading binance klines from: {filepath_str}");
     let file = File::open(file_path.clone())
         .unwrap_or_else(|_| panic!("Unable to open file {}", filepath_str));
 
@@ -68,6 +67,8 @@ pub fn load_binance_klines(
             close: binance_kline.close,
             volume: binance_kline.volume,
             close_time: binance_kline.close_time,
+            // binance data dumps only contain complete klines
+            is_closed: true,
         };
         klines.push(kline);
     }
@@ -92,16 +93,9 @@ pub fn save_klines(filename: std::path::PathBuf, klines: &[Kline], _append: bool
     //     .open(&filename)
     //     .expect(&format!("unable to open file: {}", str_filename));
 
-    let mut writer = csv::WriterBuilder::new()
-        .has_headers(false)
-        .from_writer(&file);
-
-    for kline in klines {
-        // Serialize and write all klines to the file
-        writer.serialize(kline.clone()).unwrap_or_else(|_| {
-            panic!("Unable to save kline: {:?} to file:{}", kline, str_filename)
-        });
-    }
+    // write in same format as storage manager so file can be loaded by market
+    write_klines(&file, klines)
+        .unwrap_or_else(|_| panic!("Unable to save klines to file:{}", str_filename));
 }
 
 pub fn generate_kline_filenames_in_range(kline_key: &str, from_ts: u64, to_ts: u64) -> Vec<String> {
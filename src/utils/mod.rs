@@ -1,7 +1,9 @@
 pub mod channel;
+pub mod clock;
 pub mod crypt;
 pub mod csv;
 pub mod json;
 pub mod kline;
 pub mod number;
+pub mod rate_limit;
 pub mod time;
@@ -2,8 +2,8 @@ use rand::Rng;
 
 use std::collections::HashMap;
 
-use crate::exchange::types::ApiError;
 use crate::exchange::types::ApiResult;
+use crate::exchange::types::ExchangeError;
 
 use serde_json::Value;
 
@@ -23,7 +23,15 @@ pub fn parse_f64_from_lookup(key: &str, lookup: &HashMap<String, Value>) -> ApiR
 
     match num {
         Ok(num) => Ok(num),
-        Err(e) => Err(ApiError::Parsing(e.to_string())),
+        Err(e) => Err(ExchangeError::Parsing(e.to_string())),
+    }
+}
+
+/// Parse optional number sent as string or number, None if missing or invalid
+pub fn parse_optional_f64_from_lookup(key: &str, lookup: &HashMap<String, Value>) -> Option<f64> {
+    match lookup.get(key)? {
+        Value::String(num) => num.parse::<f64>().ok(),
+        num => num.as_f64(),
     }
 }
 
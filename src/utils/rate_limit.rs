@@ -0,0 +1,16 @@
+use std::num::NonZeroU32;
+use std::sync::Arc;
+
+use governor::{DefaultDirectRateLimiter, Quota};
+
+pub type ArcRateLimiter = Arc<DefaultDirectRateLimiter>;
+
+/// Quota allowing number of requests each second, bursts of up to
+/// the same number of requests are allowed, zero is treated as one
+pub fn requests_per_second(requests: u32) -> Quota {
+    Quota::per_second(NonZeroU32::new(requests).unwrap_or(NonZeroU32::MIN))
+}
+
+pub fn build_rate_limiter(quota: Quota) -> ArcRateLimiter {
+    Arc::new(DefaultDirectRateLimiter::direct(quota))
+}
@@ -2,9 +2,11 @@ use chrono::prelude::DateTime;
 
 use chrono::NaiveDate;
 use chrono::Utc;
-use chrono::{NaiveDateTime, TimeZone};
 
 use std::time::SystemTime;
+use tracing::{debug, warn};
+
+use crate::market::interval::Interval;
 
 pub fn generate_ts() -> u64 {
     let now = SystemTime::now()
@@ -24,10 +26,7 @@ pub fn timestamp_to_datetime(timestamp: u64) -> DateTime<Utc> {
         s_ts.push('0');
     }
     let n_ts = s_ts.parse::<u64>().unwrap();
-    let naive =
-        NaiveDateTime::from_timestamp_opt(n_ts as i64 / 1000, (n_ts % 1000) as u32 * 1_000_000)
-            .unwrap();
-    DateTime::<Utc>::from_utc(naive, Utc)
+    DateTime::<Utc>::from_timestamp(n_ts as i64 / 1000, (n_ts % 1000) as u32 * 1_000_000).unwrap()
 }
 
 pub fn year_month_day_to_ts(year: u32, month: u32, day: u32) -> Option<u64> {
@@ -36,7 +35,7 @@ pub fn year_month_day_to_ts(year: u32, month: u32, day: u32) -> Option<u64> {
     match date {
         Some(date) => {
             if let Some(date) = date.and_hms_opt(0, 0, 0) {
-                let timestamp = date.timestamp() as u64;
+                let timestamp = date.and_utc().timestamp() as u64;
                 let mut s_ts = format!("{}", timestamp);
                 while s_ts.len() < 13 {
                     s_ts.push('0');
@@ -70,28 +69,37 @@ fn convert_timestamp(timestamp: u64) {
     let days = (timestamp / (1000 * 60 * 60 * 24)) % 365;
     let years = timestamp / (1000 * 60 * 60 * 24 * 365);
 
-    // Print the converted values
-    println!("Years: {}", years);
-    println!("Days: {}", days);
-    println!("Hours: {}", hours);
-    println!("Minutes: {}", minutes);
-    println!("Seconds: {}", seconds);
-    println!("Milliseconds: {}", milliseconds);
+    debug!(
+        years,
+        days, hours, minutes, seconds, milliseconds, "Time difference"
+    );
+}
+
+/// Length of kline interval in milliseconds, eg. "5m" or "1h",
+/// month intervals are not fixed length and return None
+pub fn interval_to_millis(interval: &str) -> Option<u64> {
+    match interval.parse::<Interval>().ok()? {
+        Interval::Month1 => None,
+        interval => Some(interval.to_duration().as_millis() as u64),
+    }
+}
+
+/// Close time of kline is last millisecond before next kline opens,
+/// close time of month intervals is unknown so open time is returned
+pub fn calculate_kline_close_time(open_time: u64, interval: &str) -> u64 {
+    interval_to_millis(interval)
+        .map(|millis| open_time + millis - 1)
+        .unwrap_or(open_time)
 }
 
+/// Open time of kline from its close time, close time is last
+/// millisecond before next kline opens
 pub fn calculate_kline_open_time(close_time: u64, interval: &str) -> u64 {
-    // Convert the interval to seconds
-    let interval_seconds = match interval {
-        "1m" => 60,
-        "5m" => 5 * 60,
-        "15m" => 15 * 60,
-        // Add more interval cases as needed
-        _ => {
-            println!("Unsupported interval: {}", interval);
-            return 0; // Return 0 if interval is unsupported
+    match interval_to_millis(interval) {
+        Some(millis) => (close_time + 1).saturating_sub(millis),
+        None => {
+            warn!("Unsupported interval: {}", interval);
+            close_time
         }
-    };
-
-    // Calculate the open time by subtracting interval seconds from the close time
-    (close_time + 1) - (interval_seconds * 1000)
+    }
 }
@@ -8,18 +8,30 @@ use actix_web::{
 use serde::Deserialize;
 use serde_json::json;
 
-use crate::account::trade::OrderSide;
-use crate::app::AppState;
+use crate::account::{sizing, trade::OrderSide};
+use crate::app::{AppState, MARGIN_ASSET};
 
 #[derive(Debug, Deserialize)]
 pub struct ClosePosParams {
-    position_id: u64,
+    position_id: String,
 }
 #[get("/close-position")]
-async fn close_position(_app_data: web::Data<AppState>, req: HttpRequest) -> impl Responder {
-    let _params = web::Query::<ClosePosParams>::from_query(req.query_string()).unwrap();
+async fn close_position(app_data: web::Data<AppState>, req: HttpRequest) -> impl Responder {
+    let params = web::Query::<ClosePosParams>::from_query(req.query_string()).unwrap();
+    let account = app_data.get_account().await;
+
+    let res = account
+        .lock()
+        .await
+        .close_position(&params.position_id)
+        .await;
 
-    let json_data = json!({ "success": "Position Closed","position_id":42 });
+    let json_data = match res {
+        Some(res) => {
+            json!({ "success": "Position Closed","position_id":params.position_id,"data":res })
+        }
+        None => json!({ "error": "Unable to close position" }),
+    };
 
     HttpResponse::Ok().json(json_data)
 }
@@ -58,6 +70,56 @@ async fn open_position(app_data: web::Data<AppState>, req: HttpRequest) -> impl
     }
 }
 
+#[derive(Debug, Deserialize)]
+pub struct FillVwapParams {
+    symbol: String,
+    since_ts: u64,
+}
+#[get("/fill-vwap")]
+async fn fill_vwap(app_data: web::Data<AppState>, req: HttpRequest) -> impl Responder {
+    let params = web::Query::<FillVwapParams>::from_query(req.query_string()).unwrap();
+    let account = app_data.get_account().await;
+    let position_tracker = account.lock().await.position_tracker();
+
+    let vwap = position_tracker
+        .lock()
+        .await
+        .recent_fill_vwap(&params.symbol, params.since_ts);
+
+    let json_data = json!({ "symbol": params.symbol, "vwap": vwap });
+
+    HttpResponse::Ok().json(json_data)
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PnlParams {
+    symbol: String,
+}
+#[get("/pnl")]
+async fn pnl(app_data: web::Data<AppState>, req: HttpRequest) -> impl Responder {
+    let params = web::Query::<PnlParams>::from_query(req.query_string()).unwrap();
+    let account = app_data.get_account().await;
+
+    let account = account.lock().await;
+    let pnl = account.pnl(&params.symbol).await;
+    let portfolio = account.portfolio();
+    let position = portfolio.lock().await.position(&params.symbol).cloned();
+
+    // unrealized PnL needs last price of symbol from market
+    let json_data = match pnl {
+        Some(pnl) => json!({
+            "symbol": params.symbol,
+            "realized": pnl.realized,
+            "unrealized": pnl.unrealized,
+            "total": pnl.total(),
+            "position": position,
+        }),
+        None => json!({ "error": "Last price not found", "symbol": params.symbol }),
+    };
+
+    HttpResponse::Ok().json(json_data)
+}
+
 #[get("/list-positions")]
 async fn list_positions(app_data: web::Data<AppState>, _req: HttpRequest) -> impl Responder {
     let account = app_data.get_account().await;
@@ -89,12 +151,39 @@ async fn get_account(app_data: web::Data<AppState>, _req: HttpRequest) -> impl R
     HttpResponse::Ok().json(json_data)
 }
 
+#[derive(Debug, Deserialize)]
+pub struct PositionSizeParams {
+    risk_pct: f64,
+    entry: f64,
+    stop: f64,
+}
+#[get("/position-size")]
+async fn position_size(app_data: web::Data<AppState>, req: HttpRequest) -> impl Responder {
+    let params = web::Query::<PositionSizeParams>::from_query(req.query_string()).unwrap();
+    let exchange_api = app_data.get_exchange_api().await;
+
+    // risk is sized from free balance of margin asset
+    let json_data = match exchange_api.get_balance(MARGIN_ASSET).await {
+        Ok(balance) => {
+            let quantity =
+                sizing::position_size(balance.free, params.risk_pct, params.entry, params.stop);
+            json!({ "balance": balance, "quantity": quantity })
+        }
+        Err(e) => json!({ "error": format!("Unable to get balance: {e}") }),
+    };
+
+    HttpResponse::Ok().json(json_data)
+}
+
 pub fn register_account_service() -> Scope {
     scope("/account")
         .service(get_account)
         .service(open_position)
         .service(close_position)
         .service(list_positions)
+        .service(fill_vwap)
+        .service(pnl)
+        .service(position_size)
     // .service(get_market_meta)
     // .service(get_kline_data)
     // .service(get_market_data)
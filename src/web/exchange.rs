@@ -1,3 +1,5 @@
+use std::time::Duration;
+
 use actix_web::HttpRequest;
 use actix_web::{
     get,
@@ -8,13 +10,17 @@ use actix_web::{
 use serde::Deserialize;
 use serde_json::json;
 
+use crate::account::trade::{MarginMode, OrderOptions, OrderSide, PositionSide};
 use crate::app::AppState;
+use crate::market::interval::Interval;
 
 #[get("/exchange-info")]
 async fn exchange_info(app_data: web::Data<AppState>, _req: HttpRequest) -> impl Responder {
-    let exchange = app_data.get_exchange_api().await;
+    let market = app_data.get_market().await;
 
-    let data = exchange
+    let data = market
+        .lock()
+        .await
         .exchange_info()
         .await
         .expect("Unable to get exchange info");
@@ -59,9 +65,15 @@ async fn get_kline(app_data: web::Data<AppState>, req: HttpRequest) -> impl Resp
     let exchange_api = app_data.get_exchange_api().await;
     let params = web::Query::<GetKlineParams>::from_query(req.query_string()).unwrap();
 
-    let kline = exchange_api
-        .get_kline(&params.symbol, &params.interval)
-        .await;
+    let interval = match params.interval.parse::<Interval>() {
+        Ok(interval) => interval,
+        Err(e) => {
+            let json_data = json!({ "error": e });
+            return HttpResponse::Ok().json(json_data);
+        }
+    };
+
+    let kline = exchange_api.get_kline(&params.symbol, interval).await;
 
     if let Ok(kline) = kline {
         // Return the stream data as JSON
@@ -87,8 +99,12 @@ async fn get_ticker(app_data: web::Data<AppState>, req: HttpRequest) -> impl Res
     let ticker = exchange_api.get_ticker(&params.symbol).await;
 
     if let Ok(ticker) = ticker {
-        // Return the stream data as JSON
-        let json_data = json!({ "ticker_data": ticker });
+        // spread and mid price are null if ticker has no bid and ask
+        let json_data = json!({
+            "ticker_data": ticker,
+            "spread": ticker.spread(),
+            "mid_price": ticker.mid_price(),
+        });
         HttpResponse::Ok().json(json_data)
     } else {
         let json_data = json!({ "error": "Ticker data not found" });
@@ -97,6 +113,237 @@ async fn get_ticker(app_data: web::Data<AppState>, req: HttpRequest) -> impl Res
     }
 }
 
+#[derive(Debug, Deserialize)]
+pub struct OpenLimitOrderParams {
+    symbol: String,
+    order_side: OrderSide,
+    quantity: f64,
+    price: f64,
+}
+#[get("/open-limit-order")]
+async fn open_limit_order(app_data: web::Data<AppState>, req: HttpRequest) -> impl Responder {
+    let params = web::Query::<OpenLimitOrderParams>::from_query(req.query_string()).unwrap();
+    let exchange_api = app_data.get_exchange_api().await;
+
+    let res = exchange_api
+        .open_limit_order(
+            &params.symbol,
+            params.order_side.clone(),
+            params.quantity,
+            params.price,
+            OrderOptions::default(),
+        )
+        .await;
+
+    let json_data = match res {
+        Ok(data) => json!({ "success": "Limit order opened", "data": data }),
+        Err(e) => json!({ "error": format!("Unable to open limit order: {e}") }),
+    };
+
+    HttpResponse::Ok().json(json_data)
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ClosePositionParams {
+    position_id: String,
+}
+#[get("/close-position")]
+async fn close_position(app_data: web::Data<AppState>, req: HttpRequest) -> impl Responder {
+    let params = web::Query::<ClosePositionParams>::from_query(req.query_string()).unwrap();
+    let exchange_api = app_data.get_exchange_api().await;
+
+    let res = exchange_api.close_position(&params.position_id).await;
+
+    let json_data = match res {
+        Ok(data) => {
+            json!({ "success": "Position closed", "position_id": params.position_id, "data": data })
+        }
+        Err(e) => json!({ "error": format!("Unable to close position: {e}") }),
+    };
+
+    HttpResponse::Ok().json(json_data)
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CloseSymbolPositionParams {
+    symbol: String,
+    // side position was opened with
+    order_side: OrderSide,
+}
+#[get("/close-symbol-position")]
+async fn close_symbol_position(app_data: web::Data<AppState>, req: HttpRequest) -> impl Responder {
+    let params = web::Query::<CloseSymbolPositionParams>::from_query(req.query_string()).unwrap();
+    let exchange_api = app_data.get_exchange_api().await;
+
+    let res = exchange_api
+        .close_position_by_symbol(&params.symbol, params.order_side.clone())
+        .await;
+
+    let json_data = match res {
+        Ok(data) => json!({ "success": "Position closed", "symbol": params.symbol, "data": data }),
+        Err(e) => json!({ "error": format!("Unable to close position: {e}") }),
+    };
+
+    HttpResponse::Ok().json(json_data)
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SetLeverageParams {
+    symbol: String,
+    leverage: u32,
+    position_side: PositionSide,
+}
+#[get("/set-leverage")]
+async fn set_leverage(app_data: web::Data<AppState>, req: HttpRequest) -> impl Responder {
+    let params = web::Query::<SetLeverageParams>::from_query(req.query_string()).unwrap();
+    let exchange_api = app_data.get_exchange_api().await;
+
+    let res = exchange_api
+        .set_leverage(
+            &params.symbol,
+            params.leverage,
+            params.position_side.clone(),
+        )
+        .await;
+
+    let json_data = match res {
+        Ok(data) => json!({ "success": "Leverage set", "data": data }),
+        Err(e) => json!({ "error": format!("Unable to set leverage: {e}") }),
+    };
+
+    HttpResponse::Ok().json(json_data)
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SetMarginModeParams {
+    symbol: String,
+    margin_mode: MarginMode,
+}
+#[get("/set-margin-mode")]
+async fn set_margin_mode(app_data: web::Data<AppState>, req: HttpRequest) -> impl Responder {
+    let params = web::Query::<SetMarginModeParams>::from_query(req.query_string()).unwrap();
+    let exchange_api = app_data.get_exchange_api().await;
+
+    let res = exchange_api
+        .set_margin_mode(&params.symbol, params.margin_mode.clone())
+        .await;
+
+    let json_data = match res {
+        Ok(data) => json!({ "success": "Margin mode set", "data": data }),
+        Err(e) => json!({ "error": format!("Unable to set margin mode: {e}") }),
+    };
+
+    HttpResponse::Ok().json(json_data)
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CancelOrderParams {
+    symbol: String,
+    order_id: String,
+}
+#[get("/cancel-order")]
+async fn cancel_order(app_data: web::Data<AppState>, req: HttpRequest) -> impl Responder {
+    let params = web::Query::<CancelOrderParams>::from_query(req.query_string()).unwrap();
+    let exchange_api = app_data.get_exchange_api().await;
+
+    let res = exchange_api
+        .cancel_order(&params.symbol, &params.order_id)
+        .await;
+
+    let json_data = match res {
+        Ok(data) => {
+            json!({ "success": "Order canceled", "order_id": params.order_id, "data": data })
+        }
+        Err(e) => json!({ "error": format!("Unable to cancel order: {e}") }),
+    };
+
+    HttpResponse::Ok().json(json_data)
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CancelAllOrdersParams {
+    symbol: String,
+}
+#[get("/cancel-all-orders")]
+async fn cancel_all_orders(app_data: web::Data<AppState>, req: HttpRequest) -> impl Responder {
+    let params = web::Query::<CancelAllOrdersParams>::from_query(req.query_string()).unwrap();
+    let exchange_api = app_data.get_exchange_api().await;
+
+    let res = exchange_api.cancel_all_orders(&params.symbol).await;
+
+    let json_data = match res {
+        Ok(data) => json!({ "success": "Orders canceled", "symbol": params.symbol, "data": data }),
+        Err(e) => json!({ "error": format!("Unable to cancel orders: {e}") }),
+    };
+
+    HttpResponse::Ok().json(json_data)
+}
+
+#[derive(Debug, Deserialize)]
+pub struct OrderStatusParams {
+    symbol: String,
+    order_id: String,
+}
+#[get("/order-status")]
+async fn order_status(app_data: web::Data<AppState>, req: HttpRequest) -> impl Responder {
+    let params = web::Query::<OrderStatusParams>::from_query(req.query_string()).unwrap();
+    let exchange_api = app_data.get_exchange_api().await;
+
+    let res = exchange_api
+        .order_status(&params.symbol, &params.order_id)
+        .await;
+
+    let json_data = match res {
+        Ok(status) => json!({ "order_id": params.order_id, "status": status }),
+        Err(e) => json!({ "error": format!("Unable to get order status: {e}") }),
+    };
+
+    HttpResponse::Ok().json(json_data)
+}
+
+#[derive(Debug, Deserialize)]
+pub struct WaitForFillParams {
+    symbol: String,
+    order_id: String,
+    // secs to wait for order to fill before returning open status
+    timeout_secs: u64,
+}
+#[get("/wait-for-fill")]
+async fn wait_for_fill(app_data: web::Data<AppState>, req: HttpRequest) -> impl Responder {
+    let params = web::Query::<WaitForFillParams>::from_query(req.query_string()).unwrap();
+    let exchange_api = app_data.get_exchange_api().await;
+
+    let res = exchange_api
+        .wait_for_fill(
+            &params.symbol,
+            &params.order_id,
+            Duration::from_secs(params.timeout_secs),
+        )
+        .await;
+
+    let json_data = match res {
+        Ok(status) => json!({ "order_id": params.order_id, "status": status }),
+        Err(e) => json!({ "error": format!("Unable to wait for order fill: {e}") }),
+    };
+
+    HttpResponse::Ok().json(json_data)
+}
+
+#[get("/refresh-exchange-info")]
+async fn refresh_exchange_info(app_data: web::Data<AppState>, _req: HttpRequest) -> impl Responder {
+    let market = app_data.get_market().await;
+
+    // cached metadata is replaced even if not stale, eg. after precision rejections
+    let res = market.lock().await.refresh_metadata().await;
+
+    let json_data = match res {
+        Ok(data) => json!({ "success": "Exchange info refreshed", "data": data }),
+        Err(e) => json!({ "error": format!("Unable to refresh exchange info: {e}") }),
+    };
+
+    HttpResponse::Ok().json(json_data)
+}
+
 pub fn register_exchange_service() -> Scope {
     scope("/exchange")
         .service(exchange_info)
@@ -104,4 +351,14 @@ pub fn register_exchange_service() -> Scope {
         .service(get_ticker)
         .service(list_list_open_orders)
         .service(all_orders)
+        .service(open_limit_order)
+        .service(close_position)
+        .service(close_symbol_position)
+        .service(set_leverage)
+        .service(set_margin_mode)
+        .service(cancel_order)
+        .service(cancel_all_orders)
+        .service(order_status)
+        .service(wait_for_fill)
+        .service(refresh_exchange_info)
 }
@@ -1,3 +1,5 @@
+use std::time::Duration;
+
 use actix_web::HttpRequest;
 use actix_web::{
     get,
@@ -9,9 +11,19 @@ use serde::Deserialize;
 use serde_json::json;
 
 use crate::exchange::types::StreamType;
+use crate::market::interval::Interval;
+use crate::market::market::MarketData;
+use crate::market::patterns::{detect_patterns, PatternConfig};
 
 use crate::app::AppState;
 
+// klines averaged by indicators if period not given
+const DEFAULT_INDICATOR_PERIOD: usize = 14;
+const DEFAULT_BOLLINGER_K: f64 = 2.0;
+const DEFAULT_MACD_FAST: usize = 12;
+const DEFAULT_MACD_SLOW: usize = 26;
+const DEFAULT_MACD_SIGNAL: usize = 9;
+
 #[get("/meta")]
 async fn get_market_meta(_app_data: web::Data<AppState>) -> impl Responder {
     let json_data = json!({ "success": "Market meta data" });
@@ -26,6 +38,8 @@ pub struct GetKlineDataParams {
     from_ts: Option<u64>,
     to_ts: Option<u64>,
     limit: Option<usize>,
+    // higher interval klines are aggregated into, eg. 1h
+    resample: Option<String>,
 }
 #[get("/kline-data")]
 async fn get_kline_data(app_data: web::Data<AppState>, req: HttpRequest) -> impl Responder {
@@ -33,10 +47,11 @@ async fn get_kline_data(app_data: web::Data<AppState>, req: HttpRequest) -> impl
 
     let market = app_data.get_market().await;
 
+    // unsupported interval is returned as error
     let kline_data = market
         .lock()
         .await
-        .kline_data(
+        .kline_data_str(
             &params.symbol,
             &params.interval,
             params.from_ts,
@@ -45,17 +60,173 @@ async fn get_kline_data(app_data: web::Data<AppState>, req: HttpRequest) -> impl
         )
         .await;
 
-    if let Some(kline_data) = kline_data {
-        // Return the stream data as JSON
-        let json_data = json!({ "kline_data": kline_data });
+    // aggregate klines into resample interval if given
+    let kline_data = match (kline_data, &params.resample) {
+        (Ok(Some(kline_data)), Some(resample)) => kline_data.resample(resample).map(Some),
+        (kline_data, _) => kline_data,
+    };
+
+    match kline_data {
+        Ok(Some(kline_data)) => {
+            // Return the stream data as JSON
+            let json_data = json!({ "kline_data": kline_data });
+            HttpResponse::Ok().json(json_data)
+        }
+        Ok(None) => {
+            let json_data = json!({ "error": "Kline data not found" });
+            // Stream ID not found
+            HttpResponse::Ok().json(json_data)
+        }
+        Err(e) => {
+            let json_data = json!({ "error": format!("Unable to load kline data: {e}") });
+            HttpResponse::Ok().json(json_data)
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PatternsParams {
+    symbol: String,
+    interval: String,
+    from_ts: Option<u64>,
+    to_ts: Option<u64>,
+    limit: Option<usize>,
+    // thresholds of detected patterns, defaults used if not given
+    doji_body_ratio: Option<f64>,
+    shadow_body_ratio: Option<f64>,
+    short_shadow_ratio: Option<f64>,
+}
+#[get("/patterns")]
+async fn get_patterns(app_data: web::Data<AppState>, req: HttpRequest) -> impl Responder {
+    let params = web::Query::<PatternsParams>::from_query(req.query_string()).unwrap();
+
+    let interval = match params.interval.parse::<Interval>() {
+        Ok(interval) => interval,
+        Err(e) => {
+            let json_data = json!({ "error": e });
+            return HttpResponse::Ok().json(json_data);
+        }
+    };
+
+    let default_config = PatternConfig::default();
+    let config = PatternConfig {
+        doji_body_ratio: params
+            .doji_body_ratio
+            .unwrap_or(default_config.doji_body_ratio),
+        shadow_body_ratio: params
+            .shadow_body_ratio
+            .unwrap_or(default_config.shadow_body_ratio),
+        short_shadow_ratio: params
+            .short_shadow_ratio
+            .unwrap_or(default_config.short_shadow_ratio),
+    };
+
+    let market = app_data.get_market().await;
+
+    let kline_data = market
+        .lock()
+        .await
+        .kline_data(
+            &params.symbol,
+            interval,
+            params.from_ts,
+            params.to_ts,
+            params.limit,
+        )
+        .await;
+
+    match kline_data {
+        Ok(Some(kline_data)) => {
+            let patterns = detect_patterns(&kline_data, &config);
+            let json_data = json!({ "patterns": patterns, "config": config });
+            HttpResponse::Ok().json(json_data)
+        }
+        Ok(None) => {
+            let json_data = json!({ "error": "Kline data not found" });
+            HttpResponse::Ok().json(json_data)
+        }
+        Err(e) => {
+            let json_data = json!({ "error": format!("Unable to load kline data: {e}") });
+            HttpResponse::Ok().json(json_data)
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct GetLatestKlineParams {
+    symbol: String,
+    interval: String,
+}
+#[get("/latest-kline")]
+async fn get_latest_kline(app_data: web::Data<AppState>, req: HttpRequest) -> impl Responder {
+    let params = web::Query::<GetLatestKlineParams>::from_query(req.query_string()).unwrap();
+
+    let interval = match params.interval.parse::<Interval>() {
+        Ok(interval) => interval,
+        Err(e) => {
+            let json_data = json!({ "error": e });
+            return HttpResponse::Ok().json(json_data);
+        }
+    };
+
+    let market = app_data.get_market().await;
+
+    let kline = market
+        .lock()
+        .await
+        .latest_kline(&params.symbol, interval)
+        .await;
+
+    if let Some(kline) = kline {
+        let json_data = json!({ "kline": kline });
         HttpResponse::Ok().json(json_data)
     } else {
-        let json_data = json!({ "error": "Kline data not found" });
-        // Stream ID not found
+        let json_data = json!({ "error": "Kline not found" });
         HttpResponse::Ok().json(json_data)
     }
 }
 
+#[derive(Debug, Deserialize)]
+pub struct AvailableRangeParams {
+    symbol: String,
+    interval: String,
+}
+#[get("/available-range")]
+async fn available_range(app_data: web::Data<AppState>, req: HttpRequest) -> impl Responder {
+    let params = web::Query::<AvailableRangeParams>::from_query(req.query_string()).unwrap();
+
+    let interval = match params.interval.parse::<Interval>() {
+        Ok(interval) => interval,
+        Err(e) => {
+            let json_data = json!({ "error": e });
+            return HttpResponse::Ok().json(json_data);
+        }
+    };
+
+    let storage_manager = app_data
+        .get_market()
+        .await
+        .lock()
+        .await
+        .storage_manager()
+        .await;
+
+    // only klines stored on disk can be backtested
+    let kline_key = MarketData::build_kline_key(&params.symbol, interval.as_str());
+
+    let json_data = match storage_manager.available_range(&kline_key) {
+        Some((from_ts, to_ts)) => json!({
+            "symbol": params.symbol,
+            "interval": interval.as_str(),
+            "from_ts": from_ts,
+            "to_ts": to_ts,
+            "months": storage_manager.list_kline_files(&kline_key),
+        }),
+        None => json!({ "error": "Stored klines not found" }),
+    };
+    HttpResponse::Ok().json(json_data)
+}
+
 #[derive(Debug, Deserialize)]
 pub struct GetTickerDataParams {
     symbol: String,
@@ -80,13 +251,28 @@ async fn last_price(app_data: web::Data<AppState>, req: HttpRequest) -> impl Res
     }
 }
 
+#[derive(Debug, Deserialize)]
+pub struct TickerDataParams {
+    symbol: String,
+    // secs since last update after which ticker is stale
+    max_age: Option<u64>,
+}
 #[get("/ticker-data")]
 async fn get_ticker_data(app_data: web::Data<AppState>, req: HttpRequest) -> impl Responder {
-    let params = web::Query::<GetTickerDataParams>::from_query(req.query_string()).unwrap();
+    let params = web::Query::<TickerDataParams>::from_query(req.query_string()).unwrap();
 
     let market = app_data.get_market().await;
+    let market = market.lock().await;
 
-    let ticker_data = market.lock().await.ticker_data(&params.symbol).await;
+    // stale ticker is not found, eg. if ticker stream silently died
+    let ticker_data = match params.max_age {
+        Some(max_age) => {
+            market
+                .ticker_data_fresh(&params.symbol, Duration::from_secs(max_age))
+                .await
+        }
+        None => market.ticker_data(&params.symbol).await,
+    };
 
     if let Some(ticker_data) = ticker_data {
         // Return the stream data as JSON
@@ -109,15 +295,97 @@ async fn get_market_data(app_data: web::Data<AppState>) -> impl Responder {
     HttpResponse::Ok().json(json_data)
 }
 
+#[derive(Debug, Deserialize)]
+pub struct ActiveStreamsParams {
+    symbol: Option<String>,
+    // kline or ticker
+    stream_type: Option<String>,
+}
 #[get("/active-streams")]
-async fn active_streams(app_data: web::Data<AppState>) -> impl Responder {
+async fn active_streams(app_data: web::Data<AppState>, req: HttpRequest) -> impl Responder {
+    let params = web::Query::<ActiveStreamsParams>::from_query(req.query_string()).unwrap();
+
+    let stream_type = match params.stream_type.as_deref().map(str::parse::<StreamType>) {
+        Some(Err(e)) => {
+            let json_data = json!({ "error": "Invalid stream type", "msg": e });
+            return HttpResponse::Ok().json(json_data);
+        }
+        Some(Ok(stream_type)) => Some(stream_type),
+        None => None,
+    };
+
     let market = app_data.get_market().await;
-    let active_streams = market.lock().await.active_streams().await;
+    let market = market.lock().await;
+
+    // streams filtered by symbol and stream type if given
+    let active_streams = match (&params.symbol, stream_type) {
+        (Some(symbol), Some(stream_type)) => {
+            let mut active_streams = market.active_streams_for(symbol).await;
+            active_streams.retain(|meta| meta.stream_type == stream_type);
+            active_streams
+        }
+        (Some(symbol), None) => market.active_streams_for(symbol).await,
+        (None, Some(stream_type)) => market.active_streams_of_type(stream_type).await,
+        (None, None) => market.active_streams().await,
+    };
     // Return the stream data as JSON
     let json_data = json!({ "active_streams": active_streams });
     HttpResponse::Ok().json(json_data)
 }
 
+#[get("/status")]
+async fn market_status(app_data: web::Data<AppState>) -> impl Responder {
+    let bot = app_data.get_bot().await;
+    let dropped_messages = bot.lock().await.dropped_messages();
+
+    // dropped messages increase when market falls behind exchange streams
+    let json_data = json!({ "dropped_messages": dropped_messages });
+    HttpResponse::Ok().json(json_data)
+}
+
+#[get("/stop-recording")]
+async fn stop_recording(app_data: web::Data<AppState>) -> impl Responder {
+    let bot = app_data.get_bot().await;
+    let stopped = bot.lock().await.stop_recording().await;
+
+    let json_data = if stopped {
+        json!({ "success": "Market recording stopped" })
+    } else {
+        json!({ "error": "Market is not being recorded" })
+    };
+    HttpResponse::Ok().json(json_data)
+}
+
+#[derive(Debug, Deserialize)]
+pub struct StreamHealthParams {
+    stream_id: String,
+}
+#[get("/stream-health")]
+async fn stream_health(app_data: web::Data<AppState>, req: HttpRequest) -> impl Responder {
+    let params = web::Query::<StreamHealthParams>::from_query(req.query_string()).unwrap();
+
+    let stream_manager = app_data.get_stream_manager().await;
+    let stream_manager = stream_manager.lock().await;
+
+    // find active stream with matching id
+    let active_stream = stream_manager
+        .active_streams()
+        .await
+        .into_iter()
+        .find(|meta| meta.id.as_str() == params.stream_id);
+
+    let stream_health = match active_stream {
+        Some(active_stream) => stream_manager.stream_health(&active_stream.id).await,
+        None => None,
+    };
+
+    let json_data = match stream_health {
+        Some(stream_health) => json!({ "stream_health": stream_health }),
+        None => json!({ "error": format!("Stream with ID {} not found", &params.stream_id) }),
+    };
+    HttpResponse::Ok().json(json_data)
+}
+
 #[derive(Debug, Deserialize)]
 pub struct CloseStreamParams {
     stream_id: String,
@@ -127,8 +395,19 @@ async fn close_stream(app_data: web::Data<AppState>, req: HttpRequest) -> HttpRe
     let params = web::Query::<CloseStreamParams>::from_query(req.query_string()).unwrap();
 
     let market = app_data.get_market().await;
+    let market = market.lock().await;
+
+    // find active stream with matching id
+    let active_stream = market
+        .active_streams()
+        .await
+        .into_iter()
+        .find(|meta| meta.id.as_str() == params.stream_id);
 
-    let stream_meta = market.lock().await.close_stream(&params.stream_id).await;
+    let stream_meta = match active_stream {
+        Some(active_stream) => market.close_stream(&active_stream.id).await,
+        None => None,
+    };
 
     // TODO: handle error
     match stream_meta {
@@ -147,7 +426,8 @@ async fn close_stream(app_data: web::Data<AppState>, req: HttpRequest) -> HttpRe
 
 #[derive(Debug, Deserialize)]
 pub struct OpenStreamParams {
-    stream_type: StreamType,
+    // kline or ticker
+    stream_type: String,
     symbol: String,
     interval: Option<String>,
 }
@@ -155,7 +435,29 @@ pub struct OpenStreamParams {
 async fn open_stream(app_data: web::Data<AppState>, req: HttpRequest) -> impl Responder {
     let params = web::Query::<OpenStreamParams>::from_query(req.query_string()).unwrap();
 
-    let stream_type = params.stream_type.clone();
+    let stream_type = match params.stream_type.parse::<StreamType>() {
+        Ok(stream_type) => stream_type,
+        Err(e) => {
+            let json_data = json!({ "error": "Unable to open stream", "msg": e });
+            return HttpResponse::Ok().json(json_data);
+        }
+    };
+    // kline streams need interval
+    let interval = match (&stream_type, &params.interval) {
+        (StreamType::Kline, Some(interval)) => match interval.parse::<Interval>() {
+            Ok(interval) => Some(interval),
+            Err(e) => {
+                let json_data = json!({ "error": "Unable to open stream", "msg": e });
+                return HttpResponse::Ok().json(json_data);
+            }
+        },
+        (StreamType::Kline, None) => {
+            let json_data =
+                json!({ "error": "Unable to open stream", "msg": "Missing kline interval" });
+            return HttpResponse::Ok().json(json_data);
+        }
+        (StreamType::Ticker, _) => None,
+    };
     let market = app_data.get_market().await;
 
     // TODO: handle errors
@@ -163,11 +465,10 @@ async fn open_stream(app_data: web::Data<AppState>, req: HttpRequest) -> impl Re
     let stream_id = match stream_type {
         StreamType::Kline => {
             let symbol = params.symbol.to_string();
-            let interval = params.interval.clone().unwrap().to_string();
             market
                 .lock()
                 .await
-                .open_stream(stream_type, &symbol, Some(&interval))
+                .open_stream(stream_type, &symbol, interval)
                 .await
         }
         StreamType::Ticker => {
@@ -192,14 +493,55 @@ async fn open_stream(app_data: web::Data<AppState>, req: HttpRequest) -> impl Re
     HttpResponse::Ok().json(data)
 }
 
+#[derive(Debug, Deserialize)]
+pub struct BackfillParams {
+    symbol: String,
+    interval: String,
+    from_ts: u64,
+    to_ts: u64,
+}
+#[get("/backfill-gaps")]
+async fn backfill_gaps(app_data: web::Data<AppState>, req: HttpRequest) -> impl Responder {
+    let params = web::Query::<BackfillParams>::from_query(req.query_string()).unwrap();
+
+    let interval = match params.interval.parse::<Interval>() {
+        Ok(interval) => interval,
+        Err(e) => {
+            let json_data = json!({ "error": e });
+            return HttpResponse::Ok().json(json_data);
+        }
+    };
+
+    let market = app_data.get_market().await;
+
+    // only klines missing from storage are fetched
+    let saved = market
+        .lock()
+        .await
+        .backfill_gaps(&params.symbol, interval, params.from_ts, params.to_ts)
+        .await;
+
+    let json_data = match saved {
+        Ok(saved) => json!({ "success": "Kline gaps backfilled", "klines": saved }),
+        Err(e) => json!({ "error": format!("Unable to backfill kline gaps: {e}") }),
+    };
+    HttpResponse::Ok().json(json_data)
+}
+
 #[get("/remote-kline")]
 async fn get_remote_kline(app_data: web::Data<AppState>, req: HttpRequest) -> impl Responder {
     let exchange_api = app_data.get_exchange_api().await;
     let params = web::Query::<GetKlineDataParams>::from_query(req.query_string()).unwrap();
 
-    let kline = exchange_api
-        .get_kline(&params.symbol, &params.interval)
-        .await;
+    let interval = match params.interval.parse::<Interval>() {
+        Ok(interval) => interval,
+        Err(e) => {
+            let json_data = json!({ "error": e });
+            return HttpResponse::Ok().json(json_data);
+        }
+    };
+
+    let kline = exchange_api.get_kline(&params.symbol, interval).await;
 
     if let Ok(kline) = kline {
         // Return the stream data as JSON
@@ -212,6 +554,257 @@ async fn get_remote_kline(app_data: web::Data<AppState>, req: HttpRequest) -> im
     }
 }
 
+#[derive(Debug, Deserialize)]
+pub struct SubscribeWarmupParams {
+    symbol: String,
+    interval: String,
+    // klines of history loaded before live stream is opened
+    warmup_bars: usize,
+}
+#[get("/subscribe-warmup")]
+async fn subscribe_warmup(app_data: web::Data<AppState>, req: HttpRequest) -> impl Responder {
+    let params = web::Query::<SubscribeWarmupParams>::from_query(req.query_string()).unwrap();
+
+    let market = app_data.get_market().await;
+
+    // responds once warmup klines are in market data
+    let stream_id = market
+        .lock()
+        .await
+        .subscribe_with_warmup_str(&params.symbol, &params.interval, params.warmup_bars)
+        .await;
+
+    let json_data = match stream_id {
+        Ok(stream_id) => json!({ "success": "Stream created", "stream_id": stream_id }),
+        Err(e) => json!({ "error": "Unable to open stream", "msg": e.to_string() }),
+    };
+    HttpResponse::Ok().json(json_data)
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BackfillKlinesParams {
+    symbol: String,
+    interval: String,
+    from_ts: u64,
+    to_ts: u64,
+}
+#[get("/backfill-klines")]
+async fn backfill_klines(app_data: web::Data<AppState>, req: HttpRequest) -> impl Responder {
+    let params = web::Query::<BackfillKlinesParams>::from_query(req.query_string()).unwrap();
+
+    let market = app_data.get_market().await;
+
+    // all klines in range are fetched, see backfill-gaps to only fetch missing klines
+    let saved = market
+        .lock()
+        .await
+        .backfill_klines_str(
+            &params.symbol,
+            &params.interval,
+            params.from_ts,
+            params.to_ts,
+        )
+        .await;
+
+    let json_data = match saved {
+        Ok(saved) => json!({ "success": "Klines backfilled", "klines": saved }),
+        Err(e) => json!({ "error": format!("Unable to backfill klines: {e}") }),
+    };
+    HttpResponse::Ok().json(json_data)
+}
+
+#[derive(Debug, Deserialize)]
+pub struct NeededStreamParams {
+    // kline or ticker
+    stream_type: String,
+    symbol: String,
+    interval: Option<String>,
+}
+#[get("/add-needed-stream")]
+async fn add_needed_stream(app_data: web::Data<AppState>, req: HttpRequest) -> impl Responder {
+    let params = web::Query::<NeededStreamParams>::from_query(req.query_string()).unwrap();
+
+    let stream_type = match params.stream_type.parse::<StreamType>() {
+        Ok(stream_type) => stream_type,
+        Err(e) => {
+            let json_data = json!({ "error": "Unable to add needed stream", "msg": e });
+            return HttpResponse::Ok().json(json_data);
+        }
+    };
+
+    let market = app_data.get_market().await;
+
+    // needed streams are reopened by stream monitor if closed
+    let stream_id = market
+        .lock()
+        .await
+        .add_needed_stream_str(&params.symbol, stream_type, params.interval.as_deref())
+        .await;
+
+    let json_data = match stream_id {
+        Ok(stream_id) => json!({ "success": "Needed stream added", "stream_id": stream_id }),
+        Err(e) => json!({ "error": "Unable to add needed stream", "msg": e.to_string() }),
+    };
+    HttpResponse::Ok().json(json_data)
+}
+
+#[get("/remove-needed-stream")]
+async fn remove_needed_stream(app_data: web::Data<AppState>, req: HttpRequest) -> impl Responder {
+    let params = web::Query::<NeededStreamParams>::from_query(req.query_string()).unwrap();
+
+    let stream_type = match params.stream_type.parse::<StreamType>() {
+        Ok(stream_type) => stream_type,
+        Err(e) => {
+            let json_data = json!({ "error": "Unable to remove needed stream", "msg": e });
+            return HttpResponse::Ok().json(json_data);
+        }
+    };
+
+    let market = app_data.get_market().await;
+
+    // live stream is closed once no longer needed
+    let res = market
+        .lock()
+        .await
+        .remove_needed_stream_str(&params.symbol, stream_type, params.interval.as_deref())
+        .await;
+
+    let json_data = match res {
+        Ok(_) => json!({ "success": "Needed stream removed" }),
+        Err(e) => json!({ "error": "Unable to remove needed stream", "msg": e.to_string() }),
+    };
+    HttpResponse::Ok().json(json_data)
+}
+
+#[derive(Debug, Deserialize)]
+pub struct IndicatorParams {
+    symbol: String,
+    interval: String,
+    from_ts: Option<u64>,
+    to_ts: Option<u64>,
+    limit: Option<usize>,
+    // eg. sma, ema, rsi, vwap, atr, bollinger or macd
+    indicator: String,
+    period: Option<usize>,
+    // standard deviations of bollinger bands from middle
+    k: Option<f64>,
+    // EMA periods of macd
+    fast: Option<usize>,
+    slow: Option<usize>,
+    signal: Option<usize>,
+}
+#[get("/indicator")]
+async fn get_indicator(app_data: web::Data<AppState>, req: HttpRequest) -> impl Responder {
+    let params = web::Query::<IndicatorParams>::from_query(req.query_string()).unwrap();
+
+    let market = app_data.get_market().await;
+
+    let kline_data = market
+        .lock()
+        .await
+        .kline_data_str(
+            &params.symbol,
+            &params.interval,
+            params.from_ts,
+            params.to_ts,
+            params.limit,
+        )
+        .await;
+
+    let kline_data = match kline_data {
+        Ok(Some(kline_data)) => kline_data,
+        Ok(None) => {
+            let json_data = json!({ "error": "Kline data not found" });
+            return HttpResponse::Ok().json(json_data);
+        }
+        Err(e) => {
+            let json_data = json!({ "error": format!("Unable to load kline data: {e}") });
+            return HttpResponse::Ok().json(json_data);
+        }
+    };
+
+    let period = params.period.unwrap_or(DEFAULT_INDICATOR_PERIOD);
+
+    // values are aligned to open times of klines, except vwap
+    let values = match params.indicator.as_str() {
+        "sma" => json!(kline_data.sma(period)),
+        "ema" => json!(kline_data.ema(period)),
+        "rsi" => json!(kline_data.rsi(period)),
+        // single value over all klines
+        "vwap" => json!(kline_data.vwap()),
+        "atr" => json!(kline_data.atr(period)),
+        "bollinger" => {
+            let k = params.k.unwrap_or(DEFAULT_BOLLINGER_K);
+            json!(kline_data.bollinger(period, k))
+        }
+        "macd" => {
+            let fast = params.fast.unwrap_or(DEFAULT_MACD_FAST);
+            let slow = params.slow.unwrap_or(DEFAULT_MACD_SLOW);
+            let signal = params.signal.unwrap_or(DEFAULT_MACD_SIGNAL);
+            json!(kline_data.macd(fast, slow, signal))
+        }
+        indicator => {
+            let json_data = json!({ "error": format!("Unknown indicator: {indicator}") });
+            return HttpResponse::Ok().json(json_data);
+        }
+    };
+
+    let open_times: Vec<u64> = kline_data
+        .klines
+        .iter()
+        .map(|kline| kline.open_time)
+        .collect();
+
+    let json_data = json!({
+        "indicator": params.indicator,
+        "open_times": open_times,
+        "values": values,
+    });
+    HttpResponse::Ok().json(json_data)
+}
+
+#[derive(Debug, Deserialize)]
+pub struct KlineCsvParams {
+    symbol: String,
+    interval: String,
+    from_ts: Option<u64>,
+    to_ts: Option<u64>,
+    limit: Option<usize>,
+}
+#[get("/kline-csv")]
+async fn get_kline_csv(app_data: web::Data<AppState>, req: HttpRequest) -> impl Responder {
+    let params = web::Query::<KlineCsvParams>::from_query(req.query_string()).unwrap();
+
+    let market = app_data.get_market().await;
+
+    let kline_data = market
+        .lock()
+        .await
+        .kline_data_str(
+            &params.symbol,
+            &params.interval,
+            params.from_ts,
+            params.to_ts,
+            params.limit,
+        )
+        .await;
+
+    // CSV for external analysis, eg. loading into pandas
+    match kline_data {
+        Ok(Some(kline_data)) => HttpResponse::Ok()
+            .content_type("text/csv")
+            .body(kline_data.to_csv_string()),
+        Ok(None) => {
+            let json_data = json!({ "error": "Kline data not found" });
+            HttpResponse::Ok().json(json_data)
+        }
+        Err(e) => {
+            let json_data = json!({ "error": format!("Unable to load kline data: {e}") });
+            HttpResponse::Ok().json(json_data)
+        }
+    }
+}
+
 pub fn register_market_service() -> Scope {
     scope("/market")
         .service(get_remote_kline)
@@ -220,7 +813,20 @@ pub fn register_market_service() -> Scope {
         .service(open_stream)
         .service(get_market_meta)
         .service(get_kline_data)
+        .service(get_latest_kline)
+        .service(get_patterns)
+        .service(available_range)
         .service(get_market_data)
         .service(active_streams)
+        .service(stream_health)
         .service(get_ticker_data)
+        .service(market_status)
+        .service(stop_recording)
+        .service(backfill_gaps)
+        .service(subscribe_warmup)
+        .service(backfill_klines)
+        .service(add_needed_stream)
+        .service(remove_needed_stream)
+        .service(get_indicator)
+        .service(get_kline_csv)
 }
@@ -51,20 +51,23 @@ async fn date_to_timestamp(req: HttpRequest) -> impl Responder {
 }
 
 #[get("/load-klines")]
-async fn load_klines(_app_data: web::Data<AppState>, _req: HttpRequest) -> impl Responder {
+async fn load_klines(app_data: web::Data<AppState>, _req: HttpRequest) -> impl Responder {
     let user_dirs = UserDirs::new().expect("Failed to get user directories");
     let home_dir = user_dirs.home_dir();
     let data_dir = home_dir.join("Projects/BinanceData");
 
     let entries = fs::read_dir(data_dir).unwrap();
 
-    let user_dirs = UserDirs::new().expect("Failed to get user directories");
-    let home_dir = user_dirs.home_dir();
-
-    let mut data_dir = home_dir.join(".raderbot");
-    data_dir.push("default");
-    data_dir.push("market");
-    data_dir.push("klines");
+    // save in configured storage directory, in directory of exchange klines came from
+    let mut storage_manager = app_data
+        .get_market()
+        .await
+        .lock()
+        .await
+        .storage_manager()
+        .await;
+    storage_manager.set_exchange("binance");
+    let data_dir = storage_manager.kline_directory();
 
     std::fs::create_dir_all(&data_dir).expect("unable to create data directory");
 